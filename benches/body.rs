@@ -0,0 +1,142 @@
+//! Benchmarks of the request and response body paths.
+//!
+//! Each case sends requests with a 64 KiB body to a local keep-alive server that
+//! echoes a response of the same size, and reports the time, allocations and
+//! allocated bytes per request. Buffered `write` and `read` copy every body once more
+//! than `write_bytes` and `next_chunk`, which hand `Bytes` over.
+//!
+//! Run with `cargo bench --bench body`.
+
+use embedded_svc::http::client::Connection;
+use embedded_svc::http::{Headers, Method};
+use embedded_svc::io::{Read, Write};
+use hyper::body::Bytes;
+use native_svc::HyperHttpConnection;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::io::{BufRead, BufReader, Read as _, Write as _};
+use std::net::{SocketAddr, TcpListener};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Instant;
+
+/// Size of the request and response bodies.
+const BODY_SIZE: usize = 64 * 1024;
+
+/// Requests sent per case.
+const ITERATIONS: usize = 500;
+
+/// Allocator counting allocations and allocated bytes.
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    /// Counts the allocation and forwards it to the system allocator.
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    /// Forwards the deallocation to the system allocator.
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    /// Counts the reallocation as an allocation of the new size.
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED.fetch_add(new_size, Ordering::Relaxed);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+/// Starts a server answering every request with a `BODY_SIZE` body.
+fn serve() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            thread::spawn(move || {
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let head = format!("HTTP/1.1 200 OK\r\nContent-Length: {BODY_SIZE}\r\n\r\n");
+                let body = vec![b'x'; BODY_SIZE];
+                loop {
+                    let mut length = 0;
+                    let mut line = String::new();
+                    loop {
+                        line.clear();
+                        if reader.read_line(&mut line).unwrap_or(0) == 0 {
+                            return;
+                        }
+                        if line == "\r\n" {
+                            break;
+                        }
+                        let lower = line.to_ascii_lowercase();
+                        if let Some(value) = lower.strip_prefix("content-length:") {
+                            length = value.trim().parse().unwrap();
+                        }
+                    }
+                    let mut request_body = vec![0; length];
+                    reader.read_exact(&mut request_body).unwrap();
+                    stream.write_all(head.as_bytes()).unwrap();
+                    stream.write_all(&body).unwrap();
+                }
+            });
+        }
+    });
+    addr
+}
+
+/// Runs `request` `ITERATIONS` times and prints its cost per request.
+fn bench(name: &str, mut request: impl FnMut()) {
+    request();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let allocated = ALLOCATED.load(Ordering::Relaxed);
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        request();
+    }
+    let elapsed = start.elapsed() / ITERATIONS as u32;
+    let allocations = (ALLOCATIONS.load(Ordering::Relaxed) - allocations) / ITERATIONS;
+    let allocated = (ALLOCATED.load(Ordering::Relaxed) - allocated) / ITERATIONS;
+    println!("{name:<24} {elapsed:>10.2?} {allocations:>8} allocs {allocated:>10} bytes");
+}
+
+fn main() {
+    let uri = format!("http://{}/", serve());
+    let mut conn = HyperHttpConnection::new().unwrap();
+    let payload = Bytes::from(vec![b'y'; BODY_SIZE]);
+    let mut buffer = vec![0; BODY_SIZE];
+
+    bench("write + read", || {
+        conn.initiate_request(Method::Post, &uri, &[]).unwrap();
+        conn.write_all(&payload).unwrap();
+        conn.initiate_response().unwrap();
+        let mut received = 0;
+        loop {
+            match conn.read(&mut buffer).unwrap() {
+                0 => break,
+                n => received += n,
+            }
+        }
+        assert_eq!(received, BODY_SIZE);
+    });
+
+    bench("write_bytes + next_chunk", || {
+        conn.initiate_request(Method::Post, &uri, &[]).unwrap();
+        conn.write_bytes(payload.clone()).unwrap();
+        conn.initiate_response().unwrap();
+        let mut received = 0;
+        while let Some(chunk) = conn.next_chunk().unwrap() {
+            received += chunk.len();
+        }
+        assert_eq!(received, BODY_SIZE);
+        assert_eq!(conn.content_len(), Some(BODY_SIZE as u64));
+    });
+}
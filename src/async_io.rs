@@ -0,0 +1,332 @@
+//! Async readers and writers over the bodies of a connection.
+//!
+//! `HyperHttpConnection::async_writer` streams the request body from async code, and
+//! `HyperHttpConnection::async_reader` hands the rest of the response body over to
+//! it. Both implement the `tokio::io` traits, and the `futures_io` ones with the
+//! `futures-io` feature, so the combinators of either ecosystem can copy, frame or
+//! decode bodies instead of the `embedded-svc` byte API. They make progress on the
+//! connection's runtime, so they are driven with `HyperHttpConnection::block_on`.
+
+#[cfg(feature = "decompression")]
+use crate::decode::Decoder;
+use crate::error::HyperError;
+use hyper::body::{Body, Bytes, Incoming};
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll, ready};
+use tokio::io::ReadBuf;
+use tokio::sync::mpsc::error::SendError;
+use tokio::sync::mpsc::{self, OwnedPermit};
+
+/// Rest of a response body, read asynchronously.
+///
+/// Decompression applies as for the `embedded-svc` reads of the connection.
+///
+/// # Example
+///
+/// ```no_run
+/// use embedded_svc::http::Method;
+/// use embedded_svc::http::client::Connection;
+/// use native_svc::HyperHttpConnection;
+/// use tokio::io::AsyncReadExt;
+///
+/// let mut conn = HyperHttpConnection::new().unwrap();
+/// conn.initiate_request(Method::Get, "http://example.com", &[]).unwrap();
+/// conn.initiate_response().unwrap();
+/// let mut reader = conn.async_reader().unwrap();
+/// let mut page = String::new();
+/// conn.block_on(reader.read_to_string(&mut page)).unwrap();
+/// ```
+pub struct AsyncResponseBody {
+    /// Body still being received, `None` once it ended.
+    body: Option<Incoming>,
+    /// Received data not read yet.
+    buffer: Bytes,
+    /// Decoder of the response's `Content-Encoding`.
+    #[cfg(feature = "decompression")]
+    decoder: Option<Decoder>,
+}
+
+impl AsyncResponseBody {
+    /// Reads `body`, after the data already received in `buffer`.
+    pub(crate) fn new(
+        body: Option<Incoming>,
+        buffer: Bytes,
+        #[cfg(feature = "decompression")] decoder: Option<Decoder>,
+    ) -> Self {
+        Self {
+            body,
+            buffer,
+            #[cfg(feature = "decompression")]
+            decoder,
+        }
+    }
+
+    /// Receives data until the buffer holds some, leaving it empty at the end.
+    fn poll_fill(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        while self.buffer.is_empty() {
+            let Some(body) = self.body.as_mut() else {
+                break;
+            };
+            match ready!(Pin::new(body).poll_frame(cx)) {
+                Some(frame) => {
+                    // Trailers carry no body data.
+                    if let Ok(data) = frame.map_err(HyperError::from)?.into_data() {
+                        #[cfg(feature = "decompression")]
+                        let data = match self.decoder.as_mut() {
+                            Some(decoder) => decoder.decode(&data)?,
+                            None => data,
+                        };
+                        self.buffer = data;
+                    }
+                }
+                None => {
+                    self.body = None;
+                    #[cfg(feature = "decompression")]
+                    if let Some(mut decoder) = self.decoder.take() {
+                        self.buffer = decoder.finish()?;
+                    }
+                }
+            }
+        }
+        Poll::Ready(Ok(&self.buffer))
+    }
+
+    /// Marks `amount` bytes of the buffer as read.
+    fn consume_buffered(&mut self, amount: usize) {
+        self.buffer = self.buffer.slice(amount.min(self.buffer.len())..);
+    }
+}
+
+impl tokio::io::AsyncRead for AsyncResponseBody {
+    /// Copies received data into `buf`, receiving the next chunk if needed. Reads
+    /// nothing at the end of the body.
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let data = ready!(this.poll_fill(cx))?;
+        let length = data.len().min(buf.remaining());
+        buf.put_slice(&data[..length]);
+        this.consume_buffered(length);
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl tokio::io::AsyncBufRead for AsyncResponseBody {
+    /// Returns the received data not read yet, receiving the next chunk if needed.
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        self.get_mut().poll_fill(cx)
+    }
+
+    /// Marks `amount` bytes returned by `poll_fill_buf` as read.
+    fn consume(self: Pin<&mut Self>, amount: usize) {
+        self.get_mut().consume_buffered(amount);
+    }
+}
+
+#[cfg(feature = "futures-io")]
+impl futures_io::AsyncRead for AsyncResponseBody {
+    /// Copies received data into `buf`, receiving the next chunk if needed. Returns
+    /// `Ok(0)` at the end of the body.
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let data = ready!(this.poll_fill(cx))?;
+        let length = data.len().min(buf.len());
+        buf[..length].copy_from_slice(&data[..length]);
+        this.consume_buffered(length);
+        Poll::Ready(Ok(length))
+    }
+}
+
+#[cfg(feature = "futures-io")]
+impl futures_io::AsyncBufRead for AsyncResponseBody {
+    /// Returns the received data not read yet, receiving the next chunk if needed.
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        self.get_mut().poll_fill(cx)
+    }
+
+    /// Marks `amount` bytes returned by `poll_fill_buf` as read.
+    fn consume(self: Pin<&mut Self>, amount: usize) {
+        self.get_mut().consume_buffered(amount);
+    }
+}
+
+/// Wait for room in the upload queue.
+type Reserve = Pin<Box<dyn Future<Output = Result<OwnedPermit<Bytes>, SendError<()>>> + Send>>;
+
+/// Body of a streamed request, written asynchronously.
+///
+/// The body ends once the writer is shut down or dropped and `initiate_response` is
+/// called, which waits for the response until then.
+///
+/// # Example
+///
+/// ```no_run
+/// use embedded_svc::http::Method;
+/// use embedded_svc::http::client::Connection;
+/// use native_svc::HyperHttpConnection;
+/// use tokio::io::AsyncWriteExt;
+///
+/// let mut conn = HyperHttpConnection::new().unwrap();
+/// conn.initiate_request(Method::Post, "http://example.com/upload", &[])
+///     .unwrap();
+/// let mut writer = conn.async_writer().unwrap();
+/// conn.block_on(async {
+///     writer.write_all(b"hello").await?;
+///     writer.shutdown().await
+/// })
+/// .unwrap();
+/// conn.initiate_response().unwrap();
+/// ```
+pub struct AsyncRequestBody {
+    /// Sender of the body chunks, `None` once shut down.
+    sender: Option<mpsc::Sender<Bytes>>,
+    /// Pending wait for room in the queue.
+    reserve: Option<Reserve>,
+}
+
+impl AsyncRequestBody {
+    /// Writes the body chunks to `sender`.
+    pub(crate) fn new(sender: mpsc::Sender<Bytes>) -> Self {
+        Self {
+            sender: Some(sender),
+            reserve: None,
+        }
+    }
+
+    /// Sends a copy of `buf` once the queue has room for it.
+    fn poll_send(&mut self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        let Some(sender) = self.sender.as_ref() else {
+            return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()));
+        };
+        let reserve = self
+            .reserve
+            .get_or_insert_with(|| Box::pin(sender.clone().reserve_owned()));
+        let permit = ready!(reserve.as_mut().poll(cx));
+        self.reserve = None;
+        // The connection dropped the body, so the request failed or was reset.
+        let permit = permit.map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))?;
+        permit.send(Bytes::copy_from_slice(buf));
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    /// Ends the body.
+    fn close(&mut self) {
+        self.sender = None;
+        self.reserve = None;
+    }
+}
+
+impl tokio::io::AsyncWrite for AsyncRequestBody {
+    /// Queues a copy of `buf` as the next chunk of the body.
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut().poll_send(cx, buf)
+    }
+
+    /// Does nothing, as written chunks are queued right away.
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    /// Ends the body.
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().close();
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(feature = "futures-io")]
+impl futures_io::AsyncWrite for AsyncRequestBody {
+    /// Queues a copy of `buf` as the next chunk of the body.
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut().poll_send(cx, buf)
+    }
+
+    /// Does nothing, as written chunks are queued right away.
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    /// Ends the body.
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().close();
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::HyperHttpConnection;
+    use embedded_svc::http::Method;
+    use embedded_svc::http::client::Connection;
+    use embedded_svc::io::Write as _;
+    use std::io::{BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+
+    /// Tests streaming a request body from an async writer and reading the response
+    /// lines from an async reader.
+    #[test]
+    fn test_async_bodies() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut request = Vec::new();
+            while !request.ends_with(b"\r\n0\r\n\r\n") {
+                let mut byte = [0u8];
+                reader.read_exact(&mut byte).unwrap();
+                request.push(byte[0]);
+            }
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 12\r\n\r\nfirst\nsecond")
+                .unwrap();
+            request
+        });
+
+        let mut conn = HyperHttpConnection::new().unwrap();
+        conn.initiate_request(Method::Post, &format!("http://{addr}/"), &[])
+            .unwrap();
+        conn.write(b"buffered ").unwrap();
+        let mut writer = conn.async_writer().unwrap();
+        conn.block_on(async {
+            writer.write_all(b"streamed").await?;
+            writer.shutdown().await
+        })
+        .unwrap();
+        assert!(conn.block_on(writer.write_all(b"late")).is_err());
+        conn.initiate_response().unwrap();
+
+        let mut lines = conn.async_reader().unwrap().lines();
+        let mut received = Vec::new();
+        while let Some(line) = conn.block_on(lines.next_line()).unwrap() {
+            received.push(line);
+        }
+        assert_eq!(received, ["first", "second"]);
+
+        let request = String::from_utf8(server.join().unwrap()).unwrap();
+        assert!(request.contains("buffered "));
+        assert!(request.contains("streamed"));
+    }
+}
@@ -0,0 +1,148 @@
+//! Concurrent batches of requests.
+//!
+//! `HyperHttpConnection` handles one request at a time through the `Connection`
+//! trait. `send_all` sends a batch of `PreparedRequest`s concurrently on the shared
+//! runtime and connection pool instead, returning the responses in request order.
+
+use crate::HyperHttpConnection;
+use crate::body::exchange;
+use crate::error::HyperError;
+use crate::options::RequestOptions;
+use embedded_svc::http::Method;
+use hyper::Response;
+use hyper::body::Bytes;
+use hyper::header::{CONTENT_LENGTH, HeaderValue, TRANSFER_ENCODING};
+use std::io;
+use tokio::task::JoinHandle;
+
+/// A request with its body, ready to be sent by `send_all`.
+#[derive(Debug, Clone)]
+pub struct PreparedRequest {
+    method: Method,
+    uri: String,
+    headers: Vec<(String, String)>,
+    body: Bytes,
+}
+
+impl PreparedRequest {
+    /// Creates a request without headers or body.
+    pub fn new(method: Method, uri: impl Into<String>) -> Self {
+        Self {
+            method,
+            uri: uri.into(),
+            headers: Vec::new(),
+            body: Bytes::new(),
+        }
+    }
+
+    /// Adds a header.
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Sets the body, sent with its `Content-Length`.
+    pub fn body(mut self, body: impl Into<Bytes>) -> Self {
+        self.body = body.into();
+        self
+    }
+}
+
+impl HyperHttpConnection {
+    /// Sends `requests` concurrently and returns their responses in the same order.
+    ///
+    /// Requests go through HSTS and the interceptors like requests made through the
+    /// `Connection` trait, but are not retried or checked against the status policy.
+    /// The request in progress on the connection, if any, is left untouched.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use embedded_svc::http::Method;
+    /// use native_svc::HyperHttpConnection;
+    /// use native_svc::batch::PreparedRequest;
+    ///
+    /// let conn = HyperHttpConnection::new().unwrap();
+    /// let requests = (1..=3)
+    ///     .map(|page| PreparedRequest::new(Method::Get, format!("https://example.com/{page}")))
+    ///     .collect();
+    /// for response in conn.send_all(requests) {
+    ///     println!("{}", response.unwrap().status());
+    /// }
+    /// ```
+    pub fn send_all(
+        &self,
+        requests: Vec<PreparedRequest>,
+    ) -> Vec<Result<Response<Bytes>, HyperError>> {
+        let pending: Vec<_> = requests
+            .into_iter()
+            .map(|request| self.spawn_prepared(request))
+            .collect();
+        pending
+            .into_iter()
+            .map(|pending| self.rt.block_on(pending?).map_err(io::Error::other)?)
+            .collect()
+    }
+
+    /// Builds `prepared` and starts sending it on the runtime.
+    fn spawn_prepared(
+        &self,
+        prepared: PreparedRequest,
+    ) -> Result<JoinHandle<Result<Response<Bytes>, HyperError>>, HyperError> {
+        let headers: Vec<_> = prepared
+            .headers
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect();
+        let options = RequestOptions::default();
+        let mut request = self.build_request(prepared.method, &prepared.uri, &headers, options)?;
+
+        let request_headers = request.headers_mut();
+        if !prepared.body.is_empty()
+            && !request_headers.contains_key(CONTENT_LENGTH)
+            && !request_headers.contains_key(TRANSFER_ENCODING)
+        {
+            request_headers.insert(CONTENT_LENGTH, HeaderValue::from(prepared.body.len()));
+        }
+        *request.body_mut() = prepared.body;
+        self.intercept(&mut request, false)?;
+
+        let limit = self.limit.clone();
+        let clock = self.clock.clone();
+        let client = self.client.clone();
+        Ok(self.rt.spawn(async move {
+            let _permit = match &limit {
+                Some(limit) => Some(limit.acquire(&*clock).await?),
+                None => None,
+            };
+            exchange(client, request).await
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{ok_response, spawn_handler};
+
+    /// Tests that batched responses come back in request order.
+    #[test]
+    fn test_send_all_in_order() {
+        let (addr, server) = spawn_handler(3, |_, _, body| ok_response("text/plain", body));
+        let conn = HyperHttpConnection::new().unwrap();
+        let uri = format!("http://{addr}/post");
+        let requests = ["first", "second", "third"]
+            .iter()
+            .map(|name| PreparedRequest::new(Method::Post, uri.clone()).body(*name))
+            .collect();
+
+        let responses = conn.send_all(requests);
+        assert_eq!(responses.len(), 3);
+        for (response, name) in responses.into_iter().zip(["first", "second", "third"]) {
+            let response = response.unwrap();
+            assert_eq!(response.status(), 200);
+            assert_eq!(response.body(), name);
+        }
+        assert_eq!(server.join().unwrap().len(), 3);
+    }
+}
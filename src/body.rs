@@ -0,0 +1,63 @@
+//! Request bodies sent by `HyperHttpConnection`.
+//!
+//! Bodies are either buffered in full, with a known length, or streamed from `write`
+//! calls through a channel for uploads of unknown length, which `hyper` sends with
+//! chunked transfer encoding.
+
+use crate::HyperClient;
+use crate::error::HyperError;
+use http_body_util::combinators::BoxBody;
+use http_body_util::{BodyExt, Full};
+use hyper::body::{Body, Bytes, Frame};
+use hyper::{Request, Response};
+use std::convert::Infallible;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::sync::mpsc;
+
+/// Body type of requests sent by `HyperHttpConnection`.
+pub(crate) type RequestBody = BoxBody<Bytes, Infallible>;
+
+/// Number of written chunks queued before `write` waits for the connection.
+pub(crate) const UPLOAD_QUEUE: usize = 16;
+
+/// A request body streamed from `write` calls; it ends when the sender is dropped.
+pub(crate) struct ChannelBody {
+    receiver: mpsc::Receiver<Bytes>,
+}
+
+impl ChannelBody {
+    /// Creates a body and the sender feeding it.
+    pub(crate) fn new() -> (mpsc::Sender<Bytes>, Self) {
+        let (sender, receiver) = mpsc::channel(UPLOAD_QUEUE);
+        (sender, Self { receiver })
+    }
+}
+
+impl Body for ChannelBody {
+    type Data = Bytes;
+    type Error = Infallible;
+
+    /// Yields the next written chunk, or ends once the sender is dropped.
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        self.receiver
+            .poll_recv(cx)
+            .map(|chunk| chunk.map(|data| Ok(Frame::data(data))))
+    }
+}
+
+/// Sends a buffered `request` with `client` and collects the response body.
+pub(crate) async fn exchange(
+    client: HyperClient,
+    request: Request<Bytes>,
+) -> Result<Response<Bytes>, HyperError> {
+    let response = client
+        .request(request.map(|body| Full::new(body).boxed()))
+        .await?;
+    let (parts, body) = response.into_parts();
+    let body = body.collect().await?.to_bytes();
+    Ok(Response::from_parts(parts, body))
+}
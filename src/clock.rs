@@ -0,0 +1,156 @@
+//! Clocks timing retries and queue timeouts of the HTTP client.
+//!
+//! The client reads the time, waits between retries, and times out requests queued
+//! behind a concurrency limit through a `Clock`. The `SystemClock` used by default
+//! sleeps the calling thread, while a `ManualClock` only moves forward when told to,
+//! so backoff behavior can be tested without real sleeps.
+
+use std::fmt::Debug;
+use std::future::{self, Future};
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::task::{Poll, Waker};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Source of the current time and of waits.
+pub trait Clock: Debug + Send + Sync {
+    /// Returns the current time.
+    fn now(&self) -> Instant;
+
+    /// Waits for `duration`.
+    fn sleep(&self, duration: Duration);
+
+    /// Returns a future completing once the clock reaches `deadline`.
+    ///
+    /// The default implementation waits on the Tokio timer, following the system
+    /// clock.
+    fn wait_until(&self, deadline: Instant) -> Pin<Box<dyn Future<Output = ()> + Send + '_>> {
+        Box::pin(tokio::time::sleep_until(deadline.into()))
+    }
+}
+
+/// The monotonic system clock, sleeping the calling thread.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    /// Returns `Instant::now()`.
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    /// Sleeps the calling thread for `duration`.
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration);
+    }
+}
+
+/// A clock that only advances when told to.
+///
+/// Sleeping advances the clock by the requested duration and returns at once, while
+/// futures waiting for a deadline complete once the clock is advanced past it.
+///
+/// # Example
+///
+/// ```no_run
+/// use native_svc::HyperHttpConnection;
+/// use native_svc::clock::ManualClock;
+/// use native_svc::retry::RetryPolicy;
+/// use std::sync::Arc;
+///
+/// let clock = Arc::new(ManualClock::new());
+/// let conn = HyperHttpConnection::new()
+///     .unwrap()
+///     .with_retry(RetryPolicy::new(3))
+///     .with_clock(clock.clone());
+/// ```
+#[derive(Debug)]
+pub struct ManualClock {
+    start: Instant,
+    elapsed: Mutex<Duration>,
+    /// Futures waiting for the clock to advance.
+    waiters: Mutex<Vec<Waker>>,
+}
+
+impl ManualClock {
+    /// Creates a clock starting at the current time.
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            elapsed: Mutex::new(Duration::ZERO),
+            waiters: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Moves the clock forward by `duration`.
+    pub fn advance(&self, duration: Duration) {
+        let mut elapsed = self.elapsed.lock().unwrap_or_else(|e| e.into_inner());
+        *elapsed += duration;
+        drop(elapsed);
+
+        let mut waiters = self.waiters.lock().unwrap_or_else(|e| e.into_inner());
+        waiters.drain(..).for_each(Waker::wake);
+    }
+
+    /// Returns how far the clock moved since it was created.
+    pub fn elapsed(&self) -> Duration {
+        *self.elapsed.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Default for ManualClock {
+    /// Creates a clock starting at the current time.
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for ManualClock {
+    /// Returns the creation time plus the elapsed duration.
+    fn now(&self) -> Instant {
+        self.start + self.elapsed()
+    }
+
+    /// Advances the clock by `duration` without waiting.
+    fn sleep(&self, duration: Duration) {
+        self.advance(duration);
+    }
+
+    /// Completes once the clock is advanced to `deadline`.
+    fn wait_until(&self, deadline: Instant) -> Pin<Box<dyn Future<Output = ()> + Send + '_>> {
+        Box::pin(future::poll_fn(move |cx| {
+            if self.now() >= deadline {
+                return Poll::Ready(());
+            }
+            let mut waiters = self.waiters.lock().unwrap_or_else(|e| e.into_inner());
+            waiters.push(cx.waker().clone());
+            drop(waiters);
+            if self.now() >= deadline {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that a manual clock moves only when advanced or slept on.
+    #[test]
+    fn test_manual_clock() {
+        let clock = ManualClock::new();
+        let start = clock.now();
+        assert_eq!(clock.now(), start);
+
+        clock.advance(Duration::from_secs(5));
+        let before = Instant::now();
+        clock.sleep(Duration::from_secs(60));
+        assert!(before.elapsed() < Duration::from_secs(1));
+        assert_eq!(clock.elapsed(), Duration::from_secs(65));
+        assert_eq!(clock.now() - start, Duration::from_secs(65));
+    }
+}
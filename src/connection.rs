@@ -0,0 +1,229 @@
+//! Information on the connection that carried a response.
+//!
+//! The connector of the client counts the requests written on each connection it
+//! opens, and adds the count to the extensions of the responses received on it,
+//! along with the phases of opening the connection measured by `timing`. A
+//! response to a connection's second request or later came on a connection reused
+//! from the pool, which `ConnectionInfo` reports along with the socket addresses,
+//! so downloads can be traced to the CDN edge or mirror that served them.
+
+use crate::timing::{self, ConnectPhases};
+use hyper::Uri;
+use hyper::rt::{Read, ReadBufCursor, Write};
+use hyper_util::client::legacy::connect::{Connected, Connection};
+use std::future::Future;
+use std::io::{self, IoSlice};
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::task::{Context, Poll};
+use tower_service::Service;
+
+/// Connection that carried the last response of a `HyperHttpConnection`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConnectionInfo {
+    /// Address of the server, or of the proxy forwarding plain HTTP requests.
+    pub peer_addr: SocketAddr,
+    /// Local address of the connection.
+    pub local_addr: SocketAddr,
+    /// `true` if the connection carried earlier requests and was reused from the
+    /// pool rather than opened for this one.
+    pub reused: bool,
+}
+
+/// Number of requests written on a connection, in the extensions of its responses.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct RequestCount(Arc<AtomicUsize>);
+
+impl RequestCount {
+    /// Returns `true` if the response came after the first request of its connection.
+    pub(crate) fn is_reused(&self) -> bool {
+        self.0.load(Ordering::Relaxed) > 1
+    }
+}
+
+/// Connector timing the connections of `C` and counting the requests written on them.
+#[derive(Clone)]
+pub(crate) struct TrackingConnector<C> {
+    inner: C,
+}
+
+impl<C> TrackingConnector<C> {
+    /// Tracks the connections of `inner`.
+    pub(crate) fn new(inner: C) -> Self {
+        Self { inner }
+    }
+}
+
+impl<C> Service<Uri> for TrackingConnector<C>
+where
+    C: Service<Uri>,
+    C::Future: Send + 'static,
+{
+    type Response = TrackedStream<C::Response>;
+    type Error = C::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, C::Error>> + Send>>;
+
+    /// Waits until the inner connector is ready.
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    /// Connects to `dst` with the inner connector, measuring the phases.
+    fn call(&mut self, dst: Uri) -> Self::Future {
+        let connecting = self.inner.call(dst);
+        Box::pin(async move {
+            let (stream, phases) = timing::measure(connecting).await;
+            Ok(TrackedStream::new(stream?, phases))
+        })
+    }
+}
+
+/// A connection counting the requests written on it.
+///
+/// HTTP/1 alternates between writing a request and reading its response, so each
+/// write following a read, or the first write, starts a new request.
+pub(crate) struct TrackedStream<T> {
+    inner: T,
+    requests: RequestCount,
+    phases: ConnectPhases,
+    /// Whether the current request is being written.
+    writing: bool,
+}
+
+impl<T> TrackedStream<T> {
+    /// Wraps `inner`, opened in `phases`, on which no request was written yet.
+    fn new(inner: T, phases: ConnectPhases) -> Self {
+        Self {
+            inner,
+            requests: RequestCount::default(),
+            phases,
+            writing: false,
+        }
+    }
+
+    /// Counts a new request if `written` starts one.
+    fn count_write(&mut self, written: &Poll<io::Result<usize>>) {
+        if matches!(written, Poll::Ready(Ok(length)) if *length > 0) && !self.writing {
+            self.writing = true;
+            self.requests.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+impl<T: Connection> Connection for TrackedStream<T> {
+    /// Returns the connection info of the inner connection, the request count and
+    /// the phases of opening it.
+    fn connected(&self) -> Connected {
+        self.inner
+            .connected()
+            .extra(self.requests.clone())
+            .extra(self.phases)
+    }
+}
+
+impl<T: Read + Unpin> Read for TrackedStream<T> {
+    /// Reads from the inner connection, ending the request being written.
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: ReadBufCursor<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let read = Pin::new(&mut this.inner).poll_read(cx, buf);
+        if read.is_ready() {
+            this.writing = false;
+        }
+        read
+    }
+}
+
+impl<T: Write + Unpin> Write for TrackedStream<T> {
+    /// Writes to the inner connection.
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let written = Pin::new(&mut this.inner).poll_write(cx, buf);
+        this.count_write(&written);
+        written
+    }
+
+    /// Writes a list of buffers to the inner connection.
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let written = Pin::new(&mut this.inner).poll_write_vectored(cx, bufs);
+        this.count_write(&written);
+        written
+    }
+
+    /// Returns `true` if the inner connection writes buffer lists efficiently.
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+
+    /// Flushes the inner connection.
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    /// Shuts down the inner connection.
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::HyperHttpConnection;
+    use embedded_svc::http::Method;
+    use embedded_svc::http::client::Connection;
+    use embedded_svc::io::Read;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// Tests the peer address of responses, and that a kept-alive connection is
+    /// reported as reused by the second request.
+    #[test]
+    fn test_connection_info() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            for _ in 0..2 {
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+                stream
+                    .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok")
+                    .unwrap();
+            }
+        });
+
+        let mut conn = HyperHttpConnection::new().unwrap();
+        assert!(conn.connection_info().is_none());
+        let mut reused = Vec::new();
+        for _ in 0..2 {
+            conn.initiate_request(Method::Get, &format!("http://{addr}/"), &[])
+                .unwrap();
+            conn.initiate_response().unwrap();
+            let info = conn.connection_info().unwrap();
+            assert_eq!(info.peer_addr, addr);
+            reused.push(info.reused);
+            conn.read(&mut [0u8; 8]).unwrap();
+            assert_eq!(conn.read(&mut [0u8; 8]).unwrap(), 0);
+        }
+        assert_eq!(reused, [false, true]);
+        server.join().unwrap();
+    }
+}
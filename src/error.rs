@@ -3,8 +3,11 @@
 //! Defines a unified `HyperError` enum wrapping IO, HTTP, and `hyper` library errors,
 //! as well as connector-specific conditions like missing requests/responses and unsupported methods.
 
+use crate::state::ConnectionState;
 use embedded_svc::io::{Error as SvcError, ErrorKind as SvcErrorKind};
-use hyper::http;
+use hyper::body::Bytes;
+use hyper::{HeaderMap, StatusCode, http};
+use std::error::Error as StdError;
 use std::io;
 use hyper::header::{InvalidHeaderName, InvalidHeaderValue};
 use thiserror::Error;
@@ -40,7 +43,22 @@ pub enum HyperError {
 
     /// Failed to initialize the Tokio runtime.
     #[error("tokio runtime initialization error: {0:?}")]
-    RuntimeCreation(io::Error),
+    RuntimeCreation(#[source] io::Error),
+
+    /// Failed to build the TLS connector from the configured settings.
+    #[cfg(feature = "tls")]
+    #[error("tls error: {0:?}")]
+    Tls(#[from] native_tls::Error),
+
+    /// The server certificate is not one of the certificates pinned for the host.
+    #[cfg(feature = "tls")]
+    #[error("certificate of {0} is not pinned")]
+    CertificateNotPinned(String),
+
+    /// An `https://` URI was requested from a build without feature `tls`.
+    #[cfg(not(feature = "tls"))]
+    #[error("https is unavailable without the tls feature")]
+    HttpsUnavailable,
 
     /// The connector did not support an HTTP method.
     #[error("unsupported http method: {0}")]
@@ -54,6 +72,74 @@ pub enum HyperError {
     #[error("no request initialized")]
     NoRequest,
 
+    /// The connection closed while a streamed request body was being written.
+    #[error("request body upload aborted")]
+    UploadAborted,
+
+    /// The connection was used in a state that does not allow it, with strict state
+    /// checking enabled.
+    #[error("invalid connection state: expected {expected:?}, was {actual:?}")]
+    InvalidState {
+        /// State the call requires.
+        expected: ConnectionState,
+        /// State the connection was in.
+        actual: ConnectionState,
+    },
+
+    /// The OAuth 2.0 token endpoint refused the request or sent an invalid token.
+    #[cfg(feature = "oauth2")]
+    #[error("token request failed: {0}")]
+    TokenRequest(String),
+
+    /// The response body uses a character encoding that cannot be decoded.
+    #[error("unsupported charset: {0}")]
+    UnsupportedCharset(String),
+
+    /// The runtime still had blocking tasks running when its shutdown timed out.
+    #[error("runtime shutdown timed out after {0:?}")]
+    ShutdownTimeout(std::time::Duration),
+
+    /// The queue of a `ConcurrencyLimit` was full when a request was made.
+    #[error("request queue full")]
+    QueueFull,
+
+    /// A request waited longer than the queue timeout of a `ConcurrencyLimit`.
+    #[error("request queued for more than {0:?}")]
+    QueueTimeout(std::time::Duration),
+
+    /// The raw connection was used without being upgraded by a `101` response or a
+    /// `CONNECT` tunnel.
+    #[error("connection not upgraded")]
+    NotUpgraded,
+
+    /// The proxy URI has no host.
+    #[cfg(feature = "proxy")]
+    #[error("invalid proxy uri: {0}")]
+    InvalidProxy(String),
+
+    /// The proxy still required authentication after its challenge was answered.
+    #[cfg(feature = "proxy")]
+    #[error("proxy authentication required")]
+    ProxyAuthRequired,
+
+    /// The proxy refused to open a `CONNECT` tunnel.
+    #[cfg(feature = "proxy")]
+    #[error("proxy tunnel refused with status {0}")]
+    ProxyTunnel(StatusCode),
+
+    /// A `CONNECT` tunnel was requested to a destination no proxy handles.
+    #[cfg(feature = "proxy")]
+    #[error("no proxy for tunnel to {0}")]
+    NoProxy(String),
+
+    /// More redirects than the connection's `RedirectPolicy` allows were returned.
+    #[error("too many redirects (limit {0})")]
+    TooManyRedirects(usize),
+
+    /// The response status matched the connection's `StatusPolicy`.
+    #[error("{0}")]
+    Status(Box<StatusError>),
+
     /// A header name provided was invalid, according to HTTP specifications.
     #[error("invalid header name: {0:?}")]
     InvalidHeaderName(#[from] InvalidHeaderName),
@@ -63,9 +149,694 @@ pub enum HyperError {
     InvalidHeaderValue(#[from] InvalidHeaderValue),
 }
 
+impl HyperError {
+    /// Returns the stable code of the failure, to branch on without matching the
+    /// variants of every feature.
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            HyperError::Io(_) => ErrorCode::Io,
+            HyperError::Http(_) => ErrorCode::Http,
+            HyperError::Hyper(_) => ErrorCode::Hyper,
+            HyperError::Client(_) => ErrorCode::Client,
+            HyperError::RuntimeCreation(_) => ErrorCode::RuntimeCreation,
+            #[cfg(feature = "tls")]
+            HyperError::Tls(_) => ErrorCode::Tls,
+            #[cfg(feature = "tls")]
+            HyperError::CertificateNotPinned(_) => ErrorCode::CertificateNotPinned,
+            #[cfg(not(feature = "tls"))]
+            HyperError::HttpsUnavailable => ErrorCode::HttpsUnavailable,
+            HyperError::UnsupportedMethod(_) => ErrorCode::UnsupportedMethod,
+            HyperError::NoResponse => ErrorCode::NoResponse,
+            HyperError::NoRequest => ErrorCode::NoRequest,
+            HyperError::UploadAborted => ErrorCode::UploadAborted,
+            HyperError::InvalidState { .. } => ErrorCode::InvalidState,
+            #[cfg(feature = "oauth2")]
+            HyperError::TokenRequest(_) => ErrorCode::TokenRequest,
+            HyperError::UnsupportedCharset(_) => ErrorCode::UnsupportedCharset,
+            HyperError::ShutdownTimeout(_) => ErrorCode::ShutdownTimeout,
+            HyperError::QueueFull => ErrorCode::QueueFull,
+            HyperError::QueueTimeout(_) => ErrorCode::QueueTimeout,
+            HyperError::NotUpgraded => ErrorCode::NotUpgraded,
+            #[cfg(feature = "proxy")]
+            HyperError::InvalidProxy(_) => ErrorCode::InvalidProxy,
+            #[cfg(feature = "proxy")]
+            HyperError::ProxyAuthRequired => ErrorCode::ProxyAuthRequired,
+            #[cfg(feature = "proxy")]
+            HyperError::ProxyTunnel(_) => ErrorCode::ProxyTunnel,
+            #[cfg(feature = "proxy")]
+            HyperError::NoProxy(_) => ErrorCode::NoProxy,
+            HyperError::TooManyRedirects(_) => ErrorCode::TooManyRedirects,
+            HyperError::Status(_) => ErrorCode::Status,
+            HyperError::InvalidHeaderName(_) => ErrorCode::InvalidHeaderName,
+            HyperError::InvalidHeaderValue(_) => ErrorCode::InvalidHeaderValue,
+        }
+    }
+
+    /// Returns the `hyper` error behind the failure, if any, including one wrapped
+    /// by the client connector.
+    pub fn as_hyper(&self) -> Option<&hyper::Error> {
+        find_source(self)
+    }
+
+    /// Returns the I/O error behind the failure, if any, including one wrapped by
+    /// `hyper` or the client connector.
+    pub fn as_io(&self) -> Option<&io::Error> {
+        find_source(self)
+    }
+}
+
+/// Stable code of a `HyperError`, defined whatever the enabled features.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorCode {
+    /// `HyperError::Io`.
+    Io,
+    /// `HyperError::Http`.
+    Http,
+    /// `HyperError::Hyper`.
+    Hyper,
+    /// `HyperError::Client`.
+    Client,
+    /// `HyperError::RuntimeCreation`.
+    RuntimeCreation,
+    /// `HyperError::Tls`.
+    Tls,
+    /// `HyperError::CertificateNotPinned`.
+    CertificateNotPinned,
+    /// `HyperError::HttpsUnavailable`.
+    HttpsUnavailable,
+    /// `HyperError::UnsupportedMethod`.
+    UnsupportedMethod,
+    /// `HyperError::NoResponse`.
+    NoResponse,
+    /// `HyperError::NoRequest`.
+    NoRequest,
+    /// `HyperError::UploadAborted`.
+    UploadAborted,
+    /// `HyperError::InvalidState`.
+    InvalidState,
+    /// `HyperError::TokenRequest`.
+    TokenRequest,
+    /// `HyperError::UnsupportedCharset`.
+    UnsupportedCharset,
+    /// `HyperError::ShutdownTimeout`.
+    ShutdownTimeout,
+    /// `HyperError::QueueFull`.
+    QueueFull,
+    /// `HyperError::QueueTimeout`.
+    QueueTimeout,
+    /// `HyperError::NotUpgraded`.
+    NotUpgraded,
+    /// `HyperError::InvalidProxy`.
+    InvalidProxy,
+    /// `HyperError::ProxyAuthRequired`.
+    ProxyAuthRequired,
+    /// `HyperError::ProxyTunnel`.
+    ProxyTunnel,
+    /// `HyperError::NoProxy`.
+    NoProxy,
+    /// `HyperError::TooManyRedirects`.
+    TooManyRedirects,
+    /// `HyperError::Status`.
+    Status,
+    /// `HyperError::InvalidHeaderName`.
+    InvalidHeaderName,
+    /// `HyperError::InvalidHeaderValue`.
+    InvalidHeaderValue,
+}
+
+impl ErrorCode {
+    /// Returns the code as a `snake_case` name, for logs and metrics labels.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCode::Io => "io",
+            ErrorCode::Http => "http",
+            ErrorCode::Hyper => "hyper",
+            ErrorCode::Client => "client",
+            ErrorCode::RuntimeCreation => "runtime_creation",
+            ErrorCode::Tls => "tls",
+            ErrorCode::CertificateNotPinned => "certificate_not_pinned",
+            ErrorCode::HttpsUnavailable => "https_unavailable",
+            ErrorCode::UnsupportedMethod => "unsupported_method",
+            ErrorCode::NoResponse => "no_response",
+            ErrorCode::NoRequest => "no_request",
+            ErrorCode::UploadAborted => "upload_aborted",
+            ErrorCode::InvalidState => "invalid_state",
+            ErrorCode::TokenRequest => "token_request",
+            ErrorCode::UnsupportedCharset => "unsupported_charset",
+            ErrorCode::ShutdownTimeout => "shutdown_timeout",
+            ErrorCode::QueueFull => "queue_full",
+            ErrorCode::QueueTimeout => "queue_timeout",
+            ErrorCode::NotUpgraded => "not_upgraded",
+            ErrorCode::InvalidProxy => "invalid_proxy",
+            ErrorCode::ProxyAuthRequired => "proxy_auth_required",
+            ErrorCode::ProxyTunnel => "proxy_tunnel",
+            ErrorCode::NoProxy => "no_proxy",
+            ErrorCode::TooManyRedirects => "too_many_redirects",
+            ErrorCode::Status => "status",
+            ErrorCode::InvalidHeaderName => "invalid_header_name",
+            ErrorCode::InvalidHeaderValue => "invalid_header_value",
+        }
+    }
+}
+
 impl SvcError for HyperError {
     /// Maps all `HyperError` variants to `ErrorKind::Other` for embedded-svc.
     fn kind(&self) -> SvcErrorKind {
         SvcErrorKind::Other
     }
 }
+
+impl From<HyperError> for io::Error {
+    /// Wraps `error` in an `io::Error` of the closest kind, keeping it as the inner
+    /// error. I/O errors are returned as they are.
+    fn from(error: HyperError) -> Self {
+        let error = match error {
+            HyperError::Io(error) => return error,
+            error => error,
+        };
+        let kind = match &error {
+            HyperError::Io(error) | HyperError::RuntimeCreation(error) => error.kind(),
+            HyperError::Hyper(error) => find_source::<io::Error>(error)
+                .map(io::Error::kind)
+                .unwrap_or(if error.is_timeout() {
+                    io::ErrorKind::TimedOut
+                } else if error.is_incomplete_message() {
+                    io::ErrorKind::UnexpectedEof
+                } else if error.is_parse() {
+                    io::ErrorKind::InvalidData
+                } else {
+                    io::ErrorKind::Other
+                }),
+            HyperError::Client(error) => find_source::<io::Error>(error)
+                .map(io::Error::kind)
+                .unwrap_or(io::ErrorKind::Other),
+            #[cfg(feature = "tls")]
+            HyperError::Tls(_) => io::ErrorKind::Other,
+            #[cfg(feature = "tls")]
+            HyperError::CertificateNotPinned(_) => io::ErrorKind::InvalidData,
+            #[cfg(not(feature = "tls"))]
+            HyperError::HttpsUnavailable => io::ErrorKind::Unsupported,
+            HyperError::UnsupportedMethod(_) => io::ErrorKind::Unsupported,
+            HyperError::NoResponse | HyperError::NoRequest | HyperError::NotUpgraded => {
+                io::ErrorKind::NotConnected
+            }
+            HyperError::UploadAborted => io::ErrorKind::BrokenPipe,
+            HyperError::InvalidState { .. } => io::ErrorKind::InvalidInput,
+            #[cfg(feature = "oauth2")]
+            HyperError::TokenRequest(_) => io::ErrorKind::PermissionDenied,
+            HyperError::UnsupportedCharset(_) => io::ErrorKind::InvalidData,
+            HyperError::ShutdownTimeout(_) | HyperError::QueueTimeout(_) => io::ErrorKind::TimedOut,
+            HyperError::QueueFull => io::ErrorKind::ResourceBusy,
+            #[cfg(feature = "proxy")]
+            HyperError::InvalidProxy(_) | HyperError::NoProxy(_) => io::ErrorKind::InvalidInput,
+            #[cfg(feature = "proxy")]
+            HyperError::ProxyAuthRequired => io::ErrorKind::PermissionDenied,
+            #[cfg(feature = "proxy")]
+            HyperError::ProxyTunnel(_) => io::ErrorKind::ConnectionRefused,
+            HyperError::TooManyRedirects(_) | HyperError::Status(_) => io::ErrorKind::Other,
+            HyperError::Http(_)
+            | HyperError::InvalidHeaderName(_)
+            | HyperError::InvalidHeaderValue(_) => io::ErrorKind::InvalidInput,
+        };
+        io::Error::new(kind, error)
+    }
+}
+
+/// Returns the first error of type `T` among `error` and its sources.
+fn find_source<'a, T: StdError + 'static>(error: &'a (dyn StdError + 'static)) -> Option<&'a T> {
+    std::iter::successors(Some(error), |&error| error.source())
+        .find_map(|error| error.downcast_ref::<T>())
+}
+
+/// A response turned into an error by a `StatusPolicy`.
+#[derive(Error, Debug)]
+#[error("error status: {status}")]
+pub struct StatusError {
+    /// Status of the response.
+    pub status: StatusCode,
+    /// Headers of the response.
+    pub headers: HeaderMap,
+    /// Start of the response body, up to the policy's capture limit.
+    pub body: Bytes,
+    /// `true` if the body was longer than `body`.
+    pub truncated: bool,
+}
+
+/// Errors produced by the WebSocket client.
+#[cfg(feature = "ws")]
+#[derive(Error, Debug)]
+pub enum WsError {
+    /// Underlying I/O error.
+    #[error("io error: {0:?}")]
+    Io(#[from] io::Error),
+
+    /// Error returned by `tungstenite` during the handshake or frame processing.
+    #[error("websocket error: {0:?}")]
+    WebSocket(Box<tokio_tungstenite::tungstenite::Error>),
+
+    /// Failed to initialize the Tokio runtime.
+    #[error("tokio runtime initialization error: {0:?}")]
+    RuntimeCreation(io::Error),
+
+    /// Failed to build the TLS connector from the configured settings.
+    #[error("tls error: {0:?}")]
+    Tls(#[from] native_tls::Error),
+
+    /// The server certificate is not one of the certificates pinned in `TlsConfig`.
+    #[error("certificate of {0} is not pinned")]
+    CertificateNotPinned(String),
+
+    /// The WebSocket connection has been closed.
+    #[error("websocket connection closed")]
+    Closed,
+
+    /// The receive buffer cannot hold the pending frame.
+    #[error("receive buffer too small: {0} bytes required")]
+    BufferTooSmall(usize),
+
+    /// A text frame did not contain valid UTF-8.
+    #[error("text frame is not valid utf-8")]
+    InvalidUtf8,
+
+    /// A continuation frame was sent without a preceding fragmented frame.
+    #[error("continuation frame without an initial fragment")]
+    UnexpectedContinuation,
+
+    /// The peer negotiated an extension parameter that is not supported.
+    #[error("unsupported websocket extension parameter: {0}")]
+    UnsupportedExtension(String),
+
+    /// An incoming message exceeded the configured size limit.
+    #[error("websocket message too large: {0} bytes")]
+    MessageTooLarge(usize),
+
+    /// Compressing an outgoing message failed.
+    #[cfg(feature = "ws-deflate")]
+    #[error("deflate compression error: {0:?}")]
+    Compress(#[from] flate2::CompressError),
+
+    /// Inflating an incoming message failed.
+    #[cfg(feature = "ws-deflate")]
+    #[error("deflate decompression error: {0:?}")]
+    Decompress(#[from] flate2::DecompressError),
+}
+
+#[cfg(feature = "ws")]
+impl From<tokio_tungstenite::tungstenite::Error> for WsError {
+    fn from(error: tokio_tungstenite::tungstenite::Error) -> Self {
+        match error {
+            // Errors raised by the streams underneath `tungstenite` travel as `io::Error`s.
+            tokio_tungstenite::tungstenite::Error::Io(error)
+                if error.get_ref().is_some_and(|inner| inner.is::<WsError>()) =>
+            {
+                *error
+                    .into_inner()
+                    .and_then(|inner| inner.downcast().ok())
+                    .expect("inner error checked by the guard")
+            }
+            error => Self::WebSocket(Box::new(error)),
+        }
+    }
+}
+
+#[cfg(feature = "ws")]
+impl SvcError for WsError {
+    /// Maps all `WsError` variants to `ErrorKind::Other` for embedded-svc.
+    fn kind(&self) -> SvcErrorKind {
+        SvcErrorKind::Other
+    }
+}
+
+/// Errors produced by the MQTT client.
+#[cfg(feature = "mqtt")]
+#[derive(Error, Debug)]
+pub enum MqttError {
+    /// The request could not be handed to the `rumqttc` event loop.
+    #[error("mqtt client error: {0:?}")]
+    Client(Box<rumqttc::ClientError>),
+
+    /// The connection to the broker failed; `rumqttc` reconnects on the next poll.
+    #[error("mqtt connection error: {0:?}")]
+    Connection(Box<rumqttc::ConnectionError>),
+
+    /// The broker URL could not be parsed or uses an unsupported scheme.
+    #[error("invalid mqtt url: {0}")]
+    InvalidUrl(String),
+
+    /// The TLS settings for the broker connection are invalid.
+    #[error("mqtt tls error: {0}")]
+    Tls(#[from] native_tls::Error),
+
+    /// The request could not be handed to the `rumqttc` MQTT 5 event loop.
+    #[error("mqtt client error: {0:?}")]
+    ClientV5(Box<rumqttc::v5::ClientError>),
+
+    /// The MQTT 5 connection to the broker failed; `rumqttc` reconnects on the next poll.
+    #[error("mqtt connection error: {0:?}")]
+    ConnectionV5(Box<rumqttc::v5::ConnectionError>),
+
+    /// The event loop stopped because every client handle was dropped.
+    #[error("mqtt connection closed")]
+    Disconnected,
+
+    /// The offline queue is full and its overflow policy rejects new publishes.
+    #[error("mqtt offline queue full ({0} publishes)")]
+    QueueFull(usize),
+
+    /// The held publish with this id was discarded to make room in the full offline queue.
+    #[error("mqtt publish {0} dropped from the full offline queue")]
+    PublishDropped(embedded_svc::mqtt::client::MessageId),
+
+    /// The operation is not available with the configured protocol version.
+    #[error("unsupported mqtt operation: {0}")]
+    Unsupported(&'static str),
+}
+
+#[cfg(feature = "mqtt")]
+impl From<rumqttc::ClientError> for MqttError {
+    fn from(error: rumqttc::ClientError) -> Self {
+        Self::Client(Box::new(error))
+    }
+}
+
+#[cfg(feature = "mqtt")]
+impl From<rumqttc::ConnectionError> for MqttError {
+    fn from(error: rumqttc::ConnectionError) -> Self {
+        Self::Connection(Box::new(error))
+    }
+}
+
+#[cfg(feature = "mqtt")]
+impl From<rumqttc::v5::ClientError> for MqttError {
+    fn from(error: rumqttc::v5::ClientError) -> Self {
+        Self::ClientV5(Box::new(error))
+    }
+}
+
+#[cfg(feature = "mqtt")]
+impl From<rumqttc::v5::ConnectionError> for MqttError {
+    fn from(error: rumqttc::v5::ConnectionError) -> Self {
+        Self::ConnectionV5(Box::new(error))
+    }
+}
+
+/// Errors produced by the storage backends.
+#[cfg(feature = "storage")]
+#[derive(Error, Debug)]
+pub enum StorageError {
+    /// Reading or writing the backing files failed.
+    #[error("storage io error: {0:?}")]
+    Io(#[from] io::Error),
+
+    /// The key is empty or longer than the NVS limit.
+    #[error("invalid storage key: {0:?}")]
+    InvalidKey(String),
+
+    /// The read buffer cannot hold the stored value.
+    #[error("storage buffer too small: {0} bytes required")]
+    BufferTooSmall(usize),
+
+    /// The stored value and its backup both failed their integrity check.
+    #[error("storage value corrupted: {0:?}")]
+    Corrupted(String),
+
+    /// The value under the given key could not be encrypted or failed authentication.
+    #[cfg(feature = "storage-encryption")]
+    #[error("storage value {0:?} failed encryption or authentication")]
+    Crypto(String),
+
+    /// The OS keyring could not provide the encryption secret.
+    #[cfg(feature = "storage-keyring")]
+    #[error("keyring error: {0:?}")]
+    Keyring(#[from] keyring::Error),
+
+    /// The `redb` database failed to open, read, or commit.
+    #[cfg(feature = "storage-redb")]
+    #[error("storage database error: {0:?}")]
+    Database(Box<redb::Error>),
+}
+
+#[cfg(feature = "storage-redb")]
+impl From<redb::Error> for StorageError {
+    fn from(error: redb::Error) -> Self {
+        Self::Database(Box::new(error))
+    }
+}
+
+/// Errors produced by the OTA partitions.
+#[cfg(feature = "ota")]
+#[derive(Error, Debug)]
+pub enum OtaError {
+    /// Reading or writing a partition image failed.
+    #[error("ota io error: {0:?}")]
+    Io(#[from] io::Error),
+
+    /// The `otadata` file could not be parsed.
+    #[error("invalid ota data entry: {0:?}")]
+    InvalidOtaData(String),
+
+    /// The slot label is not valid.
+    #[error("invalid ota slot: {0:?}")]
+    InvalidSlot(String),
+
+    /// A factory reset was requested but no factory image is present.
+    #[error("no factory image")]
+    NoFactoryImage,
+
+    /// No valid slot is left to roll back to.
+    #[error("no valid ota slot to boot")]
+    NoValidSlot,
+
+    /// The running slot was rejected and the simulated device rebooted into another slot.
+    #[error("rebooted into ota slot {0:?}")]
+    Rebooted(String),
+
+    /// The image does not have the expected SHA-256 digest.
+    #[cfg(feature = "ota-verify")]
+    #[error("ota image digest mismatch")]
+    DigestMismatch,
+
+    /// The image signature is missing or does not match the configured public key.
+    #[cfg(feature = "ota-verify")]
+    #[error("ota image signature invalid")]
+    InvalidSignature,
+
+    /// The configured public key could not be parsed.
+    #[cfg(feature = "ota-verify")]
+    #[error("invalid ota public key: {0}")]
+    InvalidPublicKey(String),
+}
+
+#[cfg(feature = "ota")]
+impl SvcError for OtaError {
+    /// Maps all `OtaError` variants to `ErrorKind::Other` for embedded-svc.
+    fn kind(&self) -> SvcErrorKind {
+        SvcErrorKind::Other
+    }
+}
+
+/// Errors produced while downloading a firmware image into an OTA slot.
+#[cfg(feature = "ota-http")]
+#[derive(Error, Debug)]
+pub enum HttpOtaError<E> {
+    /// The HTTP request failed.
+    #[error("ota download error: {0:?}")]
+    Http(#[from] HyperError),
+
+    /// The OTA implementation rejected the update.
+    #[error("ota update error: {0:?}")]
+    Ota(E),
+
+    /// The server answered with an unexpected status.
+    #[error("unexpected ota download status: {0}")]
+    Status(u16),
+
+    /// The download ended before the whole image was received.
+    #[error("ota download incomplete: {0} of {1} bytes")]
+    Incomplete(u64, u64),
+
+    /// The downloaded image does not have the expected SHA-256 digest.
+    #[error("ota image digest mismatch")]
+    DigestMismatch,
+}
+
+/// Errors produced by the timer service.
+#[cfg(feature = "timer")]
+#[derive(Error, Debug)]
+pub enum TimerError {
+    /// Spawning the dispatch thread failed.
+    #[error("timer io error: {0:?}")]
+    Io(#[from] io::Error),
+}
+
+/// Errors produced by the event bus.
+#[cfg(feature = "event-bus")]
+#[derive(Error, Debug)]
+pub enum EventBusError {
+    /// Spawning the dispatch thread failed.
+    #[error("event bus io error: {0:?}")]
+    Io(#[from] io::Error),
+
+    /// The bus has been closed.
+    #[error("event bus closed")]
+    Closed,
+
+    /// The subscription fell behind and the given number of events were dropped.
+    #[error("event bus subscription lagged by {0} events")]
+    Lagged(u64),
+
+    /// The bounded event queue is full.
+    #[error("event bus queue full")]
+    QueueFull,
+}
+
+/// Errors produced by the Wi-Fi drivers.
+#[cfg(feature = "wifi")]
+#[derive(Error, Debug)]
+pub enum WifiError {
+    /// The driver has not been started.
+    #[error("wifi not started")]
+    NotStarted,
+
+    /// The configuration has no station part to connect with.
+    #[error("wifi configuration has no client")]
+    InvalidConfiguration,
+
+    /// No access point with the given SSID is in range.
+    #[error("wifi access point not found: {0}")]
+    NotFound(String),
+
+    /// The access point with the given SSID rejected the credentials.
+    #[error("wifi authentication failed: {0}")]
+    AuthFailed(String),
+
+    /// Running the system Wi-Fi tool failed.
+    #[error("wifi io error: {0:?}")]
+    Io(#[from] io::Error),
+
+    /// The system Wi-Fi tool reported an error.
+    #[error("wifi command failed: {0}")]
+    Command(String),
+
+    /// No matching Wi-Fi interface was found.
+    #[error("no wifi interface")]
+    NoInterface,
+
+    /// A failure injected in `MockWifi` for the given operation.
+    #[error("injected wifi {0} failure")]
+    Injected(&'static str),
+}
+
+/// Errors produced by the Ethernet driver.
+#[cfg(feature = "eth")]
+#[derive(Error, Debug)]
+pub enum EthError {
+    /// Reading interface attributes or running `ip` failed.
+    #[error("eth io error: {0:?}")]
+    Io(#[from] io::Error),
+
+    /// The system network tool reported an error.
+    #[error("eth command failed: {0}")]
+    Command(String),
+
+    /// The interface with the given name does not exist.
+    #[error("no such interface: {0}")]
+    NoInterface(String),
+
+    /// The interface reported an unparsable MAC address.
+    #[error("invalid mac address: {0}")]
+    InvalidMac(String),
+}
+
+/// Errors produced by network interface settings.
+#[cfg(feature = "netif")]
+#[derive(Error, Debug)]
+pub enum NetifError {
+    /// Reading system files or running `ip` failed.
+    #[error("netif io error: {0:?}")]
+    Io(#[from] io::Error),
+
+    /// The system network tool reported an error.
+    #[error("netif command failed: {0}")]
+    Command(String),
+
+    /// The interface with the given name has no IPv4 address.
+    #[error("no ipv4 address on interface: {0}")]
+    NoAddress(String),
+
+    /// The configuration cannot be applied on the host.
+    #[error("unsupported netif configuration")]
+    Unsupported,
+}
+
+/// Errors produced by ICMP echo.
+#[cfg(feature = "ping")]
+#[derive(Error, Debug)]
+pub enum PingError {
+    /// Opening, configuring or using the ICMP socket failed.
+    #[error("ping io error: {0:?}")]
+    Io(#[from] io::Error),
+}
+
+/// Errors produced by the SNTP client.
+#[cfg(feature = "sntp")]
+#[derive(Error, Debug)]
+pub enum SntpError {
+    /// Spawning the polling thread failed.
+    #[error("sntp io error: {0:?}")]
+    Io(#[from] io::Error),
+}
+
+/// Errors produced by the mDNS service.
+#[cfg(feature = "mdns")]
+#[derive(Error, Debug)]
+pub enum MdnsError {
+    /// The mDNS responder reported an error.
+    #[error("mdns error: {0}")]
+    Mdns(#[from] mdns_sd::Error),
+
+    /// No answer arrived before the timeout.
+    #[error("mdns query timed out")]
+    Timeout,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// Tests the kinds of `io::Error`s converted from `HyperError`s, and reaching the
+    /// original error through them.
+    #[test]
+    fn test_io_error_conversion() {
+        let error = io::Error::from(HyperError::Io(io::ErrorKind::ConnectionReset.into()));
+        assert_eq!(error.kind(), io::ErrorKind::ConnectionReset);
+        assert!(error.get_ref().is_none());
+
+        let error = io::Error::from(HyperError::QueueTimeout(Duration::from_secs(1)));
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+        let inner = error
+            .into_inner()
+            .unwrap()
+            .downcast::<HyperError>()
+            .unwrap();
+        assert!(matches!(*inner, HyperError::QueueTimeout(_)));
+    }
+
+    /// Tests the codes of errors and finding the errors behind them.
+    #[test]
+    fn test_error_code() {
+        let error = HyperError::RuntimeCreation(io::ErrorKind::OutOfMemory.into());
+        assert_eq!(error.code(), ErrorCode::RuntimeCreation);
+        assert_eq!(error.code().as_str(), "runtime_creation");
+        assert!(error.source().is_some());
+        assert_eq!(error.as_io().unwrap().kind(), io::ErrorKind::OutOfMemory);
+        assert!(error.as_hyper().is_none());
+
+        let error = HyperError::QueueFull;
+        assert_eq!(error.code(), ErrorCode::QueueFull);
+        assert!(error.source().is_none());
+        assert!(error.as_io().is_none());
+    }
+}
@@ -0,0 +1,183 @@
+//! Ethernet control implementing `embedded_svc::eth`.
+//!
+//! `NativeEth` manages a wired host interface on Linux: starting and stopping bring the
+//! link administratively up and down with `ip link`, and link status and the MAC
+//! address are read from `/sys/class/net`. Wired-network management code written for
+//! ESP Ethernet drivers therefore runs natively against real interfaces.
+
+use crate::error::EthError;
+use embedded_svc::eth::Eth;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Root of the Linux network interface attributes.
+const SYSFS_NET: &str = "/sys/class/net";
+
+/// `IFF_UP`, set in an interface's flags while it is administratively up.
+const IFF_UP: u32 = 0x1;
+
+/// `ARPHRD_ETHER`, the hardware type of Ethernet interfaces.
+const ARPHRD_ETHER: u32 = 1;
+
+/// An `Eth` driver for a host network interface.
+///
+/// # Example
+///
+/// ```no_run
+/// use embedded_svc::eth::Eth;
+/// use native_svc::eth::NativeEth;
+///
+/// let mut eth = NativeEth::new("eth0");
+/// eth.start().unwrap();
+/// println!("link {}, mac {:02x?}", eth.is_connected().unwrap(), eth.mac().unwrap());
+/// ```
+#[derive(Debug, Clone)]
+pub struct NativeEth {
+    interface: String,
+    dir: PathBuf,
+}
+
+impl NativeEth {
+    /// Creates a driver for the interface `interface`, such as `eth0`.
+    pub fn new(interface: &str) -> Self {
+        Self {
+            interface: interface.to_owned(),
+            dir: Path::new(SYSFS_NET).join(interface),
+        }
+    }
+
+    /// Returns the names of the wired Ethernet interfaces of the host.
+    pub fn interfaces() -> Result<Vec<String>, EthError> {
+        let mut interfaces = Vec::new();
+        for entry in fs::read_dir(SYSFS_NET)? {
+            let path = entry?.path();
+            let ethernet = read_number(&path.join("type")).is_ok_and(|kind| kind == ARPHRD_ETHER);
+            // Wi-Fi interfaces also report Ethernet framing.
+            if ethernet && !path.join("wireless").exists() && !path.join("phy80211").exists() {
+                interfaces.extend(
+                    path.file_name()
+                        .and_then(|name| name.to_str())
+                        .map(str::to_owned),
+                );
+            }
+        }
+        interfaces.sort();
+        Ok(interfaces)
+    }
+
+    /// Returns the interface name.
+    pub fn interface(&self) -> &str {
+        &self.interface
+    }
+
+    /// Returns the MAC address of the interface.
+    pub fn mac(&self) -> Result<[u8; 6], EthError> {
+        let text = self.attribute("address")?;
+        parse_mac(&text).ok_or(EthError::InvalidMac(text))
+    }
+
+    /// Reads the interface attribute `name`.
+    fn attribute(&self, name: &str) -> Result<String, EthError> {
+        match fs::read_to_string(self.dir.join(name)) {
+            Ok(text) => Ok(text.trim().to_owned()),
+            Err(error) if error.kind() == ErrorKind::NotFound && !self.dir.exists() => {
+                Err(EthError::NoInterface(self.interface.clone()))
+            }
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Brings the interface administratively up or down.
+    fn set_link(&self, state: &str) -> Result<(), EthError> {
+        let output = Command::new("ip")
+            .args(["link", "set", "dev", &self.interface, state])
+            .output()?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(EthError::Command(stderr.trim().to_owned()));
+        }
+        Ok(())
+    }
+}
+
+impl Eth for NativeEth {
+    type Error = EthError;
+
+    /// Brings the interface up.
+    fn start(&mut self) -> Result<(), Self::Error> {
+        self.set_link("up")
+    }
+
+    /// Brings the interface down.
+    fn stop(&mut self) -> Result<(), Self::Error> {
+        self.set_link("down")
+    }
+
+    /// Returns `true` if the interface is administratively up.
+    fn is_started(&self) -> Result<bool, Self::Error> {
+        let flags = self.attribute("flags")?;
+        let flags = u32::from_str_radix(flags.trim_start_matches("0x"), 16)
+            .map_err(|_| EthError::Command(format!("invalid flags: {flags}")))?;
+        Ok(flags & IFF_UP != 0)
+    }
+
+    /// Returns `true` if the interface is up and has a carrier.
+    fn is_connected(&self) -> Result<bool, Self::Error> {
+        if !self.is_started()? {
+            return Ok(false);
+        }
+        // `carrier` cannot be read while the interface is down.
+        Ok(self
+            .attribute("carrier")
+            .is_ok_and(|carrier| carrier == "1"))
+    }
+}
+
+/// Reads a decimal number from a sysfs attribute.
+fn read_number(path: &Path) -> Result<u32, EthError> {
+    let text = fs::read_to_string(path)?;
+    text.trim()
+        .parse()
+        .map_err(|_| EthError::Command(format!("invalid number: {}", text.trim())))
+}
+
+/// Parses a colon-separated MAC address.
+fn parse_mac(text: &str) -> Option<[u8; 6]> {
+    let mut mac = [0; 6];
+    let mut octets = text.split(':');
+    for byte in &mut mac {
+        *byte = u8::from_str_radix(octets.next()?, 16).ok()?;
+    }
+    octets.next().is_none().then_some(mac)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests reading link state and MAC address from interface attributes.
+    #[test]
+    fn test_interface_attributes() {
+        let dir = std::env::temp_dir().join(format!("native-svc-eth-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("address"), "02:42:ac:11:00:02\n").unwrap();
+        fs::write(dir.join("flags"), "0x1003\n").unwrap();
+        fs::write(dir.join("carrier"), "1\n").unwrap();
+
+        let eth = NativeEth {
+            interface: "eth0".to_owned(),
+            dir: dir.clone(),
+        };
+        assert_eq!(eth.mac().unwrap(), [0x02, 0x42, 0xac, 0x11, 0x00, 0x02]);
+        assert!(eth.is_started().unwrap());
+        assert!(eth.is_connected().unwrap());
+
+        fs::write(dir.join("flags"), "0x1002\n").unwrap();
+        assert!(!eth.is_connected().unwrap());
+
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(matches!(eth.mac(), Err(EthError::NoInterface(_))));
+    }
+}
@@ -0,0 +1,390 @@
+//! Event bus implementing `embedded_svc::event_bus`.
+//!
+//! `NativeEventBus` mirrors the ESP-IDF default event loop: any thread can post
+//! events, which are queued and delivered to every subscriber in order on a dedicated
+//! dispatch thread. Cross-component firmware events therefore work in host builds.
+//! The `asynch` module provides the async flavor over `tokio` channels.
+//!
+//! Queues are unbounded by default. `NativeEventBus::bounded` limits the queue like
+//! the fixed-size ESP-IDF event queue, with an `Overflow` policy choosing what happens
+//! when it is full, so code can be tested against queue-full behavior.
+
+pub mod asynch;
+#[cfg(feature = "event-bus-bridge")]
+pub mod bridge;
+
+use crate::error::EventBusError;
+use embedded_svc::event_bus::{ErrorType, EventBus, Postbox};
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Callback receiving posted events.
+type Callback<P> = Arc<Mutex<dyn FnMut(&P) + Send>>;
+
+/// Subscribers shared between the bus handles and the dispatch thread.
+struct Subscribers<P> {
+    callbacks: Mutex<BTreeMap<u64, Callback<P>>>,
+    next_id: AtomicU64,
+}
+
+impl<P> Subscribers<P> {
+    /// Delivers `payload` to every current subscriber except `origin`.
+    ///
+    /// Callbacks run without the subscriber list locked, so they may subscribe or
+    /// unsubscribe on the same bus.
+    fn dispatch(&self, payload: &P, origin: Option<u64>) {
+        let callbacks: Vec<_> = self
+            .callbacks
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .filter(|(id, _)| Some(**id) != origin)
+            .map(|(_, callback)| callback.clone())
+            .collect();
+
+        for callback in callbacks {
+            (callback.lock().unwrap_or_else(|e| e.into_inner()))(payload);
+        }
+    }
+}
+
+/// What posting to a full bounded queue does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Overflow {
+    /// Waits for room for up to the `wait` passed to `post`, forever if `None`.
+    #[default]
+    Block,
+    /// Drops the oldest queued event to make room.
+    DropOldest,
+    /// Drops the posted event; `post` returns `false`.
+    DropNewest,
+    /// Fails with `EventBusError::QueueFull`.
+    Error,
+}
+
+/// Queued events and handle count shared with the dispatch thread.
+struct QueueState<P> {
+    /// Events with the subscription that posted them, which does not receive them back.
+    events: VecDeque<(P, Option<u64>)>,
+    /// Live bus handles; the dispatch thread exits when it drops to zero.
+    handles: usize,
+}
+
+/// Event queue between the bus handles and the dispatch thread.
+struct Queue<P> {
+    state: Mutex<QueueState<P>>,
+    not_empty: Condvar,
+    not_full: Condvar,
+    capacity: Option<usize>,
+    overflow: Overflow,
+    /// Events not queued, or evicted, because the queue was full.
+    dropped: AtomicU64,
+}
+
+impl<P> Queue<P> {
+    /// Locks the state, ignoring poisoning by a panicking callback.
+    fn lock(&self) -> MutexGuard<'_, QueueState<P>> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Queues `payload` following the overflow policy, returning `false` if it was dropped.
+    fn push(
+        &self,
+        payload: P,
+        origin: Option<u64>,
+        wait: Option<Duration>,
+    ) -> Result<bool, EventBusError> {
+        let mut state = self.lock();
+        if let Some(capacity) = self.capacity
+            && state.events.len() >= capacity
+        {
+            match self.overflow {
+                Overflow::Block => {
+                    let deadline = wait.map(|wait| Instant::now() + wait);
+                    while state.events.len() >= capacity {
+                        state = match deadline {
+                            None => self.not_full.wait(state).unwrap_or_else(|e| e.into_inner()),
+                            Some(deadline) => {
+                                let now = Instant::now();
+                                if now >= deadline {
+                                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                                    return Ok(false);
+                                }
+                                self.not_full
+                                    .wait_timeout(state, deadline - now)
+                                    .unwrap_or_else(|e| e.into_inner())
+                                    .0
+                            }
+                        };
+                    }
+                }
+                Overflow::DropOldest => {
+                    state.events.pop_front();
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                }
+                Overflow::DropNewest => {
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                    return Ok(false);
+                }
+                Overflow::Error => {
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                    return Err(EventBusError::QueueFull);
+                }
+            }
+        }
+
+        state.events.push_back((payload, origin));
+        drop(state);
+        self.not_empty.notify_one();
+        Ok(true)
+    }
+
+    /// Waits for the next event, or `None` once every handle is dropped and the queue is empty.
+    fn pop(&self) -> Option<(P, Option<u64>)> {
+        let mut state = self.lock();
+        loop {
+            if let Some(event) = state.events.pop_front() {
+                drop(state);
+                self.not_full.notify_one();
+                return Some(event);
+            }
+            if state.handles == 0 {
+                return None;
+            }
+            state = self
+                .not_empty
+                .wait(state)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+    }
+}
+
+/// An `EventBus` and `Postbox` dispatching events on a background thread.
+///
+/// Handles are cheap to clone; the dispatch thread stops once every handle is dropped.
+///
+/// # Example
+///
+/// ```no_run
+/// use embedded_svc::event_bus::{EventBus, Postbox};
+/// use native_svc::event_bus::NativeEventBus;
+///
+/// let bus = NativeEventBus::<u32>::new().unwrap();
+/// let _subscription = bus.subscribe(|level: &u32| println!("level {level}")).unwrap();
+/// bus.post(&42, None).unwrap();
+/// ```
+pub struct NativeEventBus<P> {
+    queue: Arc<Queue<P>>,
+    subscribers: Arc<Subscribers<P>>,
+}
+
+impl<P> NativeEventBus<P>
+where
+    P: Send + 'static,
+{
+    /// Starts the dispatch thread with an unbounded queue.
+    pub fn new() -> Result<Self, EventBusError> {
+        Self::with_queue(None, Overflow::default())
+    }
+
+    /// Starts the dispatch thread with a queue of `capacity` events.
+    ///
+    /// `overflow` decides what posting to a full queue does.
+    pub fn bounded(capacity: usize, overflow: Overflow) -> Result<Self, EventBusError> {
+        Self::with_queue(Some(capacity.max(1)), overflow)
+    }
+
+    /// Starts the dispatch thread.
+    fn with_queue(capacity: Option<usize>, overflow: Overflow) -> Result<Self, EventBusError> {
+        let subscribers = Arc::new(Subscribers {
+            callbacks: Mutex::new(BTreeMap::new()),
+            next_id: AtomicU64::new(0),
+        });
+        let queue = Arc::new(Queue {
+            state: Mutex::new(QueueState {
+                events: VecDeque::new(),
+                handles: 1,
+            }),
+            not_empty: Condvar::new(),
+            not_full: Condvar::new(),
+            capacity,
+            overflow,
+            dropped: AtomicU64::new(0),
+        });
+
+        let receiver = queue.clone();
+        let dispatcher = subscribers.clone();
+        thread::Builder::new()
+            .name("native-svc-event-bus".to_owned())
+            .spawn(move || {
+                while let Some((payload, origin)) = receiver.pop() {
+                    dispatcher.dispatch(&payload, origin);
+                }
+            })?;
+
+        Ok(Self { queue, subscribers })
+    }
+}
+
+impl<P> NativeEventBus<P> {
+    /// Returns how many events were dropped or rejected because the queue was full.
+    pub fn dropped(&self) -> u64 {
+        self.queue.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of events waiting for dispatch.
+    pub fn queued(&self) -> usize {
+        self.queue.lock().events.len()
+    }
+}
+
+impl<P> Clone for NativeEventBus<P> {
+    /// Returns another handle to the same bus.
+    fn clone(&self) -> Self {
+        self.queue.lock().handles += 1;
+        Self {
+            queue: self.queue.clone(),
+            subscribers: self.subscribers.clone(),
+        }
+    }
+}
+
+impl<P> Drop for NativeEventBus<P> {
+    /// Stops the dispatch thread once every handle is dropped and the queue is drained.
+    fn drop(&mut self) {
+        self.queue.lock().handles -= 1;
+        self.queue.not_empty.notify_all();
+    }
+}
+
+impl<P> ErrorType for NativeEventBus<P> {
+    type Error = EventBusError;
+}
+
+impl<P> Postbox<P> for NativeEventBus<P>
+where
+    P: Clone + Send + 'static,
+{
+    /// Queues `payload` for dispatch, returning `false` if it was dropped.
+    ///
+    /// With `Overflow::Block`, `wait` bounds how long to wait for room in a full queue.
+    fn post(&self, payload: &P, wait: Option<Duration>) -> Result<bool, Self::Error> {
+        self.queue.push(payload.clone(), None, wait)
+    }
+}
+
+impl<P> EventBus<P> for NativeEventBus<P>
+where
+    P: Send + 'static,
+{
+    type Subscription<'a>
+        = NativeSubscription<P>
+    where
+        Self: 'a;
+
+    /// Registers `callback` for every event posted after this call.
+    fn subscribe<F>(&self, callback: F) -> Result<Self::Subscription<'_>, Self::Error>
+    where
+        F: FnMut(&P) + Send + 'static,
+    {
+        let id = self.subscribers.next_id.fetch_add(1, Ordering::Relaxed);
+        self.subscribers
+            .callbacks
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(id, Arc::new(Mutex::new(callback)));
+
+        Ok(NativeSubscription {
+            subscribers: self.subscribers.clone(),
+            id,
+        })
+    }
+}
+
+/// A subscription to a `NativeEventBus`; dropping it unsubscribes.
+pub struct NativeSubscription<P> {
+    subscribers: Arc<Subscribers<P>>,
+    id: u64,
+}
+
+impl<P> Drop for NativeSubscription<P> {
+    /// Removes the callback from the bus.
+    fn drop(&mut self) {
+        self.subscribers
+            .callbacks
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    /// Tests that events reach subscribers in order until they unsubscribe.
+    #[test]
+    fn test_post_and_subscribe() {
+        let bus = NativeEventBus::<u32>::new().unwrap();
+        let (tx, rx) = mpsc::channel();
+
+        let subscription = bus.subscribe(move |event: &u32| tx.send(*event).unwrap());
+        let subscription = subscription.unwrap();
+        for event in 0..3 {
+            assert!(bus.clone().post(&event, None).unwrap());
+        }
+        let received: Vec<_> = rx.iter().take(3).collect();
+        assert_eq!(received, [0, 1, 2]);
+
+        drop(subscription);
+        bus.post(&3, None).unwrap();
+        assert!(rx.recv_timeout(Duration::from_millis(50)).is_err());
+    }
+
+    /// Tests each overflow policy while the dispatch thread is blocked.
+    #[test]
+    fn test_bounded_overflow() {
+        let overflow = |policy| {
+            let bus = NativeEventBus::<u32>::bounded(2, policy).unwrap();
+            let (started_tx, started_rx) = mpsc::channel();
+            let (release_tx, release_rx) = mpsc::channel::<()>();
+            let (tx, rx) = mpsc::channel();
+            let _subscription = bus
+                .subscribe(move |event: &u32| {
+                    if *event == 0 {
+                        started_tx.send(()).unwrap();
+                        release_rx.recv().unwrap();
+                    }
+                    tx.send(*event).unwrap();
+                })
+                .unwrap();
+
+            bus.post(&0, None).unwrap();
+            started_rx.recv().unwrap();
+            assert!(bus.post(&1, None).unwrap());
+            assert!(bus.post(&2, None).unwrap());
+            let result = bus.post(&3, Some(Duration::from_millis(10)));
+            assert_eq!(bus.dropped(), 1);
+
+            release_tx.send(()).unwrap();
+            let received: Vec<_> = rx.iter().take(3).collect();
+            (result, received)
+        };
+
+        let (result, received) = overflow(Overflow::Block);
+        assert!(!result.unwrap());
+        assert_eq!(received, [0, 1, 2]);
+        let (result, received) = overflow(Overflow::DropOldest);
+        assert!(result.unwrap());
+        assert_eq!(received, [0, 2, 3]);
+        let (result, received) = overflow(Overflow::DropNewest);
+        assert!(!result.unwrap());
+        assert_eq!(received, [0, 1, 2]);
+        let (result, _) = overflow(Overflow::Error);
+        assert!(matches!(result, Err(EventBusError::QueueFull)));
+    }
+}
@@ -0,0 +1,194 @@
+//! Asynchronous event bus implementing `embedded_svc::event_bus::asynch`.
+//!
+//! `AsyncEventBus` is backed by a `tokio::sync::broadcast` channel: postboxes send
+//! events asynchronously and every subscription receives each event posted after it
+//! subscribed, either by awaiting `recv` or as a `Stream`.
+
+use crate::error::EventBusError;
+use embedded_svc::event_bus::ErrorType;
+use embedded_svc::event_bus::asynch::{EventBus, PostboxProvider, Receiver, Sender};
+use futures_util::Stream;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+/// Default number of events buffered for slow subscribers.
+pub const DEFAULT_CAPACITY: usize = 64;
+
+/// An async event bus delivering every event to every subscription.
+///
+/// A subscription that falls more than the bus capacity behind loses the oldest
+/// events and receives `EventBusError::Lagged` once.
+///
+/// # Example
+///
+/// ```no_run
+/// use embedded_svc::event_bus::asynch::{EventBus, PostboxProvider, Receiver, Sender};
+/// use native_svc::event_bus::asynch::AsyncEventBus;
+///
+/// # async fn run() -> Result<(), native_svc::error::EventBusError> {
+/// let bus = AsyncEventBus::<u32>::new(16);
+/// let mut subscription = bus.subscribe().await?;
+///
+/// bus.postbox().await?.send(42).await?;
+/// assert_eq!(subscription.recv().await?, 42);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct AsyncEventBus<P> {
+    sender: broadcast::Sender<P>,
+}
+
+impl<P> AsyncEventBus<P>
+where
+    P: Clone + Send + 'static,
+{
+    /// Creates a bus buffering up to `capacity` events per subscription.
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity.max(1));
+        Self { sender }
+    }
+
+    /// Posts `payload` without waiting, returning how many subscriptions will receive it.
+    pub fn post(&self, payload: P) -> usize {
+        self.sender.send(payload).unwrap_or(0)
+    }
+}
+
+impl<P> Default for AsyncEventBus<P>
+where
+    P: Clone + Send + 'static,
+{
+    /// Creates a bus with `DEFAULT_CAPACITY`.
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+
+impl<P> ErrorType for AsyncEventBus<P> {
+    type Error = EventBusError;
+}
+
+impl<P> EventBus<P> for AsyncEventBus<P>
+where
+    P: Clone + Send + 'static,
+{
+    type Subscription<'a>
+        = AsyncSubscription<P>
+    where
+        Self: 'a;
+
+    /// Subscribes to every event posted after this call.
+    async fn subscribe(&self) -> Result<Self::Subscription<'_>, Self::Error> {
+        Ok(AsyncSubscription {
+            receiver: self.sender.subscribe(),
+        })
+    }
+}
+
+impl<P> PostboxProvider<P> for AsyncEventBus<P>
+where
+    P: Clone + Send + 'static,
+{
+    type Postbox<'a>
+        = AsyncPostbox<P>
+    where
+        Self: 'a;
+
+    /// Returns a postbox sending to this bus.
+    async fn postbox(&self) -> Result<Self::Postbox<'_>, Self::Error> {
+        Ok(AsyncPostbox {
+            sender: self.sender.clone(),
+        })
+    }
+}
+
+/// A postbox sending events to an `AsyncEventBus`.
+#[derive(Debug, Clone)]
+pub struct AsyncPostbox<P> {
+    sender: broadcast::Sender<P>,
+}
+
+impl<P> ErrorType for AsyncPostbox<P> {
+    type Error = EventBusError;
+}
+
+impl<P> Sender for AsyncPostbox<P>
+where
+    P: Clone + Send + 'static,
+{
+    type Data = P;
+
+    /// Posts `value`; events posted with no subscription are dropped, as on ESP.
+    async fn send(&mut self, value: Self::Data) -> Result<(), Self::Error> {
+        let _ = self.sender.send(value);
+        Ok(())
+    }
+}
+
+/// A subscription to an `AsyncEventBus`.
+#[derive(Debug)]
+pub struct AsyncSubscription<P> {
+    receiver: broadcast::Receiver<P>,
+}
+
+impl<P> AsyncSubscription<P>
+where
+    P: Clone + Send + 'static,
+{
+    /// Converts the subscription into a stream ending when the bus is dropped.
+    pub fn into_stream(self) -> impl Stream<Item = Result<P, EventBusError>> {
+        futures_util::stream::unfold(self, |mut subscription| async move {
+            match subscription.recv().await {
+                Err(EventBusError::Closed) => None,
+                result => Some((result, subscription)),
+            }
+        })
+    }
+}
+
+impl<P> ErrorType for AsyncSubscription<P> {
+    type Error = EventBusError;
+}
+
+impl<P> Receiver for AsyncSubscription<P>
+where
+    P: Clone + Send + 'static,
+{
+    type Data = P;
+
+    /// Awaits the next event.
+    async fn recv(&mut self) -> Result<Self::Data, Self::Error> {
+        self.receiver.recv().await.map_err(|error| match error {
+            RecvError::Closed => EventBusError::Closed,
+            RecvError::Lagged(missed) => EventBusError::Lagged(missed),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::RuntimeConfig;
+    use futures_util::StreamExt;
+
+    /// Tests posting through a postbox and receiving as a stream.
+    #[test]
+    fn test_async_post_and_stream() {
+        let rt = RuntimeConfig::default().build().unwrap();
+
+        rt.block_on(async {
+            let bus = AsyncEventBus::<u32>::new(4);
+            let mut subscription = bus.subscribe().await.unwrap();
+            let stream = bus.subscribe().await.unwrap().into_stream();
+
+            let mut postbox = bus.postbox().await.unwrap();
+            postbox.send(1).await.unwrap();
+            assert_eq!(bus.post(2), 2);
+            assert_eq!(subscription.recv().await.unwrap(), 1);
+
+            drop((bus, postbox));
+            let events: Vec<_> = stream.map(Result::unwrap).collect().await;
+            assert_eq!(events, [1, 2]);
+        });
+    }
+}
@@ -0,0 +1,292 @@
+//! Cross-process transport for `NativeEventBus`.
+//!
+//! A bridge joins two buses over a Unix domain socket or TCP, so the processes of a
+//! multi-process device simulation share one logical event loop. Events posted on
+//! either side are serialized with `postcard`, sent as length-prefixed frames and
+//! posted on the other bus. Events received from a peer are not sent back to it, so
+//! buses can be joined in a star or tree around one serving process; a cycle of
+//! bridges would repeat events forever.
+//!
+//! A serving process accepts bridges until `BridgeServer::shutdown` is called or
+//! accepting fails for good; transient failures such as running out of file
+//! descriptors are retried with a growing delay.
+
+use super::NativeEventBus;
+use crate::error::EventBusError;
+use embedded_svc::event_bus::EventBus;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+#[cfg(unix)]
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Largest event frame accepted from a peer.
+pub const MAX_FRAME_SIZE: usize = 64 * 1024;
+
+/// Delay between checks for new peers and for shutdown.
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Longest delay between retries after accepting a peer failed.
+const MAX_ACCEPT_BACKOFF: Duration = Duration::from_secs(5);
+
+/// A byte stream that can carry a bridge.
+pub trait BridgeStream: Read + Write + Send + Sized + 'static {
+    /// Returns a second handle to the stream, used for writing.
+    fn try_clone(&self) -> io::Result<Self>;
+}
+
+impl BridgeStream for TcpStream {
+    /// Clones the socket handle.
+    fn try_clone(&self) -> io::Result<Self> {
+        TcpStream::try_clone(self)
+    }
+}
+
+#[cfg(unix)]
+impl BridgeStream for UnixStream {
+    /// Clones the socket handle.
+    fn try_clone(&self) -> io::Result<Self> {
+        UnixStream::try_clone(self)
+    }
+}
+
+impl<P> NativeEventBus<P>
+where
+    P: Serialize + DeserializeOwned + Send + 'static,
+{
+    /// Bridges this bus with the bus at the other end of `stream`.
+    ///
+    /// Events are exchanged on background threads until the peer disconnects. Local
+    /// events are written to the peer from the dispatch thread, so a peer that stops
+    /// reading eventually stalls this bus.
+    pub fn bridge<S>(&self, stream: S) -> Result<(), EventBusError>
+    where
+        S: BridgeStream,
+    {
+        let mut writer = stream.try_clone()?;
+        let subscription = self.subscribe(move |payload: &P| {
+            // A failed write means the peer is gone; the reader then unsubscribes.
+            if let Ok(frame) = postcard::to_stdvec(payload) {
+                let _ = write_frame(&mut writer, &frame);
+            }
+        })?;
+
+        let bus = self.clone();
+        let mut reader = stream;
+        thread::Builder::new()
+            .name("native-svc-event-bridge".to_owned())
+            .spawn(move || {
+                let origin = Some(subscription.id);
+                while let Ok(frame) = read_frame(&mut reader) {
+                    if let Ok(payload) = postcard::from_bytes(&frame) {
+                        let _ = bus.queue.push(payload, origin, None);
+                    }
+                }
+                drop(subscription);
+            })?;
+
+        Ok(())
+    }
+
+    /// Accepts bridges from other processes over TCP on a background thread.
+    ///
+    /// The bound address is available from `BridgeServer::local_addr`.
+    pub fn serve_tcp(&self, addr: impl ToSocketAddrs) -> Result<BridgeServer, EventBusError> {
+        let listener = TcpListener::bind(addr)?;
+        let local = listener.local_addr()?;
+        listener.set_nonblocking(true)?;
+        self.serve(Some(local), move || {
+            let (stream, _) = listener.accept()?;
+            stream.set_nonblocking(false)?;
+            Ok(stream)
+        })
+    }
+
+    /// Bridges this bus with a process serving on `addr` over TCP.
+    pub fn connect_tcp(&self, addr: impl ToSocketAddrs) -> Result<(), EventBusError> {
+        let stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        self.bridge(stream)
+    }
+
+    /// Accepts bridges from other processes on the Unix domain socket at `path` on a
+    /// background thread.
+    ///
+    /// `path` must not exist.
+    #[cfg(unix)]
+    pub fn serve_unix(&self, path: impl AsRef<Path>) -> Result<BridgeServer, EventBusError> {
+        let listener = UnixListener::bind(path)?;
+        listener.set_nonblocking(true)?;
+        self.serve(None, move || {
+            let (stream, _) = listener.accept()?;
+            stream.set_nonblocking(false)?;
+            Ok(stream)
+        })
+    }
+
+    /// Bridges this bus with a process serving on the Unix domain socket at `path`.
+    #[cfg(unix)]
+    pub fn connect_unix(&self, path: impl AsRef<Path>) -> Result<(), EventBusError> {
+        self.bridge(UnixStream::connect(path)?)
+    }
+
+    /// Bridges every stream returned by the non-blocking `accept` on a background
+    /// thread, until shut down or `accept` fails for good.
+    fn serve<S>(
+        &self,
+        local_addr: Option<SocketAddr>,
+        mut accept: impl FnMut() -> io::Result<S> + Send + 'static,
+    ) -> Result<BridgeServer, EventBusError>
+    where
+        S: BridgeStream,
+    {
+        let bus = self.clone();
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopping = stop.clone();
+        let thread = thread::Builder::new()
+            .name("native-svc-event-bridge-server".to_owned())
+            .spawn(move || {
+                let mut backoff = ACCEPT_POLL_INTERVAL;
+                while !stopping.load(Ordering::Acquire) {
+                    match accept() {
+                        Ok(stream) => {
+                            backoff = ACCEPT_POLL_INTERVAL;
+                            let _ = bus.bridge(stream);
+                        }
+                        Err(error) => match error.kind() {
+                            io::ErrorKind::WouldBlock => thread::park_timeout(ACCEPT_POLL_INTERVAL),
+                            io::ErrorKind::Interrupted
+                            | io::ErrorKind::ConnectionAborted
+                            | io::ErrorKind::ConnectionReset => {}
+                            io::ErrorKind::InvalidInput | io::ErrorKind::Unsupported => return,
+                            // Running out of file descriptors or memory may clear up.
+                            _ => {
+                                thread::park_timeout(backoff);
+                                backoff = (backoff * 2).min(MAX_ACCEPT_BACKOFF);
+                            }
+                        },
+                    }
+                }
+            })?;
+
+        Ok(BridgeServer {
+            local_addr,
+            stop,
+            thread,
+        })
+    }
+}
+
+/// Handle to a server accepting bridges, returned by `serve_tcp` and `serve_unix`.
+///
+/// Dropping the handle leaves the server running for the rest of the process.
+/// `shutdown` stops accepting and releases the listener and the server's handle to
+/// the bus; bridges already established are not affected.
+pub struct BridgeServer {
+    local_addr: Option<SocketAddr>,
+    stop: Arc<AtomicBool>,
+    thread: JoinHandle<()>,
+}
+
+impl BridgeServer {
+    /// Returns the bound address of a TCP server, or `None` for a Unix domain socket.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.local_addr
+    }
+
+    /// Returns `true` once the server stopped, after `shutdown` or because accepting
+    /// failed for good.
+    pub fn is_finished(&self) -> bool {
+        self.thread.is_finished()
+    }
+
+    /// Stops accepting bridges and waits for the server thread to exit.
+    pub fn shutdown(self) {
+        self.stop.store(true, Ordering::Release);
+        self.thread.thread().unpark();
+        let _ = self.thread.join();
+    }
+}
+
+/// Writes `frame` prefixed with its length as a little-endian `u32`.
+fn write_frame(writer: &mut impl Write, frame: &[u8]) -> io::Result<()> {
+    if frame.len() > MAX_FRAME_SIZE {
+        return Err(io::ErrorKind::InvalidInput.into());
+    }
+    writer.write_all(&(frame.len() as u32).to_le_bytes())?;
+    writer.write_all(frame)?;
+    writer.flush()
+}
+
+/// Reads a frame written by `write_frame`.
+fn read_frame(reader: &mut impl Read) -> io::Result<Vec<u8>> {
+    let mut len = [0; 4];
+    reader.read_exact(&mut len)?;
+    let len = u32::from_le_bytes(len) as usize;
+    if len > MAX_FRAME_SIZE {
+        return Err(io::ErrorKind::InvalidData.into());
+    }
+
+    let mut frame = vec![0; len];
+    reader.read_exact(&mut frame)?;
+    Ok(frame)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_svc::event_bus::Postbox;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    /// Tests events crossing a TCP bridge in both directions without echoing back.
+    #[test]
+    fn test_tcp_bridge() {
+        let server = NativeEventBus::<u32>::new().unwrap();
+        let client = NativeEventBus::<u32>::new().unwrap();
+        let bridge_server = server.serve_tcp("127.0.0.1:0").unwrap();
+        client
+            .connect_tcp(bridge_server.local_addr().unwrap())
+            .unwrap();
+
+        let (server_tx, server_rx) = mpsc::channel();
+        let (client_tx, client_rx) = mpsc::channel();
+        let _server_subscription = server
+            .subscribe(move |event: &u32| server_tx.send(*event).unwrap())
+            .unwrap();
+        let _client_subscription = client
+            .subscribe(move |event: &u32| client_tx.send(*event).unwrap())
+            .unwrap();
+
+        // The server side of the bridge is set up once the first event arrives.
+        client.post(&1, None).unwrap();
+        assert_eq!(server_rx.recv().unwrap(), 1);
+        server.post(&2, None).unwrap();
+        assert_eq!(server_rx.recv().unwrap(), 2);
+
+        let received: Vec<_> = client_rx.iter().take(2).collect();
+        assert_eq!(received, [1, 2]);
+        let timeout = Duration::from_millis(50);
+        assert!(client_rx.recv_timeout(timeout).is_err());
+        assert!(server_rx.recv_timeout(timeout).is_err());
+    }
+
+    /// Tests that shutting a server down stops accepting bridges.
+    #[test]
+    fn test_server_shutdown() {
+        let bus = NativeEventBus::<u32>::new().unwrap();
+        let bridge_server = bus.serve_tcp("127.0.0.1:0").unwrap();
+        let addr = bridge_server.local_addr().unwrap();
+        assert!(!bridge_server.is_finished());
+
+        bridge_server.shutdown();
+        assert!(TcpStream::connect(addr).is_err());
+    }
+}
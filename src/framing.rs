@@ -0,0 +1,114 @@
+//! Framing of response bodies.
+//!
+//! HTTP/1.1 delimits a response body by its `Content-Length`, by chunked transfer
+//! coding, or by closing the connection. `Framing` tells which one the last response
+//! uses, so a download can report progress against a known length or read until the
+//! end otherwise.
+
+use hyper::header::{CONNECTION, CONTENT_LENGTH, HeaderMap, HeaderName, TRANSFER_ENCODING};
+use hyper::{Response, Version};
+
+/// How the body of a response is delimited on the wire.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Framing {
+    /// The response has no body, as for `HEAD` requests, `1xx`, `204` and `304`.
+    Empty,
+    /// The body has the length declared by `Content-Length`.
+    Length(u64),
+    /// The body is sent in chunks, without a total length.
+    Chunked,
+    /// The body lasts until the server closes the connection.
+    CloseDelimited,
+}
+
+impl Framing {
+    /// Returns the framing of `response`, answering a `HEAD` request if `head`.
+    pub(crate) fn of<B>(response: &Response<B>, head: bool) -> Self {
+        let status = response.status().as_u16();
+        if head || matches!(status, 100..=199 | 204 | 304) {
+            return Self::Empty;
+        }
+        let headers = response.headers();
+        if last_token(headers, &TRANSFER_ENCODING).is_some_and(|t| t == "chunked") {
+            return Self::Chunked;
+        }
+        let length = headers.get(CONTENT_LENGTH).and_then(|v| v.to_str().ok());
+        match length.and_then(|length| length.trim().parse().ok()) {
+            Some(length) => Self::Length(length),
+            None => Self::CloseDelimited,
+        }
+    }
+
+    /// Returns the declared length of the body, `0` for responses without one.
+    pub fn length(&self) -> Option<u64> {
+        match self {
+            Self::Empty => Some(0),
+            Self::Length(length) => Some(*length),
+            Self::Chunked | Self::CloseDelimited => None,
+        }
+    }
+}
+
+/// Returns `true` if the connection of `response` is closed once its body is read.
+pub(crate) fn closes_connection<B>(response: &Response<B>, framing: Framing) -> bool {
+    let headers = response.headers();
+    let has_token = |token: &str| {
+        headers
+            .get_all(CONNECTION)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .any(|t| t.trim().eq_ignore_ascii_case(token))
+    };
+    framing == Framing::CloseDelimited
+        || has_token("close")
+        || (response.version() <= Version::HTTP_10 && !has_token("keep-alive"))
+}
+
+/// Returns the last comma-separated token of the `name` headers, lowercase.
+fn last_token(headers: &HeaderMap, name: &HeaderName) -> Option<String> {
+    let value = headers.get_all(name).iter().next_back()?.to_str().ok()?;
+    let token = value.rsplit(',').next()?.trim();
+    Some(token.to_ascii_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests telling the framing and connection reuse of responses apart.
+    #[test]
+    fn test_framing() {
+        let response = |status: u16, headers: &[(&str, &str)]| {
+            let mut builder = Response::builder().status(status);
+            for (name, value) in headers {
+                builder = builder.header(*name, *value);
+            }
+            builder.body(()).unwrap()
+        };
+
+        let sized = response(200, &[("content-length", "42")]);
+        assert_eq!(Framing::of(&sized, false), Framing::Length(42));
+        assert_eq!(Framing::of(&sized, true), Framing::Empty);
+        assert!(!closes_connection(&sized, Framing::Length(42)));
+
+        let chunked = response(200, &[("transfer-encoding", "gzip, Chunked")]);
+        assert_eq!(Framing::of(&chunked, false), Framing::Chunked);
+        assert_eq!(Framing::Chunked.length(), None);
+
+        let no_content = response(204, &[]);
+        assert_eq!(Framing::of(&no_content, false).length(), Some(0));
+
+        let unframed = response(200, &[("connection", "Keep-Alive, Close")]);
+        let framing = Framing::of(&unframed, false);
+        assert_eq!(framing, Framing::CloseDelimited);
+        assert!(closes_connection(&unframed, framing));
+
+        let mut old = response(200, &[("content-length", "1")]);
+        *old.version_mut() = Version::HTTP_10;
+        assert!(closes_connection(&old, Framing::Length(1)));
+        old.headers_mut()
+            .insert(CONNECTION, "keep-alive".parse().unwrap());
+        assert!(!closes_connection(&old, Framing::Length(1)));
+    }
+}
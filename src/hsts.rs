@@ -0,0 +1,180 @@
+//! HTTP Strict Transport Security (RFC 6797) for the HTTP client.
+//!
+//! An `HstsStore` records the `Strict-Transport-Security` directives of HTTPS responses
+//! and upgrades later `http://` requests to known hosts to HTTPS before they leave the
+//! device. Stores can be seeded with a preload list and shared between connections.
+
+use hyper::Uri;
+use hyper::http::uri::{Authority, Scheme};
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// A host known to require HTTPS.
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    /// When the policy lapses; `None` for preloaded hosts.
+    expires: Option<Instant>,
+    include_subdomains: bool,
+}
+
+/// Hosts known to require HTTPS.
+///
+/// # Example
+///
+/// ```no_run
+/// use native_svc::HyperHttpConnection;
+/// use native_svc::hsts::HstsStore;
+/// use std::sync::Arc;
+///
+/// let store = Arc::new(HstsStore::with_preload(["api.example.com"]));
+/// let conn = HyperHttpConnection::new().unwrap().with_hsts(store);
+/// ```
+#[derive(Debug, Default)]
+pub struct HstsStore {
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+impl HstsStore {
+    /// Creates an empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a store preloaded with `hosts` and their subdomains.
+    pub fn with_preload<I, H>(hosts: I) -> Self
+    where
+        I: IntoIterator<Item = H>,
+        H: AsRef<str>,
+    {
+        let store = Self::new();
+        for host in hosts {
+            store.preload(host.as_ref(), true);
+        }
+        store
+    }
+
+    /// Requires HTTPS for `host`, and its subdomains if `include_subdomains` is set,
+    /// permanently.
+    pub fn preload(&self, host: &str, include_subdomains: bool) {
+        self.lock().insert(
+            host.to_ascii_lowercase(),
+            Entry {
+                expires: None,
+                include_subdomains,
+            },
+        );
+    }
+
+    /// Records a `Strict-Transport-Security` header received from `host` over HTTPS.
+    ///
+    /// `max-age=0` removes the host. Headers without a valid `max-age`, and hosts given
+    /// as IP addresses, are ignored as RFC 6797 requires.
+    pub fn record(&self, host: &str, header: &str) {
+        if host.parse::<std::net::IpAddr>().is_ok() || host.starts_with('[') {
+            return;
+        }
+
+        let mut max_age = None;
+        let mut include_subdomains = false;
+        for directive in header.split(';').map(str::trim) {
+            let (name, value) = directive.split_once('=').unwrap_or((directive, ""));
+            if name.trim().eq_ignore_ascii_case("max-age") {
+                max_age = value.trim().trim_matches('"').parse::<u64>().ok();
+            } else if name.trim().eq_ignore_ascii_case("includeSubDomains") {
+                include_subdomains = true;
+            }
+        }
+        let Some(max_age) = max_age else {
+            return;
+        };
+
+        let host = host.to_ascii_lowercase();
+        let mut entries = self.lock();
+        if max_age == 0 {
+            entries.remove(&host);
+            return;
+        }
+        entries.insert(
+            host,
+            Entry {
+                // An age too large to represent never lapses.
+                expires: Instant::now().checked_add(Duration::from_secs(max_age)),
+                include_subdomains,
+            },
+        );
+    }
+
+    /// Returns `true` if requests to `host` must use HTTPS.
+    pub fn is_secure(&self, host: &str) -> bool {
+        let host = host.to_ascii_lowercase();
+        let now = Instant::now();
+        let entries = self.lock();
+        let active = |name: &str, subdomain: bool| {
+            entries.get(name).is_some_and(|entry| {
+                entry.expires.is_none_or(|expires| expires > now)
+                    && (!subdomain || entry.include_subdomains)
+            })
+        };
+
+        if active(&host, false) {
+            return true;
+        }
+        host.match_indices('.')
+            .any(|(index, _)| active(&host[index + 1..], true))
+    }
+
+    /// Returns `uri` rewritten to HTTPS if its host requires it.
+    pub(crate) fn upgrade(&self, uri: &Uri) -> Option<Uri> {
+        if uri.scheme() != Some(&Scheme::HTTP) || !self.is_secure(uri.host()?) {
+            return None;
+        }
+
+        let authority = uri.authority()?;
+        // The default HTTP port becomes the default HTTPS port.
+        let authority = match authority.port_u16() {
+            Some(80) => authority.host().parse::<Authority>().ok()?,
+            _ => authority.clone(),
+        };
+        let mut parts = uri.clone().into_parts();
+        parts.scheme = Some(Scheme::HTTPS);
+        parts.authority = Some(authority);
+        Uri::from_parts(parts).ok()
+    }
+
+    /// Locks the entries, ignoring poisoning.
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, Entry>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests recording directives and upgrading matching requests.
+    #[test]
+    fn test_record_and_upgrade() {
+        let store = HstsStore::with_preload(["preloaded.example"]);
+        store.record("example.com", "max-age=31536000; includeSubDomains");
+        store.record("plain.example.org", "max-age=600");
+        store.record("192.168.1.1", "max-age=600");
+        store.record("broken.example", "includeSubDomains");
+
+        assert!(store.is_secure("API.example.com"));
+        assert!(store.is_secure("plain.example.org"));
+        assert!(!store.is_secure("sub.plain.example.org"));
+        assert!(store.is_secure("a.b.preloaded.example"));
+        assert!(!store.is_secure("192.168.1.1"));
+        assert!(!store.is_secure("broken.example"));
+
+        let uri: Uri = "http://api.example.com:80/v1?q=1".parse().unwrap();
+        let upgraded = store.upgrade(&uri).unwrap();
+        assert_eq!(upgraded.to_string(), "https://api.example.com/v1?q=1");
+        let uri: Uri = "http://other.test/".parse().unwrap();
+        assert!(store.upgrade(&uri).is_none());
+
+        store.record("example.com", "max-age=0");
+        assert!(!store.is_secure("api.example.com"));
+    }
+}
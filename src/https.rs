@@ -0,0 +1,287 @@
+//! HTTPS connector of the HTTP client.
+//!
+//! `HttpsConnector` wraps the connections of an inner connector in TLS for
+//! `https://` URIs. The server name presented through SNI and verified against the
+//! certificate is the URI host, unless a server name override is registered for
+//! that host, so a device can be reached by IP address while validating its DNS
+//! name.
+//!
+//! Hosts matching a pattern of the connection's `TlsOverrides` use their own TLS
+//! settings, and connections to servers whose certificate is not one of the pinned
+//! certificates, if any, are rejected after the handshake.
+//!
+//! The details of the TLS session, such as the protocol negotiated through ALPN, are
+//! added to the extensions of the responses received on a TLS connection, and the
+//! duration of the handshake is recorded for `timing`.
+
+use crate::error::HyperError;
+use crate::timing;
+use crate::tls::{self, TlsConfig, TlsInfo, TlsOverrides};
+use hyper::Uri;
+use hyper::rt::{Read, ReadBufCursor, Write};
+use hyper_util::client::legacy::connect::{Connected, Connection};
+use hyper_util::rt::TokioIo;
+use std::collections::HashMap;
+use std::error::Error;
+use std::future::Future;
+use std::io::{self, IoSlice};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Instant;
+use tokio_native_tls::{TlsConnector, TlsStream};
+use tower_service::Service;
+
+/// Error type of connectors.
+type BoxError = Box<dyn Error + Send + Sync>;
+
+/// TLS connector and certificate pins applied to a host.
+#[derive(Clone)]
+struct HostTls {
+    connector: TlsConnector,
+    /// DER encodings of the accepted server certificates; empty to accept any.
+    pins: Arc<[Vec<u8>]>,
+}
+
+impl HostTls {
+    /// Builds the connector of `config`, never offering `h2` through ALPN.
+    fn new(config: &TlsConfig) -> Result<Self, native_tls::Error> {
+        Ok(Self {
+            connector: config.without_h2().connector()?.into(),
+            pins: config.pinned_certificates().into(),
+        })
+    }
+}
+
+/// TLS settings of the client: the default, per-host overrides, and server names.
+#[derive(Clone)]
+pub(crate) struct TlsPolicy {
+    default: HostTls,
+    overrides: Vec<(String, HostTls)>,
+    /// Server names keyed by lowercase URI host.
+    server_names: HashMap<String, String>,
+}
+
+impl TlsPolicy {
+    /// Applies `config` to every host.
+    pub(crate) fn new(config: &TlsConfig) -> Result<Self, native_tls::Error> {
+        Ok(Self {
+            default: HostTls::new(config)?,
+            overrides: Vec::new(),
+            server_names: HashMap::new(),
+        })
+    }
+
+    /// Replaces the per-host overrides with `overrides`.
+    pub(crate) fn set_overrides(
+        &mut self,
+        overrides: &TlsOverrides,
+    ) -> Result<(), native_tls::Error> {
+        self.overrides = overrides
+            .iter()
+            .map(|(pattern, config)| Ok((pattern.to_owned(), HostTls::new(config)?)))
+            .collect::<Result<_, native_tls::Error>>()?;
+        Ok(())
+    }
+
+    /// Presents and verifies `server_name` when connecting to `host`.
+    pub(crate) fn set_server_name(&mut self, host: &str, server_name: &str) {
+        let host = host.trim_matches(['[', ']']).to_ascii_lowercase();
+        self.server_names.insert(host, server_name.to_owned());
+    }
+
+    /// Returns the server name to present and verify when connecting to `host`.
+    fn server_name(&self, host: &str) -> String {
+        match self.server_names.get(&host.to_ascii_lowercase()) {
+            Some(name) => name.clone(),
+            None => host.to_owned(),
+        }
+    }
+
+    /// Returns the TLS settings of `host`.
+    fn host_tls(&self, host: &str) -> &HostTls {
+        self.overrides
+            .iter()
+            .find(|(pattern, _)| tls::host_matches(pattern, host))
+            .map_or(&self.default, |(_, tls)| tls)
+    }
+}
+
+/// Connector securing the connections of `C` to `https://` URIs.
+#[derive(Clone)]
+pub(crate) struct HttpsConnector<C> {
+    inner: C,
+    policy: Arc<TlsPolicy>,
+}
+
+impl<C> HttpsConnector<C> {
+    /// Wraps the connections of `inner` in TLS following `policy`.
+    pub(crate) fn new(inner: C, policy: TlsPolicy) -> Self {
+        Self {
+            inner,
+            policy: Arc::new(policy),
+        }
+    }
+}
+
+impl<C> Service<Uri> for HttpsConnector<C>
+where
+    C: Service<Uri>,
+    C::Response: Read + Write + Send + Unpin + 'static,
+    C::Future: Send + 'static,
+    C::Error: Into<BoxError>,
+{
+    type Response = MaybeTlsStream<C::Response>;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, BoxError>> + Send>>;
+
+    /// Waits until the inner connector is ready.
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    /// Connects to `dst`, performing the TLS handshake for `https://` URIs.
+    fn call(&mut self, dst: Uri) -> Self::Future {
+        let is_https = dst.scheme_str() == Some("https");
+        let host = dst.host().unwrap_or("").trim_matches(['[', ']']);
+        let server_name = self.policy.server_name(host);
+        let tls = self.policy.host_tls(host).clone();
+        let connecting = self.inner.call(dst);
+
+        Box::pin(async move {
+            let stream = connecting.await.map_err(Into::into)?;
+            if !is_https {
+                return Ok(MaybeTlsStream::Plain(stream));
+            }
+            let started = Instant::now();
+            let stream = tls
+                .connector
+                .connect(&server_name, TokioIo::new(stream))
+                .await?;
+            timing::record_tls(started.elapsed());
+            if !tls.pins.is_empty() {
+                let certificate = stream.get_ref().peer_certificate()?;
+                let der = certificate.map(|c| c.to_der()).transpose()?;
+                if !der.is_some_and(|der| tls.pins.contains(&der)) {
+                    return Err(HyperError::CertificateNotPinned(server_name).into());
+                }
+            }
+            Ok(MaybeTlsStream::Tls(TokioIo::new(stream)))
+        })
+    }
+}
+
+/// A connection, secured with TLS for `https://` URIs.
+pub(crate) enum MaybeTlsStream<T> {
+    /// A plain HTTP connection.
+    Plain(T),
+    /// A TLS connection.
+    Tls(TokioIo<TlsStream<TokioIo<T>>>),
+}
+
+impl<T> Connection for MaybeTlsStream<T>
+where
+    T: Read + Write + Connection + Unpin,
+{
+    /// Returns the connection info of the underlying connection and the TLS session.
+    fn connected(&self) -> Connected {
+        match self {
+            Self::Plain(stream) => stream.connected(),
+            Self::Tls(stream) => {
+                let tls = stream.inner().get_ref();
+                let connected = tls.get_ref().get_ref().inner().connected();
+                connected.extra(TlsInfo::new(tls))
+            }
+        }
+    }
+}
+
+impl<T: Read + Write + Unpin> Read for MaybeTlsStream<T> {
+    /// Reads from the connection.
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: ReadBufCursor<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Plain(stream) => Pin::new(stream).poll_read(cx, buf),
+            Self::Tls(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl<T: Read + Write + Unpin> Write for MaybeTlsStream<T> {
+    /// Writes to the connection.
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Self::Plain(stream) => Pin::new(stream).poll_write(cx, buf),
+            Self::Tls(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    /// Writes a list of buffers to the connection.
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Self::Plain(stream) => Pin::new(stream).poll_write_vectored(cx, bufs),
+            Self::Tls(stream) => Pin::new(stream).poll_write_vectored(cx, bufs),
+        }
+    }
+
+    /// Returns `true` if the connection writes buffer lists efficiently.
+    fn is_write_vectored(&self) -> bool {
+        match self {
+            Self::Plain(stream) => stream.is_write_vectored(),
+            Self::Tls(stream) => stream.is_write_vectored(),
+        }
+    }
+
+    /// Flushes the connection.
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Plain(stream) => Pin::new(stream).poll_flush(cx),
+            Self::Tls(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    /// Shuts down the connection.
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Plain(stream) => Pin::new(stream).poll_shutdown(cx),
+            Self::Tls(stream) => Pin::new(stream).poll_shutdown(cx),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that server names and TLS overrides apply to their hosts only.
+    #[test]
+    fn test_host_policy() {
+        let mut policy = TlsPolicy::new(&TlsConfig::new()).unwrap();
+        policy.set_server_name("10.0.0.7", "device.lab");
+        let internal = TlsConfig::new().disable_built_in_roots(true);
+        let overrides = TlsOverrides::new().host("*.internal", internal);
+        policy.set_overrides(&overrides).unwrap();
+
+        assert_eq!(policy.server_name("10.0.0.7"), "device.lab");
+        assert_eq!(policy.server_name("Example.com"), "Example.com");
+        assert!(std::ptr::eq(
+            policy.host_tls("example.com"),
+            &policy.default
+        ));
+        assert!(std::ptr::eq(
+            policy.host_tls("ota.internal"),
+            &policy.overrides[0].1
+        ));
+    }
+}
@@ -0,0 +1,86 @@
+//! Request interceptors run by `HyperHttpConnection` just before a request is sent.
+//!
+//! Interceptors see the fully built request, including its buffered body, and can
+//! rewrite headers or the URI, for example to sign the request. They run in the order
+//! they were added, before every attempt: each retry and each followed redirect passes
+//! a fresh copy of the request through them again.
+
+#[cfg(feature = "oauth2")]
+pub mod oauth2;
+#[cfg(feature = "hmac-signing")]
+pub mod signing;
+#[cfg(feature = "sigv4")]
+pub mod sigv4;
+
+use crate::HyperClient;
+use crate::body::exchange;
+use crate::error::HyperError;
+use hyper::body::Bytes;
+use hyper::{HeaderMap, Request, Response, Uri};
+use std::sync::Mutex;
+use tokio::runtime::Runtime;
+
+/// The request being intercepted and the connection sending it.
+#[derive(Clone, Copy)]
+pub struct Context<'a> {
+    streaming: bool,
+    rt: &'a Runtime,
+    client: &'a HyperClient,
+}
+
+impl<'a> Context<'a> {
+    /// Creates the context of a buffered or streamed request sent with `client`.
+    pub(crate) fn new(streaming: bool, rt: &'a Runtime, client: &'a HyperClient) -> Self {
+        Self {
+            streaming,
+            rt,
+            client,
+        }
+    }
+
+    /// Returns `true` if the body is streamed with `Transfer-Encoding: chunked`.
+    ///
+    /// The body of a streamed request is not known yet and is empty when intercepted.
+    pub fn is_streaming(&self) -> bool {
+        self.streaming
+    }
+
+    /// Sends `request` on the same connection and returns the response with its body.
+    ///
+    /// The request does not go through the interceptors, so they can use it to fetch
+    /// credentials without recursing.
+    pub fn fetch(&self, request: Request<Bytes>) -> Result<Response<Bytes>, HyperError> {
+        self.rt.block_on(exchange(self.client.clone(), request))
+    }
+}
+
+/// Modifies requests before they are sent.
+pub trait Interceptor: Send + Sync {
+    /// Modifies `request`, or fails the request by returning an error.
+    fn intercept(
+        &self,
+        request: &mut Request<Bytes>,
+        context: &Context<'_>,
+    ) -> Result<(), HyperError>;
+}
+
+/// An interceptor calling a closure with the request URI and headers.
+pub(crate) struct PreRequestHook<F>(pub(crate) Mutex<F>);
+
+impl<F> Interceptor for PreRequestHook<F>
+where
+    F: FnMut(&mut Uri, &mut HeaderMap) + Send,
+{
+    /// Calls the hook with the URI and headers of `request`.
+    fn intercept(
+        &self,
+        request: &mut Request<Bytes>,
+        _context: &Context<'_>,
+    ) -> Result<(), HyperError> {
+        let mut hook = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        let (mut parts, body) = std::mem::take(request).into_parts();
+        hook(&mut parts.uri, &mut parts.headers);
+        *request = Request::from_parts(parts, body);
+        Ok(())
+    }
+}
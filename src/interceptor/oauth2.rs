@@ -0,0 +1,239 @@
+//! OAuth 2.0 client credentials grant (RFC 6749, section 4.4).
+//!
+//! `ClientCredentials` obtains an access token from a token endpoint through the
+//! connection it is installed on, caches it until shortly before it expires, and sends
+//! it as a bearer token with every request.
+
+use super::{Context, Interceptor};
+use crate::error::HyperError;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use hyper::body::Bytes;
+use hyper::header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE, HeaderValue};
+use hyper::{Method, Request, Uri};
+use serde::Deserialize;
+use std::fmt::Write;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// How the client authenticates to the token endpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ClientAuth {
+    /// HTTP Basic authentication (`client_secret_basic`).
+    #[default]
+    Basic,
+    /// Client ID and secret in the request body (`client_secret_post`).
+    Body,
+}
+
+/// Successful token endpoint response.
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    token_type: String,
+    expires_in: Option<u64>,
+}
+
+/// A cached access token.
+#[derive(Debug)]
+struct Token {
+    /// `Authorization` header value.
+    authorization: HeaderValue,
+    /// When the token must be renewed; `None` if it does not expire.
+    renew_at: Option<Instant>,
+}
+
+/// An interceptor authenticating requests with client credentials tokens.
+///
+/// # Example
+///
+/// ```no_run
+/// use native_svc::HyperHttpConnection;
+/// use native_svc::interceptor::oauth2::ClientCredentials;
+///
+/// let oauth = ClientCredentials::new("https://auth.example.com/token", "device", "secret")
+///     .unwrap()
+///     .scope("telemetry:write");
+/// let conn = HyperHttpConnection::new().unwrap().with_interceptor(oauth);
+/// ```
+#[derive(Debug)]
+pub struct ClientCredentials {
+    token_url: Uri,
+    client_id: String,
+    client_secret: String,
+    scopes: Vec<String>,
+    auth: ClientAuth,
+    /// How long before expiry tokens are renewed.
+    leeway: Duration,
+    token: Mutex<Option<Token>>,
+}
+
+impl ClientCredentials {
+    /// Creates a token manager for the token endpoint at `token_url`.
+    pub fn new(
+        token_url: &str,
+        client_id: impl Into<String>,
+        client_secret: impl Into<String>,
+    ) -> Result<Self, HyperError> {
+        Ok(Self {
+            token_url: token_url.parse().map_err(hyper::http::Error::from)?,
+            client_id: client_id.into(),
+            client_secret: client_secret.into(),
+            scopes: Vec::new(),
+            auth: ClientAuth::default(),
+            leeway: Duration::from_secs(30),
+            token: Mutex::new(None),
+        })
+    }
+
+    /// Adds `scope` to the requested scopes.
+    pub fn scope(mut self, scope: impl Into<String>) -> Self {
+        self.scopes.push(scope.into());
+        self
+    }
+
+    /// Sets how the client authenticates to the token endpoint.
+    pub fn client_auth(mut self, auth: ClientAuth) -> Self {
+        self.auth = auth;
+        self
+    }
+
+    /// Sets how long before expiry tokens are renewed, 30 seconds by default.
+    pub fn leeway(mut self, leeway: Duration) -> Self {
+        self.leeway = leeway;
+        self
+    }
+
+    /// Drops the cached token, for example after a `401 Unauthorized` response.
+    pub fn invalidate(&self) {
+        *self.lock() = None;
+    }
+
+    /// Locks the cached token.
+    fn lock(&self) -> MutexGuard<'_, Option<Token>> {
+        self.token.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Requests a new token from the token endpoint.
+    fn request_token(&self, context: &Context<'_>) -> Result<Token, HyperError> {
+        let mut form = String::from("grant_type=client_credentials");
+        if !self.scopes.is_empty() {
+            let _ = write!(form, "&scope={}", encode(&self.scopes.join(" ")));
+        }
+
+        let mut request = Request::builder()
+            .method(Method::POST)
+            .uri(self.token_url.clone())
+            .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .header(ACCEPT, "application/json");
+        match self.auth {
+            ClientAuth::Basic => {
+                let credentials = format!(
+                    "{}:{}",
+                    encode(&self.client_id),
+                    encode(&self.client_secret)
+                );
+                let basic = format!("Basic {}", STANDARD.encode(credentials));
+                request = request.header(AUTHORIZATION, basic);
+            }
+            ClientAuth::Body => {
+                let _ = write!(
+                    form,
+                    "&client_id={}&client_secret={}",
+                    encode(&self.client_id),
+                    encode(&self.client_secret)
+                );
+            }
+        }
+
+        let response = context.fetch(request.body(Bytes::from(form))?)?;
+        if !response.status().is_success() {
+            return Err(HyperError::TokenRequest(format!(
+                "{}: {}",
+                response.status(),
+                String::from_utf8_lossy(response.body())
+            )));
+        }
+        parse_token(response.body(), self.leeway)
+    }
+}
+
+impl Interceptor for ClientCredentials {
+    /// Adds the bearer token, requesting a new one if none is cached or it expires soon.
+    fn intercept(
+        &self,
+        request: &mut Request<Bytes>,
+        context: &Context<'_>,
+    ) -> Result<(), HyperError> {
+        let mut token = self.lock();
+        let expired = token
+            .as_ref()
+            .is_none_or(|token| token.renew_at.is_some_and(|at| at <= Instant::now()));
+        if expired {
+            *token = Some(self.request_token(context)?);
+        }
+        let token = token.as_ref().expect("token requested above");
+        request
+            .headers_mut()
+            .insert(AUTHORIZATION, token.authorization.clone());
+        Ok(())
+    }
+}
+
+/// Parses a token endpoint response, renewing the token `leeway` before it expires.
+fn parse_token(body: &[u8], leeway: Duration) -> Result<Token, HyperError> {
+    let response: TokenResponse = serde_json::from_slice(body)
+        .map_err(|e| HyperError::TokenRequest(format!("invalid token response: {e}")))?;
+    if !response.token_type.eq_ignore_ascii_case("bearer") {
+        return Err(HyperError::TokenRequest(format!(
+            "unsupported token type: {}",
+            response.token_type
+        )));
+    }
+
+    let authorization = HeaderValue::from_str(&format!("Bearer {}", response.access_token))?;
+    let renew_at = response
+        .expires_in
+        .map(|expires_in| Instant::now() + Duration::from_secs(expires_in).saturating_sub(leeway));
+    Ok(Token {
+        authorization,
+        renew_at,
+    })
+}
+
+/// Encodes `value` as `application/x-www-form-urlencoded`.
+fn encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'*' => {
+                encoded.push(byte as char)
+            }
+            b' ' => encoded.push('+'),
+            _ => {
+                let _ = write!(encoded, "%{byte:02X}");
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests parsing token responses and form encoding of credentials.
+    #[test]
+    fn test_parse_token() {
+        let body = br#"{"access_token":"abc","token_type":"Bearer","expires_in":3600}"#;
+        let token = parse_token(body, Duration::from_secs(30)).unwrap();
+        assert_eq!(token.authorization, "Bearer abc");
+        let renew_in = token.renew_at.unwrap() - Instant::now();
+        assert!(renew_in > Duration::from_secs(3500) && renew_in <= Duration::from_secs(3570));
+
+        let body = br#"{"access_token":"abc","token_type":"mac"}"#;
+        assert!(parse_token(body, Duration::ZERO).is_err());
+
+        assert_eq!(encode("read write:all"), "read+write%3Aall");
+    }
+}
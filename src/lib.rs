@@ -4,27 +4,192 @@
 //! HTTP client `Connection` trait, allowing synchronous-style HTTP requests on top of
 //! the asynchronous `hyper` library.
 
+pub mod async_io;
+pub mod batch;
+mod body;
+pub mod clock;
+pub mod connection;
+#[cfg(feature = "decompression")]
+mod decode;
 pub mod error;
+#[cfg(all(feature = "eth", target_os = "linux"))]
+pub mod eth;
+#[cfg(feature = "event-bus")]
+pub mod event_bus;
+pub mod framing;
+pub mod hsts;
+#[cfg(feature = "tls")]
+mod https;
+pub mod interceptor;
+pub mod limit;
+pub mod link;
+#[cfg(feature = "mdns")]
+pub mod mdns;
+pub mod monitoring;
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
+#[cfg(all(feature = "netif", target_os = "linux"))]
+pub mod netif;
+pub mod options;
+#[cfg(feature = "ota")]
+pub mod ota;
+#[cfg(feature = "ping")]
+pub mod ping;
+pub mod pool;
+#[cfg(feature = "proxy")]
+pub mod proxy;
+pub mod raw;
+pub mod reader;
+pub mod redirect;
+pub mod response;
+pub mod retry;
+pub mod runtime;
+#[cfg(feature = "sntp")]
+pub mod sntp;
+pub mod sse;
+pub mod state;
+pub mod status;
+#[cfg(feature = "storage")]
+pub mod storage;
+pub mod text;
+#[cfg(feature = "timer")]
+pub mod timer;
+pub mod timing;
+#[cfg(feature = "tls")]
+pub mod tls;
+#[cfg(feature = "wifi")]
+pub mod wifi;
+#[cfg(feature = "ws")]
+pub mod ws;
 
+use crate::async_io::{AsyncRequestBody, AsyncResponseBody};
+use crate::body::{ChannelBody, RequestBody};
+use crate::clock::{Clock, SystemClock};
+use crate::connection::{ConnectionInfo, TrackingConnector};
+#[cfg(feature = "decompression")]
+use crate::decode::{ACCEPTED_ENCODINGS, Decoder};
 use crate::error::HyperError;
+use crate::framing::Framing;
+use crate::hsts::HstsStore;
+#[cfg(feature = "tls")]
+use crate::https::{HttpsConnector, TlsPolicy};
+use crate::interceptor::{Context, Interceptor, PreRequestHook};
+use crate::limit::ConcurrencyLimit;
+use crate::options::RequestOptions;
+#[cfg(feature = "proxy")]
+use crate::proxy::{Proxy, ProxyConnector};
+use crate::raw::RawConnection;
+use crate::reader::BodyReader;
+use crate::redirect::{Redirect, RedirectPolicy};
+use crate::response::{ResponseBody, ResponseHead};
+use crate::retry::RetryPolicy;
+use crate::runtime::{ManagedRuntime, RuntimeConfig, Shutdown};
+use crate::state::ConnectionState;
+use crate::status::StatusPolicy;
+use crate::timing::{ConnectPhases, TimedResolver, Timings};
+#[cfg(feature = "tls")]
+use crate::tls::{TlsConfig, TlsInfo, TlsOverrides};
 use embedded_svc::http::client::Connection;
 use embedded_svc::http::{Headers, Method, Status};
 use embedded_svc::io::{ErrorType, Read, Write};
 use http_body_util::{BodyExt, Full};
-use hyper::body::{Bytes, Incoming};
-use hyper::header::{HeaderName, HeaderValue};
-use hyper::{HeaderMap, Request, Response};
-use hyper_tls::HttpsConnector;
+use hyper::body::{Body, Bytes, Incoming};
+#[cfg(feature = "proxy")]
+use hyper::header::PROXY_AUTHORIZATION;
+#[cfg(feature = "decompression")]
+use hyper::header::{ACCEPT_ENCODING, CONTENT_ENCODING};
+use hyper::header::{
+    CONTENT_LENGTH, COOKIE, HOST, HeaderName, HeaderValue, STRICT_TRANSPORT_SECURITY,
+    TRANSFER_ENCODING,
+};
+use hyper::http::uri::Scheme;
+use hyper::upgrade::OnUpgrade;
+use hyper::{HeaderMap, Request, Response, StatusCode, Uri, Version};
 use hyper_util::client::legacy::Client;
 use hyper_util::client::legacy::connect::HttpConnector;
 use hyper_util::rt::TokioExecutor;
-use tokio::runtime::Runtime;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{OwnedSemaphorePermit, mpsc};
+use tokio::task::JoinHandle;
 
 /// Default capacity for the internal write buffer.
 const DEFAULT_BUFFER_SIZE: usize = 8192;
 
-/// Type alias for the Hyper client with TLS support and full-body requests.
-type HyperClient = Client<HttpsConnector<HttpConnector>, Full<Bytes>>;
+/// Most prefetched responses held at once.
+const MAX_PREFETCHES: usize = 8;
+
+/// How long a prefetched response is held before it is dropped unused.
+const PREFETCH_TTL: Duration = Duration::from_secs(30);
+
+/// Connector opening the connections of the client, wrapped in TLS with feature `tls`.
+#[cfg(feature = "proxy")]
+type Connector = ProxyConnector;
+#[cfg(not(feature = "proxy"))]
+type Connector = HttpConnector<TimedResolver>;
+
+/// Type alias for the Hyper client with TLS support.
+#[cfg(feature = "tls")]
+type HyperClient = Client<TrackingConnector<HttpsConnector<Connector>>, RequestBody>;
+/// Type alias for the plain HTTP Hyper client.
+#[cfg(not(feature = "tls"))]
+type HyperClient = Client<TrackingConnector<Connector>, RequestBody>;
+
+/// Callback receiving the status and headers of `1xx` interim responses.
+type InformationalCallback = Arc<dyn Fn(StatusCode, &HeaderMap) + Send + Sync>;
+
+/// Callback customizing requests as they are initiated.
+type RequestHook = Arc<dyn Fn(&mut Request<Bytes>) + Send + Sync>;
+
+/// The response of a request sent on the runtime.
+type PendingResponse = JoinHandle<Result<Response<Incoming>, hyper_util::client::legacy::Error>>;
+
+/// Returns an HTTP connector, also accepting `https://` URIs for TLS to wrap when
+/// feature `tls` is enabled.
+fn http_connector() -> HttpConnector<TimedResolver> {
+    let mut http = HttpConnector::new_with_resolver(TimedResolver::new());
+    http.enforce_http(!cfg!(feature = "tls"));
+    http
+}
+
+/// Builds a client securing the connections of `connector` following `tls`.
+#[cfg(feature = "tls")]
+fn build_client(connector: Connector, tls: &TlsPolicy) -> HyperClient {
+    let https = HttpsConnector::new(connector, tls.clone());
+    Client::builder(TokioExecutor::new()).build(TrackingConnector::new(https))
+}
+
+/// Builds a plain HTTP client over `connector`.
+#[cfg(not(feature = "tls"))]
+fn build_client(connector: Connector) -> HyperClient {
+    Client::builder(TokioExecutor::new()).build(TrackingConnector::new(connector))
+}
+
+/// A request sent while its body is still being written.
+struct Upload {
+    sender: mpsc::Sender<Bytes>,
+    response: PendingResponse,
+}
+
+/// A request sent ahead of time by `prefetch`.
+struct Prefetch {
+    method: hyper::Method,
+    uri: Uri,
+    headers: HeaderMap,
+    response: PendingResponse,
+    sent: Instant,
+}
+
+impl Prefetch {
+    /// Returns `true` if `request` is the prefetched request.
+    fn matches(&self, request: &Request<Bytes>) -> bool {
+        request.body().is_empty()
+            && request.method() == self.method
+            && *request.uri() == self.uri
+            && *request.headers() == self.headers
+    }
+}
 
 /// An HTTP connection using the Hyper library and Tokio runtime.
 ///
@@ -44,33 +209,434 @@ type HyperClient = Client<HttpsConnector<HttpConnector>, Full<Bytes>>;
 /// // read, process, etc.
 /// ```
 pub struct HyperHttpConnection {
-    rt: Runtime,
+    rt: ManagedRuntime,
     client: HyperClient,
-    request: Option<Request<Full<Bytes>>>,
-    response: Option<Response<Incoming>>,
-    read_buffer: Bytes,
+    /// TLS settings the client is rebuilt with when they change.
+    #[cfg(feature = "tls")]
+    tls: TlsPolicy,
+    #[cfg(feature = "proxy")]
+    proxy: Option<Arc<Proxy>>,
+    /// The initiated request, with its buffered body.
+    request: Option<Request<Bytes>>,
+    /// URI of the last request.
+    uri: Uri,
+    upload: Option<Upload>,
+    /// Status and headers of the last response.
+    head: ResponseHead,
+    /// Body of the last response.
+    body: ResponseBody,
+    /// Framing of the last response body on the wire.
+    framing: Option<Framing>,
     write_buffer: Vec<u8>,
+    /// Body handed over by `write_bytes` while nothing else was written, sent as is.
+    write_chunk: Option<Bytes>,
+    hsts: Option<Arc<HstsStore>>,
+    retry: Option<RetryPolicy>,
+    /// Clock waiting between retries, timing out queued requests and aging prefetched
+    /// responses.
+    clock: Arc<dyn Clock>,
+    limit: Option<Arc<ConcurrencyLimit>>,
+    redirect_policy: Option<RedirectPolicy>,
+    /// Redirects followed while answering the last request.
+    redirects: Vec<Redirect>,
+    interceptors: Vec<Box<dyn Interceptor>>,
+    status_policy: Option<StatusPolicy>,
+    raw: RawConnection,
+    prefetches: Vec<Prefetch>,
+    informational: Option<InformationalCallback>,
+    request_hook: Option<RequestHook>,
+    /// `Host` header sent instead of the one derived from the URI.
+    host_override: Option<HeaderValue>,
+    /// Behaviors enabled for the current request.
+    options: RequestOptions,
+    /// Whether calls made in the wrong state fail with `HyperError::InvalidState`.
+    strict: bool,
+    /// Whether the phases of each request are timed.
+    timing: bool,
+    /// When the current request was sent, if timed.
+    request_started: Option<Instant>,
+    /// Phases of the last request up to its response head.
+    timings: Option<Timings>,
+    #[cfg(feature = "decompression")]
+    decompression: bool,
 }
 
 impl HyperHttpConnection {
     /// Creates a new `HyperHttpConnection` instance.
     ///
-    /// Initializes a Tokio runtime, a TLS-enabled Hyper client, and
-    /// prepares internal buffers. Returns an error if the runtime
+    /// Initializes a Tokio runtime, a Hyper client, TLS-enabled with feature
+    /// `tls`, and prepares internal buffers. Returns an error if the runtime
     /// cannot be created.
     pub fn new() -> Result<Self, HyperError> {
-        let https = HttpsConnector::new();
-        let client = Client::builder(TokioExecutor::new()).build(https);
-        let rt = Runtime::new().map_err(HyperError::RuntimeCreation)?;
+        Self::with_runtime_config(&RuntimeConfig::default())
+    }
+
+    /// Creates a new `HyperHttpConnection` using the given TLS settings.
+    ///
+    /// The same `TlsConfig` can be shared with the WebSocket client so both
+    /// transports trust the same roots and present the same identity.
+    #[cfg(feature = "tls")]
+    pub fn with_tls_config(tls: &TlsConfig) -> Result<Self, HyperError> {
+        Self::with_config(tls, &RuntimeConfig::default())
+    }
+
+    /// Creates a new `HyperHttpConnection` whose runtime follows `runtime`.
+    pub fn with_runtime_config(runtime: &RuntimeConfig) -> Result<Self, HyperError> {
+        #[cfg(feature = "tls")]
+        {
+            Self::with_config(&TlsConfig::default(), runtime)
+        }
+        #[cfg(not(feature = "tls"))]
+        {
+            Self::build(runtime)
+        }
+    }
+
+    /// Creates a new `HyperHttpConnection` using the given TLS and runtime settings.
+    ///
+    /// `h2` is left out of the ALPN protocols of `tls`, as the client only speaks
+    /// HTTP/1.1.
+    #[cfg(feature = "tls")]
+    pub fn with_config(tls: &TlsConfig, runtime: &RuntimeConfig) -> Result<Self, HyperError> {
+        Self::build(runtime, TlsPolicy::new(tls)?)
+    }
+
+    /// Creates a new `HyperHttpConnection` from its runtime and TLS settings.
+    fn build(
+        runtime: &RuntimeConfig,
+        #[cfg(feature = "tls")] tls: TlsPolicy,
+    ) -> Result<Self, HyperError> {
+        #[cfg(feature = "proxy-system")]
+        let proxy = Proxy::system().map(Arc::new);
+        #[cfg(all(feature = "proxy", not(feature = "proxy-system")))]
+        let proxy = None;
+        #[cfg(feature = "proxy")]
+        let connector = ProxyConnector::new(http_connector(), proxy.clone());
+        #[cfg(not(feature = "proxy"))]
+        let connector = http_connector();
+        #[cfg(feature = "tls")]
+        let client = build_client(connector, &tls);
+        #[cfg(not(feature = "tls"))]
+        let client = build_client(connector);
+        let rt = ManagedRuntime::new(runtime.build().map_err(HyperError::RuntimeCreation)?);
+
+        Ok(Self::from_transport(
+            rt,
+            client,
+            #[cfg(feature = "tls")]
+            tls,
+            #[cfg(feature = "proxy")]
+            proxy,
+        ))
+    }
+
+    /// Creates a connection sharing the runtime, client, TLS and proxy settings of
+    /// this one, with the defaults for every other setting.
+    pub(crate) fn sibling(&self) -> Self {
+        Self::from_transport(
+            self.rt.share(),
+            self.client.clone(),
+            #[cfg(feature = "tls")]
+            self.tls.clone(),
+            #[cfg(feature = "proxy")]
+            self.proxy.clone(),
+        )
+    }
+
+    /// Creates a connection sending requests with `client` on `rt`.
+    fn from_transport(
+        rt: ManagedRuntime,
+        client: HyperClient,
+        #[cfg(feature = "tls")] tls: TlsPolicy,
+        #[cfg(feature = "proxy")] proxy: Option<Arc<Proxy>>,
+    ) -> Self {
+        let raw = RawConnection::new(rt.downgrade());
+        let body = ResponseBody::new(rt.downgrade());
 
-        Ok(Self {
+        Self {
             rt,
             client,
+            #[cfg(feature = "tls")]
+            tls,
+            #[cfg(feature = "proxy")]
+            proxy,
             request: None,
-            response: None,
-            read_buffer: Bytes::new(),
+            uri: Uri::default(),
+            upload: None,
+            head: ResponseHead::default(),
+            body,
+            framing: None,
             write_buffer: Vec::with_capacity(DEFAULT_BUFFER_SIZE),
-        })
+            write_chunk: None,
+            hsts: None,
+            retry: None,
+            clock: Arc::new(SystemClock),
+            limit: None,
+            redirect_policy: None,
+            redirects: Vec::new(),
+            interceptors: Vec::new(),
+            status_policy: None,
+            raw,
+            prefetches: Vec::new(),
+            informational: None,
+            request_hook: None,
+            host_override: None,
+            options: RequestOptions::default(),
+            strict: false,
+            timing: false,
+            request_started: None,
+            timings: None,
+            #[cfg(feature = "decompression")]
+            decompression: false,
+        }
+    }
+
+    /// Enforces HSTS with `store`, which may be shared with other connections.
+    ///
+    /// `Strict-Transport-Security` headers of HTTPS responses are recorded in the
+    /// store, and `http://` requests to the hosts it lists are sent over HTTPS.
+    pub fn with_hsts(mut self, store: Arc<HstsStore>) -> Self {
+        self.hsts = Some(store);
+        self
+    }
+
+    /// Routes requests through `proxy`.
+    ///
+    /// With the `proxy-system` feature, this replaces the proxy detected from the
+    /// OS settings when the connection was created.
+    #[cfg(feature = "proxy")]
+    pub fn with_proxy(mut self, proxy: Proxy) -> Self {
+        self.proxy = Some(Arc::new(proxy));
+        self.rebuild_client();
+        self
+    }
+
+    /// Connects to every host directly, ignoring any detected system proxy.
+    #[cfg(feature = "proxy")]
+    pub fn without_proxy(mut self) -> Self {
+        self.proxy = None;
+        self.rebuild_client();
+        self
+    }
+
+    /// Sends `host` as the `Host` header of every request instead of the authority of
+    /// the URI.
+    ///
+    /// This reaches a virtual-hosted server through its IP address; over HTTPS,
+    /// `with_server_name` presents the matching name in the TLS handshake. The header
+    /// is dropped when a redirect leaves the origin.
+    pub fn with_host_override(mut self, host: &str) -> Result<Self, HyperError> {
+        self.host_override = Some(HeaderValue::from_str(host)?);
+        Ok(self)
+    }
+
+    /// Presents and verifies `server_name` in TLS handshakes with `host`.
+    ///
+    /// This reaches a server by IP address, or through another name, while
+    /// validating its certificate against the name it was issued for.
+    #[cfg(feature = "tls")]
+    pub fn with_server_name(mut self, host: &str, server_name: &str) -> Self {
+        self.tls.set_server_name(host, server_name);
+        self.rebuild_client();
+        self
+    }
+
+    /// Uses the TLS settings of `overrides` for the hosts they match.
+    ///
+    /// Other hosts keep the `TlsConfig` the connection was created with.
+    #[cfg(feature = "tls")]
+    pub fn with_tls_overrides(mut self, overrides: &TlsOverrides) -> Result<Self, HyperError> {
+        self.tls.set_overrides(overrides)?;
+        self.rebuild_client();
+        Ok(self)
+    }
+
+    /// Rebuilds the client after its connection settings changed.
+    #[cfg(any(feature = "tls", feature = "proxy"))]
+    fn rebuild_client(&mut self) {
+        #[cfg(feature = "proxy")]
+        let connector = ProxyConnector::new(http_connector(), self.proxy.clone());
+        #[cfg(not(feature = "proxy"))]
+        let connector = http_connector();
+        #[cfg(feature = "tls")]
+        let client = build_client(connector, &self.tls);
+        #[cfg(not(feature = "tls"))]
+        let client = build_client(connector);
+        self.client = client;
+    }
+
+    /// Sets how the runtime is shut down when the connection is dropped or closed.
+    pub fn with_shutdown(mut self, shutdown: Shutdown) -> Self {
+        self.rt.set_shutdown(shutdown);
+        self
+    }
+
+    /// Closes the connection, shutting its runtime down following the shutdown policy.
+    ///
+    /// Unlike dropping the connection, this reports a streamed upload cut short and a
+    /// runtime that did not shut down within `Shutdown::Timeout`.
+    pub fn close(mut self) -> Result<(), HyperError> {
+        let upload = self.upload.take();
+        if let Some(upload) = &upload {
+            upload.response.abort();
+        }
+        self.rt.close()?;
+        match upload {
+            Some(_) => Err(HyperError::UploadAborted),
+            None => Ok(()),
+        }
+    }
+
+    /// Retries failed requests following `policy`.
+    pub fn with_retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = Some(policy);
+        self
+    }
+
+    /// Waits between retries, times out requests queued by the concurrency limit and
+    /// expires prefetched responses on `clock` instead of the system clock.
+    ///
+    /// A `ManualClock` lets tests run retry schedules, queue timeouts and prefetch
+    /// expiry without sleeping.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Lets requests in flight only as allowed by `limit`, which may be shared with
+    /// other connections.
+    ///
+    /// Requests beyond the limit wait in its queue, and fail with
+    /// `HyperError::QueueFull` or `HyperError::QueueTimeout` if the queue is bounded.
+    pub fn with_concurrency_limit(mut self, limit: Arc<ConcurrencyLimit>) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Follows redirects of buffered requests following `policy`.
+    pub fn with_redirects(mut self, policy: RedirectPolicy) -> Self {
+        self.redirect_policy = Some(policy);
+        self
+    }
+
+    /// Fails `initiate_response` with `HyperError::Status` on statuses matching `policy`.
+    pub fn with_status_policy(mut self, policy: StatusPolicy) -> Self {
+        self.status_policy = Some(policy);
+        self
+    }
+
+    /// Calls `callback` with the status and headers of `1xx` interim responses.
+    ///
+    /// Interim responses such as `103 Early Hints` arrive before the final response,
+    /// so resources they announce in `Link` headers can be preloaded meanwhile. The
+    /// callback runs on a runtime thread.
+    pub fn with_informational_callback<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(StatusCode, &HeaderMap) + Send + Sync + 'static,
+    {
+        self.informational = Some(Arc::new(callback));
+        self
+    }
+
+    /// Calls `hook` with every request as it is initiated, before it is stored.
+    ///
+    /// The hook has full access to the `hyper` request, to set extensions, pin the
+    /// HTTP version or add header values that the `embedded-svc` string headers
+    /// cannot express. Unlike interceptors, it runs once per request, not before
+    /// each retry or redirect.
+    pub fn with_request_hook<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&mut Request<Bytes>) + Send + Sync + 'static,
+    {
+        self.request_hook = Some(Arc::new(hook));
+        self
+    }
+
+    /// Calls `callback` when response body data arrives after `try_read` found none.
+    ///
+    /// The callback runs on a runtime thread, and should only signal the thread
+    /// polling the connection, for example by writing to a pipe or an `eventfd`
+    /// watched by its `select` or `poll` loop, which then calls `try_read` again.
+    ///
+    /// Data is only received in the background with the multi-threaded runtime
+    /// (feature `rt-multi-thread`); the current-thread runtime runs during calls to
+    /// the connection only.
+    pub fn with_readable_callback<F>(mut self, callback: F) -> Self
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        self.body.set_readable_callback(Box::new(callback));
+        self
+    }
+
+    /// Checks that calls follow the request/response cycle if `strict` is set.
+    ///
+    /// Writing the body of a request that was already sent, reading before a response
+    /// is received, or sending a request that was not initiated then fails with
+    /// `HyperError::InvalidState` instead of being ignored or reporting a missing
+    /// request or response.
+    pub fn with_strict_state(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Times the phases of each request, reported by `timings`.
+    pub fn with_timing(mut self) -> Self {
+        self.timing = true;
+        self
+    }
+
+    /// Returns where the connection is in the request/response cycle.
+    pub fn state(&self) -> ConnectionState {
+        if self.is_request_initiated() {
+            ConnectionState::Request
+        } else if self.head.get().is_some() {
+            ConnectionState::Response
+        } else {
+            ConnectionState::Idle
+        }
+    }
+
+    /// Fails with `HyperError::InvalidState` in strict mode unless the connection is
+    /// in the `expected` state.
+    fn expect_state(&self, expected: ConnectionState) -> Result<(), HyperError> {
+        let actual = self.state();
+        if self.strict && actual != expected {
+            return Err(HyperError::InvalidState { expected, actual });
+        }
+        Ok(())
+    }
+
+    /// Requests compressed responses and decodes them as their body arrives.
+    ///
+    /// Requests without an `Accept-Encoding` header accept gzip, deflate and brotli.
+    /// Encoded responses are decoded chunk by chunk while they are read, and lose their
+    /// `Content-Encoding` and `Content-Length` headers.
+    #[cfg(feature = "decompression")]
+    pub fn with_decompression(mut self) -> Self {
+        self.decompression = true;
+        self
+    }
+
+    /// Runs `interceptor` on every request just before it is sent.
+    ///
+    /// Interceptors run in the order they were added, again before each retry and
+    /// redirect, on a fresh copy of the request, so signatures and nonces are never
+    /// reused.
+    pub fn with_interceptor(mut self, interceptor: impl Interceptor + 'static) -> Self {
+        self.interceptors.push(Box::new(interceptor));
+        self
+    }
+
+    /// Calls `hook` with the URI and headers of every request just before it is sent.
+    ///
+    /// The hook runs as an interceptor, after the interceptors added before it, which
+    /// makes it a lightweight way to add per-request headers such as nonces or trace IDs.
+    pub fn with_pre_request_hook<F>(self, hook: F) -> Self
+    where
+        F: FnMut(&mut Uri, &mut HeaderMap) + Send + 'static,
+    {
+        self.with_interceptor(PreRequestHook(Mutex::new(hook)))
     }
 
     /// Helper for mapping the embedded-svc HTTP `Method` enum to `hyper::Method`.
@@ -94,40 +660,695 @@ impl HyperHttpConnection {
     /// Constructs a `HeaderMap` from a slice of `(name, value)` pairs.
     ///
     /// Performs validation on header names and values, returning an error
-    /// if any are invalid.
+    /// if any are invalid. Repeated names are all sent, except `Cookie` values,
+    /// which are joined into a single header as servers expect.
     fn build_headers(headers: &[(&str, &str)]) -> Result<HeaderMap, HyperError> {
-        let mut header_map = HeaderMap::with_capacity(headers.len());
+        let mut header_map: HeaderMap = HeaderMap::with_capacity(headers.len());
 
         for &(name, value) in headers {
             let header_name =
                 HeaderName::from_bytes(name.as_bytes()).map_err(HyperError::InvalidHeaderName)?;
             let header_value =
                 HeaderValue::from_str(value).map_err(HyperError::InvalidHeaderValue)?;
-            header_map.insert(header_name, header_value);
+            if header_name == COOKIE
+                && let Some(cookies) = header_map.get_mut(COOKIE)
+            {
+                let mut joined = cookies.as_bytes().to_vec();
+                joined.extend_from_slice(b"; ");
+                joined.extend_from_slice(header_value.as_bytes());
+                *cookies = HeaderValue::from_bytes(&joined)?;
+                continue;
+            }
+            header_map.append(header_name, header_value);
         }
 
         Ok(header_map)
     }
 
-    /// Ensures that a response has been received, returning a reference to it.
+    /// Builds a request with an empty body, upgrading its URI to HTTPS if HSTS requires it.
     ///
-    /// Returns `HyperError::NoResponse` if no response is available.
-    fn ensure_response(&self) -> Result<&Response<Incoming>, HyperError> {
-        self.response.as_ref().ok_or(HyperError::NoResponse)
+    /// The request hook, if any, customizes the request last.
+    fn build_request<U>(
+        &self,
+        method: Method,
+        uri: U,
+        headers: &[(&str, &str)],
+        options: RequestOptions,
+    ) -> Result<Request<Bytes>, HyperError>
+    where
+        U: TryInto<Uri>,
+        U::Error: Into<hyper::http::Error>,
+    {
+        let mapped_method = Self::map_method(method)?;
+        let header_map = Self::build_headers(headers)?;
+        let uri = uri.try_into().map_err(Into::<hyper::http::Error>::into)?;
+        self.prepare_request(mapped_method, uri, header_map, options)
+    }
+
+    /// Builds a request with an empty body from parsed parts, applying the
+    /// connection's settings as `build_request` does.
+    fn prepare_request(
+        &self,
+        method: hyper::Method,
+        mut uri: Uri,
+        headers: HeaderMap,
+        #[allow(unused_variables)] options: RequestOptions,
+    ) -> Result<Request<Bytes>, HyperError> {
+        if let Some(upgraded) = self.hsts.as_ref().and_then(|hsts| hsts.upgrade(&uri)) {
+            uri = upgraded;
+        }
+        #[cfg(not(feature = "tls"))]
+        if uri.scheme() == Some(&Scheme::HTTPS) {
+            return Err(HyperError::HttpsUnavailable);
+        }
+
+        let mut request_builder = Request::builder().method(method).uri(uri);
+        if let Some(headers_mut) = request_builder.headers_mut() {
+            headers_mut.extend(headers);
+            if let Some(host) = &self.host_override {
+                headers_mut.insert(HOST, host.clone());
+            }
+        }
+
+        let mut request = request_builder
+            .body(Bytes::new())
+            .map_err(HyperError::Http)?;
+        #[cfg(feature = "decompression")]
+        if self.decompression
+            && options.decodes()
+            && !request.headers().contains_key(ACCEPT_ENCODING)
+        {
+            let accepted = HeaderValue::from_static(ACCEPTED_ENCODINGS);
+            request.headers_mut().insert(ACCEPT_ENCODING, accepted);
+        }
+        if let Some(callback) = self.informational.clone() {
+            hyper::ext::on_informational(&mut request, move |response| {
+                callback(response.status(), response.headers())
+            });
+        }
+        if let Some(hook) = &self.request_hook {
+            hook(&mut request);
+        }
+        #[cfg(feature = "proxy")]
+        self.authorize_proxy(&mut request);
+        Ok(request)
+    }
+
+    /// Adds the proxy credentials to a plain HTTP `request` sent through the proxy.
+    ///
+    /// HTTPS requests are tunnelled, so their credentials go in the `CONNECT` request.
+    #[cfg(feature = "proxy")]
+    fn authorize_proxy(&self, request: &mut Request<Bytes>) {
+        if let Some(proxy) = &self.proxy
+            && proxy.intercepts(request.uri())
+            && request.uri().scheme() != Some(&Scheme::HTTPS)
+            && !request.headers().contains_key(PROXY_AUTHORIZATION)
+            && let Some(value) = proxy.authorization(&[])
+        {
+            request.headers_mut().insert(PROXY_AUTHORIZATION, value);
+        }
+    }
+
+    /// Resends `request` once with new credentials if the proxy answered `407`.
+    #[cfg(feature = "proxy")]
+    fn answer_proxy_challenge(
+        &self,
+        request: &mut Request<Bytes>,
+        response: Response<Incoming>,
+    ) -> Result<Response<Incoming>, HyperError> {
+        let Some(proxy) = self.proxy.as_ref().filter(|p| p.intercepts(request.uri())) else {
+            return Ok(response);
+        };
+        if response.status() != StatusCode::PROXY_AUTHENTICATION_REQUIRED
+            || request.uri().scheme() == Some(&Scheme::HTTPS)
+        {
+            return Ok(response);
+        }
+        match proxy.authorization(&proxy::challenges(response.headers())) {
+            Some(value) if request.headers().get(PROXY_AUTHORIZATION) != Some(&value) => {
+                request.headers_mut().insert(PROXY_AUTHORIZATION, value);
+                self.send(request)
+            }
+            _ => Ok(response),
+        }
+    }
+
+    /// Opens a tunnel to `authority` (`host:port`) with a `CONNECT` request to the proxy.
+    ///
+    /// Returns the raw connection carrying the tunnel, which the tunnelled protocol
+    /// reads and writes directly. Fails with `HyperError::NoProxy` if no proxy handles
+    /// `authority`, and with `HyperError::ProxyTunnel` if the proxy refuses the tunnel.
+    #[cfg(feature = "proxy")]
+    pub fn connect_tunnel(
+        &mut self,
+        authority: &str,
+        headers: &[(&str, &str)],
+    ) -> Result<&mut RawConnection, HyperError> {
+        let uri = Uri::try_from(authority).map_err(hyper::http::Error::from)?;
+        if uri.scheme().is_some() || uri.path_and_query().is_some() || uri.port().is_none() {
+            let message = "tunnel destination must be host:port";
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, message).into());
+        }
+        if !self
+            .proxy
+            .as_ref()
+            .is_some_and(|proxy| proxy.intercepts(&uri))
+        {
+            return Err(HyperError::NoProxy(authority.to_owned()));
+        }
+
+        self.initiate_request_to(Method::Connect, uri, headers)?;
+        self.initiate_response()?;
+        let status = self.ensure_response()?.status();
+        if status == StatusCode::PROXY_AUTHENTICATION_REQUIRED {
+            return Err(HyperError::ProxyAuthRequired);
+        }
+        if !status.is_success() {
+            return Err(HyperError::ProxyTunnel(status));
+        }
+        self.raw_connection()
+    }
+
+    /// Begins constructing an HTTP request to a typed URI, such as a `hyper::Uri`.
+    ///
+    /// This is `Connection::initiate_request` without parsing a URI that is already
+    /// validated; a `url::Url` converts with `Uri::try_from(url.as_str())`.
+    pub fn initiate_request_to<U>(
+        &mut self,
+        method: Method,
+        uri: U,
+        headers: &[(&str, &str)],
+    ) -> Result<(), HyperError>
+    where
+        U: TryInto<Uri>,
+        U::Error: Into<hyper::http::Error>,
+    {
+        self.initiate_request_with(method, uri, headers, RequestOptions::default())
+    }
+
+    /// Begins constructing an HTTP request with some automatic behaviors of the
+    /// connection turned off by `options`.
+    ///
+    /// The options apply to this request only; the next one enables the connection's
+    /// settings again.
+    pub fn initiate_request_with<U>(
+        &mut self,
+        method: Method,
+        uri: U,
+        headers: &[(&str, &str)],
+        options: RequestOptions,
+    ) -> Result<(), HyperError>
+    where
+        U: TryInto<Uri>,
+        U::Error: Into<hyper::http::Error>,
+    {
+        let request = self.build_request(method, uri, headers, options)?;
+        self.start_request(request, options);
+        Ok(())
+    }
+
+    /// Begins a request built with the `http` crate types.
+    ///
+    /// Any method is accepted, including those `embedded_svc::http::Method` lacks.
+    /// The connection's settings apply as for `initiate_request`, the extensions of
+    /// `request` are kept, and its body is written as by `write_bytes`.
+    pub fn initiate_http_request(&mut self, request: Request<Bytes>) -> Result<(), HyperError> {
+        let (parts, body) = request.into_parts();
+        let options = RequestOptions::default();
+        let mut request = self.prepare_request(parts.method, parts.uri, parts.headers, options)?;
+        request.extensions_mut().extend(parts.extensions);
+        self.start_request(request, options);
+        if !body.is_empty() {
+            self.write_bytes(body)?;
+        }
+        Ok(())
+    }
+
+    /// Makes `request` the request in progress, forgetting the previous one.
+    fn start_request(&mut self, request: Request<Bytes>, options: RequestOptions) {
+        self.reset();
+        self.uri = request.uri().clone();
+        self.request = Some(request);
+        self.options = options;
+    }
+
+    /// Forgets the request and response in progress, keeping the settings.
+    pub(crate) fn reset(&mut self) {
+        self.request = None;
+        self.upload = None;
+        self.head.clear();
+        self.body.clear();
+        self.framing = None;
+        self.write_buffer.clear();
+        self.write_chunk = None;
+        self.raw.reset();
+        self.redirects.clear();
+        self.options = RequestOptions::default();
+        self.request_started = None;
+        self.timings = None;
     }
 
-    /// Loads the entire response body into the internal read buffer.
+    /// Sends a bodiless request ahead of time, holding its response until it is made.
     ///
-    /// This consumes the `Response<Incoming>` and collects its body
-    /// into a contiguous `Bytes` buffer for streamline `Read` operations.
-    fn load_response_body(&mut self) -> Result<(), HyperError> {
-        if let Some(mut response) = self.response.take() {
-            let body_future = response.body_mut().collect();
-            let body = self.rt.block_on(body_future).map_err(HyperError::Hyper)?;
-            self.read_buffer = body.to_bytes();
+    /// When a later request has the same method, URI and headers and no body,
+    /// `initiate_response` returns the held response instead of sending it again, so
+    /// fetching the next page overlaps with processing the current one.
+    ///
+    /// At most 8 responses are held, the oldest being dropped to make room, and a
+    /// response no request claimed within 30 seconds is dropped too.
+    pub fn prefetch<U>(
+        &mut self,
+        method: Method,
+        uri: U,
+        headers: &[(&str, &str)],
+    ) -> Result<(), HyperError>
+    where
+        U: TryInto<Uri>,
+        U::Error: Into<hyper::http::Error>,
+    {
+        let mut request = self.build_request(method, uri, headers, RequestOptions::default())?;
+        let method = request.method().clone();
+        let uri = request.uri().clone();
+        let headers = request.headers().clone();
+        self.intercept(&mut request, false)?;
+
+        let response = self.spawn_request(buffered(&request))?;
+        self.expire_prefetches();
+        if self.prefetches.len() == MAX_PREFETCHES {
+            self.prefetches.remove(0).response.abort();
         }
+        self.prefetches.push(Prefetch {
+            method,
+            uri,
+            headers,
+            response,
+            sent: self.clock.now(),
+        });
+        Ok(())
+    }
+
+    /// Drops the prefetched responses held for longer than `PREFETCH_TTL`.
+    fn expire_prefetches(&mut self) {
+        let now = self.clock.now();
+        self.prefetches.retain(|prefetch| {
+            let fresh = now.duration_since(prefetch.sent) < PREFETCH_TTL;
+            if !fresh {
+                prefetch.response.abort();
+            }
+            fresh
+        });
+    }
+
+    /// Resolves the host of `uri` and opens a connection to it ahead of the first
+    /// request, so that request does not wait for DNS, TCP and TLS setup.
+    ///
+    /// The connection is opened by a `HEAD` request to `uri`, whose response is
+    /// discarded, and is kept in the client's pool for the next requests to the same
+    /// origin; connections of a `ConnectionPool` share it. The request goes through
+    /// HSTS and the request hook, but not through the interceptors.
+    pub fn warm_up<U>(&self, uri: U) -> Result<(), HyperError>
+    where
+        U: TryInto<Uri>,
+        U::Error: Into<hyper::http::Error>,
+    {
+        let request = self.build_request(Method::Head, uri, &[], RequestOptions::default())?;
+        let _permit = self.acquire_permit()?;
+        let response = self.rt.block_on(self.client.request(buffered(&request)))?;
+        // The connection returns to the pool once the response is read.
+        self.rt.block_on(response.into_body().collect())?;
         Ok(())
     }
+
+    /// Returns the protocol negotiated through ALPN on the connection of the last
+    /// response, or `None` for plain HTTP or if the server picked none.
+    #[cfg(feature = "tls")]
+    pub fn negotiated_protocol(&self) -> Option<&[u8]> {
+        self.tls_info()?.alpn_protocol()
+    }
+
+    /// Returns the details of the TLS session that carried the last response, or
+    /// `None` for plain HTTP.
+    #[cfg(feature = "tls")]
+    pub fn tls_info(&self) -> Option<&TlsInfo> {
+        self.head.get()?.extensions().get::<TlsInfo>()
+    }
+
+    /// Returns how the body of the last response is delimited on the wire.
+    ///
+    /// The declared length is that of the body as sent, before any decompression.
+    pub fn framing(&self) -> Option<Framing> {
+        self.framing
+    }
+
+    /// Returns the addresses of the connection that carried the last response, and
+    /// whether it was reused from the pool.
+    pub fn connection_info(&self) -> Option<ConnectionInfo> {
+        self.raw.info()
+    }
+
+    /// Returns the durations of the phases of the last request, if timed with
+    /// `with_timing`.
+    ///
+    /// The total duration is known once the end of the response body is read.
+    pub fn timings(&self) -> Option<Timings> {
+        let mut timings = self.timings?;
+        timings.total = self
+            .body
+            .ended()
+            .zip(self.request_started)
+            .map(|(ended, started)| ended.duration_since(started));
+        Some(timings)
+    }
+
+    /// Returns the HTTP version the last response was received with, such as
+    /// `Version::HTTP_11` or `Version::HTTP_2`.
+    pub fn version(&self) -> Option<Version> {
+        self.head.version()
+    }
+
+    /// Returns `true` if the connection of the last response is closed once its body
+    /// is read, rather than kept for the next request.
+    pub fn will_close(&self) -> bool {
+        match (self.head.get(), self.framing) {
+            (Some(response), Some(framing)) => framing::closes_connection(response, framing),
+            _ => false,
+        }
+    }
+
+    /// Returns `true` if the last response carries a body.
+    ///
+    /// Responses to `HEAD` requests, `1xx`, `204` and `304` responses, and responses
+    /// with an empty body have none, so reading them returns EOF immediately and
+    /// callers can skip their read loop.
+    pub fn has_body(&self) -> bool {
+        self.body.has_body()
+    }
+
+    /// Returns the next chunk of the response body as received, or `None` at the end.
+    ///
+    /// Unlike `read`, chunks keep the boundaries of the data frames sent by the server
+    /// and are not copied. Both can be mixed; `read` returns what is left.
+    pub fn next_chunk(&mut self) -> Result<Option<Bytes>, HyperError> {
+        self.expect_state(ConnectionState::Response)?;
+        self.body.next_chunk()
+    }
+
+    /// Reads response body data already received, without blocking.
+    ///
+    /// Returns `None` if no data has arrived yet, and `Some(0)` at the end of the
+    /// body.
+    pub fn try_read(&mut self, buffer: &mut [u8]) -> Result<Option<usize>, HyperError> {
+        self.expect_state(ConnectionState::Response)?;
+        self.body.try_read(buffer)
+    }
+
+    /// Takes the rest of the response body out as an async reader.
+    ///
+    /// Data already received is read first. The reader makes progress on the
+    /// connection's runtime, so it is driven with `block_on`.
+    pub fn async_reader(&mut self) -> Result<AsyncResponseBody, HyperError> {
+        self.expect_state(ConnectionState::Response)?;
+        Ok(self.body.take_async())
+    }
+
+    /// Wraps the connection in a `std::io` reader of the response body.
+    ///
+    /// The connection is given back by `BodyReader::into_inner`.
+    pub fn into_reader(self) -> Result<BodyReader, HyperError> {
+        self.expect_state(ConnectionState::Response)?;
+        Ok(BodyReader::new(self))
+    }
+
+    /// Writes `chunk` to the request body without copying it.
+    ///
+    /// A body made of a single chunk is sent as is, and each chunk of a streamed upload
+    /// is handed to the connection directly. Chunks following other written data are
+    /// copied into the body buffer like `write` does.
+    pub fn write_bytes(&mut self, chunk: Bytes) -> Result<(), HyperError> {
+        self.expect_state(ConnectionState::Request)?;
+        if self.upload.is_some() || self.is_chunked() {
+            if chunk.is_empty() {
+                return Ok(());
+            }
+            let sender = self.start_upload()?.sender.clone();
+            return self
+                .rt
+                .block_on(sender.send(chunk))
+                .map_err(|_| HyperError::UploadAborted);
+        }
+        if self.write_buffer.is_empty() && self.write_chunk.is_none() {
+            self.write_chunk = Some(chunk);
+        } else {
+            self.buffer_write_chunk();
+            self.write_buffer.extend_from_slice(&chunk);
+        }
+        Ok(())
+    }
+
+    /// Moves the chunk given to `write_bytes` into the body buffer, if any.
+    fn buffer_write_chunk(&mut self) {
+        if let Some(chunk) = self.write_chunk.take() {
+            self.write_buffer.extend_from_slice(&chunk);
+        }
+    }
+
+    /// Sends the initiated request with a body streamed from an async writer.
+    ///
+    /// Data written before is sent first. The writer makes progress on the
+    /// connection's runtime, so it is driven with `block_on`, and must be shut down
+    /// or dropped before `initiate_response` returns the response.
+    pub fn async_writer(&mut self) -> Result<AsyncRequestBody, HyperError> {
+        self.expect_state(ConnectionState::Request)?;
+        let written = match self.write_chunk.take() {
+            Some(chunk) => chunk,
+            None => Bytes::from(std::mem::take(&mut self.write_buffer)),
+        };
+        let sender = self.start_upload()?.sender.clone();
+        if !written.is_empty() {
+            self.rt
+                .block_on(sender.send(written))
+                .map_err(|_| HyperError::UploadAborted)?;
+        }
+        Ok(AsyncRequestBody::new(sender))
+    }
+
+    /// Passes the rest of the response body to `f` one chunk at a time.
+    pub fn for_each_chunk<F>(&mut self, mut f: F) -> Result<(), HyperError>
+    where
+        F: FnMut(Bytes),
+    {
+        while let Some(chunk) = self.next_chunk()? {
+            f(chunk);
+        }
+        Ok(())
+    }
+
+    /// Takes the last response out of the connection as a `hyper` response, keeping
+    /// the client and its settings for the next requests.
+    ///
+    /// This gives access to what the `embedded-svc` interface hides, such as the
+    /// extensions, the HTTP version or `hyper::upgrade::on`. The body is the one sent
+    /// by the server: it is not decompressed and lacks the data already read. It is
+    /// received on the connection's runtime, so it must be polled with `block_on`.
+    pub fn take_hyper_response(&mut self) -> Result<Response<Incoming>, HyperError> {
+        let head = self.head.take().ok_or(HyperError::NoResponse)?;
+        let body = self.body.take().ok_or(HyperError::NoResponse)?;
+        self.framing = None;
+        Ok(head.map(|()| body))
+    }
+
+    /// Takes the last response out of the connection as an `http` response, with the
+    /// rest of its body read and decoded.
+    ///
+    /// Unlike `take_hyper_response`, the body needs no further polling, so the
+    /// response can be handed to code built around the `http` crate types.
+    pub fn take_http_response(&mut self) -> Result<Response<Bytes>, HyperError> {
+        self.expect_state(ConnectionState::Response)?;
+        self.ensure_response()?;
+        let mut body = Vec::new();
+        self.for_each_chunk(|chunk| body.extend_from_slice(&chunk))?;
+        let head = self.head.take().ok_or(HyperError::NoResponse)?;
+        self.body.clear();
+        self.framing = None;
+        Ok(head.map(|()| Bytes::from(body)))
+    }
+
+    /// Runs `future` to completion on the connection's runtime.
+    ///
+    /// Futures of `hyper` types taken from the connection, such as the body of
+    /// `take_hyper_response`, make progress while blocked on here.
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.rt.block_on(future)
+    }
+
+    /// Passes `request` through the interceptors.
+    fn intercept(&self, request: &mut Request<Bytes>, streaming: bool) -> Result<(), HyperError> {
+        let context = Context::new(streaming, &self.rt, &self.client);
+        self.interceptors
+            .iter()
+            .try_for_each(|interceptor| interceptor.intercept(request, &context))
+    }
+
+    /// Sends `request` through the interceptors, retrying as allowed by the retry policy.
+    ///
+    /// The interceptors run on a copy of `request` before every attempt.
+    fn send(&self, request: &Request<Bytes>) -> Result<Response<Incoming>, HyperError> {
+        let enabled = self.options.retries_failures();
+        let policy = self.retry.as_ref().filter(|p| enabled && p.allows(request));
+        let mut retries = 0;
+        loop {
+            let mut attempt = duplicate(request);
+            self.intercept(&mut attempt, false)?;
+            let permit = self.acquire_permit()?;
+            let response = self.rt.block_on(self.client.request(buffered(&attempt)));
+            drop(permit);
+            let failed = match &response {
+                Ok(response) => RetryPolicy::is_retryable_status(response.status()),
+                Err(_) => true,
+            };
+            match policy {
+                Some(policy) if failed && retries < policy.max_retries() => {
+                    self.clock.sleep(policy.delay(retries));
+                    retries += 1;
+                }
+                _ => return response.map_err(HyperError::Client),
+            }
+        }
+    }
+
+    /// Waits until the concurrency limit, if any, lets another request in flight.
+    fn acquire_permit(&self) -> Result<Option<OwnedSemaphorePermit>, HyperError> {
+        let limit = self.limit.as_ref();
+        limit
+            .map(|limit| self.rt.block_on(limit.acquire(&*self.clock)))
+            .transpose()
+    }
+
+    /// Starts sending `request` on the runtime once the concurrency limit allows it.
+    fn spawn_request(&self, request: Request<RequestBody>) -> Result<PendingResponse, HyperError> {
+        let permit = self.acquire_permit()?;
+        let response = self.client.request(request);
+        Ok(self.rt.spawn(async move {
+            let response = response.await;
+            drop(permit);
+            response
+        }))
+    }
+
+    /// Follows the redirects answering `request` as allowed by the redirect policy.
+    fn follow_redirects(
+        &mut self,
+        mut request: Request<Bytes>,
+        mut response: Response<Incoming>,
+    ) -> Result<Response<Incoming>, HyperError> {
+        let Some(policy) = self
+            .redirect_policy
+            .as_ref()
+            .filter(|_| self.options.follows_redirects())
+        else {
+            return Ok(response);
+        };
+        while let Some(mut location) = redirect::location(request.uri(), &response) {
+            if self.redirects.len() == policy.max_redirects() {
+                return Err(HyperError::TooManyRedirects(policy.max_redirects()));
+            }
+            if let Some(upgraded) = self.hsts.as_ref().and_then(|hsts| hsts.upgrade(&location)) {
+                location = upgraded;
+            }
+            let status = response.status();
+            self.redirects.push(Redirect {
+                uri: request.uri().clone(),
+                status,
+                location: location.clone(),
+            });
+            policy.follow(&mut request, status, location);
+            #[cfg(feature = "proxy")]
+            self.authorize_proxy(&mut request);
+            response = self.send(&request)?;
+        }
+        self.uri = request.uri().clone();
+        Ok(response)
+    }
+
+    /// Returns the redirects followed while answering the last request, in order.
+    ///
+    /// The `location` of the last one is the URI that produced the response.
+    pub fn redirect_history(&self) -> &[Redirect] {
+        &self.redirects
+    }
+
+    /// Returns `true` if the initiated request asked for `Transfer-Encoding: chunked`.
+    fn is_chunked(&self) -> bool {
+        self.request.as_ref().is_some_and(|request| {
+            request
+                .headers()
+                .get(TRANSFER_ENCODING)
+                .is_some_and(|value| value.as_bytes().eq_ignore_ascii_case(b"chunked"))
+        })
+    }
+
+    /// Sends the initiated request with a body streamed from `write` calls.
+    fn start_upload(&mut self) -> Result<&mut Upload, HyperError> {
+        if self.upload.is_none() {
+            let mut request = self.request.take().ok_or(HyperError::NoRequest)?;
+            self.intercept(&mut request, true)?;
+            let (sender, body) = ChannelBody::new();
+            let request = request.map(|_| body.boxed());
+            self.start_timing();
+            let response = self.spawn_request(request)?;
+            self.upload = Some(Upload { sender, response });
+        }
+        Ok(self.upload.as_mut().expect("upload started above"))
+    }
+
+    /// Starts timing the request being sent, if timed and not started yet.
+    fn start_timing(&mut self) {
+        if self.timing && self.request_started.is_none() {
+            self.request_started = Some(Instant::now());
+        }
+    }
+
+    /// Ensures that a response has been received, returning a reference to it.
+    ///
+    /// Returns `HyperError::NoResponse` if no response is available.
+    fn ensure_response(&self) -> Result<&Response<()>, HyperError> {
+        self.head.get().ok_or(HyperError::NoResponse)
+    }
+
+    /// Returns the decoder of `response` if decompression is enabled and it is encoded,
+    /// removing the headers describing the encoded body.
+    #[cfg(feature = "decompression")]
+    fn start_decoding<B>(&self, response: &mut Response<B>) -> Option<Decoder> {
+        if !self.decompression || !self.options.decodes() {
+            return None;
+        }
+        let decoder = response
+            .headers()
+            .get(CONTENT_ENCODING)
+            .and_then(|value| value.to_str().ok())
+            .and_then(Decoder::for_encoding);
+        if decoder.is_some() {
+            response.headers_mut().remove(CONTENT_ENCODING);
+            response.headers_mut().remove(CONTENT_LENGTH);
+        }
+        decoder
+    }
+}
+
+/// Returns a copy of `request`.
+fn duplicate(request: &Request<Bytes>) -> Request<Bytes> {
+    let mut copy = Request::new(request.body().clone());
+    *copy.method_mut() = request.method().clone();
+    *copy.uri_mut() = request.uri().clone();
+    *copy.version_mut() = request.version();
+    *copy.headers_mut() = request.headers().clone();
+    *copy.extensions_mut() = request.extensions().clone();
+    copy
+}
+
+/// Returns a copy of `request` with a body that can be sent.
+fn buffered(request: &Request<Bytes>) -> Request<RequestBody> {
+    duplicate(request).map(|body| Full::new(body).boxed())
 }
 
 impl Default for HyperHttpConnection {
@@ -139,6 +1360,18 @@ impl Default for HyperHttpConnection {
     }
 }
 
+impl TryFrom<Request<Bytes>> for HyperHttpConnection {
+    /// The error returned when the connection or the request cannot be set up.
+    type Error = HyperError;
+
+    /// Creates a connection with the default settings and begins `request` on it.
+    fn try_from(request: Request<Bytes>) -> Result<Self, HyperError> {
+        let mut conn = Self::new()?;
+        conn.initiate_http_request(request)?;
+        Ok(conn)
+    }
+}
+
 impl ErrorType for HyperHttpConnection {
     /// The error type returned by this connection.
     type Error = HyperError;
@@ -147,71 +1380,93 @@ impl ErrorType for HyperHttpConnection {
 impl Status for HyperHttpConnection {
     /// Returns the HTTP status code of the last response, or 500 if none.
     fn status(&self) -> u16 {
-        self.ensure_response()
-            .map(|response| response.status().as_u16())
-            .unwrap_or(500)
+        self.head.status()
     }
 
     /// Returns the reason phrase of the last response status, if available.
     fn status_message(&self) -> Option<&'_ str> {
-        self.ensure_response()
-            .ok()
-            .and_then(|response| response.status().canonical_reason())
+        self.head.status_message()
     }
 }
 
 impl Headers for HyperHttpConnection {
     /// Retrieves a header value by name from the last response, if set.
     fn header(&self, name: &str) -> Option<&'_ str> {
-        self.ensure_response()
-            .ok()
-            .and_then(|response| response.headers().get(name))
-            .and_then(|value| value.to_str().ok())
+        self.head.header(name)
     }
-}
 
-impl Read for HyperHttpConnection {
-    /// Reads data from the internal buffer, loading the response
-    /// body if needed. Returns `Ok(0)` on EOF.
-    fn read(&mut self, buffer: &mut [u8]) -> Result<usize, Self::Error> {
-        // Load the body if buffer empty and response exists
-        if self.read_buffer.is_empty() && self.response.is_some() {
-            self.load_response_body()?;
-        }
+    /// Returns the `Content-Type` of the last response, looked up by its known name.
+    fn content_type(&self) -> Option<&'_ str> {
+        self.head.content_type()
+    }
 
-        if self.read_buffer.is_empty() {
-            return Ok(0); // EOF
-        }
+    /// Returns the `Content-Length` of the last response, parsed when it arrived.
+    ///
+    /// Decoded responses have none, as their length is only known once read.
+    fn content_len(&self) -> Option<u64> {
+        self.head.content_len()
+    }
 
-        let length = self.read_buffer.len().min(buffer.len());
-        buffer[..length].copy_from_slice(&self.read_buffer[..length]);
-        self.read_buffer = self.read_buffer.slice(length..);
+    /// Returns the `Content-Encoding` of the last response, looked up by its known name.
+    fn content_encoding(&self) -> Option<&'_ str> {
+        self.head.content_encoding()
+    }
+}
 
-        Ok(length)
+impl Read for HyperHttpConnection {
+    /// Reads data from the internal buffer, receiving the next body chunk
+    /// if needed. Returns `Ok(0)` on EOF.
+    fn read(&mut self, buffer: &mut [u8]) -> Result<usize, Self::Error> {
+        self.expect_state(ConnectionState::Response)?;
+        self.body.read(buffer)
     }
 }
 
 impl Write for HyperHttpConnection {
     /// Buffers data to be sent in the request body.
+    ///
+    /// If the request has a `Transfer-Encoding: chunked` header, the request is sent on
+    /// the first write and each write is streamed as a chunk instead, so bodies of
+    /// unknown length need not be buffered.
     fn write(&mut self, buf: &[u8]) -> Result<usize, HyperError> {
-        self.write_buffer.extend_from_slice(buf);
+        self.expect_state(ConnectionState::Request)?;
+        if self.upload.is_none() && !self.is_chunked() {
+            self.buffer_write_chunk();
+            self.write_buffer.extend_from_slice(buf);
+            return Ok(buf.len());
+        }
+        self.write_bytes(Bytes::copy_from_slice(buf))?;
         Ok(buf.len())
     }
 
     /// Finalizes the request body by replacing it with the buffered data.
+    ///
+    /// Sets `Content-Length` to the body size unless the request already has a
+    /// `Content-Length` or `Transfer-Encoding` header.
     fn flush(&mut self) -> Result<(), HyperError> {
+        self.expect_state(ConnectionState::Request)?;
+        if self.upload.is_some() {
+            return Ok(());
+        }
         let request = self.request.as_mut().ok_or(HyperError::NoRequest)?;
-        let body_data = std::mem::take(&mut self.write_buffer);
-        *request.body_mut() = Full::from(body_data);
+        let body_data = match self.write_chunk.take() {
+            Some(chunk) => chunk,
+            None => Bytes::from(std::mem::take(&mut self.write_buffer)),
+        };
+        let headers = request.headers_mut();
+        if !headers.contains_key(CONTENT_LENGTH) && !headers.contains_key(TRANSFER_ENCODING) {
+            headers.insert(CONTENT_LENGTH, HeaderValue::from(body_data.len()));
+        }
+        *request.body_mut() = body_data;
         Ok(())
     }
 }
 
 impl Connection for HyperHttpConnection {
-    type Headers = Self;
-    type Read = Self;
+    type Headers = ResponseHead;
+    type Read = ResponseBody;
     type RawConnectionError = HyperError;
-    type RawConnection = Self;
+    type RawConnection = RawConnection;
 
     /// Begins constructing an HTTP request with method, URI, and headers.
     fn initiate_request<'a>(
@@ -220,67 +1475,217 @@ impl Connection for HyperHttpConnection {
         uri: &'a str,
         headers: &'a [(&'a str, &'a str)],
     ) -> Result<(), Self::Error> {
-        let mapped_method = Self::map_method(method)?;
-        let header_map = Self::build_headers(headers)?;
-
-        let mut request_builder = Request::builder().method(mapped_method).uri(uri);
-        if let Some(headers_mut) = request_builder.headers_mut() {
-            headers_mut.extend(header_map);
-        }
-
-        let request = request_builder
-            .body(Full::from(Bytes::new()))
-            .map_err(HyperError::Http)?;
-
-        self.request = Some(request);
-        self.response = None;
-        self.read_buffer = Bytes::new();
-        self.write_buffer.clear();
-
-        Ok(())
+        self.initiate_request_to(method, uri, headers)
     }
 
     /// Returns `true` if a request has been initiated.
     fn is_request_initiated(&self) -> bool {
-        self.request.is_some()
+        self.request.is_some() || self.upload.is_some()
     }
 
     /// Sends the initiated request and stores the response.
+    ///
+    /// Data written but not flushed yet is sent as the body. A streamed body is
+    /// ended with the final chunk.
     fn initiate_response(&mut self) -> Result<(), Self::Error> {
-        let request = self.request.take().ok_or(HyperError::NoRequest)?;
-        let response_future = self.client.request(request);
-        let response = self
-            .rt
-            .block_on(response_future)
-            .map_err(HyperError::Client)?;
-
-        self.response = Some(response);
+        self.expect_state(ConnectionState::Request)?;
+        if self.is_chunked() {
+            self.start_upload()?;
+        }
+        let (head, response) = if let Some(upload) = self.upload.take() {
+            drop(upload.sender);
+            let response = self
+                .rt
+                .block_on(upload.response)
+                .map_err(io::Error::other)?;
+            (false, response.map_err(HyperError::Client)?)
+        } else {
+            if !self.write_buffer.is_empty() || self.write_chunk.is_some() {
+                self.flush()?;
+            }
+            #[allow(unused_mut)]
+            let mut request = self.request.take().ok_or(HyperError::NoRequest)?;
+            self.start_timing();
+            let head = request.method() == hyper::Method::HEAD;
+            self.expire_prefetches();
+            let prefetched = self.prefetches.iter().position(|p| p.matches(&request));
+            let response = if let Some(index) = prefetched {
+                let prefetch = self.prefetches.remove(index);
+                let response = self
+                    .rt
+                    .block_on(prefetch.response)
+                    .map_err(io::Error::other)?;
+                response.map_err(HyperError::Client)?
+            } else {
+                self.send(&request)?
+            };
+            #[cfg(feature = "proxy")]
+            let response = self.answer_proxy_challenge(&mut request, response)?;
+            (head, self.follow_redirects(request, response)?)
+        };
+
+        self.raw.record(&response);
+        if let Some(started) = self.request_started {
+            let phases = response.extensions().get::<ConnectPhases>();
+            let phases = phases.filter(|_| !self.raw.is_reused()).copied();
+            self.timings = Some(Timings::new(phases.unwrap_or_default(), started.elapsed()));
+        }
+        if let Some(hsts) = &self.hsts
+            && self.uri.scheme() == Some(&Scheme::HTTPS)
+            && let Some(host) = self.uri.host()
+            && let Some(header) = response.headers().get(STRICT_TRANSPORT_SECURITY)
+            && let Ok(header) = header.to_str()
+        {
+            hsts.record(host, header);
+        }
+
+        if let Some(policy) = &self.status_policy
+            && policy.matches(response.status())
+        {
+            let error = policy.capture(&self.rt, response);
+            return Err(HyperError::Status(Box::new(error)));
+        }
+
+        self.framing = Some(Framing::of(&response, head));
+        #[cfg(feature = "decompression")]
+        let mut response = response;
+        #[cfg(feature = "decompression")]
+        let decoder = self.start_decoding(&mut response);
+
+        let status = response.status().as_u16();
+        let has_body =
+            !head && !matches!(status, 100..=199 | 204 | 304) && !response.body().is_end_stream();
+        let (parts, body) = response.into_parts();
+        self.head.set(Response::from_parts(parts, ()));
+        self.body.set(
+            body,
+            has_body,
+            #[cfg(feature = "decompression")]
+            decoder,
+        );
         Ok(())
     }
 
     /// Returns `true` if a response has been received.
     fn is_response_initiated(&self) -> bool {
-        self.response.is_some()
+        self.head.get().is_some()
     }
 
     /// Splits the connection into its header and body parts.
     fn split(&mut self) -> (&Self::Headers, &mut Self::Read) {
-        // Safe pointer dance to return two references to self
-        let headers: *const Self = self;
-        let headers = unsafe { &*headers };
-        (headers, self)
+        (&self.head, &mut self.body)
     }
 
-    /// Returns a mutable reference to the raw connection.
+    /// Returns the connection of the last response.
+    ///
+    /// After a `101 Switching Protocols` response or a successful `CONNECT`, the
+    /// connection is taken over from HTTP and its stream can be read and written
+    /// directly.
     fn raw_connection(&mut self) -> Result<&mut Self::RawConnection, Self::Error> {
-        Ok(self)
+        self.expect_state(ConnectionState::Response)?;
+        // `hyper` only offers the connection of responses ending HTTP on it.
+        if let Some(response) = self.head.get_mut()
+            && response.extensions().get::<OnUpgrade>().is_some()
+        {
+            self.raw.upgrade(response)?;
+        }
+        Ok(&mut self.raw)
     }
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
+    use crate::clock::ManualClock;
     use embedded_svc::http::client::Client;
+    use std::net::SocketAddr;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    /// Starts a server answering `count` requests, one per connection, with the raw
+    /// response `respond` returns for the index, head and body of each request, and
+    /// returning the heads and bodies it received.
+    ///
+    /// Header names in the heads are lowercase, as sent by hyper, and chunked bodies
+    /// are decoded.
+    pub(crate) fn spawn_handler<F>(
+        count: usize,
+        mut respond: F,
+    ) -> (SocketAddr, thread::JoinHandle<Vec<(String, String)>>)
+    where
+        F: FnMut(usize, &str, &str) -> String + Send + 'static,
+    {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            use std::io::{BufRead, BufReader, Read, Write};
+
+            let mut requests = Vec::new();
+            for index in 0..count {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut head = String::new();
+                while reader.read_line(&mut head).unwrap() > 2 {}
+
+                let mut body = Vec::new();
+                if head.contains("transfer-encoding: chunked\r\n") {
+                    loop {
+                        let mut size = String::new();
+                        reader.read_line(&mut size).unwrap();
+                        let size = usize::from_str_radix(size.trim(), 16).unwrap();
+                        let mut chunk = vec![0; size + 2];
+                        reader.read_exact(&mut chunk).unwrap();
+                        if size == 0 {
+                            break;
+                        }
+                        body.extend_from_slice(&chunk[..size]);
+                    }
+                } else if let Some(length) = head
+                    .split("\r\n")
+                    .find_map(|line| line.strip_prefix("content-length: "))
+                {
+                    body.resize(length.parse().unwrap(), 0);
+                    reader.read_exact(&mut body).unwrap();
+                }
+
+                let body = String::from_utf8(body).unwrap();
+                let response = respond(index, &head, &body);
+                stream.write_all(response.as_bytes()).unwrap();
+                requests.push((head, body));
+            }
+            requests
+        });
+        (addr, server)
+    }
+
+    /// Returns a `200` response closing the connection, carrying `body` of type
+    /// `content_type`.
+    pub(crate) fn ok_response(content_type: &str, body: &str) -> String {
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\n\
+             Connection: close\r\n\r\n{body}",
+            body.len()
+        )
+    }
+
+    /// Starts a server answering one request per connection with each of `statuses`
+    /// in turn, redirecting to `/next`, and returning the request heads it received.
+    pub(crate) fn spawn_server(
+        statuses: &'static [&'static str],
+    ) -> (SocketAddr, thread::JoinHandle<Vec<String>>) {
+        let (addr, server) = spawn_handler(statuses.len(), |index, _, _| {
+            format!(
+                "HTTP/1.1 {}\r\nLocation: /next\r\nContent-Length: 0\r\n\
+                 Connection: close\r\n\r\n",
+                statuses[index]
+            )
+        });
+        let heads = thread::spawn(move || {
+            let requests = server.join().unwrap();
+            requests.into_iter().map(|(head, _)| head).collect()
+        });
+        (addr, heads)
+    }
 
     /// Tests a full GET request/response cycle against httpbin.org
     #[test]
@@ -336,4 +1741,844 @@ mod tests {
 
         println!("{}", str::from_utf8(&body).unwrap());
     }
+
+    /// Tests that bodiless responses report no body and read as EOF.
+    #[test]
+    fn test_bodiless_responses() {
+        let (addr, server) = spawn_handler(2, |index, _, _| match index {
+            0 => "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\
+                  Content-Length: 20\r\nConnection: close\r\n\r\n"
+                .to_owned(),
+            _ => "HTTP/1.1 204 No Content\r\nConnection: close\r\n\r\n".to_owned(),
+        });
+        let mut conn = HyperHttpConnection::new().unwrap();
+        let mut buf = [0u8; 16];
+
+        conn.initiate_request(Method::Head, &format!("http://{addr}/get"), &[])
+            .unwrap();
+        conn.initiate_response().unwrap();
+        assert!(!conn.has_body());
+        assert!(conn.header("Content-Type").is_some());
+        assert_eq!(conn.read(&mut buf).unwrap(), 0);
+
+        conn.initiate_request(Method::Get, &format!("http://{addr}/status/204"), &[])
+            .unwrap();
+        conn.initiate_response().unwrap();
+        assert_eq!(conn.status(), 204);
+        assert!(!conn.has_body());
+        assert_eq!(conn.read(&mut buf).unwrap(), 0);
+        assert!(server.join().unwrap()[0].0.starts_with("HEAD /get "));
+    }
+
+    /// Tests that `Content-Length` is derived from the buffered body.
+    #[test]
+    fn test_automatic_content_length() {
+        let (addr, server) = spawn_handler(1, |_, _, _| ok_response("text/plain", ""));
+        let conn = HyperHttpConnection::new().unwrap();
+        let mut client = Client::wrap(conn);
+
+        let headers = &[("Content-Type", "application/json")];
+        let uri = format!("http://{addr}/post");
+        let mut request = client.post(&uri, headers).unwrap();
+        request.write(br#"{"test": 2}"#).unwrap();
+        let response = request.submit().unwrap();
+        assert_eq!(response.status(), 200);
+
+        let (head, body) = &server.join().unwrap()[0];
+        assert!(head.contains("content-length: 11\r\n"));
+        assert_eq!(body, r#"{"test": 2}"#);
+    }
+
+    /// Tests streaming a request body with chunked transfer encoding.
+    #[test]
+    fn test_chunked_upload() {
+        let (addr, server) = spawn_handler(1, |_, _, body| ok_response("text/plain", body));
+        let conn = HyperHttpConnection::new().unwrap();
+        let mut client = Client::wrap(conn);
+
+        let headers = &[("Transfer-Encoding", "chunked")];
+        let uri = format!("http://{addr}/post");
+        let mut request = client.post(&uri, headers).unwrap();
+        for part in ["streamed ", "in ", "chunks"] {
+            request.write(part.as_bytes()).unwrap();
+        }
+        let mut response = request.submit().unwrap();
+        assert_eq!(response.status(), 200);
+
+        let mut body = Vec::new();
+        let mut buf = [0u8; 1024];
+        loop {
+            match response.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => body.extend_from_slice(&buf[..n]),
+                Err(e) => panic!("{:?}", e),
+            }
+        }
+        assert_eq!(str::from_utf8(&body).unwrap(), "streamed in chunks");
+
+        let (head, _) = &server.join().unwrap()[0];
+        assert!(head.contains("transfer-encoding: chunked\r\n"));
+        assert!(!head.contains("content-length"));
+    }
+
+    /// Tests consuming a streamed response body chunk by chunk.
+    #[test]
+    fn test_for_each_chunk() {
+        let (addr, server) = spawn_handler(1, |_, _, _| {
+            let chunk = format!("200\r\n{}\r\n", "x".repeat(512));
+            format!(
+                "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n\
+                 {}0\r\n\r\n",
+                chunk.repeat(8)
+            )
+        });
+        let mut conn = HyperHttpConnection::new().unwrap();
+        let uri = format!("http://{addr}/stream-bytes/4096");
+        conn.initiate_request(Method::Get, &uri, &[]).unwrap();
+        conn.initiate_response().unwrap();
+        server.join().unwrap();
+
+        let mut chunks = 0;
+        let mut length = 0;
+        conn.for_each_chunk(|chunk| {
+            chunks += 1;
+            length += chunk.len();
+        })
+        .unwrap();
+        assert!(chunks > 1);
+        assert_eq!(length, 4096);
+        assert_eq!(conn.next_chunk().unwrap(), None);
+    }
+
+    /// Tests that interceptors run again on each retried attempt.
+    #[test]
+    fn test_interceptors_on_retry() {
+        struct Attempts(std::sync::atomic::AtomicUsize);
+
+        impl Interceptor for Attempts {
+            fn intercept(
+                &self,
+                request: &mut Request<Bytes>,
+                _: &Context,
+            ) -> Result<(), HyperError> {
+                let attempt = self.0.fetch_add(1, std::sync::atomic::Ordering::Relaxed) + 1;
+                request
+                    .headers_mut()
+                    .insert("x-attempt", HeaderValue::from(attempt));
+                Ok(())
+            }
+        }
+
+        let (addr, server) = spawn_server(&["503 Service Unavailable", "200 OK"]);
+        let mut conn = HyperHttpConnection::new()
+            .unwrap()
+            .with_retry(RetryPolicy::new(1).backoff(Duration::ZERO))
+            .with_interceptor(Attempts(Default::default()));
+        conn.initiate_request(Method::Get, &format!("http://{addr}/"), &[])
+            .unwrap();
+        conn.initiate_response().unwrap();
+        assert_eq!(conn.status(), 200);
+
+        let heads = server.join().unwrap();
+        assert!(heads[0].contains("x-attempt: 1\r\n"));
+        assert!(heads[1].contains("x-attempt: 2\r\n"));
+    }
+
+    /// Tests that a prefetched response is used by the matching request.
+    #[test]
+    fn test_prefetch() {
+        let (addr, server) = spawn_handler(1, |_, head, _| {
+            let page = head.split(['=', ' ']).nth(2).unwrap();
+            ok_response("text/plain", &format!("page {page}"))
+        });
+        let mut conn = HyperHttpConnection::new().unwrap();
+        let uri = format!("http://{addr}/get?page=2");
+        conn.prefetch(Method::Get, &uri, &[]).unwrap();
+        assert_eq!(conn.prefetches.len(), 1);
+
+        conn.initiate_request(Method::Get, &uri, &[]).unwrap();
+        conn.initiate_response().unwrap();
+        assert!(conn.prefetches.is_empty());
+        assert_eq!(conn.status(), 200);
+        let mut body = Vec::new();
+        conn.for_each_chunk(|chunk| body.extend_from_slice(&chunk))
+            .unwrap();
+        assert_eq!(str::from_utf8(&body).unwrap(), "page 2");
+        assert_eq!(server.join().unwrap().len(), 1);
+    }
+
+    /// Tests that unmatched prefetches are capped and expire instead of piling up.
+    #[test]
+    fn test_unmatched_prefetch() {
+        // Prefetches to this listener are never answered.
+        let silent = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let silent = format!("http://{}/", silent.local_addr().unwrap());
+        let (addr, server) = spawn_server(&["200 OK", "200 OK"]);
+        let clock = Arc::new(ManualClock::new());
+        let mut conn = HyperHttpConnection::new()
+            .unwrap()
+            .with_clock(clock.clone());
+
+        for page in 0..=MAX_PREFETCHES {
+            conn.prefetch(Method::Get, format!("{silent}?page={page}"), &[])
+                .unwrap();
+        }
+        assert_eq!(conn.prefetches.len(), MAX_PREFETCHES);
+        let oldest: Uri = format!("{silent}?page=0").parse().unwrap();
+        assert!(conn.prefetches.iter().all(|p| p.uri != oldest));
+
+        let uri = format!("http://{addr}/");
+        conn.initiate_request(Method::Get, &uri, &[]).unwrap();
+        conn.initiate_response().unwrap();
+        assert_eq!(conn.status(), 200);
+        assert_eq!(conn.prefetches.len(), MAX_PREFETCHES);
+
+        clock.advance(PREFETCH_TTL);
+        conn.initiate_request(Method::Get, &uri, &[]).unwrap();
+        conn.initiate_response().unwrap();
+        assert_eq!(conn.status(), 200);
+        assert!(conn.prefetches.is_empty());
+        assert_eq!(server.join().unwrap().len(), 2);
+    }
+
+    /// Tests that `103 Early Hints` responses reach the informational callback.
+    #[test]
+    fn test_early_hints() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            use std::io::{BufRead, BufReader, Write};
+
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+            stream
+                .write_all(b"HTTP/1.1 103 Early Hints\r\nLink: </style.css>; rel=preload\r\n\r\n")
+                .unwrap();
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok")
+                .unwrap();
+        });
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        let tx = Mutex::new(tx);
+        let mut conn = HyperHttpConnection::new()
+            .unwrap()
+            .with_informational_callback(move |status, headers| {
+                let link = headers.get("link").cloned();
+                tx.lock().unwrap().send((status, link)).unwrap();
+            });
+        conn.initiate_request(Method::Get, &format!("http://{addr}/"), &[])
+            .unwrap();
+        conn.initiate_response().unwrap();
+        assert_eq!(conn.status(), 200);
+
+        let (status, link) = rx.try_recv().unwrap();
+        assert_eq!(status.as_u16(), 103);
+        assert_eq!(link.unwrap(), "</style.css>; rel=preload");
+        server.join().unwrap();
+    }
+
+    /// Tests that the pre-request hook can add headers to every request.
+    #[test]
+    fn test_pre_request_hook() {
+        let (addr, server) = spawn_handler(2, |_, _, _| ok_response("text/plain", ""));
+        let mut nonce = 0;
+        let mut conn =
+            HyperHttpConnection::new()
+                .unwrap()
+                .with_pre_request_hook(move |_uri, headers| {
+                    nonce += 1;
+                    headers.insert("x-nonce", HeaderValue::from(nonce));
+                });
+
+        for _ in 0..2 {
+            conn.initiate_request(Method::Get, &format!("http://{addr}/headers"), &[])
+                .unwrap();
+            conn.initiate_response().unwrap();
+            assert_eq!(conn.status(), 200);
+        }
+
+        let requests = server.join().unwrap();
+        assert!(requests[0].0.contains("x-nonce: 1\r\n"));
+        assert!(requests[1].0.contains("x-nonce: 2\r\n"));
+    }
+
+    /// Tests that the interceptors run again before each retry and redirect.
+    #[test]
+    fn test_interceptor_attempts() {
+        let (addr, server) = spawn_server(&["503 Service Unavailable", "302 Found", "200 OK"]);
+        let mut attempt = 0;
+        let mut conn = HyperHttpConnection::new()
+            .unwrap()
+            .with_retry(RetryPolicy::new(1))
+            .with_clock(Arc::new(ManualClock::new()))
+            .with_redirects(RedirectPolicy::default())
+            .with_pre_request_hook(move |_uri, headers| {
+                attempt += 1;
+                assert!(!headers.contains_key("x-attempt"));
+                headers.insert("x-attempt", HeaderValue::from(attempt));
+            });
+        conn.initiate_request(Method::Get, &format!("http://{addr}/"), &[])
+            .unwrap();
+        conn.initiate_response().unwrap();
+        assert_eq!(conn.status(), 200);
+
+        let heads = server.join().unwrap();
+        assert!(heads[0].starts_with("GET / ") && heads[0].contains("x-attempt: 1\r\n"));
+        assert!(heads[1].starts_with("GET / ") && heads[1].contains("x-attempt: 2\r\n"));
+        assert!(heads[2].starts_with("GET /next ") && heads[2].contains("x-attempt: 3\r\n"));
+    }
+
+    /// Tests reading the HTTP version of the last response.
+    #[test]
+    fn test_version() {
+        let (addr, server) = spawn_server(&["200 OK"]);
+        let mut conn = HyperHttpConnection::new().unwrap();
+        assert_eq!(conn.version(), None);
+
+        conn.initiate_request(Method::Get, &format!("http://{addr}/"), &[])
+            .unwrap();
+        conn.initiate_response().unwrap();
+        assert_eq!(conn.version(), Some(Version::HTTP_11));
+        assert!(server.join().unwrap()[0].starts_with("GET / HTTP/1.1\r\n"));
+    }
+
+    /// Starts a TLS server presenting the test certificate for `localhost`, answering
+    /// one request with an empty `200` response, and returns its port.
+    #[cfg(feature = "tls")]
+    fn spawn_tls_server() -> (u16, thread::JoinHandle<()>) {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            use std::io::{BufRead, BufReader, Write};
+
+            let (stream, _) = listener.accept().unwrap();
+            let mut stream = crate::tls::tests::acceptor().accept(stream).unwrap();
+            let mut head = String::new();
+            let mut reader = BufReader::new(&mut stream);
+            while reader.read_line(&mut head).unwrap() > 2 {}
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+                .unwrap();
+            stream.shutdown().unwrap();
+        });
+        (port, server)
+    }
+
+    /// Tests that no protocol is reported when the server picks none through ALPN,
+    /// or for plain HTTP.
+    #[cfg(feature = "tls")]
+    #[test]
+    fn test_negotiated_protocol() {
+        let (port, tls_server) = spawn_tls_server();
+        let (addr, server) = spawn_handler(1, |_, _, _| ok_response("text/plain", ""));
+        let tls = crate::tls::tests::trusting_config().alpn_protocols(&["http/1.1"]);
+        let mut conn = HyperHttpConnection::with_tls_config(&tls).unwrap();
+        conn.initiate_request(Method::Get, &format!("https://localhost:{port}/"), &[])
+            .unwrap();
+        conn.initiate_response().unwrap();
+        assert_eq!(conn.status(), 200);
+        assert_eq!(conn.negotiated_protocol(), None);
+        assert!(conn.tls_info().is_some());
+        tls_server.join().unwrap();
+
+        conn.initiate_request(Method::Get, &format!("http://{addr}/"), &[])
+            .unwrap();
+        conn.initiate_response().unwrap();
+        assert_eq!(conn.negotiated_protocol(), None);
+        assert!(conn.tls_info().is_none());
+        server.join().unwrap();
+    }
+
+    /// Tests reading the details of the TLS session against a local server.
+    #[cfg(feature = "tls")]
+    #[test]
+    fn test_tls_info() {
+        let (port, server) = spawn_tls_server();
+        let tls = crate::tls::tests::trusting_config();
+        let mut conn = HyperHttpConnection::with_tls_config(&tls).unwrap();
+        conn.initiate_request(Method::Get, &format!("https://localhost:{port}/"), &[])
+            .unwrap();
+        conn.initiate_response().unwrap();
+        assert_eq!(conn.status(), 200);
+        server.join().unwrap();
+
+        let certificate =
+            crate::tls::Certificate::from_pem(crate::tls::tests::CERTIFICATE).unwrap();
+        let info = conn.tls_info().unwrap();
+        assert_eq!(
+            info.peer_certificate(),
+            Some(&certificate.to_der().unwrap()[..])
+        );
+        assert_eq!(info.alpn_protocol(), None);
+        assert_eq!(conn.negotiated_protocol(), None);
+    }
+
+    /// Tests that followed redirects are recorded in order.
+    #[test]
+    fn test_redirect_history() {
+        let (addr, server) = spawn_handler(3, |index, _, _| match index {
+            0 => "HTTP/1.1 302 Found\r\nLocation: /redirect/1\r\n\
+                  Content-Length: 0\r\nConnection: close\r\n\r\n"
+                .to_owned(),
+            1 => "HTTP/1.1 302 Found\r\nLocation: /get\r\n\
+                  Content-Length: 0\r\nConnection: close\r\n\r\n"
+                .to_owned(),
+            _ => ok_response("text/plain", ""),
+        });
+        let mut conn = HyperHttpConnection::new()
+            .unwrap()
+            .with_redirects(RedirectPolicy::default());
+        let uri = format!("http://{addr}/redirect/2");
+        conn.initiate_request(Method::Get, &uri, &[]).unwrap();
+        conn.initiate_response().unwrap();
+
+        assert_eq!(conn.status(), 200);
+        let history = conn.redirect_history();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].uri, uri.as_str());
+        assert_eq!(history[0].status, StatusCode::FOUND);
+        assert_eq!(history[1].location, format!("http://{addr}/get").as_str());
+        assert!(server.join().unwrap()[2].0.starts_with("GET /get "));
+    }
+
+    /// Tests customizing the hyper request as it is initiated.
+    #[test]
+    fn test_request_hook() {
+        let mut conn = HyperHttpConnection::new()
+            .unwrap()
+            .with_request_hook(|request| {
+                request.extensions_mut().insert(7u32);
+                let value = HeaderValue::from_bytes(b"caf\xe9").unwrap();
+                request.headers_mut().insert("x-label", value);
+            });
+        conn.initiate_request(Method::Get, "http://example.com/", &[])
+            .unwrap();
+
+        let request = conn.request.as_ref().unwrap();
+        assert_eq!(request.extensions().get::<u32>(), Some(&7));
+        assert_eq!(request.headers()["x-label"].as_bytes(), b"caf\xe9");
+    }
+
+    /// Tests that repeated header names are all sent, with cookies joined.
+    #[test]
+    fn test_repeated_headers() {
+        let mut conn = HyperHttpConnection::new().unwrap();
+        let headers = [
+            ("X-Tag", "a"),
+            ("Cookie", "session=1"),
+            ("x-tag", "b"),
+            ("cookie", "theme=dark"),
+        ];
+        conn.initiate_request(Method::Get, "http://example.com/", &headers)
+            .unwrap();
+
+        let request = conn.request.as_ref().unwrap();
+        let tags: Vec<_> = request.headers().get_all("x-tag").iter().collect();
+        assert_eq!(tags, ["a", "b"]);
+        assert_eq!(request.headers().get_all(COOKIE).iter().count(), 1);
+        assert_eq!(request.headers()[COOKIE], "session=1; theme=dark");
+    }
+
+    /// Tests that the `Host` override replaces the header derived from the URI.
+    #[test]
+    fn test_host_override() {
+        let mut conn = HyperHttpConnection::new()
+            .unwrap()
+            .with_host_override("api.example.com")
+            .unwrap();
+        let headers = [("Host", "ignored.example.com")];
+        conn.initiate_request(Method::Get, "http://192.0.2.10/status", &headers)
+            .unwrap();
+
+        let request = conn.request.as_ref().unwrap();
+        assert_eq!(request.headers()[HOST], "api.example.com");
+        assert_eq!(request.headers().get_all(HOST).iter().count(), 1);
+        assert!(
+            HyperHttpConnection::new()
+                .unwrap()
+                .with_host_override("bad\nhost")
+                .is_err()
+        );
+    }
+
+    /// Tests initiating requests to typed URIs.
+    #[test]
+    fn test_initiate_request_to() {
+        let mut conn = HyperHttpConnection::new().unwrap();
+        let uri = Uri::from_static("http://example.com/items?page=2");
+        conn.initiate_request_to(Method::Get, &uri, &[]).unwrap();
+        assert_eq!(conn.request.as_ref().unwrap().uri(), &uri);
+
+        conn.initiate_request_to(Method::Get, String::from("http://example.com/"), &[])
+            .unwrap();
+        assert_eq!(conn.uri, "http://example.com/");
+        let invalid = conn.initiate_request_to(Method::Get, "http://exa mple.com", &[]);
+        assert!(matches!(invalid, Err(HyperError::Http(_))));
+    }
+
+    /// Tests the content headers read from the last response.
+    #[test]
+    fn test_content_headers() {
+        let (addr, server) = spawn_handler(1, |_, _, _| ok_response("application/json", "{}"));
+        let mut conn = HyperHttpConnection::new().unwrap();
+        conn.initiate_request(Method::Get, &format!("http://{addr}/json"), &[])
+            .unwrap();
+        conn.initiate_response().unwrap();
+        server.join().unwrap();
+
+        assert_eq!(conn.content_type(), Some("application/json"));
+        let length = conn.header("Content-Length").unwrap().parse().unwrap();
+        assert_eq!(conn.content_len(), Some(length));
+        assert_eq!(conn.content_encoding(), None);
+
+        conn.take_hyper_response().unwrap();
+        assert_eq!(conn.content_len(), None);
+        assert_eq!(conn.content_type(), None);
+    }
+
+    /// Tests that a body written with `write_bytes` is sent without copying.
+    #[test]
+    fn test_write_bytes() {
+        let mut conn = HyperHttpConnection::new().unwrap();
+        let payload = Bytes::from_static(b"{\"id\":1}");
+        conn.initiate_request(Method::Post, "http://example.com/", &[])
+            .unwrap();
+        conn.write_bytes(payload.clone()).unwrap();
+        conn.flush().unwrap();
+        let body = conn.request.as_ref().unwrap().body();
+        assert_eq!(body.as_ptr(), payload.as_ptr());
+
+        conn.initiate_request(Method::Post, "http://example.com/", &[])
+            .unwrap();
+        conn.write_bytes(payload.clone()).unwrap();
+        conn.write_all(b",").unwrap();
+        conn.write_bytes(payload).unwrap();
+        conn.flush().unwrap();
+        let request = conn.request.as_ref().unwrap();
+        assert_eq!(request.body(), "{\"id\":1},{\"id\":1}");
+        assert_eq!(request.headers()[CONTENT_LENGTH], "17");
+    }
+
+    /// Tests taking the hyper response and reusing the connection afterwards.
+    #[test]
+    fn test_take_hyper_response() {
+        let (addr, server) = spawn_handler(2, |_, _, _| ok_response("text/plain", "hello"));
+        let uri = format!("http://{addr}/get");
+        let mut conn = HyperHttpConnection::new().unwrap();
+        conn.initiate_request(Method::Get, &uri, &[]).unwrap();
+        conn.initiate_response().unwrap();
+        assert_eq!(conn.version(), Some(Version::HTTP_11));
+
+        let response = conn.take_hyper_response().unwrap();
+        assert_eq!(response.version(), Version::HTTP_11);
+        assert_eq!(conn.version(), None);
+        let body = conn.block_on(response.into_body().collect()).unwrap();
+        assert_eq!(body.to_bytes(), "hello");
+        assert!(!conn.is_response_initiated());
+        assert!(matches!(
+            conn.take_hyper_response(),
+            Err(HyperError::NoResponse)
+        ));
+
+        conn.initiate_request(Method::Get, &uri, &[]).unwrap();
+        conn.initiate_response().unwrap();
+        assert_eq!(conn.status(), 200);
+        server.join().unwrap();
+    }
+
+    /// Tests initiating a request from `http` types and taking the response as one.
+    #[test]
+    fn test_http_interop() {
+        let request = Request::builder()
+            .method("PURGE")
+            .uri("http://example.com/cache")
+            .body(Bytes::new())
+            .unwrap();
+        let conn = HyperHttpConnection::try_from(request).unwrap();
+        assert_eq!(conn.request.as_ref().unwrap().method().as_str(), "PURGE");
+
+        let (addr, server) = spawn_handler(1, |_, _, body| ok_response("text/plain", body));
+        let mut conn = HyperHttpConnection::new().unwrap();
+        let request = Request::post(format!("http://{addr}/post"))
+            .header("x-tag", "interop")
+            .body(Bytes::from_static(b"hello"))
+            .unwrap();
+        conn.initiate_http_request(request).unwrap();
+        conn.initiate_response().unwrap();
+
+        let response = conn.take_http_response().unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.body(), "hello");
+        assert!(matches!(
+            conn.take_http_response(),
+            Err(HyperError::NoResponse)
+        ));
+        assert!(server.join().unwrap()[0].0.contains("x-tag: interop\r\n"));
+    }
+
+    /// Tests that retries wait on the connection's clock instead of sleeping.
+    #[test]
+    fn test_retry_clock() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            use std::io::{BufRead, BufReader, Write};
+
+            for status in [
+                "503 Service Unavailable",
+                "503 Service Unavailable",
+                "200 OK",
+            ] {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+                let response =
+                    format!("HTTP/1.1 {status}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n");
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+
+        let clock = Arc::new(ManualClock::new());
+        let policy = RetryPolicy::new(3).backoff(Duration::from_secs(10));
+        let mut conn = HyperHttpConnection::new()
+            .unwrap()
+            .with_retry(policy)
+            .with_clock(clock.clone());
+        let start = Instant::now();
+        conn.initiate_request(Method::Get, &format!("http://{addr}/"), &[])
+            .unwrap();
+        conn.initiate_response().unwrap();
+
+        assert_eq!(conn.status(), 200);
+        assert_eq!(clock.elapsed(), Duration::from_secs(30));
+        assert!(start.elapsed() < Duration::from_secs(10));
+        server.join().unwrap();
+    }
+
+    /// Tests polling the response body without blocking until it ends, woken by the
+    /// readable callback.
+    #[cfg(feature = "rt-multi-thread")]
+    #[test]
+    fn test_try_read() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (resume, resumed) = std::sync::mpsc::channel::<()>();
+        let server = thread::spawn(move || {
+            use std::io::{BufRead, BufReader, Write};
+
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nhello")
+                .unwrap();
+            resumed.recv().unwrap();
+            stream.write_all(b"world").unwrap();
+        });
+
+        let (readable, woken) = std::sync::mpsc::channel();
+        let mut conn = HyperHttpConnection::new()
+            .unwrap()
+            .with_readable_callback(move || {
+                let _ = readable.send(());
+            });
+        conn.initiate_request(Method::Get, &format!("http://{addr}/"), &[])
+            .unwrap();
+        conn.initiate_response().unwrap();
+
+        let mut buf = [0u8; 16];
+        let mut poll = |conn: &mut HyperHttpConnection| {
+            let deadline = Instant::now() + Duration::from_secs(5);
+            loop {
+                if let Some(n) = conn.try_read(&mut buf).unwrap() {
+                    return buf[..n].to_vec();
+                }
+                assert!(Instant::now() < deadline);
+                thread::sleep(Duration::from_millis(5));
+            }
+        };
+        assert_eq!(poll(&mut conn), b"hello");
+        assert_eq!(conn.try_read(&mut [0u8; 16]).unwrap(), None);
+        while woken.try_recv().is_ok() {}
+        resume.send(()).unwrap();
+        woken.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(poll(&mut conn), b"world");
+        assert_eq!(poll(&mut conn), b"");
+        server.join().unwrap();
+    }
+
+    /// Tests turning retries and redirects off for a single request.
+    #[test]
+    fn test_request_options() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            use std::io::{BufRead, BufReader, Write};
+
+            for status in [
+                "503 Service Unavailable",
+                "302 Found",
+                "302 Found",
+                "200 OK",
+            ] {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+                let response = format!(
+                    "HTTP/1.1 {status}\r\nLocation: /next\r\nContent-Length: 0\r\n\
+                     Connection: close\r\n\r\n"
+                );
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+
+        let mut conn = HyperHttpConnection::new()
+            .unwrap()
+            .with_retry(RetryPolicy::new(3))
+            .with_clock(Arc::new(ManualClock::new()))
+            .with_redirects(RedirectPolicy::default());
+        let uri = format!("http://{addr}/");
+        let options = RequestOptions::new().retries(false).redirects(false);
+        conn.initiate_request_with(Method::Get, uri.as_str(), &[], options)
+            .unwrap();
+        conn.initiate_response().unwrap();
+        assert_eq!(conn.status(), 503);
+
+        let options = RequestOptions::new().redirects(false);
+        conn.initiate_request_with(Method::Get, uri.as_str(), &[], options)
+            .unwrap();
+        conn.initiate_response().unwrap();
+        assert_eq!(conn.status(), 302);
+
+        conn.initiate_request(Method::Get, &uri, &[]).unwrap();
+        conn.initiate_response().unwrap();
+        assert_eq!(conn.status(), 200);
+        assert_eq!(conn.redirect_history().len(), 1);
+        server.join().unwrap();
+    }
+
+    /// Tests that strict state checking rejects calls out of the request cycle.
+    #[test]
+    fn test_strict_state() {
+        let (addr, server) = spawn_handler(1, |_, _, body| ok_response("text/plain", body));
+        let mut conn = HyperHttpConnection::new().unwrap().with_strict_state(true);
+        assert_eq!(conn.state(), ConnectionState::Idle);
+        assert!(matches!(
+            conn.read(&mut [0; 8]),
+            Err(HyperError::InvalidState {
+                expected: ConnectionState::Response,
+                actual: ConnectionState::Idle,
+            })
+        ));
+        assert!(matches!(
+            conn.initiate_response(),
+            Err(HyperError::InvalidState { .. })
+        ));
+
+        conn.initiate_request(Method::Post, &format!("http://{addr}/post"), &[])
+            .unwrap();
+        assert_eq!(conn.state(), ConnectionState::Request);
+        assert!(matches!(
+            conn.next_chunk(),
+            Err(HyperError::InvalidState { .. })
+        ));
+        conn.write_all(b"body").unwrap();
+        conn.initiate_response().unwrap();
+        assert_eq!(conn.state(), ConnectionState::Response);
+
+        assert!(matches!(
+            conn.write(b"late"),
+            Err(HyperError::InvalidState {
+                expected: ConnectionState::Request,
+                actual: ConnectionState::Response,
+            })
+        ));
+        let mut body = Vec::new();
+        conn.for_each_chunk(|chunk| body.extend_from_slice(&chunk))
+            .unwrap();
+        assert_eq!(body, b"body");
+        server.join().unwrap();
+    }
+
+    /// Tests reading the body while holding the headers borrowed from `split`.
+    #[test]
+    fn test_split() {
+        let json = r#"{"slideshow": {"title": "Sample"}}"#;
+        let (addr, server) = spawn_handler(1, move |_, _, _| ok_response("application/json", json));
+        let mut conn = HyperHttpConnection::new().unwrap();
+        conn.initiate_request(Method::Get, &format!("http://{addr}/json"), &[])
+            .unwrap();
+        conn.initiate_response().unwrap();
+        server.join().unwrap();
+
+        let (headers, body) = conn.split();
+        let content_type = headers.content_type();
+        let mut data = Vec::new();
+        while let Some(chunk) = body.next_chunk().unwrap() {
+            data.extend_from_slice(&chunk);
+        }
+        assert_eq!(content_type, Some("application/json"));
+        assert_eq!(headers.status(), 200);
+        assert!(str::from_utf8(&data).unwrap().contains("slideshow"));
+    }
+
+    /// Tests that the connection opened by `warm_up` serves the next request.
+    #[test]
+    fn test_warm_up() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (tx, rx) = std::sync::mpsc::channel();
+        thread::spawn(move || {
+            use std::io::{BufRead, BufReader, Write};
+
+            for (index, stream) in listener.incoming().enumerate() {
+                let mut stream = stream.unwrap();
+                let tx = tx.clone();
+                thread::spawn(move || {
+                    let mut reader = BufReader::new(stream.try_clone().unwrap());
+                    let mut request_line = String::new();
+                    while reader.read_line(&mut request_line).unwrap() > 0 {
+                        let mut line = String::new();
+                        while reader.read_line(&mut line).unwrap() > 2 {
+                            line.clear();
+                        }
+                        let head = b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n";
+                        stream.write_all(head).unwrap();
+                        if !request_line.starts_with("HEAD") {
+                            stream.write_all(b"ok").unwrap();
+                        }
+                        tx.send((index, request_line.trim_end().to_owned()))
+                            .unwrap();
+                        request_line.clear();
+                    }
+                });
+            }
+        });
+
+        let mut conn = HyperHttpConnection::new().unwrap();
+        let uri = format!("http://{addr}/status");
+        conn.warm_up(uri.as_str()).unwrap();
+        assert_eq!(rx.recv().unwrap(), (0, "HEAD /status HTTP/1.1".to_owned()));
+        // The client takes the connection back in the background.
+        thread::sleep(Duration::from_millis(50));
+
+        conn.initiate_request(Method::Get, &uri, &[]).unwrap();
+        conn.initiate_response().unwrap();
+        assert_eq!(conn.status(), 200);
+        assert_eq!(rx.recv().unwrap(), (0, "GET /status HTTP/1.1".to_owned()));
+    }
 }
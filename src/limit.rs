@@ -0,0 +1,177 @@
+//! Limits on the number of requests in flight.
+//!
+//! A `ConcurrencyLimit` shared by connections lets at most a given number of their
+//! requests be in flight at once. Further requests wait in a first-in, first-out
+//! queue, which can be bounded in length and in waiting time so bursts of requests
+//! fail fast instead of piling up connections to the server.
+
+use crate::clock::Clock;
+use crate::error::HyperError;
+use std::future::{self, Future};
+use std::pin::pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::task::Poll;
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Maximum number of requests in flight, shared by the connections it is set on.
+///
+/// A request is in flight from when it is sent until its response head arrives, or
+/// until its whole response is received for `send_all`.
+///
+/// # Example
+///
+/// ```no_run
+/// use native_svc::HyperHttpConnection;
+/// use native_svc::limit::ConcurrencyLimit;
+/// use std::sync::Arc;
+/// use std::time::Duration;
+///
+/// let limit = Arc::new(ConcurrencyLimit::new(8).queue_timeout(Duration::from_secs(5)));
+/// let conn = HyperHttpConnection::new()
+///     .unwrap()
+///     .with_concurrency_limit(limit.clone());
+/// ```
+#[derive(Debug)]
+pub struct ConcurrencyLimit {
+    semaphore: Arc<Semaphore>,
+    max_in_flight: usize,
+    max_queued: Option<usize>,
+    queue_timeout: Option<Duration>,
+    queued: AtomicUsize,
+}
+
+impl ConcurrencyLimit {
+    /// Lets at most `max_in_flight` requests be in flight, queueing the others
+    /// without limit.
+    pub fn new(max_in_flight: usize) -> Self {
+        Self {
+            semaphore: Arc::new(Semaphore::new(max_in_flight)),
+            max_in_flight,
+            max_queued: None,
+            queue_timeout: None,
+            queued: AtomicUsize::new(0),
+        }
+    }
+
+    /// Fails requests with `HyperError::QueueFull` when `max_queued` requests are
+    /// already waiting.
+    pub fn max_queued(mut self, max_queued: usize) -> Self {
+        self.max_queued = Some(max_queued);
+        self
+    }
+
+    /// Fails requests with `HyperError::QueueTimeout` after waiting `timeout` in the
+    /// queue, as measured by the clock of their connection.
+    pub fn queue_timeout(mut self, timeout: Duration) -> Self {
+        self.queue_timeout = Some(timeout);
+        self
+    }
+
+    /// Returns the number of requests in flight.
+    pub fn in_flight(&self) -> usize {
+        self.max_in_flight - self.semaphore.available_permits()
+    }
+
+    /// Returns the number of requests waiting in the queue.
+    pub fn queued(&self) -> usize {
+        self.queued.load(Ordering::Relaxed)
+    }
+
+    /// Waits for a request to be allowed in flight, until the permit is dropped,
+    /// timing the wait on `clock`.
+    pub(crate) async fn acquire(
+        &self,
+        clock: &dyn Clock,
+    ) -> Result<OwnedSemaphorePermit, HyperError> {
+        if let Ok(permit) = self.semaphore.clone().try_acquire_owned() {
+            return Ok(permit);
+        }
+        let queued = self.queued.fetch_add(1, Ordering::Relaxed);
+        let _queued = Queued(&self.queued);
+        if self.max_queued.is_some_and(|max| queued >= max) {
+            return Err(HyperError::QueueFull);
+        }
+
+        let mut permit = pin!(self.semaphore.clone().acquire_owned());
+        let permit = match self.queue_timeout {
+            Some(timeout) => {
+                let mut expired = clock.wait_until(clock.now() + timeout);
+                future::poll_fn(|cx| match permit.as_mut().poll(cx) {
+                    Poll::Ready(permit) => Poll::Ready(Ok(permit)),
+                    Poll::Pending => expired
+                        .as_mut()
+                        .poll(cx)
+                        .map(|()| Err(HyperError::QueueTimeout(timeout))),
+                })
+                .await?
+            }
+            None => permit.await,
+        };
+        Ok(permit.expect("semaphore never closed"))
+    }
+}
+
+/// Counts a request as queued until dropped.
+struct Queued<'a>(&'a AtomicUsize);
+
+impl Drop for Queued<'_> {
+    /// Removes the request from the queue count.
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+
+    /// Tests queueing requests beyond the limit, and failing them when the queue is
+    /// full or they waited too long on the clock.
+    #[test]
+    fn test_concurrency_limit() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let clock = Arc::new(ManualClock::new());
+        let limit = Arc::new(
+            ConcurrencyLimit::new(1)
+                .max_queued(1)
+                .queue_timeout(Duration::from_secs(5)),
+        );
+        let spawn_acquire = || {
+            let (limit, clock) = (limit.clone(), clock.clone());
+            rt.spawn(async move { limit.acquire(&*clock).await.map(drop) })
+        };
+
+        let first = rt.block_on(limit.acquire(&*clock)).unwrap();
+        assert_eq!(limit.in_flight(), 1);
+
+        let timed_out = spawn_acquire();
+        rt.block_on(tokio::task::yield_now());
+        assert_eq!(limit.queued(), 1);
+        assert!(matches!(
+            rt.block_on(limit.acquire(&*clock)),
+            Err(HyperError::QueueFull)
+        ));
+
+        clock.advance(Duration::from_secs(4));
+        rt.block_on(tokio::task::yield_now());
+        assert_eq!(limit.queued(), 1);
+        clock.advance(Duration::from_secs(1));
+        assert!(matches!(
+            rt.block_on(timed_out).unwrap(),
+            Err(HyperError::QueueTimeout(_))
+        ));
+        assert_eq!(limit.queued(), 0);
+
+        let waiting = spawn_acquire();
+        rt.block_on(tokio::task::yield_now());
+        drop(first);
+        rt.block_on(waiting).unwrap().unwrap();
+        assert_eq!(limit.in_flight(), 0);
+    }
+}
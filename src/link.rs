@@ -0,0 +1,243 @@
+//! RFC 8288 `Link` headers and pagination.
+//!
+//! `HyperHttpConnection::links` parses the `Link` headers of the last response, and
+//! `paginate` walks APIs that announce the next page with a `rel="next"` link,
+//! fetching each page through the same connection.
+
+use crate::HyperHttpConnection;
+use crate::error::HyperError;
+use embedded_svc::http::Method;
+use embedded_svc::http::client::Connection;
+use hyper::body::Bytes;
+use hyper::header::LINK;
+use hyper::{Response, Uri};
+
+/// A link parsed from a `Link` header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Link {
+    /// Target URI, resolved against the request URI.
+    pub uri: String,
+    /// Parameters in order, with lowercase names and unquoted values.
+    pub params: Vec<(String, String)>,
+}
+
+impl Link {
+    /// Returns the value of the parameter `name`.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(param, _)| param.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns `true` if `rel` is one of the link's relation types.
+    pub fn has_rel(&self, rel: &str) -> bool {
+        self.param("rel")
+            .is_some_and(|rels| rels.split_whitespace().any(|r| r.eq_ignore_ascii_case(rel)))
+    }
+}
+
+/// Parses the links of a `Link` header value, ignoring malformed trailing input.
+pub fn parse(header: &str) -> Vec<Link> {
+    let mut links = Vec::new();
+    let mut rest = header.trim_start();
+
+    while let Some(after) = rest.strip_prefix('<') {
+        let Some(end) = after.find('>') else {
+            break;
+        };
+        let uri = after[..end].to_owned();
+        rest = &after[end + 1..];
+
+        let mut params = Vec::new();
+        while let Some(after) = rest.trim_start().strip_prefix(';') {
+            let after = after.trim_start();
+            let end = after.find(['=', ';', ',']).unwrap_or(after.len());
+            let name = after[..end].trim().to_ascii_lowercase();
+            rest = &after[end..];
+
+            let mut value = String::new();
+            if let Some(after) = rest.strip_prefix('=') {
+                let after = after.trim_start();
+                if let Some(quoted) = after.strip_prefix('"') {
+                    let mut end = quoted.len();
+                    let mut escaped = false;
+                    for (i, c) in quoted.char_indices() {
+                        match c {
+                            _ if escaped => {
+                                value.push(c);
+                                escaped = false;
+                            }
+                            '\\' => escaped = true,
+                            '"' => {
+                                end = i + 1;
+                                break;
+                            }
+                            _ => value.push(c),
+                        }
+                    }
+                    rest = &quoted[end..];
+                } else {
+                    let end = after.find([';', ',']).unwrap_or(after.len());
+                    value = after[..end].trim().to_owned();
+                    rest = &after[end..];
+                }
+            }
+            params.push((name, value));
+        }
+        links.push(Link { uri, params });
+
+        match rest.trim_start().strip_prefix(',') {
+            Some(after) => rest = after.trim_start(),
+            None => break,
+        }
+    }
+    links
+}
+
+/// Resolves `reference` against `base`, without removing dot segments.
+pub(crate) fn resolve(base: &Uri, reference: &str) -> String {
+    if reference
+        .parse::<Uri>()
+        .is_ok_and(|uri| uri.scheme().is_some())
+    {
+        return reference.to_owned();
+    }
+
+    let scheme = base.scheme_str().unwrap_or("http");
+    let authority = base.authority().map_or("", |authority| authority.as_str());
+    let path = base.path();
+    if let Some(network_path) = reference.strip_prefix("//") {
+        format!("{scheme}://{network_path}")
+    } else if reference.starts_with('/') {
+        format!("{scheme}://{authority}{reference}")
+    } else if reference.starts_with('?') {
+        format!("{scheme}://{authority}{path}{reference}")
+    } else {
+        let directory = &path[..path.rfind('/').map_or(0, |slash| slash + 1)];
+        format!("{scheme}://{authority}{directory}{reference}")
+    }
+}
+
+impl HyperHttpConnection {
+    /// Returns the links of the last response's `Link` headers.
+    pub fn links(&self) -> Vec<Link> {
+        let Ok(response) = self.ensure_response() else {
+            return Vec::new();
+        };
+        response
+            .headers()
+            .get_all(LINK)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(parse)
+            .map(|mut link| {
+                link.uri = resolve(&self.uri, &link.uri);
+                link
+            })
+            .collect()
+    }
+
+    /// Returns an iterator fetching `uri` and then every `rel="next"` page with `GET`.
+    ///
+    /// Each page is returned with its full body. Iteration stops after the last page or
+    /// the first error.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use native_svc::HyperHttpConnection;
+    ///
+    /// let mut conn = HyperHttpConnection::new().unwrap();
+    /// let headers = [("Accept", "application/vnd.github+json")];
+    /// for page in conn.paginate("https://api.github.com/repos/rust-lang/rust/issues", &headers) {
+    ///     println!("{} bytes", page.unwrap().body().len());
+    /// }
+    /// ```
+    pub fn paginate(&mut self, uri: &str, headers: &[(&str, &str)]) -> Pages<'_> {
+        Pages {
+            conn: self,
+            next: Some(uri.to_owned()),
+            headers: headers
+                .iter()
+                .map(|&(name, value)| (name.to_owned(), value.to_owned()))
+                .collect(),
+        }
+    }
+}
+
+/// Iterator over the pages of a paginated resource, created by `paginate`.
+pub struct Pages<'a> {
+    conn: &'a mut HyperHttpConnection,
+    next: Option<String>,
+    headers: Vec<(String, String)>,
+}
+
+impl Pages<'_> {
+    /// Fetches the page at `uri` and records the link to the next one.
+    fn fetch(&mut self, uri: &str) -> Result<Response<Bytes>, HyperError> {
+        let headers: Vec<_> = self
+            .headers
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect();
+        self.conn.initiate_request(Method::Get, uri, &headers)?;
+        self.conn.initiate_response()?;
+        self.next = self
+            .conn
+            .links()
+            .into_iter()
+            .find(|link| link.has_rel("next"))
+            .map(|link| link.uri);
+
+        let response = self.conn.ensure_response()?;
+        let mut page = Response::builder()
+            .status(response.status())
+            .version(response.version());
+        if let Some(headers) = page.headers_mut() {
+            *headers = response.headers().clone();
+        }
+        let mut body = Vec::new();
+        self.conn
+            .for_each_chunk(|chunk| body.extend_from_slice(&chunk))?;
+        Ok(page.body(Bytes::from(body))?)
+    }
+}
+
+impl Iterator for Pages<'_> {
+    type Item = Result<Response<Bytes>, HyperError>;
+
+    /// Fetches the next page.
+    fn next(&mut self) -> Option<Self::Item> {
+        let uri = self.next.take()?;
+        Some(self.fetch(&uri))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests parsing GitHub-style pagination links and resolving relative targets.
+    #[test]
+    fn test_parse_links() {
+        let header = r#"<https://api.example.com/items?page=2>; rel="next", </items?page=5>; rel="last"; title="a, \"b\"""#;
+        let links = parse(header);
+        assert_eq!(links.len(), 2);
+        assert_eq!(links[0].uri, "https://api.example.com/items?page=2");
+        assert!(links[0].has_rel("next"));
+        assert!(links[1].has_rel("last"));
+        assert_eq!(links[1].param("title"), Some(r#"a, "b""#));
+
+        let base: Uri = "https://api.example.com/v1/items?page=1".parse().unwrap();
+        assert_eq!(
+            resolve(&base, "/items?page=5"),
+            "https://api.example.com/items?page=5"
+        );
+        assert_eq!(resolve(&base, "other"), "https://api.example.com/v1/other");
+        assert_eq!(
+            resolve(&base, "?page=2"),
+            "https://api.example.com/v1/items?page=2"
+        );
+    }
+}
@@ -0,0 +1,292 @@
+//! mDNS service advertisement and discovery mirroring `esp-idf-svc`'s `mdns` module.
+//!
+//! `NativeMdns` follows the `EspMdns` API on top of the pure-Rust `mdns-sd` responder:
+//! the host name and instance name apply to every advertised service, services are
+//! registered by type and protocol with TXT records, and `query_ptr` and `query_a`
+//! browse for services and resolve host names on the local network.
+
+use crate::error::MdnsError;
+use mdns_sd::{HostnameResolutionEvent, ServiceDaemon, ServiceEvent, ServiceInfo};
+use std::collections::BTreeMap;
+use std::net::{IpAddr, Ipv4Addr};
+use std::time::{Duration, Instant};
+
+/// Host name used until `set_hostname` is called.
+const DEFAULT_HOSTNAME: &str = "native-svc";
+
+/// A service found by `query_ptr`, like `esp_idf_svc::mdns::QueryResult`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryResult {
+    /// Instance name of the service.
+    pub instance_name: Option<String>,
+    /// Host name of the device providing the service, without `.local`.
+    pub hostname: Option<String>,
+    /// Port of the service.
+    pub port: u16,
+    /// TXT records of the service.
+    pub txt: Vec<(String, String)>,
+    /// Addresses of the host.
+    pub addr: Vec<IpAddr>,
+}
+
+/// A service advertised by this host.
+struct Service {
+    instance_name: Option<String>,
+    port: u16,
+    txt: Vec<(String, String)>,
+    /// Full name under which the service is registered.
+    fullname: Option<String>,
+}
+
+/// An mDNS responder and querier.
+///
+/// # Example
+///
+/// ```no_run
+/// use native_svc::mdns::NativeMdns;
+/// use std::time::Duration;
+///
+/// let mut mdns = NativeMdns::new().unwrap();
+/// mdns.set_hostname("sensor-1").unwrap();
+/// mdns.add_service(None, "_http", "_tcp", 80, &[("path", "/")])
+///     .unwrap();
+///
+/// for result in mdns.query_ptr("_http", "_tcp", Duration::from_secs(2), 10).unwrap() {
+///     println!("{:?} on port {}", result.instance_name, result.port);
+/// }
+/// ```
+pub struct NativeMdns {
+    daemon: ServiceDaemon,
+    hostname: String,
+    instance_name: Option<String>,
+    /// Advertised services by service type domain.
+    services: BTreeMap<String, Service>,
+}
+
+impl NativeMdns {
+    /// Starts the mDNS responder.
+    pub fn new() -> Result<Self, MdnsError> {
+        Ok(Self {
+            daemon: ServiceDaemon::new()?,
+            hostname: DEFAULT_HOSTNAME.to_owned(),
+            instance_name: None,
+            services: BTreeMap::new(),
+        })
+    }
+
+    /// Sets the host name advertised as `<hostname>.local`.
+    pub fn set_hostname(&mut self, hostname: &str) -> Result<(), MdnsError> {
+        self.hostname = hostname.to_owned();
+        self.reregister()
+    }
+
+    /// Sets the default instance name of the advertised services.
+    pub fn set_instance_name(&mut self, instance_name: &str) -> Result<(), MdnsError> {
+        self.instance_name = Some(instance_name.to_owned());
+        self.reregister()
+    }
+
+    /// Advertises a service such as `_http` over `_tcp` on `port` with `txt` records.
+    ///
+    /// `instance_name` defaults to the instance name, then to the host name. Adding a
+    /// service of a type already advertised replaces it.
+    pub fn add_service(
+        &mut self,
+        instance_name: Option<&str>,
+        service_type: &str,
+        proto: &str,
+        port: u16,
+        txt: &[(&str, &str)],
+    ) -> Result<(), MdnsError> {
+        let ty_domain = ty_domain(service_type, proto);
+        self.unregister(&ty_domain);
+
+        let mut service = Service {
+            instance_name: instance_name.map(str::to_owned),
+            port,
+            txt: txt
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+            fullname: None,
+        };
+        self.register(&ty_domain, &mut service)?;
+        self.services.insert(ty_domain, service);
+        Ok(())
+    }
+
+    /// Stops advertising the service `service_type` over `proto`.
+    pub fn remove_service(&mut self, service_type: &str, proto: &str) -> Result<(), MdnsError> {
+        let ty_domain = ty_domain(service_type, proto);
+        self.unregister(&ty_domain);
+        self.services.remove(&ty_domain);
+        Ok(())
+    }
+
+    /// Browses for services `service_type` over `proto` for `timeout`.
+    ///
+    /// Returns early once `max_results` services are resolved.
+    pub fn query_ptr(
+        &self,
+        service_type: &str,
+        proto: &str,
+        timeout: Duration,
+        max_results: usize,
+    ) -> Result<Vec<QueryResult>, MdnsError> {
+        let ty_domain = ty_domain(service_type, proto);
+        let events = self.daemon.browse(&ty_domain)?;
+        let deadline = Instant::now() + timeout;
+
+        let mut results = Vec::new();
+        while results.len() < max_results {
+            match events.recv_deadline(deadline) {
+                Ok(ServiceEvent::ServiceResolved(info)) => results.push(query_result(&info)),
+                Ok(_) => {}
+                Err(_) => break,
+            }
+        }
+        // The browse has ended either way; a failure to stop it is harmless.
+        let _ = self.daemon.stop_browse(&ty_domain);
+        Ok(results)
+    }
+
+    /// Resolves the IPv4 address of the host `hostname`, given without `.local`.
+    pub fn query_a(&self, hostname: &str, timeout: Duration) -> Result<Ipv4Addr, MdnsError> {
+        let fqdn = format!("{hostname}.local.");
+        let timeout_ms = timeout.as_millis().try_into().unwrap_or(u64::MAX);
+        let events = self.daemon.resolve_hostname(&fqdn, Some(timeout_ms))?;
+        let deadline = Instant::now() + timeout;
+
+        let address = loop {
+            match events.recv_deadline(deadline) {
+                Ok(HostnameResolutionEvent::AddressesFound(_, addresses)) => {
+                    let ipv4 = addresses.into_iter().find_map(|address| match address {
+                        IpAddr::V4(address) => Some(address),
+                        IpAddr::V6(_) => None,
+                    });
+                    if ipv4.is_some() {
+                        break ipv4;
+                    }
+                }
+                Ok(HostnameResolutionEvent::SearchTimeout(_)) | Err(_) => break None,
+                Ok(_) => {}
+            }
+        };
+        let _ = self.daemon.stop_resolve_hostname(&fqdn);
+        address.ok_or(MdnsError::Timeout)
+    }
+
+    /// Registers `service` under `ty_domain`, recording its full name.
+    fn register(&self, ty_domain: &str, service: &mut Service) -> Result<(), MdnsError> {
+        let instance_name = service
+            .instance_name
+            .as_deref()
+            .or(self.instance_name.as_deref())
+            .unwrap_or(&self.hostname);
+        let host = format!("{}.local.", self.hostname);
+        let info = ServiceInfo::new(
+            ty_domain,
+            instance_name,
+            &host,
+            "",
+            service.port,
+            service.txt.as_slice(),
+        )?
+        .enable_addr_auto();
+
+        service.fullname = Some(info.get_fullname().to_owned());
+        self.daemon.register(info)?;
+        Ok(())
+    }
+
+    /// Stops advertising the service registered under `ty_domain`, if any.
+    fn unregister(&mut self, ty_domain: &str) {
+        let fullname = self
+            .services
+            .get_mut(ty_domain)
+            .and_then(|service| service.fullname.take());
+        if let Some(fullname) = fullname {
+            // Goodbye packets are best effort, as on ESP.
+            let _ = self.daemon.unregister(&fullname);
+        }
+    }
+
+    /// Registers every service again after a name change.
+    fn reregister(&mut self) -> Result<(), MdnsError> {
+        let ty_domains: Vec<_> = self.services.keys().cloned().collect();
+        for ty_domain in ty_domains {
+            self.unregister(&ty_domain);
+            let mut service = self.services.remove(&ty_domain).expect("listed above");
+            let result = self.register(&ty_domain, &mut service);
+            self.services.insert(ty_domain, service);
+            result?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for NativeMdns {
+    /// Stops the responder, withdrawing every service.
+    fn drop(&mut self) {
+        let _ = self.daemon.shutdown();
+    }
+}
+
+/// Returns the DNS-SD domain of `service_type` over `proto`, such as `_http._tcp.local.`.
+fn ty_domain(service_type: &str, proto: &str) -> String {
+    format!("{service_type}.{proto}.local.")
+}
+
+/// Converts a resolved service to a `QueryResult`.
+fn query_result(info: &ServiceInfo) -> QueryResult {
+    let instance_name = info
+        .get_fullname()
+        .strip_suffix(info.get_type())
+        .and_then(|name| name.strip_suffix('.'))
+        .map(str::to_owned);
+    let hostname = info
+        .get_hostname()
+        .trim_end_matches('.')
+        .strip_suffix(".local")
+        .map(str::to_owned);
+
+    let mut addr: Vec<_> = info.get_addresses().iter().copied().collect();
+    addr.sort();
+    QueryResult {
+        instance_name,
+        hostname,
+        port: info.get_port(),
+        txt: info
+            .get_properties()
+            .iter()
+            .map(|property| (property.key().to_owned(), property.val_str().to_owned()))
+            .collect(),
+        addr,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests converting a resolved service into a query result.
+    #[test]
+    fn test_query_result() {
+        let info = ServiceInfo::new(
+            &ty_domain("_http", "_tcp"),
+            "Kitchen Sensor",
+            "sensor-1.local.",
+            "192.168.1.20",
+            8080,
+            &[("path", "/status")][..],
+        )
+        .unwrap();
+
+        let result = query_result(&info);
+        assert_eq!(result.instance_name.as_deref(), Some("Kitchen Sensor"));
+        assert_eq!(result.hostname.as_deref(), Some("sensor-1"));
+        assert_eq!(result.port, 8080);
+        assert_eq!(result.txt, [("path".to_owned(), "/status".to_owned())]);
+        assert_eq!(result.addr, [IpAddr::from([192, 168, 1, 20])]);
+    }
+}
@@ -0,0 +1,468 @@
+//! Health and metrics endpoints for `embedded_svc` HTTP servers.
+//!
+//! `ServerMetrics` collects request counts, latencies, and requests in flight.
+//! Wrapping handlers with `ServerMetrics::instrument` records their traffic, while
+//! `HealthHandler` and `MetricsHandler` serve `/healthz` and a Prometheus-style
+//! `/metrics` page for fleet monitoring. `ServerMetrics::mount` does both in one
+//! call, for a handler registered on every path of the server.
+
+use embedded_svc::http::Query;
+use embedded_svc::http::server::{Connection, Handler};
+use std::fmt::Write as _;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Upper bounds of the latency histogram buckets.
+const LATENCY_BUCKETS: [Duration; 8] = [
+    Duration::from_millis(5),
+    Duration::from_millis(10),
+    Duration::from_millis(25),
+    Duration::from_millis(50),
+    Duration::from_millis(100),
+    Duration::from_millis(250),
+    Duration::from_millis(500),
+    Duration::from_millis(1000),
+];
+
+/// Default path for the health endpoint.
+pub const HEALTH_PATH: &str = "/healthz";
+
+/// Default path for the metrics endpoint.
+pub const METRICS_PATH: &str = "/metrics";
+
+/// Shared counters describing the traffic handled by a server.
+///
+/// Cloning is cheap; all clones observe the same counters.
+#[derive(Debug, Clone, Default)]
+pub struct ServerMetrics {
+    inner: Arc<MetricsInner>,
+}
+
+/// Atomic storage behind `ServerMetrics`.
+#[derive(Debug, Default)]
+struct MetricsInner {
+    requests: AtomicU64,
+    failures: AtomicU64,
+    active: AtomicU64,
+    latency_micros: AtomicU64,
+    buckets: [AtomicU64; LATENCY_BUCKETS.len()],
+}
+
+/// A point-in-time copy of the server metrics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MetricsSnapshot {
+    /// Total number of completed requests.
+    pub requests: u64,
+    /// Number of requests whose handler returned an error.
+    pub failures: u64,
+    /// Number of requests currently being handled.
+    pub active: u64,
+    /// Sum of all request latencies.
+    pub total_latency: Duration,
+}
+
+impl ServerMetrics {
+    /// Creates an empty set of counters.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Wraps `handler` so that every request it serves is recorded.
+    pub fn instrument<H>(&self, handler: H) -> Instrumented<H> {
+        Instrumented {
+            handler,
+            metrics: self.clone(),
+        }
+    }
+
+    /// Serves `/healthz` and `/metrics` in front of `handler`, which handles every
+    /// other path and is instrumented.
+    ///
+    /// The returned handler is meant to be registered on the `/*` wildcard URI of
+    /// the server, such as `EspHttpServer::handler("/*", Method::Get, ...)`.
+    pub fn mount<H>(&self, handler: H) -> Monitored<H> {
+        Monitored {
+            handler: self.instrument(handler),
+            metrics: self.metrics_handler(),
+        }
+    }
+
+    /// Returns the `/healthz` handler.
+    pub fn health_handler() -> HealthHandler {
+        HealthHandler
+    }
+
+    /// Returns the `/metrics` handler bound to these metrics.
+    pub fn metrics_handler(&self) -> MetricsHandler {
+        MetricsHandler {
+            metrics: self.clone(),
+        }
+    }
+
+    /// Returns the current counter values.
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        MetricsSnapshot {
+            requests: self.inner.requests.load(Ordering::Relaxed),
+            failures: self.inner.failures.load(Ordering::Relaxed),
+            active: self.inner.active.load(Ordering::Relaxed),
+            total_latency: Duration::from_micros(self.inner.latency_micros.load(Ordering::Relaxed)),
+        }
+    }
+
+    /// Renders the counters in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let snapshot = self.snapshot();
+        let mut out = String::new();
+
+        let _ = writeln!(out, "# TYPE http_requests_total counter");
+        let _ = writeln!(out, "http_requests_total {}", snapshot.requests);
+        let _ = writeln!(out, "# TYPE http_request_failures_total counter");
+        let _ = writeln!(out, "http_request_failures_total {}", snapshot.failures);
+        let _ = writeln!(out, "# TYPE http_requests_in_flight gauge");
+        let _ = writeln!(out, "http_requests_in_flight {}", snapshot.active);
+        let _ = writeln!(out, "# TYPE http_request_duration_seconds histogram");
+
+        let mut cumulative = 0;
+        for (bound, bucket) in LATENCY_BUCKETS.iter().zip(&self.inner.buckets) {
+            cumulative += bucket.load(Ordering::Relaxed);
+            let _ = writeln!(
+                out,
+                "http_request_duration_seconds_bucket{{le=\"{}\"}} {cumulative}",
+                bound.as_secs_f64()
+            );
+        }
+        let _ = writeln!(
+            out,
+            "http_request_duration_seconds_bucket{{le=\"+Inf\"}} {}",
+            snapshot.requests
+        );
+        let _ = writeln!(
+            out,
+            "http_request_duration_seconds_sum {}",
+            snapshot.total_latency.as_secs_f64()
+        );
+        let _ = writeln!(
+            out,
+            "http_request_duration_seconds_count {}",
+            snapshot.requests
+        );
+
+        out
+    }
+
+    /// Records a completed request.
+    fn record(&self, latency: Duration, failed: bool) {
+        self.inner.requests.fetch_add(1, Ordering::Relaxed);
+        if failed {
+            self.inner.failures.fetch_add(1, Ordering::Relaxed);
+        }
+        self.inner
+            .latency_micros
+            .fetch_add(latency.as_micros() as u64, Ordering::Relaxed);
+
+        if let Some(index) = LATENCY_BUCKETS.iter().position(|&bound| latency <= bound) {
+            self.inner.buckets[index].fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// A handler wrapper recording request counts, latencies, and requests in flight.
+pub struct Instrumented<H> {
+    handler: H,
+    metrics: ServerMetrics,
+}
+
+impl<C, H> Handler<C> for Instrumented<H>
+where
+    C: Connection,
+    H: Handler<C>,
+{
+    type Error = H::Error;
+
+    fn handle(&self, connection: &mut C) -> Result<(), Self::Error> {
+        let _active = ActiveRequest::new(&self.metrics.inner);
+        let started = Instant::now();
+
+        let result = self.handler.handle(connection);
+
+        self.metrics.record(started.elapsed(), result.is_err());
+        result
+    }
+}
+
+/// A request counted as active until dropped, even by a panicking handler.
+struct ActiveRequest<'a> {
+    inner: &'a MetricsInner,
+}
+
+impl<'a> ActiveRequest<'a> {
+    /// Counts a new active request.
+    fn new(inner: &'a MetricsInner) -> Self {
+        inner.active.fetch_add(1, Ordering::Relaxed);
+        Self { inner }
+    }
+}
+
+impl Drop for ActiveRequest<'_> {
+    fn drop(&mut self) {
+        self.inner.active.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// An instrumented handler with the health and metrics endpoints in front of it.
+pub struct Monitored<H> {
+    handler: Instrumented<H>,
+    metrics: MetricsHandler,
+}
+
+impl<C, H> Handler<C> for Monitored<H>
+where
+    C: Connection,
+    H: Handler<C>,
+    H::Error: From<C::Error>,
+{
+    type Error = H::Error;
+
+    fn handle(&self, connection: &mut C) -> Result<(), Self::Error> {
+        let path = connection.uri().split('?').next().unwrap_or_default();
+        match path {
+            HEALTH_PATH => Ok(HealthHandler.handle(connection)?),
+            METRICS_PATH => Ok(self.metrics.handle(connection)?),
+            _ => self.handler.handle(connection),
+        }
+    }
+}
+
+/// Serves a plain `ok` liveness response.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HealthHandler;
+
+impl<C> Handler<C> for HealthHandler
+where
+    C: Connection,
+{
+    type Error = C::Error;
+
+    fn handle(&self, connection: &mut C) -> Result<(), Self::Error> {
+        connection.initiate_response(200, Some("OK"), &[("Content-Type", "text/plain")])?;
+        connection.write_all(b"ok")
+    }
+}
+
+/// Serves the Prometheus rendering of a `ServerMetrics`.
+#[derive(Debug, Clone)]
+pub struct MetricsHandler {
+    metrics: ServerMetrics,
+}
+
+impl<C> Handler<C> for MetricsHandler
+where
+    C: Connection,
+{
+    type Error = C::Error;
+
+    fn handle(&self, connection: &mut C) -> Result<(), Self::Error> {
+        let body = self.metrics.render();
+        connection.initiate_response(
+            200,
+            Some("OK"),
+            &[("Content-Type", "text/plain; version=0.0.4")],
+        )?;
+        connection.write_all(body.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_svc::http::{Headers, Method};
+    use embedded_svc::io::{ErrorKind, ErrorType, Read, Write};
+    use std::panic::{self, AssertUnwindSafe};
+
+    /// Request line of a `TestConnection`.
+    struct TestRequest {
+        uri: String,
+    }
+
+    impl Query for TestRequest {
+        fn uri(&self) -> &str {
+            &self.uri
+        }
+
+        fn method(&self) -> Method {
+            Method::Get
+        }
+    }
+
+    impl Headers for TestRequest {
+        fn header(&self, _name: &str) -> Option<&str> {
+            None
+        }
+    }
+
+    /// Empty request body.
+    struct EmptyBody;
+
+    impl ErrorType for EmptyBody {
+        type Error = ErrorKind;
+    }
+
+    impl Read for EmptyBody {
+        fn read(&mut self, _buf: &mut [u8]) -> Result<usize, Self::Error> {
+            Ok(0)
+        }
+    }
+
+    /// Server connection recording the response written by a handler.
+    struct TestConnection {
+        request: TestRequest,
+        body: EmptyBody,
+        status: Option<u16>,
+        response: Vec<u8>,
+    }
+
+    impl TestConnection {
+        /// Creates a connection receiving a bodiless request for `uri`.
+        fn new(uri: &str) -> Self {
+            Self {
+                request: TestRequest {
+                    uri: uri.to_owned(),
+                },
+                body: EmptyBody,
+                status: None,
+                response: Vec::new(),
+            }
+        }
+    }
+
+    impl ErrorType for TestConnection {
+        type Error = ErrorKind;
+    }
+
+    impl Read for TestConnection {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            self.body.read(buf)
+        }
+    }
+
+    impl Write for TestConnection {
+        fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            self.response.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    impl Query for TestConnection {
+        fn uri(&self) -> &str {
+            self.request.uri()
+        }
+
+        fn method(&self) -> Method {
+            self.request.method()
+        }
+    }
+
+    impl Headers for TestConnection {
+        fn header(&self, name: &str) -> Option<&str> {
+            self.request.header(name)
+        }
+    }
+
+    impl Connection for TestConnection {
+        type Headers = TestRequest;
+        type Read = EmptyBody;
+        type RawConnectionError = ErrorKind;
+        type RawConnection = Self;
+
+        fn split(&mut self) -> (&Self::Headers, &mut Self::Read) {
+            (&self.request, &mut self.body)
+        }
+
+        fn initiate_response<'a>(
+            &'a mut self,
+            status: u16,
+            _message: Option<&'a str>,
+            _headers: &'a [(&'a str, &'a str)],
+        ) -> Result<(), Self::Error> {
+            self.status = Some(status);
+            Ok(())
+        }
+
+        fn is_response_initiated(&self) -> bool {
+            self.status.is_some()
+        }
+
+        fn raw_connection(&mut self) -> Result<&mut Self::RawConnection, Self::Error> {
+            Ok(self)
+        }
+    }
+
+    /// Application handler answering `204`, or panicking on `/panic`.
+    struct App;
+
+    impl Handler<TestConnection> for App {
+        type Error = ErrorKind;
+
+        fn handle(&self, connection: &mut TestConnection) -> Result<(), Self::Error> {
+            if connection.uri() == "/panic" {
+                panic!("handler failure");
+            }
+            connection.initiate_response(204, None, &[])
+        }
+    }
+
+    /// Tests that mounted endpoints are served in front of the application handler,
+    /// and that a panicking handler does not stay counted as active.
+    #[test]
+    fn test_mount() {
+        let metrics = ServerMetrics::new();
+        let handler = metrics.mount(App);
+
+        let mut conn = TestConnection::new("/healthz");
+        handler.handle(&mut conn).unwrap();
+        assert_eq!(
+            (conn.status, conn.response.as_slice()),
+            (Some(200), &b"ok"[..])
+        );
+
+        let mut conn = TestConnection::new("/items?page=2");
+        handler.handle(&mut conn).unwrap();
+        assert_eq!(conn.status, Some(204));
+
+        let mut conn = TestConnection::new("/panic");
+        let result = panic::catch_unwind(AssertUnwindSafe(|| handler.handle(&mut conn)));
+        assert!(result.is_err());
+        assert_eq!(metrics.snapshot().active, 0);
+
+        let mut conn = TestConnection::new("/metrics?format=text");
+        handler.handle(&mut conn).unwrap();
+        let rendered = String::from_utf8(conn.response).unwrap();
+        assert!(rendered.contains("http_requests_total 1"));
+        assert!(rendered.contains("http_requests_in_flight 0"));
+    }
+
+    /// Tests that recorded requests are reflected in the snapshot and rendering.
+    #[test]
+    fn test_record_and_render() {
+        let metrics = ServerMetrics::new();
+        metrics.record(Duration::from_millis(3), false);
+        metrics.record(Duration::from_micros(5900), false);
+        metrics.record(Duration::from_millis(300), true);
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.requests, 3);
+        assert_eq!(snapshot.failures, 1);
+        assert_eq!(snapshot.active, 0);
+
+        let rendered = metrics.render();
+        assert!(rendered.contains("http_requests_total 3"));
+        assert!(rendered.contains("http_request_duration_seconds_bucket{le=\"0.005\"} 1"));
+        assert!(rendered.contains("http_request_duration_seconds_bucket{le=\"0.01\"} 2"));
+        assert!(rendered.contains("http_request_duration_seconds_bucket{le=\"0.5\"} 3"));
+    }
+}
@@ -0,0 +1,395 @@
+//! Asynchronous MQTT client implementing `embedded_svc::mqtt::client::asynch`.
+//!
+//! `AsyncRumqttcMqttClient` and `AsyncRumqttcMqttConnection` are backed by the
+//! `rumqttc` async event loop and run on the caller's Tokio runtime. Events can be
+//! awaited one at a time through `Connection::next` or consumed as a `Stream`.
+
+use crate::error::MqttError;
+use crate::mqtt::offline::{Backoff, OfflinePublish, OfflineQueue};
+use crate::mqtt::v5::{self, PublishProperties};
+use crate::mqtt::{MessageIds, MqttClientConfiguration, MqttEvent, map_qos};
+use embedded_svc::mqtt::client::asynch::{Client, Connection, Publish};
+use embedded_svc::mqtt::client::{ErrorType, MessageId, QoS};
+use futures_util::Stream;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// The publishing and subscribing half of an asynchronous MQTT session.
+///
+/// # Example
+///
+/// ```no_run
+/// use embedded_svc::mqtt::client::asynch::{Client, Connection, Publish};
+/// use embedded_svc::mqtt::client::{Event, QoS};
+/// use native_svc::mqtt::MqttClientConfiguration;
+/// use native_svc::mqtt::asynch::AsyncRumqttcMqttClient;
+///
+/// # async fn run() -> Result<(), native_svc::error::MqttError> {
+/// let conf = MqttClientConfiguration::default();
+/// let (mut client, mut connection) =
+///     AsyncRumqttcMqttClient::new("mqtt://localhost:1883", &conf)?;
+///
+/// tokio::spawn(async move {
+///     while let Ok(event) = connection.next().await {
+///         println!("{:?}", event.payload());
+///     }
+/// });
+///
+/// client.publish("devices/1/state", QoS::AtLeastOnce, false, b"on").await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct AsyncRumqttcMqttClient {
+    client: ClientBackend,
+    ids: Arc<Mutex<MessageIds>>,
+    offline: Arc<Mutex<OfflineQueue>>,
+}
+
+/// The `rumqttc` async client of the configured protocol version.
+#[derive(Clone)]
+enum ClientBackend {
+    V4(rumqttc::AsyncClient),
+    V5(rumqttc::v5::AsyncClient),
+}
+
+impl ClientBackend {
+    /// Hands a held publish to `rumqttc` without waiting.
+    fn try_send(&self, publish: &OfflinePublish) -> Result<(), MqttError> {
+        match (self, &publish.properties) {
+            (ClientBackend::V4(client), _) => Ok(client.try_publish(
+                &publish.topic,
+                map_qos(publish.qos),
+                publish.retain,
+                publish.payload.clone(),
+            )?),
+            (ClientBackend::V5(client), Some(properties)) => Ok(client
+                .try_publish_with_properties(
+                    &publish.topic,
+                    v5::map_qos(publish.qos),
+                    publish.retain,
+                    publish.payload.clone(),
+                    properties.clone(),
+                )?),
+            (ClientBackend::V5(client), None) => Ok(client.try_publish(
+                &publish.topic,
+                v5::map_qos(publish.qos),
+                publish.retain,
+                publish.payload.clone(),
+            )?),
+        }
+    }
+}
+
+/// The `rumqttc` event loop of the configured protocol version.
+enum EventLoopBackend {
+    V4(rumqttc::EventLoop),
+    V5(rumqttc::v5::EventLoop),
+}
+
+impl AsyncRumqttcMqttClient {
+    /// Creates a client for the broker at `url` and the connection driving it.
+    ///
+    /// Nothing is sent until `AsyncRumqttcMqttConnection::next` is polled.
+    pub fn new(
+        url: &str,
+        conf: &MqttClientConfiguration<'_>,
+    ) -> Result<(Self, AsyncRumqttcMqttConnection), MqttError> {
+        let cap = conf.queue_size.max(1);
+        let (client, event_loop) = if conf.is_v5() {
+            let (client, event_loop) = rumqttc::v5::AsyncClient::new(conf.to_v5_options(url)?, cap);
+            (ClientBackend::V5(client), EventLoopBackend::V5(event_loop))
+        } else {
+            let (client, event_loop) = rumqttc::AsyncClient::new(conf.to_options(url)?, cap);
+            (ClientBackend::V4(client), EventLoopBackend::V4(event_loop))
+        };
+        let ids = Arc::new(Mutex::new(MessageIds::default()));
+        let (offline, backoff) = conf.session();
+
+        Ok((
+            Self {
+                client: client.clone(),
+                ids: ids.clone(),
+                offline: offline.clone(),
+            },
+            AsyncRumqttcMqttConnection {
+                event_loop,
+                client,
+                ids,
+                offline,
+                backoff,
+                delay: None,
+            },
+        ))
+    }
+
+    /// Holds a QoS 1 or 2 publish in the offline queue while the broker is unreachable.
+    fn hold<F>(&self, qos: QoS, publish: F) -> Result<Option<MessageId>, MqttError>
+    where
+        F: FnOnce() -> OfflinePublish,
+    {
+        let mut offline = self.offline.lock().unwrap_or_else(|e| e.into_inner());
+        if !offline.should_hold(qos) {
+            return Ok(None);
+        }
+        offline.push(&self.ids, publish()).map(Some)
+    }
+
+    /// Allocates the id of the request about to be queued.
+    fn allocate(&self) -> MessageId {
+        self.ids
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .allocate()
+    }
+
+    /// Forgets an allocated id whose request could not be queued.
+    fn cancel(&self, id: MessageId, error: MqttError) -> MqttError {
+        self.ids
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .cancel(id);
+        error
+    }
+
+    /// Publishes `payload` with MQTT 5 properties such as message expiry or user properties.
+    ///
+    /// Fails with `MqttError::Unsupported` on MQTT 3.1.1 sessions.
+    pub async fn publish_with_properties(
+        &mut self,
+        topic: &str,
+        qos: QoS,
+        retain: bool,
+        payload: &[u8],
+        properties: PublishProperties,
+    ) -> Result<MessageId, MqttError> {
+        let ClientBackend::V5(client) = &self.client else {
+            return Err(MqttError::Unsupported("publish properties require MQTT 5"));
+        };
+        let held = self.hold(qos, || {
+            OfflinePublish::new(topic, qos, retain, payload, Some(properties.clone()))
+        })?;
+        if let Some(id) = held {
+            return Ok(id);
+        }
+
+        let id = self.allocate();
+        match client
+            .publish_with_properties(
+                topic,
+                v5::map_qos(qos),
+                retain,
+                payload.to_vec(),
+                properties,
+            )
+            .await
+        {
+            Ok(()) => Ok(id),
+            Err(error) => Err(self.cancel(id, error.into())),
+        }
+    }
+}
+
+impl ErrorType for AsyncRumqttcMqttClient {
+    /// The error type returned by this client.
+    type Error = MqttError;
+}
+
+impl Client for AsyncRumqttcMqttClient {
+    /// Subscribes to `topic`, returning the id reported by the `Subscribed` event.
+    async fn subscribe(&mut self, topic: &str, qos: QoS) -> Result<MessageId, Self::Error> {
+        let id = self.allocate();
+        let result: Result<(), MqttError> = match &self.client {
+            ClientBackend::V4(client) => client
+                .subscribe(topic, map_qos(qos))
+                .await
+                .map_err(Into::into),
+            ClientBackend::V5(client) => client
+                .subscribe(topic, v5::map_qos(qos))
+                .await
+                .map_err(Into::into),
+        };
+        result.map(|()| id).map_err(|error| self.cancel(id, error))
+    }
+
+    /// Unsubscribes from `topic`, returning the id reported by the `Unsubscribed` event.
+    async fn unsubscribe(&mut self, topic: &str) -> Result<MessageId, Self::Error> {
+        let id = self.allocate();
+        let result: Result<(), MqttError> = match &self.client {
+            ClientBackend::V4(client) => client.unsubscribe(topic).await.map_err(Into::into),
+            ClientBackend::V5(client) => client.unsubscribe(topic).await.map_err(Into::into),
+        };
+        result.map(|()| id).map_err(|error| self.cancel(id, error))
+    }
+}
+
+impl Publish for AsyncRumqttcMqttClient {
+    /// Publishes `payload` to `topic`, returning the id reported by the `Published` event.
+    async fn publish(
+        &mut self,
+        topic: &str,
+        qos: QoS,
+        retain: bool,
+        payload: &[u8],
+    ) -> Result<MessageId, Self::Error> {
+        if let Some(id) = self.hold(qos, || {
+            OfflinePublish::new(topic, qos, retain, payload, None)
+        })? {
+            return Ok(id);
+        }
+
+        let id = self.allocate();
+        let result: Result<(), MqttError> = match &self.client {
+            ClientBackend::V4(client) => client
+                .publish(topic, map_qos(qos), retain, payload)
+                .await
+                .map_err(Into::into),
+            ClientBackend::V5(client) => client
+                .publish(topic, v5::map_qos(qos), retain, payload.to_vec())
+                .await
+                .map_err(Into::into),
+        };
+        result.map(|()| id).map_err(|error| self.cancel(id, error))
+    }
+}
+
+/// The event-yielding half of an asynchronous MQTT session.
+///
+/// Awaiting it drives the network. After an error the next poll waits for the configured
+/// backoff and reconnects; publishes held while offline are sent once connected.
+pub struct AsyncRumqttcMqttConnection {
+    event_loop: EventLoopBackend,
+    client: ClientBackend,
+    ids: Arc<Mutex<MessageIds>>,
+    offline: Arc<Mutex<OfflineQueue>>,
+    backoff: Backoff,
+    delay: Option<Duration>,
+}
+
+impl AsyncRumqttcMqttConnection {
+    /// Awaits the next event, surfacing connection errors and publishes discarded
+    /// from the full offline queue as error events.
+    async fn next_event(&mut self) -> MqttEvent {
+        let dropped = self
+            .offline
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take_dropped();
+        if let Some(event) = dropped {
+            return event;
+        }
+
+        if let Some(delay) = self.delay.take() {
+            tokio::time::sleep(delay).await;
+        }
+
+        let event = self.poll().await;
+        self.delay = self.backoff.observe(&event);
+        let client = &self.client;
+        self.offline
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .observe(&event, &self.ids, |publish| client.try_send(publish));
+        event
+    }
+
+    /// Polls the event loop until an event with an `embedded_svc` counterpart arrives.
+    async fn poll(&mut self) -> MqttEvent {
+        loop {
+            let translated = match &mut self.event_loop {
+                EventLoopBackend::V4(event_loop) => match event_loop.poll().await {
+                    Ok(event) => self
+                        .ids
+                        .lock()
+                        .unwrap_or_else(|e| e.into_inner())
+                        .translate(event),
+                    Err(error) => return MqttEvent::error(error.into()),
+                },
+                EventLoopBackend::V5(event_loop) => match event_loop.poll().await {
+                    Ok(event) => self
+                        .ids
+                        .lock()
+                        .unwrap_or_else(|e| e.into_inner())
+                        .translate_v5(event),
+                    Err(error) => return MqttEvent::error(error.into()),
+                },
+            };
+
+            if let Some(event) = translated {
+                return event;
+            }
+        }
+    }
+
+    /// Converts the connection into an endless stream of events.
+    pub fn into_stream(self) -> impl Stream<Item = MqttEvent> {
+        futures_util::stream::unfold(self, |mut connection| async move {
+            let event = connection.next_event().await;
+            Some((event, connection))
+        })
+    }
+}
+
+impl ErrorType for AsyncRumqttcMqttConnection {
+    /// The error type returned by this connection.
+    type Error = MqttError;
+}
+
+impl Connection for AsyncRumqttcMqttConnection {
+    type Event<'a>
+        = MqttEvent
+    where
+        Self: 'a;
+
+    /// Awaits the next event.
+    async fn next(&mut self) -> Result<Self::Event<'_>, Self::Error> {
+        Ok(self.next_event().await)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mqtt::OwnedPayload;
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Instant;
+
+    /// Tests that reconnections to a broker closing every connection are spaced out by
+    /// the backoff instead of retried in a tight loop.
+    #[test]
+    fn test_reconnect_backoff() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let accepted = Arc::new(AtomicUsize::new(0));
+        let counter = accepted.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                counter.fetch_add(1, Ordering::SeqCst);
+                drop(stream);
+            }
+        });
+
+        let conf = MqttClientConfiguration {
+            reconnect_timeout: Some(Duration::from_millis(100)),
+            ..Default::default()
+        };
+        let url = format!("mqtt://127.0.0.1:{port}");
+        let (_client, mut connection) = AsyncRumqttcMqttClient::new(&url, &conf).unwrap();
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+
+        let started = Instant::now();
+        rt.block_on(async {
+            for _ in 0..3 {
+                let event = connection.next().await.unwrap();
+                assert!(matches!(event.payload, OwnedPayload::Error(_)));
+            }
+        });
+
+        // The second and third attempts wait 100 ms and 200 ms.
+        assert!(started.elapsed() >= Duration::from_millis(300));
+        assert_eq!(accepted.load(Ordering::SeqCst), 3);
+    }
+}
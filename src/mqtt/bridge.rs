@@ -0,0 +1,186 @@
+//! Bridge between `embedded_svc` event buses and MQTT topics.
+//!
+//! `MqttBridge` forwards events posted on an event bus to MQTT topics and posts
+//! received MQTT messages back onto a bus. Serialization is left to caller-supplied
+//! hooks, so host simulations mirror a device's cloud messaging without bespoke glue.
+//! Any `embedded_svc` event bus and MQTT client can be bridged.
+
+use embedded_svc::event_bus::{EventBus, Postbox};
+use embedded_svc::mqtt::client::{Client, Event, EventPayload, Publish, QoS};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Handler decoding a received message and posting it to an event bus.
+type InboundHandler = Box<dyn FnMut(&str, &[u8]) -> bool + Send>;
+
+/// An MQTT topic filter and the handler for messages matching it.
+struct Route {
+    filter: String,
+    handler: InboundHandler,
+}
+
+/// Mirrors event-bus events to MQTT topics and MQTT messages to event buses.
+///
+/// # Example
+///
+/// ```no_run
+/// use embedded_svc::mqtt::client::{Connection, QoS};
+/// use native_svc::mqtt::bridge::MqttBridge;
+/// use native_svc::mqtt::{MqttClientConfiguration, RumqttcMqttClient};
+/// # fn run<B: embedded_svc::event_bus::EventBus<u32> + embedded_svc::event_bus::Postbox<u32> + Clone + Send + 'static>(bus: B) {
+///
+/// let conf = MqttClientConfiguration::default();
+/// let (client, mut connection) =
+///     RumqttcMqttClient::new("mqtt://localhost:1883", &conf).unwrap();
+///
+/// let mut bridge = MqttBridge::new(client);
+/// let _forwarding = bridge.forward(&bus, QoS::AtLeastOnce, false, |level: &u32| {
+///     Some(("devices/1/level".to_owned(), level.to_string().into_bytes()))
+/// });
+/// bridge.route("devices/1/set", bus.clone(), |_topic, data| {
+///     std::str::from_utf8(data).ok()?.parse().ok()
+/// });
+/// bridge.subscribe_routes(QoS::AtLeastOnce).unwrap();
+///
+/// while let Ok(event) = connection.next() {
+///     bridge.dispatch(&event);
+/// }
+/// # }
+/// ```
+pub struct MqttBridge<C> {
+    client: Arc<Mutex<C>>,
+    routes: Vec<Route>,
+    publish_errors: Arc<AtomicUsize>,
+}
+
+impl<C> MqttBridge<C>
+where
+    C: Publish + Send + 'static,
+{
+    /// Creates a bridge publishing through `client`.
+    pub fn new(client: C) -> Self {
+        Self {
+            client: Arc::new(Mutex::new(client)),
+            routes: Vec::new(),
+            publish_errors: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Returns the shared MQTT client, for publishing outside the bridge.
+    pub fn client(&self) -> Arc<Mutex<C>> {
+        self.client.clone()
+    }
+
+    /// Returns how many forwarded events failed to publish.
+    pub fn publish_errors(&self) -> usize {
+        self.publish_errors.load(Ordering::Relaxed)
+    }
+
+    /// Publishes every event posted on `bus` that `encode` maps to a topic and payload.
+    ///
+    /// Forwarding lasts as long as the returned subscription is kept alive.
+    pub fn forward<'b, B, P, F>(
+        &self,
+        bus: &'b B,
+        qos: QoS,
+        retain: bool,
+        mut encode: F,
+    ) -> Result<B::Subscription<'b>, B::Error>
+    where
+        B: EventBus<P>,
+        F: FnMut(&P) -> Option<(String, Vec<u8>)> + Send + 'static,
+    {
+        let client = self.client.clone();
+        let errors = self.publish_errors.clone();
+
+        bus.subscribe(move |event: &P| {
+            let Some((topic, payload)) = encode(event) else {
+                return;
+            };
+            let mut client = client.lock().unwrap_or_else(|e| e.into_inner());
+            if client.publish(&topic, qos, retain, &payload).is_err() {
+                errors.fetch_add(1, Ordering::Relaxed);
+            }
+        })
+    }
+
+    /// Posts messages on topics matching `filter` to `postbox` after decoding them.
+    ///
+    /// `filter` may use the `+` and `#` wildcards; messages `decode` rejects are skipped.
+    pub fn route<T, P, F>(&mut self, filter: &str, postbox: T, mut decode: F)
+    where
+        T: Postbox<P> + Send + 'static,
+        F: FnMut(&str, &[u8]) -> Option<P> + Send + 'static,
+    {
+        self.routes.push(Route {
+            filter: filter.to_owned(),
+            handler: Box::new(move |topic, data| match decode(topic, data) {
+                Some(payload) => postbox.post(&payload, None).is_ok_and(|posted| posted),
+                None => false,
+            }),
+        });
+    }
+
+    /// Subscribes the client to the filter of every route.
+    pub fn subscribe_routes(&self, qos: QoS) -> Result<(), C::Error>
+    where
+        C: Client,
+    {
+        let mut client = self.client.lock().unwrap_or_else(|e| e.into_inner());
+        for route in &self.routes {
+            client.subscribe(&route.filter, qos)?;
+        }
+        Ok(())
+    }
+
+    /// Posts a received MQTT message to every matching route, returning how many accepted it.
+    pub fn dispatch<E: Event>(&mut self, event: &E) -> usize {
+        let EventPayload::Received {
+            topic: Some(topic),
+            data,
+            ..
+        } = event.payload()
+        else {
+            return 0;
+        };
+
+        self.routes
+            .iter_mut()
+            .filter(|route| topic_matches(&route.filter, topic))
+            .map(|route| (route.handler)(topic, data))
+            .filter(|&posted| posted)
+            .count()
+    }
+}
+
+/// Returns `true` if `topic` matches the MQTT topic `filter`.
+pub fn topic_matches(filter: &str, topic: &str) -> bool {
+    let mut levels = topic.split('/');
+
+    for pattern in filter.split('/') {
+        match (pattern, levels.next()) {
+            ("#", _) => return true,
+            ("+", Some(_)) => {}
+            (pattern, Some(level)) if pattern == level => {}
+            _ => return false,
+        }
+    }
+
+    levels.next().is_none()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests MQTT wildcard matching.
+    #[test]
+    fn test_topic_matches() {
+        assert!(topic_matches("devices/+/state", "devices/1/state"));
+        assert!(topic_matches("devices/#", "devices/1/state"));
+        assert!(topic_matches("devices/#", "devices"));
+        assert!(!topic_matches("devices/+", "devices/1/state"));
+        assert!(!topic_matches("devices/1/state", "devices/2/state"));
+        assert!(!topic_matches("devices/1/state/x", "devices/1/state"));
+    }
+}
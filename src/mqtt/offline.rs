@@ -0,0 +1,247 @@
+//! Reconnection backoff and the offline publish queue.
+//!
+//! While the broker is unreachable, QoS 1 and QoS 2 publishes are held in a bounded
+//! `OfflineQueue` instead of the `rumqttc` request channel and are handed over in order
+//! once the connection is re-established. `Backoff` spaces out reconnection attempts.
+
+use crate::error::MqttError;
+use crate::mqtt::v5::PublishProperties;
+use crate::mqtt::{MessageIds, MqttEvent, OwnedPayload};
+use embedded_svc::mqtt::client::{MessageId, QoS};
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// What happens to a publish when the offline queue is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverflowPolicy {
+    /// Discards the oldest held publish to make room, reporting its id in a
+    /// `MqttError::PublishDropped` error event.
+    #[default]
+    DropOldest,
+    /// Rejects the new publish with `MqttError::QueueFull`.
+    Reject,
+}
+
+/// A publish held while the broker is unreachable.
+#[derive(Debug)]
+pub(crate) struct OfflinePublish {
+    pub(crate) id: MessageId,
+    pub(crate) topic: String,
+    pub(crate) qos: QoS,
+    pub(crate) retain: bool,
+    pub(crate) payload: Vec<u8>,
+    pub(crate) properties: Option<PublishProperties>,
+}
+
+impl OfflinePublish {
+    /// Copies a publish request; its id is assigned when it is queued.
+    pub(crate) fn new(
+        topic: &str,
+        qos: QoS,
+        retain: bool,
+        payload: &[u8],
+        properties: Option<PublishProperties>,
+    ) -> Self {
+        Self {
+            id: 0,
+            topic: topic.to_owned(),
+            qos,
+            retain,
+            payload: payload.to_vec(),
+            properties,
+        }
+    }
+}
+
+/// Publishes held until the broker connection is up.
+#[derive(Debug)]
+pub(crate) struct OfflineQueue {
+    connected: bool,
+    capacity: usize,
+    policy: OverflowPolicy,
+    publishes: VecDeque<OfflinePublish>,
+    /// Ids of held publishes discarded by `OverflowPolicy::DropOldest`, not yet reported.
+    dropped: VecDeque<MessageId>,
+}
+
+impl OfflineQueue {
+    /// Creates a queue holding at most `capacity` publishes; `0` disables holding.
+    pub(crate) fn new(capacity: usize, policy: OverflowPolicy) -> Self {
+        Self {
+            connected: false,
+            capacity,
+            policy,
+            publishes: VecDeque::new(),
+            dropped: VecDeque::new(),
+        }
+    }
+
+    /// Returns `true` if a publish with `qos` must be held instead of sent.
+    pub(crate) fn should_hold(&self, qos: QoS) -> bool {
+        !self.connected && self.capacity > 0 && qos != QoS::AtMostOnce
+    }
+
+    /// Holds `publish`, returning the id its `Published` event will carry.
+    pub(crate) fn push(
+        &mut self,
+        ids: &Mutex<MessageIds>,
+        mut publish: OfflinePublish,
+    ) -> Result<MessageId, MqttError> {
+        if self.publishes.len() >= self.capacity {
+            match self.policy {
+                OverflowPolicy::DropOldest => {
+                    if let Some(dropped) = self.publishes.pop_front() {
+                        self.dropped.push_back(dropped.id);
+                    }
+                }
+                OverflowPolicy::Reject => return Err(MqttError::QueueFull(self.capacity)),
+            }
+        }
+
+        publish.id = ids.lock().unwrap_or_else(|e| e.into_inner()).reserve();
+        let id = publish.id;
+        self.publishes.push_back(publish);
+        Ok(id)
+    }
+
+    /// Returns the error event reporting the oldest discarded publish not yet reported.
+    pub(crate) fn take_dropped(&mut self) -> Option<MqttEvent> {
+        let id = self.dropped.pop_front()?;
+        Some(MqttEvent::error(MqttError::PublishDropped(id)))
+    }
+
+    /// Tracks connectivity from `event` and, while connected, hands held publishes to `send`.
+    ///
+    /// Publishes that `send` refuses stay queued and are retried after the next event.
+    pub(crate) fn observe<F>(&mut self, event: &MqttEvent, ids: &Mutex<MessageIds>, mut send: F)
+    where
+        F: FnMut(&OfflinePublish) -> Result<(), MqttError>,
+    {
+        match event.payload {
+            OwnedPayload::Connected(_) => self.connected = true,
+            OwnedPayload::Disconnected | OwnedPayload::Error(_) => self.connected = false,
+            _ => {}
+        }
+        if !self.connected {
+            return;
+        }
+
+        while let Some(publish) = self.publishes.front() {
+            let mut ids = ids.lock().unwrap_or_else(|e| e.into_inner());
+            ids.enqueue(publish.id);
+            if send(publish).is_err() {
+                ids.cancel(publish.id);
+                break;
+            }
+            self.publishes.pop_front();
+        }
+    }
+}
+
+/// Reconnection delays that double after every consecutive failure.
+#[derive(Debug)]
+pub(crate) struct Backoff {
+    initial: Option<Duration>,
+    max: Duration,
+    next: Option<Duration>,
+}
+
+impl Backoff {
+    /// Creates a backoff starting at `initial`; `None` never delays.
+    pub(crate) fn new(initial: Option<Duration>, max: Duration) -> Self {
+        Self {
+            initial,
+            max,
+            next: None,
+        }
+    }
+
+    /// Returns the delay before the next attempt and doubles the following one.
+    pub(crate) fn fail(&mut self) -> Option<Duration> {
+        let delay = self.next.or(self.initial)?.min(self.max);
+        self.next = Some((delay * 2).min(self.max));
+        Some(delay)
+    }
+
+    /// Restarts from the initial delay after a successful connection.
+    pub(crate) fn reset(&mut self) {
+        self.next = None;
+    }
+
+    /// Updates the delay from `event`, returning how long to wait before polling again.
+    pub(crate) fn observe(&mut self, event: &MqttEvent) -> Option<Duration> {
+        match event.payload {
+            OwnedPayload::Connected(_) => {
+                self.reset();
+                None
+            }
+            OwnedPayload::Error(_) => self.fail(),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests overflow handling, reporting discarded publishes, and in-order flushing
+    /// once connected.
+    #[test]
+    fn test_offline_queue() {
+        let ids = Mutex::new(MessageIds::default());
+        let mut queue = OfflineQueue::new(2, OverflowPolicy::DropOldest);
+        assert!(queue.should_hold(QoS::AtLeastOnce));
+        assert!(!queue.should_hold(QoS::AtMostOnce));
+
+        let held: Vec<_> = ["a", "b", "c"]
+            .into_iter()
+            .map(|topic| {
+                let publish = OfflinePublish::new(topic, QoS::AtLeastOnce, false, b"", None);
+                queue.push(&ids, publish).unwrap()
+            })
+            .collect();
+
+        let dropped = queue.take_dropped().unwrap();
+        assert!(matches!(
+            dropped.payload,
+            OwnedPayload::Error(MqttError::PublishDropped(id)) if id == held[0]
+        ));
+        assert!(queue.take_dropped().is_none());
+
+        let mut sent = Vec::new();
+        let connected = MqttEvent {
+            payload: OwnedPayload::Connected(false),
+            v5: None,
+        };
+        queue.observe(&connected, &ids, |publish| {
+            sent.push(publish.topic.clone());
+            Ok(())
+        });
+        assert_eq!(sent, ["b", "c"]);
+        assert!(!queue.should_hold(QoS::AtLeastOnce));
+
+        let mut rejecting = OfflineQueue::new(1, OverflowPolicy::Reject);
+        let publish = OfflinePublish::new("a", QoS::ExactlyOnce, false, b"", None);
+        rejecting.push(&ids, publish).unwrap();
+        let publish = OfflinePublish::new("b", QoS::ExactlyOnce, false, b"", None);
+        assert!(matches!(
+            rejecting.push(&ids, publish),
+            Err(MqttError::QueueFull(1))
+        ));
+    }
+
+    /// Tests that reconnection delays double up to the limit and reset on success.
+    #[test]
+    fn test_backoff() {
+        let mut backoff = Backoff::new(Some(Duration::from_secs(1)), Duration::from_secs(3));
+        assert_eq!(backoff.fail(), Some(Duration::from_secs(1)));
+        assert_eq!(backoff.fail(), Some(Duration::from_secs(2)));
+        assert_eq!(backoff.fail(), Some(Duration::from_secs(3)));
+        backoff.reset();
+        assert_eq!(backoff.fail(), Some(Duration::from_secs(1)));
+
+        assert_eq!(Backoff::new(None, Duration::from_secs(3)).fail(), None);
+    }
+}
@@ -0,0 +1,254 @@
+//! MQTT 5 support for the MQTT clients.
+//!
+//! Selecting `MqttProtocolVersion::V5` in the configuration switches a client to the
+//! `rumqttc` v5 event loop. Publishes may then carry `PublishProperties` (message
+//! expiry, topic aliases, user properties), and events expose reason codes and the
+//! properties sent by the broker.
+
+use crate::mqtt::{MessageIds, MqttEvent, OwnedPayload};
+use embedded_svc::mqtt::client::{MessageId, QoS};
+use rumqttc::v5::Incoming;
+use rumqttc::v5::mqttbytes::v5::{
+    PubAckReason, PubCompReason, SubscribeReasonCode, UnsubAckReason,
+};
+
+pub use rumqttc::v5::mqttbytes::v5::PublishProperties;
+
+/// MQTT 5 data attached to an event.
+#[derive(Debug, Default)]
+pub(crate) struct V5Details {
+    pub(crate) reason_codes: Vec<u8>,
+    pub(crate) user_properties: Vec<(String, String)>,
+    pub(crate) publish: Option<PublishProperties>,
+}
+
+impl V5Details {
+    /// Details of an acknowledgement carrying reason codes and user properties.
+    fn acknowledgement(reason_codes: Vec<u8>, user_properties: Vec<(String, String)>) -> Self {
+        Self {
+            reason_codes,
+            user_properties,
+            publish: None,
+        }
+    }
+}
+
+impl MessageIds {
+    /// Translates a `rumqttc` v5 event, returning `None` for events without an
+    /// `embedded_svc` counterpart.
+    pub(crate) fn translate_v5(&mut self, event: rumqttc::v5::Event) -> Option<MqttEvent> {
+        let (payload, details) = match event {
+            rumqttc::v5::Event::Incoming(Incoming::ConnAck(ack)) => {
+                self.connected(ack.session_present);
+                let user_properties = ack
+                    .properties
+                    .map(|properties| properties.user_properties)
+                    .unwrap_or_default();
+                (
+                    OwnedPayload::Connected(ack.session_present),
+                    V5Details::acknowledgement(Vec::new(), user_properties),
+                )
+            }
+            rumqttc::v5::Event::Incoming(Incoming::Publish(publish)) => {
+                let exactly_once = publish.qos == rumqttc::v5::mqttbytes::QoS::ExactlyOnce;
+                if self.is_redelivery(publish.pkid, exactly_once) {
+                    return None;
+                }
+                let mut topic = String::from_utf8_lossy(&publish.topic).into_owned();
+                let alias = publish
+                    .properties
+                    .as_ref()
+                    .and_then(|properties| properties.topic_alias);
+                if let Some(alias) = alias {
+                    if topic.is_empty() {
+                        topic = self.topic_aliases.get(&alias).cloned().unwrap_or_default();
+                    } else {
+                        self.topic_aliases.insert(alias, topic.clone());
+                    }
+                }
+
+                (
+                    OwnedPayload::Received {
+                        id: publish.pkid as MessageId,
+                        topic,
+                        data: publish.payload.to_vec(),
+                        retain: publish.retain,
+                        dup: publish.dup,
+                    },
+                    V5Details {
+                        publish: publish.properties,
+                        ..Default::default()
+                    },
+                )
+            }
+            rumqttc::v5::Event::Incoming(Incoming::PubRel(rel)) => {
+                self.release(rel.pkid);
+                return None;
+            }
+            rumqttc::v5::Event::Incoming(Incoming::PubAck(ack)) => (
+                OwnedPayload::Published(self.complete(ack.pkid)),
+                V5Details::acknowledgement(
+                    vec![puback_code(ack.reason)],
+                    ack.properties
+                        .map(|properties| properties.user_properties)
+                        .unwrap_or_default(),
+                ),
+            ),
+            rumqttc::v5::Event::Incoming(Incoming::PubComp(comp)) => (
+                OwnedPayload::Published(self.complete(comp.pkid)),
+                V5Details::acknowledgement(
+                    vec![pubcomp_code(comp.reason)],
+                    comp.properties
+                        .map(|properties| properties.user_properties)
+                        .unwrap_or_default(),
+                ),
+            ),
+            rumqttc::v5::Event::Incoming(Incoming::SubAck(ack)) => (
+                OwnedPayload::Subscribed(self.complete(ack.pkid)),
+                V5Details::acknowledgement(
+                    ack.return_codes.into_iter().map(suback_code).collect(),
+                    ack.properties
+                        .map(|properties| properties.user_properties)
+                        .unwrap_or_default(),
+                ),
+            ),
+            rumqttc::v5::Event::Incoming(Incoming::UnsubAck(ack)) => (
+                OwnedPayload::Unsubscribed(self.complete(ack.pkid)),
+                V5Details::acknowledgement(
+                    ack.reasons.into_iter().map(unsuback_code).collect(),
+                    ack.properties
+                        .map(|properties| properties.user_properties)
+                        .unwrap_or_default(),
+                ),
+            ),
+            rumqttc::v5::Event::Incoming(Incoming::Disconnect(disconnect)) => {
+                self.topic_aliases.clear();
+                (
+                    OwnedPayload::Disconnected,
+                    V5Details::acknowledgement(
+                        vec![disconnect.reason_code as u8],
+                        disconnect
+                            .properties
+                            .map(|properties| properties.user_properties)
+                            .unwrap_or_default(),
+                    ),
+                )
+            }
+            rumqttc::v5::Event::Outgoing(outgoing) => {
+                return self.translate(rumqttc::Event::Outgoing(outgoing));
+            }
+            _ => return None,
+        };
+
+        Some(MqttEvent {
+            payload,
+            v5: Some(Box::new(details)),
+        })
+    }
+}
+
+/// Maps the `embedded_svc` quality of service to the `rumqttc` MQTT 5 one.
+pub(crate) fn map_qos(qos: QoS) -> rumqttc::v5::mqttbytes::QoS {
+    match qos {
+        QoS::AtMostOnce => rumqttc::v5::mqttbytes::QoS::AtMostOnce,
+        QoS::AtLeastOnce => rumqttc::v5::mqttbytes::QoS::AtLeastOnce,
+        QoS::ExactlyOnce => rumqttc::v5::mqttbytes::QoS::ExactlyOnce,
+    }
+}
+
+/// Returns the MQTT 5 reason code of a PUBACK.
+fn puback_code(reason: PubAckReason) -> u8 {
+    match reason {
+        PubAckReason::Success => 0x00,
+        PubAckReason::NoMatchingSubscribers => 0x10,
+        PubAckReason::UnspecifiedError => 0x80,
+        PubAckReason::ImplementationSpecificError => 0x83,
+        PubAckReason::NotAuthorized => 0x87,
+        PubAckReason::TopicNameInvalid => 0x90,
+        PubAckReason::PacketIdentifierInUse => 0x91,
+        PubAckReason::QuotaExceeded => 0x97,
+        PubAckReason::PayloadFormatInvalid => 0x99,
+    }
+}
+
+/// Returns the MQTT 5 reason code of a PUBCOMP.
+fn pubcomp_code(reason: PubCompReason) -> u8 {
+    match reason {
+        PubCompReason::Success => 0x00,
+        PubCompReason::PacketIdentifierNotFound => 0x92,
+    }
+}
+
+/// Returns the MQTT 5 reason code of a SUBACK entry.
+fn suback_code(reason: SubscribeReasonCode) -> u8 {
+    match reason {
+        SubscribeReasonCode::Success(qos) => qos as u8,
+        SubscribeReasonCode::Failure | SubscribeReasonCode::Unspecified => 0x80,
+        SubscribeReasonCode::ImplementationSpecific => 0x83,
+        SubscribeReasonCode::NotAuthorized => 0x87,
+        SubscribeReasonCode::TopicFilterInvalid => 0x8f,
+        SubscribeReasonCode::PkidInUse => 0x91,
+        SubscribeReasonCode::QuotaExceeded => 0x97,
+        SubscribeReasonCode::SharedSubscriptionsNotSupported => 0x9e,
+        SubscribeReasonCode::SubscriptionIdNotSupported => 0xa1,
+        SubscribeReasonCode::WildcardSubscriptionsNotSupported => 0xa2,
+    }
+}
+
+/// Returns the MQTT 5 reason code of an UNSUBACK entry.
+fn unsuback_code(reason: UnsubAckReason) -> u8 {
+    match reason {
+        UnsubAckReason::Success => 0x00,
+        UnsubAckReason::NoSubscriptionExisted => 0x11,
+        UnsubAckReason::UnspecifiedError => 0x80,
+        UnsubAckReason::ImplementationSpecificError => 0x83,
+        UnsubAckReason::NotAuthorized => 0x87,
+        UnsubAckReason::TopicFilterInvalid => 0x8f,
+        UnsubAckReason::PacketIdentifierInUse => 0x91,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rumqttc::v5::mqttbytes::v5::Publish;
+
+    /// Tests that incoming topic aliases are resolved and properties exposed.
+    #[test]
+    fn test_incoming_topic_alias() {
+        let mut ids = MessageIds::default();
+        let properties = PublishProperties {
+            topic_alias: Some(3),
+            message_expiry_interval: Some(60),
+            ..Default::default()
+        };
+
+        let first = Publish::new(
+            "sensors/1",
+            rumqttc::v5::mqttbytes::QoS::AtMostOnce,
+            "a",
+            Some(properties.clone()),
+        );
+        let event = ids
+            .translate_v5(rumqttc::v5::Event::Incoming(Incoming::Publish(first)))
+            .unwrap();
+        assert_eq!(
+            event.publish_properties().unwrap().message_expiry_interval,
+            Some(60)
+        );
+
+        let second = Publish::new(
+            "",
+            rumqttc::v5::mqttbytes::QoS::AtMostOnce,
+            "b",
+            Some(properties),
+        );
+        let event = ids
+            .translate_v5(rumqttc::v5::Event::Incoming(Incoming::Publish(second)))
+            .unwrap();
+        assert!(matches!(
+            &event.payload,
+            OwnedPayload::Received { topic, .. } if topic == "sensors/1"
+        ));
+    }
+}
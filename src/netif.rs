@@ -0,0 +1,170 @@
+//! IPv4 settings of host network interfaces using `embedded_svc::ipv4` types.
+//!
+//! `NativeNetif` mirrors `EspNetif` on Linux: `get_ip_info` reports the address,
+//! netmask and default gateway of a host interface and the DNS servers of the host,
+//! so code built around `get_ip_info()` sees real data off-device. Fixed client
+//! settings can also be applied with `ip`, given the needed privileges.
+
+use crate::error::NetifError;
+use embedded_svc::ipv4::{ClientConfiguration, IpInfo, Ipv4Addr, Mask, Subnet};
+use std::fs;
+use std::process::Command;
+
+/// Kernel IPv4 routing table.
+const ROUTE_TABLE: &str = "/proc/net/route";
+
+/// Resolver configuration listing the DNS servers.
+const RESOLV_CONF: &str = "/etc/resolv.conf";
+
+/// IPv4 settings of a host network interface.
+///
+/// # Example
+///
+/// ```no_run
+/// use native_svc::netif::NativeNetif;
+///
+/// let netif = NativeNetif::new("eth0");
+/// let info = netif.get_ip_info().unwrap();
+/// println!("ip {} gateway {}", info.ip, info.subnet.gateway);
+/// ```
+#[derive(Debug, Clone)]
+pub struct NativeNetif {
+    interface: String,
+}
+
+impl NativeNetif {
+    /// Creates a handle to the interface `interface`, such as `eth0`.
+    pub fn new(interface: &str) -> Self {
+        Self {
+            interface: interface.to_owned(),
+        }
+    }
+
+    /// Returns the interface name.
+    pub fn interface(&self) -> &str {
+        &self.interface
+    }
+
+    /// Returns the IPv4 address, subnet and DNS servers of the interface.
+    pub fn get_ip_info(&self) -> Result<IpInfo, NetifError> {
+        let addresses = self.ip(&["-4", "-o", "addr", "show", "dev", &self.interface])?;
+        let (ip, mask) =
+            parse_inet(&addresses).ok_or_else(|| NetifError::NoAddress(self.interface.clone()))?;
+        let routes = fs::read_to_string(ROUTE_TABLE)?;
+        let gateway = default_gateway(&routes, &self.interface).unwrap_or(Ipv4Addr::UNSPECIFIED);
+        // A missing resolver configuration just means no DNS servers.
+        let resolv = fs::read_to_string(RESOLV_CONF).unwrap_or_default();
+        let mut dns = nameservers(&resolv);
+
+        Ok(IpInfo {
+            ip,
+            subnet: Subnet { gateway, mask },
+            dns: dns.next(),
+            secondary_dns: dns.next(),
+        })
+    }
+
+    /// Applies `conf` to the interface.
+    ///
+    /// Fixed settings replace the interface addresses and the default route; DNS
+    /// servers are left to the host resolver. DHCP is managed by the host and is not
+    /// supported.
+    pub fn set_configuration(&self, conf: &ClientConfiguration) -> Result<(), NetifError> {
+        let ClientConfiguration::Fixed(settings) = conf else {
+            return Err(NetifError::Unsupported);
+        };
+
+        let address = format!("{}/{}", settings.ip, settings.subnet.mask.0);
+        self.ip(&["addr", "flush", "dev", &self.interface])?;
+        self.ip(&["addr", "add", &address, "dev", &self.interface])?;
+        if !settings.subnet.gateway.is_unspecified() {
+            let gateway = settings.subnet.gateway.to_string();
+            self.ip(&[
+                "route",
+                "replace",
+                "default",
+                "via",
+                &gateway,
+                "dev",
+                &self.interface,
+            ])?;
+        }
+        Ok(())
+    }
+
+    /// Runs `ip` with `args`, returning its standard output.
+    fn ip(&self, args: &[&str]) -> Result<String, NetifError> {
+        let output = Command::new("ip").args(args).output()?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(NetifError::Command(stderr.trim().to_owned()));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+/// Parses the first `inet a.b.c.d/prefix` of `ip -o addr` output.
+fn parse_inet(output: &str) -> Option<(Ipv4Addr, Mask)> {
+    let mut words = output.split_whitespace();
+    words.find(|word| *word == "inet")?;
+    let (ip, prefix) = words.next()?.split_once('/')?;
+    let prefix: u8 = prefix.parse().ok()?;
+    (prefix <= 32).then_some((ip.parse().ok()?, Mask(prefix)))
+}
+
+/// Returns the default gateway of `interface` from a `/proc/net/route` table.
+fn default_gateway(routes: &str, interface: &str) -> Option<Ipv4Addr> {
+    routes.lines().skip(1).find_map(|line| {
+        let fields: Vec<_> = line.split_whitespace().collect();
+        let [iface, destination, gateway, ..] = fields.as_slice() else {
+            return None;
+        };
+        if *iface != interface || *destination != "00000000" {
+            return None;
+        }
+        // Addresses are printed as native-endian words holding network-order bytes.
+        let gateway = u32::from_str_radix(gateway, 16).ok()?;
+        Some(Ipv4Addr::from(gateway.to_ne_bytes()))
+    })
+}
+
+/// Returns the IPv4 `nameserver` entries of a `resolv.conf`.
+fn nameservers(resolv: &str) -> impl Iterator<Item = Ipv4Addr> + '_ {
+    resolv.lines().filter_map(|line| {
+        let mut words = line.split_whitespace();
+        (words.next() == Some("nameserver")).then_some(())?;
+        words.next()?.parse().ok()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests parsing addresses, routes and DNS servers.
+    #[test]
+    fn test_parse_ip_info() {
+        let addr = "2: eth0    inet 192.168.1.20/24 brd 192.168.1.255 scope global eth0\\";
+        let (ip, mask) = parse_inet(addr).unwrap();
+        assert_eq!(ip, Ipv4Addr::new(192, 168, 1, 20));
+        assert_eq!(mask.0, 24);
+        assert!(parse_inet("").is_none());
+
+        let gateway = u32::from_ne_bytes([192, 168, 1, 1]);
+        let routes = format!(
+            "Iface\tDestination\tGateway\tFlags\n\
+             wlan0\t00000000\t0101A8C0\t0003\n\
+             eth0\t0001A8C0\t00000000\t0001\n\
+             eth0\t00000000\t{gateway:08X}\t0003\n"
+        );
+        assert_eq!(
+            default_gateway(&routes, "eth0"),
+            Some(Ipv4Addr::new(192, 168, 1, 1))
+        );
+        assert_eq!(default_gateway(&routes, "eth1"), None);
+
+        let resolv = "# generated\nnameserver 1.1.1.1\nnameserver ::1\nnameserver 8.8.8.8\n";
+        let dns: Vec<_> = nameservers(resolv).collect();
+        assert_eq!(dns, [Ipv4Addr::new(1, 1, 1, 1), Ipv4Addr::new(8, 8, 8, 8)]);
+    }
+}
@@ -0,0 +1,89 @@
+//! Per-request overrides of the automatic behaviors of a connection.
+//!
+//! Redirects, retries and decompression are configured once on
+//! `HyperHttpConnection`. `RequestOptions` passed to
+//! `HyperHttpConnection::initiate_request_with` turns them off for a single
+//! request, such as downloading a `.gz` artifact without decoding it, while later
+//! requests keep the connection's settings.
+
+/// Automatic behaviors applied to a single request.
+///
+/// Every behavior is enabled by default, and only takes effect when the connection
+/// is configured for it.
+///
+/// # Example
+///
+/// ```no_run
+/// use embedded_svc::http::Method;
+/// use native_svc::HyperHttpConnection;
+/// use native_svc::options::RequestOptions;
+/// use native_svc::redirect::RedirectPolicy;
+///
+/// let mut conn = HyperHttpConnection::new()
+///     .unwrap()
+///     .with_redirects(RedirectPolicy::default());
+/// let options = RequestOptions::new().redirects(false);
+/// conn.initiate_request_with(Method::Get, "http://example.com/moved", &[], options)
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequestOptions {
+    redirects: bool,
+    retries: bool,
+    #[cfg(feature = "decompression")]
+    decompression: bool,
+}
+
+impl RequestOptions {
+    /// Enables every behavior configured on the connection.
+    pub fn new() -> Self {
+        Self {
+            redirects: true,
+            retries: true,
+            #[cfg(feature = "decompression")]
+            decompression: true,
+        }
+    }
+
+    /// Sets whether redirects are followed as allowed by the `RedirectPolicy`.
+    pub fn redirects(mut self, enabled: bool) -> Self {
+        self.redirects = enabled;
+        self
+    }
+
+    /// Sets whether failures are retried as allowed by the `RetryPolicy`.
+    pub fn retries(mut self, enabled: bool) -> Self {
+        self.retries = enabled;
+        self
+    }
+
+    /// Sets whether compressed responses are requested and decoded.
+    #[cfg(feature = "decompression")]
+    pub fn decompression(mut self, enabled: bool) -> Self {
+        self.decompression = enabled;
+        self
+    }
+
+    /// Returns `true` if redirects may be followed.
+    pub(crate) fn follows_redirects(&self) -> bool {
+        self.redirects
+    }
+
+    /// Returns `true` if failures may be retried.
+    pub(crate) fn retries_failures(&self) -> bool {
+        self.retries
+    }
+
+    /// Returns `true` if compressed responses may be decoded.
+    #[cfg(feature = "decompression")]
+    pub(crate) fn decodes(&self) -> bool {
+        self.decompression
+    }
+}
+
+impl Default for RequestOptions {
+    /// Enables every behavior configured on the connection.
+    fn default() -> Self {
+        Self::new()
+    }
+}
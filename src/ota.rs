@@ -0,0 +1,595 @@
+//! Firmware OTA updates implementing `embedded_svc::ota` over files.
+//!
+//! `FileOta` models an ESP partition table with a `factory` image and two OTA slots,
+//! each stored as `<label>.bin` in a directory, plus an `otadata` file recording which
+//! slot boots next and the state of every slot. Updates are streamed to a temporary
+//! file and only replace the slot image once complete, so OTA logic can be exercised
+//! natively in CI.
+//!
+//! Slots follow the ESP-IDF rollback state machine (`ImageState`): a freshly installed
+//! image boots once as pending verification and is rolled back on the next boot unless
+//! the application marks it valid. Reopening the directory, or calling
+//! `FileOta::reboot`, simulates a reboot.
+//!
+//! With feature `ota-verify`, images are hashed while written and can be checked
+//! against an expected SHA-256 digest and an Ed25519 or RSA signature before they are
+//! installed. With feature `ota-http`, `http::HttpOtaUpdater` downloads images
+//! straight into a slot.
+
+#[cfg(feature = "ota-http")]
+pub mod http;
+#[cfg(feature = "ota-verify")]
+pub mod verify;
+
+use crate::error::OtaError;
+use embedded_svc::io::{ErrorType, Write};
+use embedded_svc::ota::{Ota, OtaUpdate, OtaUpdateFinished, Slot, SlotState};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Write as _};
+use std::path::{Path, PathBuf};
+
+/// Label of the factory slot.
+pub const FACTORY_SLOT: &str = "factory";
+
+/// Labels of the OTA slots, in update order.
+pub const OTA_SLOTS: [&str; 2] = ["ota_0", "ota_1"];
+
+/// Name of the file recording the boot slot and slot states.
+const OTA_DATA: &str = "otadata";
+
+/// Boot state of an OTA image, mirroring `esp_ota_img_states_t`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ImageState {
+    /// Installed and never booted.
+    New,
+    /// Booted once and waiting for the application to confirm it.
+    PendingVerify,
+    /// Confirmed by the application.
+    Valid,
+    /// Rejected by the application.
+    Invalid,
+    /// Rolled back because it was not confirmed before the next boot.
+    Aborted,
+    /// No state recorded, as for images flashed without OTA.
+    #[default]
+    Undefined,
+}
+
+impl ImageState {
+    /// Returns the name under which the state is persisted.
+    fn name(self) -> &'static str {
+        match self {
+            Self::New => "new",
+            Self::PendingVerify => "pending_verify",
+            Self::Valid => "valid",
+            Self::Invalid => "invalid",
+            Self::Aborted => "aborted",
+            Self::Undefined => "undefined",
+        }
+    }
+
+    /// Parses a state persisted by `name`.
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "new" => Some(Self::New),
+            "pending_verify" => Some(Self::PendingVerify),
+            "valid" => Some(Self::Valid),
+            "invalid" => Some(Self::Invalid),
+            "aborted" => Some(Self::Aborted),
+            "undefined" => Some(Self::Undefined),
+            _ => None,
+        }
+    }
+
+    /// Returns the `embedded_svc` view of the state.
+    fn slot_state(self) -> SlotState {
+        match self {
+            Self::New | Self::PendingVerify => SlotState::Unverified,
+            Self::Valid => SlotState::Valid,
+            Self::Invalid | Self::Aborted => SlotState::Invalid,
+            Self::Undefined => SlotState::Unknown,
+        }
+    }
+}
+
+/// Persistent boot selection and slot states.
+#[derive(Debug, Clone, Default)]
+struct OtaData {
+    boot: Option<String>,
+    states: BTreeMap<String, ImageState>,
+}
+
+impl OtaData {
+    /// Loads the data stored in `dir`, or defaults if none was written yet.
+    fn load(dir: &Path) -> Result<Self, OtaError> {
+        let text = match fs::read_to_string(dir.join(OTA_DATA)) {
+            Ok(text) => text,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(error) => return Err(error.into()),
+        };
+
+        let mut data = Self::default();
+        for line in text.lines().filter(|line| !line.is_empty()) {
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| OtaError::InvalidOtaData(line.to_owned()))?;
+            if key == "boot" {
+                data.boot = Some(value.to_owned());
+            } else {
+                let state = ImageState::parse(value)
+                    .ok_or_else(|| OtaError::InvalidOtaData(line.to_owned()))?;
+                data.states.insert(key.to_owned(), state);
+            }
+        }
+        Ok(data)
+    }
+
+    /// Writes the data to `dir`, replacing the previous file atomically.
+    fn save(&self, dir: &Path) -> Result<(), OtaError> {
+        let mut text = String::new();
+        if let Some(boot) = &self.boot {
+            text.push_str(&format!("boot={boot}\n"));
+        }
+        for (label, state) in &self.states {
+            text.push_str(&format!("{label}={}\n", state.name()));
+        }
+
+        let temp = dir.join(format!("{OTA_DATA}.tmp"));
+        let mut file = File::create(&temp)?;
+        file.write_all(text.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(temp, dir.join(OTA_DATA))?;
+        Ok(())
+    }
+}
+
+/// An `Ota` implementation keeping partition images as files.
+///
+/// Opening the directory again simulates a reboot: the slot selected for boot
+/// becomes the running slot, or is rolled back if it was never confirmed.
+///
+/// # Example
+///
+/// ```no_run
+/// use embedded_svc::ota::{Ota, OtaUpdate};
+/// use embedded_svc::io::Write;
+/// use native_svc::ota::FileOta;
+///
+/// let mut ota = FileOta::open("/var/lib/device/flash").unwrap();
+/// let mut update = ota.initiate_update().unwrap();
+/// update.write_all(b"firmware image").unwrap();
+/// update.complete().unwrap();
+///
+/// let mut ota = FileOta::open("/var/lib/device/flash").unwrap();
+/// assert_eq!(ota.get_running_slot().unwrap().label, "ota_0");
+/// ota.mark_app_valid().unwrap();
+/// ```
+#[derive(Debug)]
+pub struct FileOta {
+    dir: PathBuf,
+    running: String,
+    data: OtaData,
+    #[cfg(feature = "ota-verify")]
+    public_key: Option<verify::PublicKey>,
+}
+
+impl FileOta {
+    /// Opens the partitions in `dir`, creating the directory if needed, and boots.
+    ///
+    /// Boots the slot recorded in `otadata`, falling back to the factory image and then
+    /// to the first OTA slot.
+    pub fn open(dir: impl Into<PathBuf>) -> Result<Self, OtaError> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        let data = OtaData::load(&dir)?;
+
+        let mut ota = Self {
+            dir,
+            running: String::new(),
+            data,
+            #[cfg(feature = "ota-verify")]
+            public_key: None,
+        };
+        ota.reboot()?;
+        Ok(ota)
+    }
+
+    /// Simulates a reboot, running the bootloader's rollback logic.
+    ///
+    /// A `New` image moves to `PendingVerify`; a `PendingVerify` image was not
+    /// confirmed during its first boot, so it becomes `Aborted` and the previous valid
+    /// slot boots instead.
+    pub fn reboot(&mut self) -> Result<(), OtaError> {
+        let mut boot = self.boot_label();
+        let state = self.image_state(&boot);
+        match state {
+            ImageState::New => {
+                self.data
+                    .states
+                    .insert(boot.clone(), ImageState::PendingVerify);
+            }
+            ImageState::PendingVerify | ImageState::Invalid | ImageState::Aborted => {
+                if state == ImageState::PendingVerify {
+                    self.data.states.insert(boot.clone(), ImageState::Aborted);
+                }
+                let fallback = self.fallback(&boot).ok_or(OtaError::NoValidSlot)?;
+                boot = fallback.to_owned();
+                self.data.boot = Some(boot.clone());
+            }
+            ImageState::Valid | ImageState::Undefined => {}
+        }
+
+        self.data.save(&self.dir)?;
+        self.running = boot;
+        Ok(())
+    }
+
+    /// Returns the boot state of the slot `label`.
+    pub fn image_state(&self, label: &str) -> ImageState {
+        self.data.states.get(label).copied().unwrap_or_default()
+    }
+
+    /// Confirms the running image, cancelling the pending rollback.
+    pub fn mark_app_valid(&mut self) -> Result<(), OtaError> {
+        let running = self.running.clone();
+        self.set_state(&running, ImageState::Valid)
+    }
+
+    /// Returns `true` if another slot could boot if the running one were rejected.
+    pub fn is_rollback_possible(&self) -> bool {
+        self.fallback(&self.running).is_some()
+    }
+
+    /// Requires every update to be signed by `key`.
+    ///
+    /// Updates without a valid signature fail with `OtaError::InvalidSignature` when
+    /// finished and are discarded.
+    #[cfg(feature = "ota-verify")]
+    pub fn with_public_key(mut self, key: verify::PublicKey) -> Self {
+        self.public_key = Some(key);
+        self
+    }
+
+    /// Returns the directory holding the partition images.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Returns the image file of the slot `label`.
+    pub fn image_path(&self, label: &str) -> PathBuf {
+        self.dir.join(format!("{label}.bin"))
+    }
+
+    /// Returns every slot, starting with the factory slot if it has an image.
+    pub fn slots(&self) -> Result<Vec<Slot>, OtaError> {
+        let factory = self.has_factory().then_some(FACTORY_SLOT);
+        factory
+            .into_iter()
+            .chain(OTA_SLOTS)
+            .map(|label| self.slot(label))
+            .collect()
+    }
+
+    /// Returns `true` if a factory image is present.
+    fn has_factory(&self) -> bool {
+        self.image_path(FACTORY_SLOT).is_file()
+    }
+
+    /// Returns the label of the slot selected for the next boot.
+    fn boot_label(&self) -> String {
+        match &self.data.boot {
+            Some(boot) => boot.clone(),
+            None if self.has_factory() => FACTORY_SLOT.to_owned(),
+            None => OTA_SLOTS[0].to_owned(),
+        }
+    }
+
+    /// Returns the slot to roll back to from `label`.
+    ///
+    /// Prefers another OTA slot holding a valid or untracked image, then the factory image.
+    fn fallback(&self, label: &str) -> Option<&'static str> {
+        OTA_SLOTS
+            .into_iter()
+            .find(|slot| {
+                *slot != label
+                    && self.image_path(slot).is_file()
+                    && matches!(
+                        self.image_state(slot),
+                        ImageState::Valid | ImageState::Undefined
+                    )
+            })
+            .or_else(|| (label != FACTORY_SLOT && self.has_factory()).then_some(FACTORY_SLOT))
+    }
+
+    /// Returns the OTA slot the next update is written to.
+    fn update_label(&self) -> &'static str {
+        OTA_SLOTS
+            .into_iter()
+            .find(|label| *label != self.running)
+            .unwrap_or(OTA_SLOTS[0])
+    }
+
+    /// Describes the slot `label`.
+    fn slot(&self, label: &str) -> Result<Slot, OtaError> {
+        let state = if label == FACTORY_SLOT {
+            if self.has_factory() {
+                SlotState::Factory
+            } else {
+                SlotState::Unknown
+            }
+        } else {
+            self.image_state(label).slot_state()
+        };
+
+        Ok(Slot {
+            label: label
+                .try_into()
+                .map_err(|_| OtaError::InvalidSlot(label.to_owned()))?,
+            state,
+            firmware: None,
+        })
+    }
+
+    /// Sets the state of the slot `label` and persists it.
+    fn set_state(&mut self, label: &str, state: ImageState) -> Result<(), OtaError> {
+        self.data.states.insert(label.to_owned(), state);
+        self.data.save(&self.dir)
+    }
+}
+
+impl ErrorType for FileOta {
+    type Error = OtaError;
+}
+
+impl Ota for FileOta {
+    type Update<'a>
+        = FileOtaUpdate<'a>
+    where
+        Self: 'a;
+
+    /// Returns the slot selected for the next boot.
+    fn get_boot_slot(&self) -> Result<Slot, Self::Error> {
+        self.slot(&self.boot_label())
+    }
+
+    /// Returns the slot the process "booted" from when the partitions were opened.
+    fn get_running_slot(&self) -> Result<Slot, Self::Error> {
+        self.slot(&self.running)
+    }
+
+    /// Returns the slot the next update is written to.
+    fn get_update_slot(&self) -> Result<Slot, Self::Error> {
+        self.slot(self.update_label())
+    }
+
+    /// Returns `true` if a factory image is present.
+    fn is_factory_reset_supported(&self) -> Result<bool, Self::Error> {
+        Ok(self.has_factory())
+    }
+
+    /// Erases the OTA slots and selects the factory image for the next boot.
+    fn factory_reset(&mut self) -> Result<(), Self::Error> {
+        if !self.has_factory() {
+            return Err(OtaError::NoFactoryImage);
+        }
+        for label in OTA_SLOTS {
+            match fs::remove_file(self.image_path(label)) {
+                Err(error) if error.kind() != io::ErrorKind::NotFound => return Err(error.into()),
+                _ => {}
+            }
+        }
+        self.data = OtaData {
+            boot: Some(FACTORY_SLOT.to_owned()),
+            states: BTreeMap::new(),
+        };
+        self.data.save(&self.dir)
+    }
+
+    /// Starts writing a new image to the update slot.
+    fn initiate_update(&mut self) -> Result<Self::Update<'_>, Self::Error> {
+        let label = self.update_label();
+        let file = File::create(self.dir.join(format!("{label}.part")))?;
+        Ok(FileOtaUpdate {
+            ota: self,
+            label,
+            file,
+            #[cfg(feature = "ota-verify")]
+            verifier: verify::ImageVerifier::default(),
+        })
+    }
+
+    /// Marks the running slot as valid.
+    fn mark_running_slot_valid(&mut self) -> Result<(), Self::Error> {
+        self.mark_app_valid()
+    }
+
+    /// Marks the running slot as invalid and "reboots" into the last valid slot.
+    ///
+    /// The returned `OtaError::Rebooted` names the slot now running.
+    fn mark_running_slot_invalid_and_reboot(&mut self) -> Self::Error {
+        let running = self.running.clone();
+        self.data.states.insert(running, ImageState::Invalid);
+        if let Err(error) = self.reboot() {
+            return error;
+        }
+        OtaError::Rebooted(self.running.clone())
+    }
+}
+
+/// An update being written to an OTA slot.
+pub struct FileOtaUpdate<'a> {
+    ota: &'a mut FileOta,
+    label: &'static str,
+    file: File,
+    #[cfg(feature = "ota-verify")]
+    verifier: verify::ImageVerifier,
+}
+
+impl FileOtaUpdate<'_> {
+    /// Returns the label of the slot being written.
+    pub fn label(&self) -> &str {
+        self.label
+    }
+
+    /// Requires the image to have the SHA-256 digest `digest`.
+    #[cfg(feature = "ota-verify")]
+    pub fn expect_sha256(&mut self, digest: [u8; 32]) {
+        self.verifier.expect_sha256(digest);
+    }
+
+    /// Sets the signature of the image digest, checked against the configured key.
+    #[cfg(feature = "ota-verify")]
+    pub fn set_signature(&mut self, signature: &[u8]) {
+        self.verifier.set_signature(signature);
+    }
+
+    /// Returns the temporary file receiving the image.
+    fn part_path(&self) -> PathBuf {
+        self.ota.dir.join(format!("{}.part", self.label))
+    }
+}
+
+impl ErrorType for FileOtaUpdate<'_> {
+    type Error = OtaError;
+}
+
+impl Write for FileOtaUpdate<'_> {
+    /// Appends `buf` to the image.
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        let written = self.file.write(buf)?;
+        #[cfg(feature = "ota-verify")]
+        self.verifier.update(&buf[..written]);
+        Ok(written)
+    }
+
+    /// Flushes the image file.
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(self.file.flush()?)
+    }
+}
+
+impl<'a> OtaUpdate for FileOtaUpdate<'a> {
+    type OtaUpdateFinished = FileOtaUpdateFinished<'a>;
+
+    /// Installs the image in its slot without selecting it for boot.
+    ///
+    /// With feature `ota-verify`, an image failing its digest or signature check is
+    /// discarded instead.
+    fn finish(self) -> Result<Self::OtaUpdateFinished, Self::Error> {
+        let part = self.part_path();
+        self.file.sync_all()?;
+        #[cfg(feature = "ota-verify")]
+        if let Err(error) = self.verifier.verify(self.ota.public_key.as_ref()) {
+            fs::remove_file(part)?;
+            return Err(error);
+        }
+        fs::rename(part, self.ota.image_path(self.label))?;
+        self.ota.set_state(self.label, ImageState::New)?;
+        Ok(FileOtaUpdateFinished {
+            ota: self.ota,
+            label: self.label,
+        })
+    }
+
+    /// Installs the image and selects it for the next boot.
+    fn complete(self) -> Result<(), Self::Error> {
+        self.finish()?.activate()
+    }
+
+    /// Discards the partially written image.
+    fn abort(self) -> Result<(), Self::Error> {
+        let part = self.part_path();
+        drop(self.file);
+        fs::remove_file(part)?;
+        Ok(())
+    }
+}
+
+/// An image installed in an OTA slot but not yet selected for boot.
+pub struct FileOtaUpdateFinished<'a> {
+    ota: &'a mut FileOta,
+    label: &'static str,
+}
+
+impl ErrorType for FileOtaUpdateFinished<'_> {
+    type Error = OtaError;
+}
+
+impl OtaUpdateFinished for FileOtaUpdateFinished<'_> {
+    /// Selects the installed image for the next boot.
+    fn activate(self) -> Result<(), Self::Error> {
+        self.ota.data.boot = Some(self.label.to_owned());
+        self.ota.data.save(&self.ota.dir)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes `image` to the update slot and selects it for boot.
+    fn install(ota: &mut FileOta, image: &[u8]) {
+        let mut update = ota.initiate_update().unwrap();
+        update.write_all(image).unwrap();
+        update.complete().unwrap();
+    }
+
+    /// Tests installing an update, rebooting into it, and rejecting it.
+    #[test]
+    fn test_update_and_rollback() {
+        let dir = std::env::temp_dir().join(format!("native-svc-ota-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("factory.bin"), b"factory").unwrap();
+
+        let mut ota = FileOta::open(&dir).unwrap();
+        assert_eq!(ota.get_running_slot().unwrap().label, FACTORY_SLOT);
+        assert_eq!(ota.slots().unwrap().len(), 3);
+
+        let mut update = ota.initiate_update().unwrap();
+        update.write_all(b"aborted").unwrap();
+        update.abort().unwrap();
+        assert!(!ota.image_path("ota_0").exists());
+
+        install(&mut ota, b"v2");
+        assert_eq!(fs::read(ota.image_path("ota_0")).unwrap(), b"v2");
+        assert_eq!(ota.image_state("ota_0"), ImageState::New);
+
+        let mut ota = FileOta::open(&dir).unwrap();
+        let running = ota.get_running_slot().unwrap();
+        assert_eq!(running.label, "ota_0");
+        assert_eq!(running.state, SlotState::Unverified);
+        assert_eq!(ota.get_update_slot().unwrap().label, "ota_1");
+
+        assert!(matches!(
+            ota.mark_running_slot_invalid_and_reboot(),
+            OtaError::Rebooted(slot) if slot == FACTORY_SLOT
+        ));
+        let ota = FileOta::open(&dir).unwrap();
+        assert_eq!(ota.get_boot_slot().unwrap().label, FACTORY_SLOT);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    /// Tests that an image not confirmed during its first boot is rolled back.
+    #[test]
+    fn test_unconfirmed_image_rollback() {
+        let dir = std::env::temp_dir().join(format!("native-svc-rollback-{}", std::process::id()));
+        let mut ota = FileOta::open(&dir).unwrap();
+        install(&mut ota, b"v1");
+
+        ota.reboot().unwrap();
+        assert_eq!(ota.image_state("ota_1"), ImageState::PendingVerify);
+        ota.mark_app_valid().unwrap();
+        install(&mut ota, b"v2");
+
+        ota.reboot().unwrap();
+        assert_eq!(ota.get_running_slot().unwrap().label, "ota_0");
+        assert!(ota.is_rollback_possible());
+
+        let ota = FileOta::open(&dir).unwrap();
+        assert_eq!(ota.get_running_slot().unwrap().label, "ota_1");
+        assert_eq!(ota.image_state("ota_0"), ImageState::Aborted);
+        assert_eq!(ota.image_state("ota_1"), ImageState::Valid);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,405 @@
+//! Firmware downloads from HTTP servers into OTA slots.
+//!
+//! `HttpOtaUpdater` streams an image from a URL through `HyperHttpConnection` into any
+//! `embedded_svc::ota::Ota` implementation. Interrupted downloads are resumed with
+//! `Range` requests, progress is reported after every chunk, and the SHA-256 digest of
+//! the image can be checked before the update is activated.
+//!
+//! Resumed requests carry the `ETag` or `Last-Modified` value of the image in
+//! `If-Range`. If the server answers with the whole image instead, or with a range
+//! that does not start where the download stopped, the update starts over from the
+//! first byte rather than splicing two images together.
+
+use crate::HyperHttpConnection;
+use crate::error::{HttpOtaError, HyperError};
+use embedded_svc::http::client::Connection;
+use embedded_svc::http::{Headers, Method, Status};
+use embedded_svc::io::Read;
+use embedded_svc::ota::{Ota, OtaUpdate};
+use sha2::{Digest, Sha256};
+use std::thread;
+use std::time::Duration;
+
+/// Default number of times an interrupted download is resumed or started over.
+pub const DEFAULT_MAX_RETRIES: usize = 3;
+
+/// Default delay before resuming an interrupted download.
+pub const DEFAULT_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Default size of the chunks written to the OTA slot.
+const DEFAULT_CHUNK_SIZE: usize = 4096;
+
+/// Progress of a download across resumed attempts.
+#[derive(Default)]
+struct Transfer {
+    hasher: Sha256,
+    written: u64,
+    total: Option<u64>,
+    /// `ETag` or `Last-Modified` value of the image, sent in `If-Range`.
+    validator: Option<String>,
+}
+
+/// Outcome of a failed download attempt.
+enum Failure<E> {
+    /// The attempt can be resumed.
+    Retry(HttpOtaError<E>),
+    /// The download must start over from the first byte.
+    Restart(HttpOtaError<E>),
+    /// The update cannot continue.
+    Fatal(HttpOtaError<E>),
+}
+
+/// Downloads firmware images into OTA slots.
+///
+/// # Example
+///
+/// ```no_run
+/// use native_svc::ota::FileOta;
+/// use native_svc::ota::http::HttpOtaUpdater;
+///
+/// let mut ota = FileOta::open("/var/lib/device/flash").unwrap();
+/// let mut updater = HttpOtaUpdater::new().unwrap().max_retries(5);
+///
+/// updater
+///     .update(&mut ota, "https://updates.example.com/fw.bin", |written, total| {
+///         println!("{written} of {total:?} bytes");
+///     })
+///     .unwrap();
+/// ```
+pub struct HttpOtaUpdater {
+    conn: HyperHttpConnection,
+    max_retries: usize,
+    retry_delay: Duration,
+    chunk_size: usize,
+    expected_sha256: Option<[u8; 32]>,
+}
+
+impl HttpOtaUpdater {
+    /// Creates an updater with a new HTTP connection.
+    pub fn new() -> Result<Self, HyperError> {
+        Ok(Self::with_connection(HyperHttpConnection::new()?))
+    }
+
+    /// Creates an updater downloading through `conn`.
+    pub fn with_connection(conn: HyperHttpConnection) -> Self {
+        Self {
+            conn,
+            max_retries: DEFAULT_MAX_RETRIES,
+            retry_delay: DEFAULT_RETRY_DELAY,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            expected_sha256: None,
+        }
+    }
+
+    /// Sets how many times an interrupted download is resumed or started over.
+    pub fn max_retries(mut self, max_retries: usize) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Sets the delay before resuming an interrupted download.
+    pub fn retry_delay(mut self, delay: Duration) -> Self {
+        self.retry_delay = delay;
+        self
+    }
+
+    /// Sets the size of the chunks written to the OTA slot.
+    pub fn chunk_size(mut self, size: usize) -> Self {
+        self.chunk_size = size.max(1);
+        self
+    }
+
+    /// Requires the downloaded image to have the SHA-256 digest `digest`.
+    pub fn expect_sha256(mut self, digest: [u8; 32]) -> Self {
+        self.expected_sha256 = Some(digest);
+        self
+    }
+
+    /// Downloads the image at `url` into the update slot of `ota` and activates it.
+    ///
+    /// `progress` receives the bytes written so far and the image size, if known.
+    /// Returns the SHA-256 digest of the image. On failure the update is aborted.
+    pub fn update<O>(
+        &mut self,
+        ota: &mut O,
+        url: &str,
+        mut progress: impl FnMut(u64, Option<u64>),
+    ) -> Result<[u8; 32], HttpOtaError<O::Error>>
+    where
+        O: Ota,
+    {
+        let mut update = ota.initiate_update().map_err(HttpOtaError::Ota)?;
+        let mut transfer = Transfer::default();
+
+        let mut retries = 0;
+        loop {
+            let error = match self.transfer(url, &mut update, &mut transfer, &mut progress) {
+                Ok(()) => break,
+                Err(Failure::Retry(_)) if retries < self.max_retries => {
+                    retries += 1;
+                    thread::sleep(self.retry_delay);
+                    continue;
+                }
+                Err(Failure::Restart(_)) if retries < self.max_retries => {
+                    retries += 1;
+                    update.abort().map_err(HttpOtaError::Ota)?;
+                    update = ota.initiate_update().map_err(HttpOtaError::Ota)?;
+                    transfer = Transfer::default();
+                    continue;
+                }
+                Err(Failure::Retry(error) | Failure::Restart(error) | Failure::Fatal(error)) => {
+                    error
+                }
+            };
+            let _ = update.abort();
+            return Err(error);
+        }
+
+        let digest: [u8; 32] = transfer.hasher.finalize().into();
+        if self
+            .expected_sha256
+            .is_some_and(|expected| expected != digest)
+        {
+            let _ = update.abort();
+            return Err(HttpOtaError::DigestMismatch);
+        }
+        update.complete().map_err(HttpOtaError::Ota)?;
+        Ok(digest)
+    }
+
+    /// Downloads the rest of the image, resuming after the bytes already written.
+    fn transfer<U>(
+        &mut self,
+        url: &str,
+        update: &mut U,
+        transfer: &mut Transfer,
+        progress: &mut impl FnMut(u64, Option<u64>),
+    ) -> Result<(), Failure<U::Error>>
+    where
+        U: OtaUpdate,
+    {
+        let range = format!("bytes={}-", transfer.written);
+        let mut headers = Vec::new();
+        if transfer.written > 0 {
+            headers.push(("Range", range.as_str()));
+            if let Some(validator) = &transfer.validator {
+                headers.push(("If-Range", validator.as_str()));
+            }
+        }
+
+        let retry = |error: HyperError| Failure::Retry(error.into());
+        self.conn
+            .initiate_request(Method::Get, url, &headers)
+            .map_err(retry)?;
+        self.conn.initiate_response().map_err(retry)?;
+
+        // A whole image in answer to `Range` was changed on the server or the server
+        // ignores ranges; either way, what was written cannot be resumed.
+        match self.conn.status() {
+            206 => {
+                let content_range = self.conn.header("Content-Range");
+                if content_range.and_then(content_range_start) != Some(transfer.written) {
+                    return Err(Failure::Restart(HttpOtaError::Status(206)));
+                }
+                transfer.total = content_range.and_then(content_range_total);
+            }
+            200 if transfer.written > 0 => return Err(Failure::Restart(HttpOtaError::Status(200))),
+            200 => {
+                transfer.total = self
+                    .conn
+                    .header("Content-Length")
+                    .and_then(|length| length.parse().ok());
+                transfer.validator = self.validator();
+            }
+            status @ 500..=599 => return Err(Failure::Retry(HttpOtaError::Status(status))),
+            status => return Err(Failure::Fatal(HttpOtaError::Status(status))),
+        }
+
+        let mut buf = vec![0; self.chunk_size];
+        loop {
+            let read = self.conn.read(&mut buf).map_err(retry)?;
+            if read == 0 {
+                break;
+            }
+
+            let data = &buf[..read];
+
+            update
+                .write_all(data)
+                .map_err(|error| Failure::Fatal(HttpOtaError::Ota(error)))?;
+            transfer.hasher.update(data);
+            transfer.written += data.len() as u64;
+            progress(transfer.written, transfer.total);
+        }
+
+        match transfer.total {
+            Some(total) if transfer.written < total => Err(Failure::Retry(
+                HttpOtaError::Incomplete(transfer.written, total),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the strong `ETag` of the response, or else its `Last-Modified` date,
+    /// to make sure a resumed download continues the same image.
+    fn validator(&self) -> Option<String> {
+        let etag = self
+            .conn
+            .header("ETag")
+            .filter(|etag| !etag.starts_with("W/"));
+        etag.or_else(|| self.conn.header("Last-Modified"))
+            .map(str::to_owned)
+    }
+}
+
+/// Parses the first byte position from a `Content-Range: bytes a-b/total` header.
+fn content_range_start(value: &str) -> Option<u64> {
+    let range = value.trim().strip_prefix("bytes ")?;
+    range.split_once('-')?.0.trim().parse().ok()
+}
+
+/// Parses the complete length from a `Content-Range: bytes a-b/total` header.
+fn content_range_total(value: &str) -> Option<u64> {
+    value.rsplit_once('/')?.1.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ota::FileOta;
+    use std::io::{Read as _, Write as _};
+    use std::net::TcpListener;
+
+    /// Firmware image served by the tests.
+    const IMAGE: &[u8] = b"firmware image";
+
+    /// Returns a `200` response carrying the first `len` bytes of a `IMAGE` tagged
+    /// `etag`, cut short if `len` is less than its size.
+    fn whole(etag: &str, len: usize) -> Vec<u8> {
+        let head = format!(
+            "HTTP/1.1 200 OK\r\nETag: {etag}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            IMAGE.len()
+        );
+        [head.as_bytes(), &IMAGE[..len]].concat()
+    }
+
+    /// Returns a `206` response carrying `IMAGE` from byte `start` on.
+    fn partial(start: usize) -> Vec<u8> {
+        let head = format!(
+            "HTTP/1.1 206 Partial Content\r\nContent-Range: bytes {start}-{}/{}\r\n\
+             Content-Length: {}\r\nConnection: close\r\n\r\n",
+            IMAGE.len() - 1,
+            IMAGE.len(),
+            IMAGE.len() - start
+        );
+        [head.as_bytes(), &IMAGE[start..]].concat()
+    }
+
+    /// Serves `responses` in turn, one per connection, and returns the URL of the
+    /// image and the request heads received.
+    fn spawn_image_server(responses: Vec<Vec<u8>>) -> (String, thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = thread::spawn(move || {
+            let mut heads = Vec::new();
+            for response in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = Vec::new();
+                let mut byte = [0; 1];
+                while !request.ends_with(b"\r\n\r\n") {
+                    stream.read_exact(&mut byte).unwrap();
+                    request.push(byte[0]);
+                }
+                stream.write_all(&response).unwrap();
+                heads.push(String::from_utf8(request).unwrap());
+            }
+            heads
+        });
+
+        (format!("http://{addr}/fw.bin"), server)
+    }
+
+    /// Downloads the image at `url` into a new OTA directory named after `name`,
+    /// and returns the image written to the slot.
+    fn download(name: &str, url: &str) -> Vec<u8> {
+        let dir =
+            std::env::temp_dir().join(format!("native-svc-http-ota-{name}-{}", std::process::id()));
+        let mut ota = FileOta::open(&dir).unwrap();
+
+        let digest: [u8; 32] = Sha256::digest(IMAGE).into();
+        let mut updater = HttpOtaUpdater::new()
+            .unwrap()
+            .max_retries(4)
+            .retry_delay(Duration::ZERO)
+            .expect_sha256(digest);
+
+        let mut reported = 0;
+        let result = updater.update(&mut ota, url, |written, total| {
+            reported = written;
+            assert_eq!(total, Some(IMAGE.len() as u64));
+        });
+        assert_eq!(result.unwrap(), digest);
+        assert_eq!(reported, IMAGE.len() as u64);
+
+        let image = std::fs::read(ota.image_path("ota_1")).unwrap();
+        std::fs::remove_dir_all(dir).unwrap();
+        image
+    }
+
+    /// Tests a download that is retried after a server error.
+    #[test]
+    fn test_download_server_error() {
+        let unavailable =
+            b"HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+        let (url, server) =
+            spawn_image_server(vec![unavailable.to_vec(), whole("\"v1\"", IMAGE.len())]);
+        assert_eq!(download("server-error", &url), IMAGE);
+
+        let heads = server.join().unwrap();
+        assert_eq!(heads.len(), 2);
+        assert!(!heads[1].contains("range"));
+    }
+
+    /// Tests a download that is resumed after the connection drops mid-body.
+    #[test]
+    fn test_download_with_retry() {
+        let (url, server) = spawn_image_server(vec![whole("\"v1\"", 4), partial(4)]);
+        assert_eq!(download("retry", &url), IMAGE);
+
+        let heads = server.join().unwrap();
+        assert!(!heads[0].contains("range"));
+        assert!(heads[1].contains("range: bytes=4-\r\n"));
+        assert!(heads[1].contains("if-range: \"v1\"\r\n"));
+    }
+
+    /// Tests that a download starts over when the resumed response is the whole
+    /// image or a range starting elsewhere.
+    #[test]
+    fn test_download_restart() {
+        let (url, server) = spawn_image_server(vec![
+            whole("\"v1\"", 4),
+            whole("\"v2\"", IMAGE.len()),
+            whole("\"v2\"", 6),
+            partial(2),
+            whole("\"v2\"", IMAGE.len()),
+        ]);
+        assert_eq!(download("restart", &url), IMAGE);
+
+        let heads = server.join().unwrap();
+        assert!(heads[1].contains("range: bytes=4-\r\n"));
+        assert!(!heads[2].contains("range"));
+        assert!(heads[3].contains("range: bytes=6-\r\n"));
+        assert!(heads[3].contains("if-range: \"v2\"\r\n"));
+        assert!(!heads[4].contains("range"));
+    }
+
+    /// Tests parsing the first byte and the image size from `Content-Range`.
+    #[test]
+    fn test_content_range() {
+        assert_eq!(content_range_start("bytes 4-9/10"), Some(4));
+        assert_eq!(content_range_start("bytes */10"), None);
+        assert_eq!(content_range_total("bytes 4-9/10"), Some(10));
+        assert_eq!(content_range_total("bytes 4-9/*"), None);
+    }
+}
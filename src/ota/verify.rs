@@ -0,0 +1,123 @@
+//! Digest and signature checks for OTA images.
+//!
+//! Images are hashed with SHA-256 while they are written. On completion the digest can
+//! be compared with an expected value and its signature checked against the public key
+//! configured on `FileOta`, as secure boot does on device. Signatures cover the 32-byte
+//! image digest: Ed25519 signs it directly and RSA uses PSS with SHA-256, the secure
+//! boot v2 scheme.
+
+use crate::error::OtaError;
+use ed25519_dalek::{Signature, VerifyingKey};
+use rsa::pkcs8::DecodePublicKey;
+use rsa::{Pss, RsaPublicKey};
+use sha2::{Digest, Sha256};
+
+/// A public key trusted to sign firmware images.
+#[derive(Debug, Clone)]
+pub enum PublicKey {
+    /// An Ed25519 key.
+    Ed25519(VerifyingKey),
+    /// An RSA key, checked with RSA-PSS and SHA-256.
+    Rsa(RsaPublicKey),
+}
+
+impl PublicKey {
+    /// Parses a raw 32-byte Ed25519 public key.
+    pub fn ed25519(bytes: &[u8; 32]) -> Result<Self, OtaError> {
+        VerifyingKey::from_bytes(bytes)
+            .map(Self::Ed25519)
+            .map_err(|error| OtaError::InvalidPublicKey(error.to_string()))
+    }
+
+    /// Parses a PEM-encoded RSA public key (`BEGIN PUBLIC KEY`).
+    pub fn rsa_pem(pem: &str) -> Result<Self, OtaError> {
+        RsaPublicKey::from_public_key_pem(pem)
+            .map(Self::Rsa)
+            .map_err(|error| OtaError::InvalidPublicKey(error.to_string()))
+    }
+
+    /// Checks that `signature` signs `digest` with this key.
+    pub fn verify(&self, digest: &[u8; 32], signature: &[u8]) -> Result<(), OtaError> {
+        let verified = match self {
+            Self::Ed25519(key) => Signature::from_slice(signature)
+                .is_ok_and(|signature| key.verify_strict(digest, &signature).is_ok()),
+            Self::Rsa(key) => key.verify(Pss::new::<Sha256>(), digest, signature).is_ok(),
+        };
+        verified.then_some(()).ok_or(OtaError::InvalidSignature)
+    }
+}
+
+/// Hashes an image while it is written and checks it once complete.
+#[derive(Default)]
+pub(crate) struct ImageVerifier {
+    hasher: Sha256,
+    expected: Option<[u8; 32]>,
+    signature: Option<Vec<u8>>,
+}
+
+impl ImageVerifier {
+    /// Adds written image bytes to the digest.
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        self.hasher.update(data);
+    }
+
+    /// Requires the image to have the SHA-256 digest `digest`.
+    pub(crate) fn expect_sha256(&mut self, digest: [u8; 32]) {
+        self.expected = Some(digest);
+    }
+
+    /// Sets the signature of the image digest.
+    pub(crate) fn set_signature(&mut self, signature: &[u8]) {
+        self.signature = Some(signature.to_vec());
+    }
+
+    /// Checks the expected digest and, if `key` is set, the signature.
+    pub(crate) fn verify(self, key: Option<&PublicKey>) -> Result<[u8; 32], OtaError> {
+        let digest: [u8; 32] = self.hasher.finalize().into();
+        if self.expected.is_some_and(|expected| expected != digest) {
+            return Err(OtaError::DigestMismatch);
+        }
+        if let Some(key) = key {
+            let signature = self.signature.ok_or(OtaError::InvalidSignature)?;
+            key.verify(&digest, &signature)?;
+        }
+        Ok(digest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signer, SigningKey};
+
+    /// Tests digest and Ed25519 signature checks.
+    #[test]
+    fn test_image_verification() {
+        let signing = SigningKey::from_bytes(&[7; 32]);
+        let key = PublicKey::ed25519(signing.verifying_key().as_bytes()).unwrap();
+        let digest: [u8; 32] = Sha256::digest(b"firmware").into();
+        let signature = signing.sign(&digest).to_bytes();
+
+        let mut verifier = ImageVerifier::default();
+        verifier.update(b"firm");
+        verifier.update(b"ware");
+        verifier.expect_sha256(digest);
+        verifier.set_signature(&signature);
+        assert_eq!(verifier.verify(Some(&key)).unwrap(), digest);
+
+        let mut verifier = ImageVerifier::default();
+        verifier.update(b"tampered");
+        verifier.set_signature(&signature);
+        assert!(matches!(
+            verifier.verify(Some(&key)),
+            Err(OtaError::InvalidSignature)
+        ));
+
+        let mut verifier = ImageVerifier::default();
+        verifier.expect_sha256(digest);
+        assert!(matches!(
+            verifier.verify(None),
+            Err(OtaError::DigestMismatch)
+        ));
+    }
+}
@@ -0,0 +1,252 @@
+//! ICMP echo using `embedded_svc::ping` types.
+//!
+//! `NativePing` mirrors `EspPing`: `ping` sends a number of echo requests to an IPv4
+//! address and returns a `Summary`, while `ping_details` also reports every reply. It
+//! uses unprivileged ICMP sockets where the OS allows them, such as Linux with
+//! `net.ipv4.ping_group_range` set and macOS, and falls back to raw sockets otherwise.
+
+use crate::error::PingError;
+use embedded_svc::ipv4::Ipv4Addr;
+use embedded_svc::ping::{Configuration, Info, Reply, Summary};
+use socket2::{Domain, Protocol, SockAddr, Socket, Type};
+use std::io::{ErrorKind, Read};
+use std::net::SocketAddrV4;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// ICMP echo request type.
+const ECHO_REQUEST: u8 = 8;
+
+/// ICMP echo reply type.
+const ECHO_REPLY: u8 = 0;
+
+/// An echo reply read from the socket.
+struct EchoReply {
+    /// TTL from the IP header, or 0 if the socket strips it.
+    ttl: u8,
+    source: Option<Ipv4Addr>,
+    id: u16,
+    seq: u16,
+    /// Length of the ICMP message.
+    len: usize,
+}
+
+/// Sends ICMP echo requests.
+///
+/// # Example
+///
+/// ```no_run
+/// use embedded_svc::ping::Configuration;
+/// use native_svc::ping::NativePing;
+/// use std::net::Ipv4Addr;
+///
+/// let summary = NativePing::new()
+///     .ping(Ipv4Addr::new(192, 168, 1, 1), &Configuration::default())
+///     .unwrap();
+/// println!("{} of {} replies", summary.received, summary.transmitted);
+/// ```
+#[derive(Debug, Clone)]
+pub struct NativePing {
+    id: u16,
+}
+
+impl NativePing {
+    /// Creates a pinger identified by the process id.
+    pub fn new() -> Self {
+        Self {
+            id: std::process::id() as u16,
+        }
+    }
+
+    /// Pings `ip` as configured by `conf` and returns the summary.
+    pub fn ping(&mut self, ip: Ipv4Addr, conf: &Configuration) -> Result<Summary, PingError> {
+        self.ping_details(ip, conf, |_, _| {})
+    }
+
+    /// Pings `ip` as configured by `conf`, passing each reply to `reply_callback`.
+    pub fn ping_details<F>(
+        &mut self,
+        ip: Ipv4Addr,
+        conf: &Configuration,
+        mut reply_callback: F,
+    ) -> Result<Summary, PingError>
+    where
+        F: FnMut(&Summary, &Reply) + Send,
+    {
+        let (socket, raw) = open_socket()?;
+        if conf.tos != 0 {
+            socket.set_tos_v4(conf.tos.into())?;
+        }
+        let target = SockAddr::from(SocketAddrV4::new(ip, 0));
+        let payload: Vec<u8> = (0..conf.data_size).map(|i| i as u8).collect();
+
+        let start = Instant::now();
+        let mut summary = Summary {
+            transmitted: 0,
+            received: 0,
+            time: Duration::ZERO,
+        };
+        for seqno in 0..conf.count {
+            let sent = Instant::now();
+            let seq = seqno as u16;
+            socket.send_to(&echo_request(self.id, seq, &payload), &target)?;
+            summary.transmitted += 1;
+
+            let reply = match self.wait_reply(&socket, raw, ip, seq, sent + conf.timeout)? {
+                Some(reply) => {
+                    summary.received += 1;
+                    Reply::Success(Info {
+                        addr: ip,
+                        seqno,
+                        ttl: reply.ttl,
+                        elapsed_time: sent.elapsed(),
+                        recv_len: reply.len as u32,
+                    })
+                }
+                None => Reply::Timeout,
+            };
+            summary.time = start.elapsed();
+            reply_callback(&summary, &reply);
+
+            if seqno + 1 < conf.count {
+                thread::sleep((sent + conf.interval).saturating_duration_since(Instant::now()));
+            }
+        }
+
+        summary.time = start.elapsed();
+        Ok(summary)
+    }
+
+    /// Waits until `deadline` for the reply to request `seq` from `ip`.
+    fn wait_reply(
+        &self,
+        socket: &Socket,
+        raw: bool,
+        ip: Ipv4Addr,
+        seq: u16,
+        deadline: Instant,
+    ) -> Result<Option<EchoReply>, PingError> {
+        let mut buf = vec![0; 65536];
+        loop {
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(None);
+            }
+            socket.set_read_timeout(Some(deadline - now))?;
+
+            let len = match (&*socket).read(&mut buf) {
+                Ok(len) => len,
+                Err(error)
+                    if matches!(error.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) =>
+                {
+                    return Ok(None);
+                }
+                Err(error) => return Err(error.into()),
+            };
+            // Raw sockets see every ICMP message; unprivileged ones only their own replies.
+            let matches = |reply: &EchoReply| {
+                reply.seq == seq && (!raw || (reply.id == self.id && reply.source == Some(ip)))
+            };
+            if let Some(reply) = parse_reply(&buf[..len]).filter(matches) {
+                return Ok(Some(reply));
+            }
+        }
+    }
+}
+
+impl Default for NativePing {
+    /// Creates a pinger identified by the process id.
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Opens an unprivileged ICMP socket, or a raw one if those are not allowed.
+///
+/// Returns the socket and whether it is raw.
+fn open_socket() -> Result<(Socket, bool), PingError> {
+    match Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::ICMPV4)) {
+        Ok(socket) => Ok((socket, false)),
+        Err(_) => Ok((
+            Socket::new(Domain::IPV4, Type::RAW, Some(Protocol::ICMPV4))?,
+            true,
+        )),
+    }
+}
+
+/// Builds an echo request carrying `payload`.
+fn echo_request(id: u16, seq: u16, payload: &[u8]) -> Vec<u8> {
+    let mut packet = vec![ECHO_REQUEST, 0, 0, 0];
+    packet.extend(id.to_be_bytes());
+    packet.extend(seq.to_be_bytes());
+    packet.extend(payload);
+    let checksum = checksum(&packet);
+    packet[2..4].copy_from_slice(&checksum.to_be_bytes());
+    packet
+}
+
+/// Parses an echo reply, with or without its IP header.
+fn parse_reply(packet: &[u8]) -> Option<EchoReply> {
+    let (ttl, source, icmp) = if packet.first()? >> 4 == 4 {
+        let header_len = usize::from(packet[0] & 0x0f) * 4;
+        let source: [u8; 4] = packet.get(12..16)?.try_into().ok()?;
+        (
+            *packet.get(8)?,
+            Some(Ipv4Addr::from(source)),
+            packet.get(header_len..)?,
+        )
+    } else {
+        (0, None, packet)
+    };
+
+    if icmp.len() < 8 || icmp[0] != ECHO_REPLY {
+        return None;
+    }
+    Some(EchoReply {
+        ttl,
+        source,
+        id: u16::from_be_bytes([icmp[4], icmp[5]]),
+        seq: u16::from_be_bytes([icmp[6], icmp[7]]),
+        len: icmp.len(),
+    })
+}
+
+/// Computes the Internet checksum of `data`.
+fn checksum(data: &[u8]) -> u16 {
+    let mut sum: u32 = data
+        .chunks(2)
+        .map(|chunk| u32::from(u16::from_be_bytes([chunk[0], *chunk.get(1).unwrap_or(&0)])))
+        .sum();
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests building requests and parsing replies with and without IP headers.
+    #[test]
+    fn test_echo_packets() {
+        let request = echo_request(0x1234, 7, b"abc");
+        assert_eq!(checksum(&request), 0);
+        assert_eq!(&request[4..8], [0x12, 0x34, 0, 7]);
+
+        let mut reply = request.clone();
+        reply[0] = ECHO_REPLY;
+        let parsed = parse_reply(&reply).unwrap();
+        assert_eq!((parsed.id, parsed.seq, parsed.len), (0x1234, 7, 11));
+        assert_eq!(parsed.source, None);
+
+        let mut packet = vec![0x45, 0, 0, 0, 0, 0, 0, 0, 64, 1, 0, 0, 10, 0, 0, 1];
+        packet.extend([10, 0, 0, 2]);
+        packet.extend(&reply);
+        let parsed = parse_reply(&packet).unwrap();
+        assert_eq!(parsed.ttl, 64);
+        assert_eq!(parsed.source, Some(Ipv4Addr::new(10, 0, 0, 1)));
+
+        assert!(parse_reply(&request).is_none());
+    }
+}
@@ -0,0 +1,211 @@
+//! Pools of HTTP connections shared between threads.
+//!
+//! A `ConnectionPool` keeps a fixed number of ready `HyperHttpConnection`s sharing
+//! one runtime and one `hyper` client, so their keep-alive connections to servers
+//! are shared as well. Threads check a connection out with `get`, use it through the
+//! returned guard, and the guard hands it back when dropped. Returned connections
+//! forget their request and response, and those left with an upgraded connection
+//! are replaced with new ones.
+
+use crate::HyperHttpConnection;
+use crate::error::HyperError;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// Settings applied to each connection of a pool.
+type Configure = Box<dyn Fn(HyperHttpConnection) -> HyperHttpConnection + Send + Sync>;
+
+/// A fixed set of connections checked out by one thread at a time.
+///
+/// # Example
+///
+/// ```no_run
+/// use embedded_svc::http::client::Connection;
+/// use embedded_svc::http::{Method, Status};
+/// use native_svc::pool::ConnectionPool;
+/// use native_svc::retry::RetryPolicy;
+///
+/// let pool = ConnectionPool::new(4, |conn| conn.with_retry(RetryPolicy::new(2))).unwrap();
+/// let mut conn = pool.get();
+/// conn.initiate_request(Method::Get, "http://example.com", &[]).unwrap();
+/// conn.initiate_response().unwrap();
+/// assert_eq!(conn.status(), 200);
+/// ```
+pub struct ConnectionPool {
+    /// Connection new pool members share their runtime and client with.
+    seed: Mutex<HyperHttpConnection>,
+    configure: Configure,
+    idle: Mutex<Vec<HyperHttpConnection>>,
+    returned: Condvar,
+}
+
+impl ConnectionPool {
+    /// Creates a pool of `size` connections with the default transport settings,
+    /// each passed through `configure`.
+    pub fn new<F>(size: usize, configure: F) -> Result<Self, HyperError>
+    where
+        F: Fn(HyperHttpConnection) -> HyperHttpConnection + Send + Sync + 'static,
+    {
+        Ok(Self::with_connection(
+            size,
+            HyperHttpConnection::new()?,
+            configure,
+        ))
+    }
+
+    /// Creates a pool of `size` connections sharing the runtime, client, TLS and
+    /// proxy settings of `seed`, each passed through `configure`.
+    ///
+    /// Other settings of `seed`, such as retries or interceptors, are not copied;
+    /// `configure` applies them to every connection.
+    pub fn with_connection<F>(size: usize, seed: HyperHttpConnection, configure: F) -> Self
+    where
+        F: Fn(HyperHttpConnection) -> HyperHttpConnection + Send + Sync + 'static,
+    {
+        let idle = (0..size).map(|_| configure(seed.sibling())).collect();
+        Self {
+            seed: Mutex::new(seed),
+            configure: Box::new(configure),
+            idle: Mutex::new(idle),
+            returned: Condvar::new(),
+        }
+    }
+
+    /// Checks a connection out, waiting until one is returned if all are in use.
+    pub fn get(&self) -> PooledConnection<'_> {
+        let mut idle = self.lock_idle();
+        loop {
+            if let Some(conn) = idle.pop() {
+                return PooledConnection::new(self, conn);
+            }
+            idle = self.returned.wait(idle).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    /// Checks a connection out, waiting at most `timeout` for one to be returned.
+    pub fn get_timeout(&self, timeout: Duration) -> Option<PooledConnection<'_>> {
+        let deadline = Instant::now() + timeout;
+        let mut idle = self.lock_idle();
+        loop {
+            if let Some(conn) = idle.pop() {
+                return Some(PooledConnection::new(self, conn));
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return None;
+            }
+            idle = self
+                .returned
+                .wait_timeout(idle, remaining)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+    }
+
+    /// Checks a connection out if one is idle.
+    pub fn try_get(&self) -> Option<PooledConnection<'_>> {
+        let conn = self.lock_idle().pop()?;
+        Some(PooledConnection::new(self, conn))
+    }
+
+    /// Returns the number of connections not checked out.
+    pub fn idle(&self) -> usize {
+        self.lock_idle().len()
+    }
+
+    /// Takes `conn` back, replacing it if it cannot serve another request.
+    fn recycle(&self, mut conn: HyperHttpConnection) {
+        if conn.raw.is_upgraded() {
+            let seed = self.seed.lock().unwrap_or_else(|e| e.into_inner());
+            conn = (self.configure)(seed.sibling());
+        } else {
+            conn.reset();
+            conn.prefetches.clear();
+        }
+        self.lock_idle().push(conn);
+        self.returned.notify_one();
+    }
+
+    /// Locks the idle connections.
+    fn lock_idle(&self) -> MutexGuard<'_, Vec<HyperHttpConnection>> {
+        self.idle.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A connection checked out of a `ConnectionPool`, returned to it when dropped.
+pub struct PooledConnection<'a> {
+    pool: &'a ConnectionPool,
+    conn: Option<HyperHttpConnection>,
+}
+
+impl<'a> PooledConnection<'a> {
+    /// Wraps `conn`, checked out of `pool`.
+    fn new(pool: &'a ConnectionPool, conn: HyperHttpConnection) -> Self {
+        Self {
+            pool,
+            conn: Some(conn),
+        }
+    }
+}
+
+impl Deref for PooledConnection<'_> {
+    type Target = HyperHttpConnection;
+
+    /// Returns the checked out connection.
+    fn deref(&self) -> &HyperHttpConnection {
+        self.conn
+            .as_ref()
+            .expect("connection present until dropped")
+    }
+}
+
+impl DerefMut for PooledConnection<'_> {
+    /// Returns the checked out connection.
+    fn deref_mut(&mut self) -> &mut HyperHttpConnection {
+        self.conn
+            .as_mut()
+            .expect("connection present until dropped")
+    }
+}
+
+impl Drop for PooledConnection<'_> {
+    /// Returns the connection to its pool.
+    fn drop(&mut self) {
+        if let Some(conn) = self.conn.take() {
+            self.pool.recycle(conn);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::ConnectionState;
+    use embedded_svc::http::Method;
+    use embedded_svc::http::client::Connection;
+
+    /// Tests checking connections out and their reset when returned.
+    #[test]
+    fn test_connection_pool() {
+        let pool = ConnectionPool::new(2, |conn| conn.with_strict_state(true)).unwrap();
+        let mut first = pool.get();
+        let second = pool.try_get().unwrap();
+        assert!(pool.try_get().is_none());
+        assert!(pool.get_timeout(Duration::from_millis(10)).is_none());
+        assert!(first.rt.downgrade().ptr_eq(&second.rt.downgrade()));
+
+        first
+            .initiate_request(Method::Get, "http://example.com", &[])
+            .unwrap();
+        assert_eq!(first.state(), ConnectionState::Request);
+        drop(first);
+        assert_eq!(pool.idle(), 1);
+
+        let mut conn = pool.get();
+        assert_eq!(conn.state(), ConnectionState::Idle);
+        assert!(conn.initiate_response().is_err());
+        drop(second);
+        assert_eq!(pool.idle(), 1);
+    }
+}
@@ -0,0 +1,249 @@
+//! The raw connection behind an HTTP exchange.
+//!
+//! `RawConnection` reports the addresses of the TCP connection that carried the last
+//! response, and whether it was reused from the pool. After a `101 Switching
+//! Protocols` response it also gives access to the upgraded stream, so protocols
+//! negotiated over HTTP can take over the socket. The same goes for the tunnel opened
+//! by `HyperHttpConnection::connect_tunnel`, which carries any protocol through an
+//! HTTP proxy.
+
+use crate::connection::{ConnectionInfo, RequestCount};
+use crate::error::HyperError;
+use embedded_svc::io::{ErrorType, Read, Write};
+use hyper::Response;
+use hyper::body::Incoming;
+use hyper::upgrade::Upgraded;
+use hyper_util::client::legacy::connect::HttpInfo;
+use hyper_util::rt::TokioIo;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, Weak};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::runtime::Runtime;
+
+/// The connection of the last response, returned by `Connection::raw_connection`.
+///
+/// Reading and writing require the connection to have been upgraded, or to carry a
+/// `CONNECT` tunnel, and fail with `HyperError::NotUpgraded` otherwise.
+pub struct RawConnection {
+    /// Runtime of the connection, driven while blocking so current-thread runtimes
+    /// make progress.
+    rt: Weak<Runtime>,
+    peer_addr: Option<SocketAddr>,
+    local_addr: Option<SocketAddr>,
+    /// Whether the connection carried earlier requests.
+    reused: bool,
+    stream: Option<TokioIo<Upgraded>>,
+}
+
+impl RawConnection {
+    /// Creates a raw connection running I/O on `rt`.
+    pub(crate) fn new(rt: Weak<Runtime>) -> Self {
+        Self {
+            rt,
+            peer_addr: None,
+            local_addr: None,
+            reused: false,
+            stream: None,
+        }
+    }
+
+    /// Returns the address of the server the last response came from.
+    pub fn peer_addr(&self) -> Option<SocketAddr> {
+        self.peer_addr
+    }
+
+    /// Returns the local address of the connection the last response came on.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.local_addr
+    }
+
+    /// Returns `true` if the connection carried earlier requests and was reused from
+    /// the pool.
+    pub fn is_reused(&self) -> bool {
+        self.reused
+    }
+
+    /// Returns the addresses of the connection and whether it was reused, if known.
+    pub(crate) fn info(&self) -> Option<ConnectionInfo> {
+        Some(ConnectionInfo {
+            peer_addr: self.peer_addr?,
+            local_addr: self.local_addr?,
+            reused: self.reused,
+        })
+    }
+
+    /// Returns `true` if the stream has been taken over from HTTP.
+    pub fn is_upgraded(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    /// Forgets the previous connection.
+    pub(crate) fn reset(&mut self) {
+        self.peer_addr = None;
+        self.local_addr = None;
+        self.reused = false;
+        self.stream = None;
+    }
+
+    /// Records the connection `response` came on.
+    pub(crate) fn record(&mut self, response: &Response<Incoming>) {
+        let info = response.extensions().get::<HttpInfo>();
+        self.peer_addr = info.map(HttpInfo::remote_addr);
+        self.local_addr = info.map(HttpInfo::local_addr);
+        let requests = response.extensions().get::<RequestCount>();
+        self.reused = requests.is_some_and(RequestCount::is_reused);
+    }
+
+    /// Takes over the stream of a `101 Switching Protocols` or `CONNECT` response.
+    pub(crate) fn upgrade<B>(&mut self, response: &mut Response<B>) -> Result<(), HyperError> {
+        if self.stream.is_none() {
+            let upgraded = self.runtime()?.block_on(hyper::upgrade::on(response))?;
+            self.stream = Some(TokioIo::new(upgraded));
+        }
+        Ok(())
+    }
+
+    /// Returns the runtime, failing once the connection has been closed.
+    fn runtime(&self) -> Result<Arc<Runtime>, HyperError> {
+        let rt = self.rt.upgrade();
+        rt.ok_or_else(|| io::Error::from(io::ErrorKind::NotConnected).into())
+    }
+
+    /// Returns the upgraded stream.
+    fn stream(&mut self) -> Result<&mut TokioIo<Upgraded>, HyperError> {
+        self.stream.as_mut().ok_or(HyperError::NotUpgraded)
+    }
+}
+
+impl ErrorType for RawConnection {
+    /// The error type returned by the raw connection.
+    type Error = HyperError;
+}
+
+impl Read for RawConnection {
+    /// Reads from the upgraded stream, returning `Ok(0)` once the peer closed it.
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let rt = self.runtime()?;
+        let stream = self.stream()?;
+        Ok(rt.block_on(stream.read(buf))?)
+    }
+}
+
+impl Write for RawConnection {
+    /// Writes to the upgraded stream.
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        let rt = self.runtime()?;
+        let stream = self.stream()?;
+        Ok(rt.block_on(stream.write(buf))?)
+    }
+
+    /// Flushes the upgraded stream.
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        let rt = self.runtime()?;
+        let stream = self.stream()?;
+        Ok(rt.block_on(stream.flush())?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::HyperHttpConnection;
+    use embedded_svc::http::Method;
+    use embedded_svc::http::client::Connection;
+    use embedded_svc::io::{Read, Write};
+    use std::io::{BufRead, BufReader, Write as _};
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// Tests taking over the socket after a `101 Switching Protocols` response.
+    #[test]
+    fn test_upgraded_connection() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+            let mut stream = stream;
+            stream
+                .write_all(b"HTTP/1.1 101 Switching Protocols\r\nConnection: upgrade\r\nUpgrade: echo\r\n\r\n")
+                .unwrap();
+            let mut echo = [0u8; 5];
+            std::io::Read::read_exact(&mut reader, &mut echo).unwrap();
+            stream.write_all(&echo).unwrap();
+        });
+
+        let mut conn = HyperHttpConnection::new().unwrap();
+        let headers = [("Connection", "upgrade"), ("Upgrade", "echo")];
+        conn.initiate_request(Method::Get, &format!("http://{addr}/"), &headers)
+            .unwrap();
+        conn.initiate_response().unwrap();
+
+        let raw = conn.raw_connection().unwrap();
+        assert_eq!(raw.peer_addr(), Some(addr));
+        assert!(raw.is_upgraded());
+        raw.write_all(b"hello").unwrap();
+        let mut buf = [0u8; 5];
+        raw.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"hello");
+        server.join().unwrap();
+    }
+
+    /// Tests opening a `CONNECT` tunnel through a proxy and using its stream.
+    #[cfg(feature = "proxy")]
+    #[test]
+    fn test_connect_tunnel() {
+        use crate::error::HyperError;
+        use crate::proxy::Proxy;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let replies = [
+                "HTTP/1.1 200 Connection established\r\n\r\n",
+                "HTTP/1.1 403 Forbidden\r\nContent-Length: 0\r\n\r\n",
+            ];
+            let mut targets = Vec::new();
+            for reply in replies {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                targets.push(line.clone());
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+                let mut stream = stream;
+                stream.write_all(reply.as_bytes()).unwrap();
+                if reply.contains("200") {
+                    let mut echo = [0u8; 5];
+                    std::io::Read::read_exact(&mut reader, &mut echo).unwrap();
+                    stream.write_all(&echo).unwrap();
+                }
+            }
+            targets
+        });
+
+        let proxy = Proxy::new(&format!("http://{addr}")).unwrap();
+        let mut conn = HyperHttpConnection::new().unwrap().with_proxy(proxy);
+        assert!(conn.connect_tunnel("http://device.lan/", &[]).is_err());
+
+        let raw = conn.connect_tunnel("device.lan:1883", &[]).unwrap();
+        assert!(raw.is_upgraded());
+        raw.write_all(b"hello").unwrap();
+        let mut buf = [0u8; 5];
+        raw.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"hello");
+
+        assert!(matches!(
+            conn.connect_tunnel("device.lan:1883", &[]),
+            Err(HyperError::ProxyTunnel(status)) if status == 403
+        ));
+        let targets = server.join().unwrap();
+        assert!(targets[0].starts_with("CONNECT device.lan:1883 HTTP/1.1"));
+    }
+}
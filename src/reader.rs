@@ -0,0 +1,115 @@
+//! Blocking `std::io` reader over a response body.
+//!
+//! `HyperHttpConnection::into_reader` wraps a connection whose response has arrived
+//! in a `BodyReader`, implementing `std::io::Read` and `BufRead` over its body, so
+//! it can be handed to parsers built on the standard library such as
+//! `serde_json::from_reader`. `BodyReader::into_inner` gives the connection back
+//! for the next requests.
+
+use crate::HyperHttpConnection;
+use std::io::{self, BufRead, Read};
+
+/// Response body of a connection, read through `std::io`.
+///
+/// # Example
+///
+/// ```no_run
+/// use embedded_svc::http::Method;
+/// use embedded_svc::http::client::Connection;
+/// use native_svc::HyperHttpConnection;
+/// use std::io::BufRead;
+///
+/// let mut conn = HyperHttpConnection::new().unwrap();
+/// conn.initiate_request(Method::Get, "http://example.com/log", &[]).unwrap();
+/// conn.initiate_response().unwrap();
+/// for line in conn.into_reader().unwrap().lines() {
+///     println!("{}", line.unwrap());
+/// }
+/// ```
+pub struct BodyReader {
+    conn: HyperHttpConnection,
+}
+
+impl BodyReader {
+    /// Reads the response body of `conn`.
+    pub(crate) fn new(conn: HyperHttpConnection) -> Self {
+        Self { conn }
+    }
+
+    /// Returns the connection, whose response head stays available.
+    pub fn get_ref(&self) -> &HyperHttpConnection {
+        &self.conn
+    }
+
+    /// Gives the connection back, with the rest of the body left unread.
+    pub fn into_inner(self) -> HyperHttpConnection {
+        self.conn
+    }
+}
+
+impl Read for BodyReader {
+    /// Reads body data, waiting for the next chunk if needed. Returns `Ok(0)` at the
+    /// end of the body.
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let data = self.fill_buf()?;
+        let length = data.len().min(buf.len());
+        buf[..length].copy_from_slice(&data[..length]);
+        self.consume(length);
+        Ok(length)
+    }
+}
+
+impl BufRead for BodyReader {
+    /// Returns the received data not read yet, waiting for the next chunk if needed.
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        Ok(self.conn.body.fill_buf()?)
+    }
+
+    /// Marks `amount` bytes returned by `fill_buf` as read.
+    fn consume(&mut self, amount: usize) {
+        self.conn.body.consume(amount);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_svc::http::client::Connection;
+    use embedded_svc::http::{Method, Status};
+    use std::io::Write;
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// Tests reading the lines of a response body through `std::io`, and reusing the
+    /// connection afterwards.
+    #[test]
+    fn test_body_reader() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = io::BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 12\r\n\r\nfirst\nsecond")
+                .unwrap();
+        });
+
+        let mut conn = HyperHttpConnection::new().unwrap();
+        conn.initiate_request(Method::Get, &format!("http://{addr}/"), &[])
+            .unwrap();
+        conn.initiate_response().unwrap();
+        let mut reader = conn.into_reader().unwrap();
+        assert_eq!(reader.get_ref().status(), 200);
+        let lines: Vec<String> = reader.by_ref().lines().map(Result::unwrap).collect();
+        assert_eq!(lines, ["first", "second"]);
+        assert_eq!(reader.read(&mut [0u8; 4]).unwrap(), 0);
+        server.join().unwrap();
+
+        let conn = reader.into_inner();
+        assert_eq!(conn.status(), 200);
+    }
+}
@@ -0,0 +1,204 @@
+//! Following of HTTP redirects.
+//!
+//! A `RedirectPolicy` set on `HyperHttpConnection` follows `301`, `302`, `303`, `307`
+//! and `308` responses to their `Location`, recording every hop so callers can tell
+//! when a request ended up on another host. `303` responses, and `301` or `302`
+//! responses to a `POST`, are followed with a bodiless `GET` as browsers do; `307` and
+//! `308` resend the same method and body.
+//!
+//! When a redirect leads to another origin, including from HTTPS to HTTP on the same
+//! host, credentials such as `Authorization` and `Cookie` are removed from the request
+//! so they are not leaked to a third party.
+
+use crate::link;
+use hyper::body::Bytes;
+use hyper::header::{
+    AUTHORIZATION, CONTENT_LENGTH, CONTENT_TYPE, COOKIE, HOST, HeaderName, LOCATION,
+    PROXY_AUTHORIZATION, TRANSFER_ENCODING,
+};
+use hyper::{Method, Request, Response, StatusCode, Uri};
+
+/// How many redirects are followed before giving up, and which headers are dropped
+/// when they lead to another origin.
+///
+/// Streamed uploads are never redirected, as their body is not kept.
+///
+/// # Example
+///
+/// ```no_run
+/// use native_svc::HyperHttpConnection;
+/// use native_svc::redirect::RedirectPolicy;
+///
+/// let conn = HyperHttpConnection::new()
+///     .unwrap()
+///     .with_redirects(RedirectPolicy::new(5));
+/// ```
+#[derive(Debug, Clone)]
+pub struct RedirectPolicy {
+    max_redirects: usize,
+    sensitive_headers: Vec<HeaderName>,
+}
+
+impl RedirectPolicy {
+    /// Follows up to `max_redirects` redirects per request, removing `Authorization`,
+    /// `Proxy-Authorization` and `Cookie` on cross-origin redirects.
+    pub fn new(max_redirects: usize) -> Self {
+        Self {
+            max_redirects,
+            sensitive_headers: vec![AUTHORIZATION, PROXY_AUTHORIZATION, COOKIE],
+        }
+    }
+
+    /// Sets the headers removed on cross-origin redirects; an empty list keeps them all.
+    pub fn sensitive_headers(mut self, headers: impl IntoIterator<Item = HeaderName>) -> Self {
+        self.sensitive_headers = headers.into_iter().collect();
+        self
+    }
+
+    /// Returns the maximum number of redirects followed per request.
+    pub fn max_redirects(&self) -> usize {
+        self.max_redirects
+    }
+
+    /// Rewrites `request` to follow a `status` redirect to `location`.
+    pub(crate) fn follow(&self, request: &mut Request<Bytes>, status: StatusCode, location: Uri) {
+        let downgrade = (status == StatusCode::SEE_OTHER && request.method() != Method::HEAD)
+            || (matches!(status.as_u16(), 301 | 302) && request.method() == Method::POST);
+        if downgrade {
+            *request.method_mut() = Method::GET;
+            *request.body_mut() = Bytes::new();
+            for name in [CONTENT_LENGTH, CONTENT_TYPE, TRANSFER_ENCODING] {
+                request.headers_mut().remove(name);
+            }
+        }
+        if !same_origin(request.uri(), &location) {
+            request.headers_mut().remove(HOST);
+            for name in &self.sensitive_headers {
+                request.headers_mut().remove(name);
+            }
+        }
+        *request.uri_mut() = location;
+    }
+}
+
+impl Default for RedirectPolicy {
+    /// Follows up to 10 redirects per request.
+    fn default() -> Self {
+        Self::new(10)
+    }
+}
+
+/// A redirect followed while answering a request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Redirect {
+    /// URI that answered with the redirect.
+    pub uri: Uri,
+    /// Redirect status.
+    pub status: StatusCode,
+    /// URI the request was redirected to.
+    pub location: Uri,
+}
+
+/// Returns the target of `response` to a request for `base` if it is a redirect.
+pub(crate) fn location<B>(base: &Uri, response: &Response<B>) -> Option<Uri> {
+    if !matches!(response.status().as_u16(), 301 | 302 | 303 | 307 | 308) {
+        return None;
+    }
+    let location = response.headers().get(LOCATION)?.to_str().ok()?;
+    link::resolve(base, location).parse().ok()
+}
+
+/// Returns `true` if `a` and `b` have the same scheme, host and port.
+fn same_origin(a: &Uri, b: &Uri) -> bool {
+    let port = |uri: &Uri| {
+        uri.port_u16().or(match uri.scheme_str() {
+            Some("https") => Some(443),
+            Some("http") => Some(80),
+            _ => None,
+        })
+    };
+    a.scheme() == b.scheme()
+        && a.host().map(str::to_ascii_lowercase) == b.host().map(str::to_ascii_lowercase)
+        && port(a) == port(b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests resolving `Location` and rewriting requests per redirect status.
+    #[test]
+    fn test_follow_redirect() {
+        let policy = RedirectPolicy::default();
+        let base: Uri = "https://example.com/files/firmware.bin".parse().unwrap();
+        let response = Response::builder()
+            .status(StatusCode::SEE_OTHER)
+            .header(LOCATION, "/mirror/firmware.bin")
+            .body(())
+            .unwrap();
+        let target = location(&base, &response).unwrap();
+        assert_eq!(target, "https://example.com/mirror/firmware.bin");
+        let ok = Response::new(());
+        assert_eq!(location(&base, &ok), None);
+
+        let post = || {
+            Request::post(base.clone())
+                .header(CONTENT_TYPE, "application/json")
+                .header(AUTHORIZATION, "Bearer secret")
+                .body(Bytes::from_static(b"{}"))
+                .unwrap()
+        };
+        let mut request = post();
+        policy.follow(&mut request, StatusCode::SEE_OTHER, target.clone());
+        assert_eq!(request.method(), Method::GET);
+        assert!(request.body().is_empty());
+        assert!(!request.headers().contains_key(CONTENT_TYPE));
+        assert!(request.headers().contains_key(AUTHORIZATION));
+        assert_eq!(request.uri(), &target);
+
+        let mut request = post();
+        policy.follow(&mut request, StatusCode::TEMPORARY_REDIRECT, target);
+        assert_eq!(request.method(), Method::POST);
+        assert_eq!(request.body(), "{}");
+    }
+
+    /// Tests that credentials are removed on cross-origin redirects and downgrades.
+    #[test]
+    fn test_strip_sensitive_headers() {
+        let request = || {
+            Request::get("https://example.com:443/file")
+                .header(AUTHORIZATION, "Bearer secret")
+                .header(COOKIE, "session=1")
+                .header(HOST, "example.com")
+                .body(Bytes::new())
+                .unwrap()
+        };
+        let follow = |policy: &RedirectPolicy, location: &str| {
+            let mut request = request();
+            let location = location.parse().unwrap();
+            policy.follow(&mut request, StatusCode::FOUND, location);
+            request
+        };
+        let policy = RedirectPolicy::default();
+
+        let same = follow(&policy, "https://EXAMPLE.com/other");
+        assert!(same.headers().contains_key(AUTHORIZATION));
+        assert!(same.headers().contains_key(COOKIE));
+
+        for location in [
+            "https://mirror.example.net/file",
+            "http://example.com/file",
+            "https://example.com:8443/file",
+        ] {
+            let request = follow(&policy, location);
+            assert!(!request.headers().contains_key(AUTHORIZATION), "{location}");
+            assert!(!request.headers().contains_key(COOKIE), "{location}");
+            assert!(!request.headers().contains_key(HOST), "{location}");
+        }
+
+        let keep_cookies = RedirectPolicy::default().sensitive_headers([AUTHORIZATION]);
+        let request = follow(&keep_cookies, "http://example.com/file");
+        assert!(!request.headers().contains_key(AUTHORIZATION));
+        assert!(request.headers().contains_key(COOKIE));
+    }
+}
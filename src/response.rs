@@ -0,0 +1,393 @@
+//! Head and body of the last response of a connection.
+//!
+//! `HyperHttpConnection` keeps the status and headers of a response apart from its
+//! body, so `Connection::split` can lend the head while the body is read without
+//! two references to the whole connection.
+
+use crate::async_io::AsyncResponseBody;
+#[cfg(feature = "decompression")]
+use crate::decode::Decoder;
+use crate::error::HyperError;
+use embedded_svc::http::{Headers, Status};
+use embedded_svc::io::{ErrorType, Read};
+use http_body_util::BodyExt;
+use hyper::body::{Body, Bytes, Frame, Incoming};
+use hyper::header::{CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE, HeaderName};
+use hyper::{Response, Version};
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Weak};
+use std::task::{Context, Poll, Wake, Waker};
+use std::time::Instant;
+use tokio::runtime::Runtime;
+
+/// Status and headers of the last response of a connection.
+#[derive(Debug, Default)]
+pub struct ResponseHead {
+    /// The response, without its body.
+    response: Option<Response<()>>,
+    /// `Content-Length` of the response, parsed once when it arrived.
+    content_length: Option<u64>,
+}
+
+impl ResponseHead {
+    /// Stores the head of a newly received response.
+    pub(crate) fn set(&mut self, response: Response<()>) {
+        let length = response.headers().get(CONTENT_LENGTH);
+        self.content_length = length.and_then(|value| value.to_str().ok()?.parse().ok());
+        self.response = Some(response);
+    }
+
+    /// Forgets the last response.
+    pub(crate) fn clear(&mut self) {
+        self.response = None;
+        self.content_length = None;
+    }
+
+    /// Takes the head of the last response out.
+    pub(crate) fn take(&mut self) -> Option<Response<()>> {
+        self.content_length = None;
+        self.response.take()
+    }
+
+    /// Returns the head of the last response, if any.
+    pub(crate) fn get(&self) -> Option<&Response<()>> {
+        self.response.as_ref()
+    }
+
+    /// Returns the head of the last response mutably, if any.
+    pub(crate) fn get_mut(&mut self) -> Option<&mut Response<()>> {
+        self.response.as_mut()
+    }
+
+    /// Returns the HTTP version of the last response, if any.
+    pub fn version(&self) -> Option<Version> {
+        self.response.as_ref().map(Response::version)
+    }
+
+    /// Returns the `name` header of the last response if it is valid text.
+    pub(crate) fn header_value(&self, name: &HeaderName) -> Option<&str> {
+        let value = self.response.as_ref()?.headers().get(name)?;
+        value.to_str().ok()
+    }
+}
+
+impl Status for ResponseHead {
+    /// Returns the HTTP status code of the last response, or 500 if none.
+    fn status(&self) -> u16 {
+        self.response
+            .as_ref()
+            .map(|response| response.status().as_u16())
+            .unwrap_or(500)
+    }
+
+    /// Returns the reason phrase of the last response status, if available.
+    fn status_message(&self) -> Option<&'_ str> {
+        self.response
+            .as_ref()
+            .and_then(|response| response.status().canonical_reason())
+    }
+}
+
+impl Headers for ResponseHead {
+    /// Retrieves a header value by name from the last response, if set.
+    fn header(&self, name: &str) -> Option<&'_ str> {
+        self.response
+            .as_ref()
+            .and_then(|response| response.headers().get(name))
+            .and_then(|value| value.to_str().ok())
+    }
+
+    /// Returns the `Content-Type` of the last response, looked up by its known name.
+    fn content_type(&self) -> Option<&'_ str> {
+        self.header_value(&CONTENT_TYPE)
+    }
+
+    /// Returns the `Content-Length` of the last response, parsed when it arrived.
+    ///
+    /// Decoded responses have none, as their length is only known once read.
+    fn content_len(&self) -> Option<u64> {
+        self.content_length
+    }
+
+    /// Returns the `Content-Encoding` of the last response, looked up by its known name.
+    fn content_encoding(&self) -> Option<&'_ str> {
+        self.header_value(&CONTENT_ENCODING)
+    }
+}
+
+/// Body of the last response of a connection, read as it arrives.
+pub struct ResponseBody {
+    rt: Weak<Runtime>,
+    body: Option<Incoming>,
+    /// Received data not read yet.
+    buffer: Bytes,
+    has_body: bool,
+    /// Decoder of the response's `Content-Encoding`.
+    #[cfg(feature = "decompression")]
+    decoder: Option<Decoder>,
+    /// Woken when data arrives after `try_read` found none.
+    waker: Waker,
+    /// When the end of the body was reached.
+    ended: Option<Instant>,
+}
+
+impl ResponseBody {
+    /// Creates an empty body received on `rt`.
+    pub(crate) fn new(rt: Weak<Runtime>) -> Self {
+        Self {
+            rt,
+            body: None,
+            buffer: Bytes::new(),
+            has_body: false,
+            #[cfg(feature = "decompression")]
+            decoder: None,
+            waker: Waker::noop().clone(),
+            ended: None,
+        }
+    }
+
+    /// Calls `callback` when data arrives after `try_read` found none.
+    pub(crate) fn set_readable_callback(&mut self, callback: Box<dyn Fn() + Send + Sync>) {
+        self.waker = Waker::from(Arc::new(ReadableCallback(callback)));
+    }
+
+    /// Stores the body of a newly received response, decoded with `decoder`.
+    pub(crate) fn set(
+        &mut self,
+        body: Incoming,
+        has_body: bool,
+        #[cfg(feature = "decompression")] decoder: Option<Decoder>,
+    ) {
+        self.body = Some(body);
+        self.buffer = Bytes::new();
+        self.has_body = has_body;
+        self.ended = (!has_body).then(Instant::now);
+        #[cfg(feature = "decompression")]
+        {
+            self.decoder = decoder;
+        }
+    }
+
+    /// Forgets the last body, with the data not read yet.
+    pub(crate) fn clear(&mut self) {
+        self.take();
+    }
+
+    /// Takes the last body out as sent by the server, forgetting the data not read yet.
+    pub(crate) fn take(&mut self) -> Option<Incoming> {
+        self.buffer = Bytes::new();
+        self.has_body = false;
+        self.ended = None;
+        #[cfg(feature = "decompression")]
+        {
+            self.decoder = None;
+        }
+        self.body.take()
+    }
+
+    /// Takes the rest of the last body out to be read asynchronously.
+    pub(crate) fn take_async(&mut self) -> AsyncResponseBody {
+        let buffer = std::mem::take(&mut self.buffer);
+        let body = self.body.take().filter(|_| self.has_body);
+        self.has_body = false;
+        AsyncResponseBody::new(
+            body,
+            buffer,
+            #[cfg(feature = "decompression")]
+            self.decoder.take(),
+        )
+    }
+
+    /// Returns `true` if the last response carries a body.
+    pub(crate) fn has_body(&self) -> bool {
+        self.has_body
+    }
+
+    /// Returns when the end of the body was read, if it was.
+    pub(crate) fn ended(&self) -> Option<Instant> {
+        self.ended
+    }
+
+    /// Returns the next chunk of the body as received, or `None` at the end.
+    pub fn next_chunk(&mut self) -> Result<Option<Bytes>, HyperError> {
+        let Poll::Ready(chunk) = self.receive(true)? else {
+            unreachable!("waiting for a chunk always completes");
+        };
+        Ok(chunk)
+    }
+
+    /// Reads body data already received, without waiting for more.
+    ///
+    /// Returns `None` if no data has arrived yet, and `Some(0)` at the end of the
+    /// body, so a cooperative loop can poll the response on every tick. The readable
+    /// callback, if any, is then called once more data or the end arrives.
+    pub fn try_read(&mut self, buffer: &mut [u8]) -> Result<Option<usize>, HyperError> {
+        if self.buffer.is_empty() {
+            match self.receive(false)? {
+                Poll::Ready(Some(chunk)) => self.buffer = chunk,
+                Poll::Ready(None) => return Ok(Some(0)),
+                Poll::Pending => return Ok(None),
+            }
+        }
+        Ok(Some(self.copy_buffered(buffer)))
+    }
+
+    /// Returns the received data not read yet, waiting for the next chunk if there is
+    /// none. Returns no data at the end of the body.
+    pub(crate) fn fill_buf(&mut self) -> Result<&[u8], HyperError> {
+        if self.buffer.is_empty()
+            && let Some(chunk) = self.next_chunk()?
+        {
+            self.buffer = chunk;
+        }
+        Ok(&self.buffer)
+    }
+
+    /// Marks `amount` bytes returned by `fill_buf` as read.
+    pub(crate) fn consume(&mut self, amount: usize) {
+        self.buffer = self.buffer.slice(amount.min(self.buffer.len())..);
+    }
+
+    /// Receives the next chunk of the body, or `None` at the end.
+    ///
+    /// Unless `wait` is set, returns `Poll::Pending` when no data has arrived yet.
+    fn receive(&mut self, wait: bool) -> Result<Poll<Option<Bytes>>, HyperError> {
+        if !self.buffer.is_empty() {
+            return Ok(Poll::Ready(Some(std::mem::take(&mut self.buffer))));
+        }
+        if !self.has_body {
+            return Ok(Poll::Ready(None));
+        }
+        let Some(body) = self.body.as_mut() else {
+            return Ok(Poll::Ready(None));
+        };
+
+        let rt = self.rt.upgrade();
+        let rt = rt.ok_or_else(|| io::Error::from(io::ErrorKind::NotConnected))?;
+        loop {
+            let frame = if wait {
+                rt.block_on(body.frame())
+            } else {
+                match poll_frame(&rt, body, &self.waker) {
+                    Poll::Ready(frame) => frame,
+                    Poll::Pending => return Ok(Poll::Pending),
+                }
+            };
+            let Some(frame) = frame else {
+                self.ended.get_or_insert_with(Instant::now);
+                break;
+            };
+            // Trailers carry no body data.
+            if let Ok(data) = frame?.into_data()
+                && !data.is_empty()
+            {
+                #[cfg(feature = "decompression")]
+                let data = match self.decoder.as_mut() {
+                    Some(decoder) => decoder.decode(&data)?,
+                    None => data,
+                };
+                if !data.is_empty() {
+                    return Ok(Poll::Ready(Some(data)));
+                }
+            }
+        }
+        #[cfg(feature = "decompression")]
+        if let Some(mut decoder) = self.decoder.take() {
+            let rest = decoder.finish()?;
+            if !rest.is_empty() {
+                return Ok(Poll::Ready(Some(rest)));
+            }
+        }
+        Ok(Poll::Ready(None))
+    }
+
+    /// Copies buffered data into `buffer`, returning its length.
+    fn copy_buffered(&mut self, buffer: &mut [u8]) -> usize {
+        let length = self.buffer.len().min(buffer.len());
+        buffer[..length].copy_from_slice(&self.buffer[..length]);
+        self.buffer = self.buffer.slice(length..);
+        length
+    }
+}
+
+/// Polls the next frame of `body` once, waking `waker` when it is ready if it is not
+/// yet, after letting `rt` run the tasks receiving it.
+fn poll_frame(
+    rt: &Runtime,
+    body: &mut Incoming,
+    waker: &Waker,
+) -> Poll<Option<Result<Frame<Bytes>, hyper::Error>>> {
+    rt.block_on(tokio::task::yield_now());
+    Pin::new(body).poll_frame(&mut Context::from_waker(waker))
+}
+
+/// Waker calling the readable callback of a body.
+struct ReadableCallback(Box<dyn Fn() + Send + Sync>);
+
+impl Wake for ReadableCallback {
+    /// Calls the callback.
+    fn wake(self: Arc<Self>) {
+        (self.0)();
+    }
+
+    /// Calls the callback.
+    fn wake_by_ref(self: &Arc<Self>) {
+        (self.0)();
+    }
+}
+
+impl ErrorType for ResponseBody {
+    /// The error type returned when reading the body.
+    type Error = HyperError;
+}
+
+impl Read for ResponseBody {
+    /// Reads data from the internal buffer, receiving the next body chunk
+    /// if needed. Returns `Ok(0)` on EOF.
+    fn read(&mut self, buffer: &mut [u8]) -> Result<usize, Self::Error> {
+        if self.buffer.is_empty()
+            && let Some(chunk) = self.next_chunk()?
+        {
+            self.buffer = chunk;
+        }
+
+        if self.buffer.is_empty() {
+            return Ok(0); // EOF
+        }
+
+        Ok(self.copy_buffered(buffer))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests reading the status and headers of a stored response head.
+    #[test]
+    fn test_response_head() {
+        let mut head = ResponseHead::default();
+        assert_eq!(head.status(), 500);
+        assert_eq!(head.content_len(), None);
+        assert_eq!(head.version(), None);
+
+        let response = Response::builder()
+            .status(404)
+            .header("content-type", "text/plain")
+            .header("content-length", "9")
+            .body(())
+            .unwrap();
+        head.set(response);
+        assert_eq!(head.status(), 404);
+        assert_eq!(head.status_message(), Some("Not Found"));
+        assert_eq!(head.content_type(), Some("text/plain"));
+        assert_eq!(head.header("Content-Length"), Some("9"));
+        assert_eq!(head.content_len(), Some(9));
+        assert_eq!(head.version(), Some(Version::HTTP_11));
+
+        head.clear();
+        assert!(head.get().is_none());
+        assert_eq!(head.content_len(), None);
+    }
+}
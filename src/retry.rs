@@ -0,0 +1,139 @@
+//! Automatic retries of failed HTTP requests.
+//!
+//! A `RetryPolicy` set on `HyperHttpConnection` resends requests that failed at the
+//! transport level or were answered with `502`, `503` or `504`, waiting with
+//! exponential backoff between attempts. By default only idempotent requests are
+//! retried, so a `POST` that may have reached the server is never silently sent twice.
+
+use hyper::header::HeaderName;
+use hyper::{Method, Request, StatusCode};
+use std::time::Duration;
+
+/// Header marking a request as safe to repeat.
+static IDEMPOTENCY_KEY: HeaderName = HeaderName::from_static("idempotency-key");
+
+/// Which requests a `RetryPolicy` may resend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RetryMode {
+    /// Only `GET`, `HEAD`, `PUT`, `DELETE`, `OPTIONS` and `TRACE` requests, and
+    /// requests carrying an `Idempotency-Key` header.
+    #[default]
+    IdempotentOnly,
+    /// Every request, including non-idempotent ones.
+    All,
+}
+
+/// When and how often failed requests are resent.
+///
+/// Streamed uploads are never retried, as their body is not kept.
+///
+/// # Example
+///
+/// ```no_run
+/// use native_svc::HyperHttpConnection;
+/// use native_svc::retry::RetryPolicy;
+/// use std::time::Duration;
+///
+/// let policy = RetryPolicy::new(3).backoff(Duration::from_millis(200));
+/// let conn = HyperHttpConnection::new().unwrap().with_retry(policy);
+/// ```
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    max_retries: usize,
+    backoff: Duration,
+    mode: RetryMode,
+}
+
+impl RetryPolicy {
+    /// Retries idempotent requests up to `max_retries` times, waiting 100 ms first.
+    pub fn new(max_retries: usize) -> Self {
+        Self {
+            max_retries,
+            backoff: Duration::from_millis(100),
+            mode: RetryMode::default(),
+        }
+    }
+
+    /// Sets the delay before the first retry; each later retry waits twice as long.
+    pub fn backoff(mut self, delay: Duration) -> Self {
+        self.backoff = delay;
+        self
+    }
+
+    /// Sets which requests may be retried.
+    pub fn mode(mut self, mode: RetryMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Returns the maximum number of retries.
+    pub fn max_retries(&self) -> usize {
+        self.max_retries
+    }
+
+    /// Returns `true` if `request` may be resent.
+    pub fn allows<B>(&self, request: &Request<B>) -> bool {
+        match self.mode {
+            RetryMode::All => true,
+            RetryMode::IdempotentOnly => {
+                is_idempotent(request.method()) || request.headers().contains_key(&IDEMPOTENCY_KEY)
+            }
+        }
+    }
+
+    /// Returns the delay before retry number `retry`, counting from zero.
+    pub fn delay(&self, retry: usize) -> Duration {
+        let factor = 1u32.checked_shl(retry as u32).unwrap_or(u32::MAX);
+        self.backoff.saturating_mul(factor)
+    }
+
+    /// Returns `true` if a response with `status` should be retried.
+    pub(crate) fn is_retryable_status(status: StatusCode) -> bool {
+        matches!(
+            status,
+            StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE | StatusCode::GATEWAY_TIMEOUT
+        )
+    }
+}
+
+/// Returns `true` for methods defined as idempotent by RFC 9110.
+fn is_idempotent(method: &Method) -> bool {
+    matches!(
+        *method,
+        Method::GET | Method::HEAD | Method::PUT | Method::DELETE | Method::OPTIONS | Method::TRACE
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests which requests each mode retries and the backoff delays.
+    #[test]
+    fn test_retry_policy() {
+        let request = |method, key: Option<&str>| {
+            let mut builder = Request::builder().method(method).uri("http://example.com");
+            if let Some(key) = key {
+                builder = builder.header("Idempotency-Key", key);
+            }
+            builder.body(()).unwrap()
+        };
+
+        let policy = RetryPolicy::new(3);
+        assert!(policy.allows(&request(Method::GET, None)));
+        assert!(policy.allows(&request(Method::PUT, None)));
+        assert!(!policy.allows(&request(Method::POST, None)));
+        assert!(!policy.allows(&request(Method::PATCH, None)));
+        assert!(policy.allows(&request(Method::POST, Some("order-42"))));
+        assert!(
+            policy
+                .clone()
+                .mode(RetryMode::All)
+                .allows(&request(Method::POST, None))
+        );
+
+        let policy = policy.backoff(Duration::from_millis(50));
+        assert_eq!(policy.delay(0), Duration::from_millis(50));
+        assert_eq!(policy.delay(2), Duration::from_millis(200));
+    }
+}
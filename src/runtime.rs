@@ -0,0 +1,222 @@
+//! The Tokio runtime owned by each `HyperHttpConnection`.
+//!
+//! A `RuntimeConfig` sets the number, names and stack size of the runtime threads.
+//! With the default `rt-multi-thread` feature the runtime is multi-threaded; without
+//! it, a current-thread runtime is driven by the thread blocking on the connection,
+//! so requests only make progress during blocking calls.
+//! Dropping a Tokio runtime waits for its blocking tasks, such as DNS lookups, which
+//! can stall the thread dropping the connection. A `Shutdown` policy bounds that wait
+//! or moves it to the background.
+
+use crate::error::HyperError;
+use std::io;
+use std::ops::Deref;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
+use tokio::runtime::{Builder, Runtime};
+
+/// Settings of the runtime threads.
+///
+/// # Example
+///
+/// ```no_run
+/// use native_svc::HyperHttpConnection;
+/// use native_svc::runtime::RuntimeConfig;
+///
+/// let runtime = RuntimeConfig::new().worker_threads(2).thread_name_prefix("http");
+/// let conn = HyperHttpConnection::with_runtime_config(&runtime).unwrap();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct RuntimeConfig {
+    worker_threads: Option<usize>,
+    thread_name_prefix: Option<String>,
+    thread_stack_size: Option<usize>,
+}
+
+impl RuntimeConfig {
+    /// Creates a configuration with Tokio's defaults: one worker per CPU core, or the
+    /// calling thread for the current-thread runtime.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the number of worker threads; ignored by the current-thread runtime.
+    pub fn worker_threads(mut self, count: usize) -> Self {
+        self.worker_threads = Some(count);
+        self
+    }
+
+    /// Names threads `{prefix}-{index}` instead of `tokio-runtime-worker`.
+    ///
+    /// Thread names are truncated to 15 bytes on Linux.
+    pub fn thread_name_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.thread_name_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Sets the stack size of the runtime threads in bytes.
+    ///
+    /// The current-thread runtime only spawns threads for blocking tasks, such as DNS
+    /// lookups, which the name prefix and stack size then apply to.
+    pub fn thread_stack_size(mut self, size: usize) -> Self {
+        self.thread_stack_size = Some(size);
+        self
+    }
+
+    /// Builds a runtime of the flavor selected by the crate features, with I/O and
+    /// timers enabled.
+    pub(crate) fn build(&self) -> io::Result<Runtime> {
+        #[cfg(feature = "rt-multi-thread")]
+        let mut builder = Builder::new_multi_thread();
+        #[cfg(not(feature = "rt-multi-thread"))]
+        let mut builder = Builder::new_current_thread();
+        builder.enable_all();
+        if let Some(count) = self.worker_threads {
+            builder.worker_threads(count);
+        }
+        if let Some(prefix) = self.thread_name_prefix.clone() {
+            let index = Arc::new(AtomicUsize::new(0));
+            builder.thread_name_fn(move || {
+                format!("{prefix}-{}", index.fetch_add(1, Ordering::Relaxed))
+            });
+        }
+        if let Some(size) = self.thread_stack_size {
+            builder.thread_stack_size(size);
+        }
+        builder.build()
+    }
+}
+
+/// How the runtime is shut down when the connection is dropped or closed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Shutdown {
+    /// Waits for all blocking tasks to finish.
+    #[default]
+    Wait,
+    /// Waits at most the given duration for blocking tasks to finish.
+    Timeout(Duration),
+    /// Returns immediately, letting blocking tasks finish in the background.
+    Background,
+}
+
+/// A runtime shut down following a `Shutdown` policy.
+pub(crate) struct ManagedRuntime {
+    /// The runtime, shared strongly by the connections of a pool only, so shutting it
+    /// down never waits for its other users.
+    rt: Option<Arc<Runtime>>,
+    shutdown: Shutdown,
+}
+
+impl ManagedRuntime {
+    /// Wraps `rt`, waiting for its tasks on shutdown.
+    pub(crate) fn new(rt: Runtime) -> Self {
+        Self {
+            rt: Some(Arc::new(rt)),
+            shutdown: Shutdown::default(),
+        }
+    }
+
+    /// Returns another owner of the runtime, which is shut down with the last one.
+    pub(crate) fn share(&self) -> Self {
+        Self {
+            rt: self.rt.clone(),
+            shutdown: self.shutdown,
+        }
+    }
+
+    /// Returns a reference to the runtime that does not keep it alive.
+    pub(crate) fn downgrade(&self) -> Weak<Runtime> {
+        self.rt.as_ref().map_or_else(Weak::new, Arc::downgrade)
+    }
+
+    /// Sets how the runtime is shut down.
+    pub(crate) fn set_shutdown(&mut self, shutdown: Shutdown) {
+        self.shutdown = shutdown;
+    }
+
+    /// Shuts the runtime down, failing if tasks were still running after the timeout.
+    pub(crate) fn close(&mut self) -> Result<(), HyperError> {
+        let Some(rt) = self.rt.take() else {
+            return Ok(());
+        };
+        // Other connections of the pool still use the runtime.
+        let Some(rt) = Arc::into_inner(rt) else {
+            return Ok(());
+        };
+        match self.shutdown {
+            Shutdown::Wait => drop(rt),
+            Shutdown::Background => rt.shutdown_background(),
+            Shutdown::Timeout(timeout) => {
+                // `shutdown_timeout` returns early once every task is done.
+                let start = Instant::now();
+                rt.shutdown_timeout(timeout);
+                if start.elapsed() >= timeout {
+                    return Err(HyperError::ShutdownTimeout(timeout));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Deref for ManagedRuntime {
+    type Target = Runtime;
+
+    /// Returns the runtime.
+    fn deref(&self) -> &Runtime {
+        self.rt.as_deref().expect("runtime used after close")
+    }
+}
+
+impl Drop for ManagedRuntime {
+    /// Shuts the runtime down, ignoring timeouts.
+    fn drop(&mut self) {
+        let _ = self.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    /// Tests the worker count and thread names of a configured runtime.
+    #[cfg(feature = "rt-multi-thread")]
+    #[test]
+    fn test_runtime_config() {
+        let rt = RuntimeConfig::new()
+            .worker_threads(2)
+            .thread_name_prefix("svc")
+            .build()
+            .unwrap();
+        assert_eq!(rt.metrics().num_workers(), 2);
+        let name = rt
+            .block_on(rt.spawn(async { thread::current().name().map(str::to_owned) }))
+            .unwrap();
+        assert!(name.unwrap().starts_with("svc-"));
+    }
+
+    /// Tests that the current-thread runtime runs tasks on the blocking thread.
+    #[cfg(not(feature = "rt-multi-thread"))]
+    #[test]
+    fn test_current_thread_runtime() {
+        let rt = RuntimeConfig::new().worker_threads(2).build().unwrap();
+        assert_eq!(rt.metrics().num_workers(), 1);
+        let id = rt.block_on(async { thread::current().id() });
+        assert_eq!(id, thread::current().id());
+    }
+
+    /// Tests that a timed shutdown reports blocking tasks still running.
+    #[test]
+    fn test_shutdown_timeout() {
+        let mut rt = ManagedRuntime::new(RuntimeConfig::new().build().unwrap());
+        rt.set_shutdown(Shutdown::Timeout(Duration::from_millis(10)));
+        rt.spawn_blocking(|| thread::sleep(Duration::from_millis(500)));
+        assert!(matches!(rt.close(), Err(HyperError::ShutdownTimeout(_))));
+
+        let mut rt = ManagedRuntime::new(RuntimeConfig::new().build().unwrap());
+        rt.set_shutdown(Shutdown::Timeout(Duration::from_secs(5)));
+        assert!(rt.close().is_ok());
+    }
+}
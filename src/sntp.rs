@@ -0,0 +1,299 @@
+//! SNTP time synchronization mirroring `esp-idf-svc`'s `sntp` module.
+//!
+//! `NativeSntp` polls NTP servers on a background thread, like `EspSntp`, and reports
+//! the sync status and synchronized time through a callback. Setting the host clock
+//! needs privileges, so instead of adjusting it the service tracks the offset of the
+//! system clock from the servers and `now` returns the corrected time.
+
+use crate::error::SntpError;
+use std::io;
+use std::net::{ToSocketAddrs, UdpSocket};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Default NTP servers, as configured by ESP-IDF.
+pub const DEFAULT_SERVERS: [&str; 4] = [
+    "0.pool.ntp.org",
+    "1.pool.ntp.org",
+    "2.pool.ntp.org",
+    "3.pool.ntp.org",
+];
+
+/// NTP server port, used when a server has none.
+const NTP_PORT: u16 = 123;
+
+/// Seconds from the NTP epoch (1900) to the Unix epoch.
+const NTP_UNIX_OFFSET: f64 = 2_208_988_800.0;
+
+/// Longest delay before retrying after every server failed.
+const RETRY_DELAY: Duration = Duration::from_secs(15);
+
+/// Synchronization status, as reported by `EspSntp::get_sync_status`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SyncStatus {
+    /// No server has answered yet.
+    #[default]
+    Reset,
+    /// A server is being queried for the first time.
+    InProgress,
+    /// The time has been synchronized.
+    Completed,
+}
+
+/// SNTP configuration.
+#[derive(Debug, Clone)]
+pub struct SntpConf {
+    /// Servers tried in order, as `host` or `host:port`.
+    pub servers: Vec<String>,
+    /// Delay between synchronizations.
+    pub poll_interval: Duration,
+    /// How long to wait for a server to answer.
+    pub timeout: Duration,
+}
+
+impl Default for SntpConf {
+    /// Uses the default servers polled every hour, like ESP-IDF.
+    fn default() -> Self {
+        Self {
+            servers: DEFAULT_SERVERS.map(str::to_owned).to_vec(),
+            poll_interval: Duration::from_secs(3600),
+            timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Synchronization state shared with the polling thread.
+#[derive(Default)]
+struct State {
+    status: SyncStatus,
+    /// Seconds to add to the system clock to get server time.
+    offset: Option<f64>,
+}
+
+/// An SNTP client polling servers on a background thread.
+///
+/// Dropping the client stops polling.
+///
+/// # Example
+///
+/// ```no_run
+/// use native_svc::sntp::{NativeSntp, SyncStatus};
+///
+/// let sntp = NativeSntp::new_with_callback(&Default::default(), |time| {
+///     println!("synchronized: {} s since epoch", time.as_secs());
+/// })
+/// .unwrap();
+/// while sntp.get_sync_status() != SyncStatus::Completed {
+///     std::thread::sleep(std::time::Duration::from_millis(100));
+/// }
+/// println!("{:?}", sntp.now());
+/// ```
+pub struct NativeSntp {
+    state: Arc<Mutex<State>>,
+    _stop: Sender<()>,
+}
+
+impl NativeSntp {
+    /// Starts polling the default servers.
+    pub fn new_default() -> Result<Self, SntpError> {
+        Self::new(&SntpConf::default())
+    }
+
+    /// Starts polling as configured by `conf`.
+    pub fn new(conf: &SntpConf) -> Result<Self, SntpError> {
+        Self::new_with_callback(conf, |_| {})
+    }
+
+    /// Starts polling, passing the synchronized time since the Unix epoch to `callback`
+    /// after every synchronization.
+    pub fn new_with_callback<F>(conf: &SntpConf, mut callback: F) -> Result<Self, SntpError>
+    where
+        F: FnMut(Duration) + Send + 'static,
+    {
+        let state = Arc::new(Mutex::new(State::default()));
+        let (stop, stopped) = mpsc::channel::<()>();
+
+        let conf = conf.clone();
+        let shared = state.clone();
+        thread::Builder::new()
+            .name("native-svc-sntp".to_owned())
+            .spawn(move || {
+                loop {
+                    let offset = {
+                        let mut state = shared.lock().unwrap_or_else(|e| e.into_inner());
+                        if state.status == SyncStatus::Reset {
+                            state.status = SyncStatus::InProgress;
+                        }
+                        drop(state);
+                        conf.servers
+                            .iter()
+                            .find_map(|server| query(server, conf.timeout).ok())
+                    };
+
+                    let delay = match offset {
+                        Some(offset) => {
+                            let mut state = shared.lock().unwrap_or_else(|e| e.into_inner());
+                            state.status = SyncStatus::Completed;
+                            state.offset = Some(offset);
+                            drop(state);
+                            callback(
+                                corrected(offset)
+                                    .duration_since(UNIX_EPOCH)
+                                    .unwrap_or_default(),
+                            );
+                            conf.poll_interval
+                        }
+                        None => conf.poll_interval.min(RETRY_DELAY),
+                    };
+
+                    match stopped.recv_timeout(delay) {
+                        Err(RecvTimeoutError::Timeout) => continue,
+                        _ => break,
+                    }
+                }
+            })?;
+
+        Ok(Self { state, _stop: stop })
+    }
+
+    /// Returns the synchronization status.
+    pub fn get_sync_status(&self) -> SyncStatus {
+        self.lock().status
+    }
+
+    /// Returns the seconds to add to the system clock to get server time, once synchronized.
+    pub fn offset(&self) -> Option<f64> {
+        self.lock().offset
+    }
+
+    /// Returns the current time, corrected by the offset once synchronized.
+    pub fn now(&self) -> SystemTime {
+        corrected(self.offset().unwrap_or_default())
+    }
+
+    /// Locks the state, ignoring poisoning by a panicking callback.
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Returns the system time shifted by `offset` seconds.
+fn corrected(offset: f64) -> SystemTime {
+    let now = SystemTime::now();
+    let shift = Duration::from_secs_f64(offset.abs());
+    if offset >= 0.0 {
+        now + shift
+    } else {
+        now - shift
+    }
+}
+
+/// Queries `server` and returns the offset of the system clock in seconds.
+fn query(server: &str, timeout: Duration) -> io::Result<f64> {
+    let addr = server
+        .to_socket_addrs()
+        .or_else(|_| (server, NTP_PORT).to_socket_addrs())?
+        .next()
+        .ok_or(io::ErrorKind::NotFound)?;
+    let socket = UdpSocket::bind(if addr.is_ipv4() {
+        "0.0.0.0:0"
+    } else {
+        "[::]:0"
+    })?;
+    socket.set_read_timeout(Some(timeout))?;
+    socket.connect(addr)?;
+
+    // Version 4, client mode; the transmit time is echoed back as the origin time.
+    let mut request = [0; 48];
+    request[0] = 0x23;
+    let sent = unix_now();
+    request[40..48].copy_from_slice(&to_ntp(sent));
+    socket.send(&request)?;
+
+    let mut response = [0; 48];
+    loop {
+        let len = socket.recv(&mut response)?;
+        let received = unix_now();
+        if len == 48 && response[24..32] == request[40..48] {
+            return parse_response(&response, sent, received);
+        }
+    }
+}
+
+/// Computes the clock offset from a server response.
+fn parse_response(response: &[u8; 48], sent: f64, received: f64) -> io::Result<f64> {
+    // Server mode with a non-zero stratum; stratum 0 is a kiss-of-death reply.
+    if response[0] & 0x07 != 4 || response[1] == 0 {
+        return Err(io::ErrorKind::InvalidData.into());
+    }
+    let server_received = from_ntp(&response[32..40]);
+    let server_sent = from_ntp(&response[40..48]);
+    Ok(((server_received - sent) + (server_sent - received)) / 2.0)
+}
+
+/// Returns the system time in seconds since the Unix epoch.
+fn unix_now() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64()
+}
+
+/// Encodes Unix seconds as an NTP timestamp.
+fn to_ntp(unix: f64) -> [u8; 8] {
+    let ntp = unix + NTP_UNIX_OFFSET;
+    let seconds = ntp.trunc() as u32;
+    let fraction = (ntp.fract() * 4_294_967_296.0) as u32;
+    let mut bytes = [0; 8];
+    bytes[..4].copy_from_slice(&seconds.to_be_bytes());
+    bytes[4..].copy_from_slice(&fraction.to_be_bytes());
+    bytes
+}
+
+/// Decodes an NTP timestamp to Unix seconds.
+fn from_ntp(bytes: &[u8]) -> f64 {
+    let seconds = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    let fraction = u32::from_be_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+    f64::from(seconds) + f64::from(fraction) / 4_294_967_296.0 - NTP_UNIX_OFFSET
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests synchronizing against a local server running 100 seconds ahead.
+    #[test]
+    fn test_sync_with_offset() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap();
+        thread::spawn(move || {
+            let mut request = [0; 48];
+            while let Ok((_, client)) = server.recv_from(&mut request) {
+                let mut response = [0; 48];
+                response[0] = 0x24;
+                response[1] = 1;
+                response[24..32].copy_from_slice(&request[40..48]);
+                let time = to_ntp(unix_now() + 100.0);
+                response[32..40].copy_from_slice(&time);
+                response[40..48].copy_from_slice(&time);
+                server.send_to(&response, client).unwrap();
+            }
+        });
+
+        let (tx, rx) = mpsc::channel();
+        let conf = SntpConf {
+            servers: vec!["127.0.0.1:1".to_owned(), addr.to_string()],
+            timeout: Duration::from_millis(200),
+            ..Default::default()
+        };
+        let sntp =
+            NativeSntp::new_with_callback(&conf, move |time| tx.send(time).unwrap()).unwrap();
+
+        let time = rx.recv().unwrap().as_secs_f64();
+        assert!((time - unix_now() - 100.0).abs() < 1.0);
+        assert_eq!(sntp.get_sync_status(), SyncStatus::Completed);
+        assert!((sntp.offset().unwrap() - 100.0).abs() < 1.0);
+    }
+}
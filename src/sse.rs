@@ -0,0 +1,201 @@
+//! Server-Sent Events support for `embedded_svc` HTTP server connections.
+//!
+//! Provides an `EventStreamResponse` that turns any `embedded_svc::http::server::Request`
+//! into a `text/event-stream` response, handling the mandatory headers, event framing,
+//! keep-alive comments, and flushing after every event.
+
+use embedded_svc::http::server::{Connection, Request, Response};
+use embedded_svc::io::Write;
+use std::fmt::Write as _;
+use std::time::{Duration, Instant};
+
+/// Default interval after which an idle stream emits a keep-alive comment.
+const DEFAULT_KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+/// Headers sent with every event stream response.
+const EVENT_STREAM_HEADERS: &[(&str, &str)] = &[
+    ("Content-Type", "text/event-stream"),
+    ("Cache-Control", "no-cache"),
+    ("Connection", "keep-alive"),
+];
+
+/// A single Server-Sent Event.
+///
+/// Only `data` is mandatory; the other fields are emitted when set.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Event<'a> {
+    /// Event identifier, reported back by clients in `Last-Event-ID`.
+    pub id: Option<&'a str>,
+    /// Event type; clients dispatch on it instead of the generic `message`.
+    pub event: Option<&'a str>,
+    /// Event payload, split into one `data:` line per input line.
+    pub data: &'a str,
+    /// Reconnection delay advertised to the client.
+    pub retry: Option<Duration>,
+}
+
+impl<'a> Event<'a> {
+    /// Creates an unnamed event carrying `data`.
+    pub fn new(data: &'a str) -> Self {
+        Self {
+            data,
+            ..Default::default()
+        }
+    }
+
+    /// Sets the event identifier.
+    pub fn id(mut self, id: &'a str) -> Self {
+        self.id = Some(id);
+        self
+    }
+
+    /// Sets the event type.
+    pub fn event(mut self, event: &'a str) -> Self {
+        self.event = Some(event);
+        self
+    }
+
+    /// Sets the reconnection delay advertised to the client.
+    pub fn retry(mut self, retry: Duration) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+
+    /// Appends the wire representation of this event, including the
+    /// terminating blank line, to `out`.
+    pub fn encode(&self, out: &mut String) {
+        if let Some(id) = self.id {
+            let _ = writeln!(out, "id: {id}");
+        }
+        if let Some(event) = self.event {
+            let _ = writeln!(out, "event: {event}");
+        }
+        if let Some(retry) = self.retry {
+            let _ = writeln!(out, "retry: {}", retry.as_millis());
+        }
+        for line in self.data.split('\n') {
+            let _ = writeln!(out, "data: {}", line.strip_suffix('\r').unwrap_or(line));
+        }
+        out.push('\n');
+    }
+}
+
+/// A streaming `text/event-stream` response.
+///
+/// # Example
+///
+/// ```no_run
+/// use embedded_svc::http::server::{Connection, Request};
+/// use native_svc::sse::{Event, EventStreamResponse};
+///
+/// fn handle<C: Connection>(request: Request<C>) -> Result<(), C::Error> {
+///     let mut stream = EventStreamResponse::new(request)?;
+///     stream.send(&Event::new("hello").event("greeting"))?;
+///     Ok(())
+/// }
+/// ```
+pub struct EventStreamResponse<C>
+where
+    C: Connection,
+{
+    response: Response<C>,
+    keep_alive: Duration,
+    last_write: Instant,
+    scratch: String,
+}
+
+impl<C> EventStreamResponse<C>
+where
+    C: Connection,
+{
+    /// Sends the `200 OK` event stream headers and returns the stream.
+    pub fn new(request: Request<C>) -> Result<Self, C::Error> {
+        Self::with_headers(request, &[])
+    }
+
+    /// Sends the event stream headers followed by `headers`, and returns the stream.
+    pub fn with_headers(request: Request<C>, headers: &[(&str, &str)]) -> Result<Self, C::Error> {
+        let mut all_headers = Vec::with_capacity(EVENT_STREAM_HEADERS.len() + headers.len());
+        all_headers.extend_from_slice(EVENT_STREAM_HEADERS);
+        all_headers.extend_from_slice(headers);
+
+        let response = request.into_response(200, Some("OK"), &all_headers)?;
+
+        Ok(Self {
+            response,
+            keep_alive: DEFAULT_KEEP_ALIVE,
+            last_write: Instant::now(),
+            scratch: String::new(),
+        })
+    }
+
+    /// Sets the idle interval after which `keep_alive` emits a comment.
+    pub fn set_keep_alive(&mut self, interval: Duration) {
+        self.keep_alive = interval;
+    }
+
+    /// Writes and flushes a single event.
+    pub fn send(&mut self, event: &Event<'_>) -> Result<(), C::Error> {
+        self.scratch.clear();
+        event.encode(&mut self.scratch);
+        let scratch = std::mem::take(&mut self.scratch);
+        let result = self.write_flushed(scratch.as_bytes());
+        self.scratch = scratch;
+        result
+    }
+
+    /// Writes and flushes a comment line, ignored by clients.
+    pub fn comment(&mut self, text: &str) -> Result<(), C::Error> {
+        let mut line = String::with_capacity(text.len() + 3);
+        for part in text.split('\n') {
+            let _ = writeln!(line, ":{part}");
+        }
+        line.push('\n');
+        self.write_flushed(line.as_bytes())
+    }
+
+    /// Emits a keep-alive comment if nothing was written during the configured interval.
+    ///
+    /// Returns `true` if a comment was sent.
+    pub fn keep_alive(&mut self) -> Result<bool, C::Error> {
+        if self.last_write.elapsed() < self.keep_alive {
+            return Ok(false);
+        }
+        self.write_flushed(b":\n\n")?;
+        Ok(true)
+    }
+
+    /// Consumes the stream, returning the underlying response.
+    pub fn into_inner(self) -> Response<C> {
+        self.response
+    }
+
+    /// Writes `data` in full and flushes it to the client.
+    fn write_flushed(&mut self, data: &[u8]) -> Result<(), C::Error> {
+        self.response.write_all(data)?;
+        self.response.flush()?;
+        self.last_write = Instant::now();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests the wire encoding of a fully populated multi-line event.
+    #[test]
+    fn test_event_encoding() {
+        let mut out = String::new();
+        Event::new("first\nsecond")
+            .id("7")
+            .event("update")
+            .retry(Duration::from_secs(3))
+            .encode(&mut out);
+
+        assert_eq!(
+            out,
+            "id: 7\nevent: update\nretry: 3000\ndata: first\ndata: second\n\n"
+        );
+    }
+}
@@ -0,0 +1,17 @@
+//! States of the request/response cycle of a connection.
+//!
+//! The `embedded-svc` `Connection` trait implies a state machine: a request is
+//! initiated, its body written, and its response received and read. With strict
+//! state checking enabled, `HyperHttpConnection` reports calls made in the wrong
+//! state as `HyperError::InvalidState` instead of ignoring them.
+
+/// Where a connection is in the request/response cycle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    /// No request is initiated and no response is held.
+    Idle,
+    /// A request is initiated and its body can be written.
+    Request,
+    /// A response is received and its body can be read.
+    Response,
+}
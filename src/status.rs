@@ -0,0 +1,122 @@
+//! Policies turning error responses into errors.
+//!
+//! With a `StatusPolicy` set, `HyperHttpConnection` fails `initiate_response` with
+//! `HyperError::Status` when the response status matches the policy, instead of
+//! handing the error page to code that forgot to check the status. The error keeps
+//! the status, the headers and the start of the body for diagnostics.
+
+use crate::error::StatusError;
+use http_body_util::BodyExt;
+use hyper::body::{Bytes, Incoming};
+use hyper::{Response, StatusCode};
+use std::fmt;
+use tokio::runtime::Runtime;
+
+/// Default number of body bytes kept in a `StatusError`.
+pub const DEFAULT_CAPTURE_LIMIT: usize = 4096;
+
+/// Which responses are turned into `HyperError::Status` errors.
+///
+/// # Example
+///
+/// ```no_run
+/// use native_svc::HyperHttpConnection;
+/// use native_svc::status::StatusPolicy;
+///
+/// // Fail on server errors only; 4xx responses are handled by the caller.
+/// let policy = StatusPolicy::matching(|status| status.is_server_error());
+/// let conn = HyperHttpConnection::new().unwrap().with_status_policy(policy);
+/// ```
+pub struct StatusPolicy {
+    predicate: Box<dyn Fn(StatusCode) -> bool + Send + Sync>,
+    capture_limit: usize,
+}
+
+impl StatusPolicy {
+    /// Fails on client (`4xx`) and server (`5xx`) error statuses.
+    pub fn new() -> Self {
+        Self::matching(|status| status.is_client_error() || status.is_server_error())
+    }
+
+    /// Fails on statuses for which `predicate` returns `true`.
+    pub fn matching<F>(predicate: F) -> Self
+    where
+        F: Fn(StatusCode) -> bool + Send + Sync + 'static,
+    {
+        Self {
+            predicate: Box::new(predicate),
+            capture_limit: DEFAULT_CAPTURE_LIMIT,
+        }
+    }
+
+    /// Sets how many body bytes are kept in errors, `DEFAULT_CAPTURE_LIMIT` by default.
+    pub fn capture_limit(mut self, limit: usize) -> Self {
+        self.capture_limit = limit;
+        self
+    }
+
+    /// Returns `true` if responses with `status` are turned into errors.
+    pub fn matches(&self, status: StatusCode) -> bool {
+        (self.predicate)(status)
+    }
+
+    /// Consumes `response` into an error, reading at most the capture limit of its body.
+    pub(crate) fn capture(&self, rt: &Runtime, mut response: Response<Incoming>) -> StatusError {
+        let mut body = Vec::new();
+        let mut truncated = false;
+        while let Some(Ok(frame)) = rt.block_on(response.body_mut().frame()) {
+            let Ok(data) = frame.into_data() else {
+                continue;
+            };
+            let room = self.capture_limit - body.len();
+            if data.len() > room {
+                body.extend_from_slice(&data[..room]);
+                truncated = true;
+                break;
+            }
+            body.extend_from_slice(&data);
+        }
+
+        StatusError {
+            status: response.status(),
+            headers: response.headers().clone(),
+            body: Bytes::from(body),
+            truncated,
+        }
+    }
+}
+
+impl Default for StatusPolicy {
+    /// Fails on client and server error statuses.
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for StatusPolicy {
+    /// Formats the policy without its predicate.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StatusPolicy")
+            .field("capture_limit", &self.capture_limit)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests the default and custom status predicates.
+    #[test]
+    fn test_status_predicates() {
+        let policy = StatusPolicy::default();
+        assert!(policy.matches(StatusCode::NOT_FOUND));
+        assert!(policy.matches(StatusCode::BAD_GATEWAY));
+        assert!(!policy.matches(StatusCode::OK));
+        assert!(!policy.matches(StatusCode::NOT_MODIFIED));
+
+        let policy = StatusPolicy::matching(|status| status == StatusCode::CONFLICT);
+        assert!(policy.matches(StatusCode::CONFLICT));
+        assert!(!policy.matches(StatusCode::INTERNAL_SERVER_ERROR));
+    }
+}
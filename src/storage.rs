@@ -0,0 +1,321 @@
+//! Persistent key/value storage implementing `embedded_svc::storage`.
+//!
+//! This module provides `FileStorage`, a `RawStorage` that keeps every key in its own
+//! file inside a directory, so code written against NVS on ESP targets can persist
+//! key/value data when running natively. Key names follow NVS rules and are limited
+//! to `MAX_KEY_LEN` bytes. Typed values are stored through `StorageImpl` with a
+//! serializer from `codec`. When transactions are needed, `database::DatabaseStorage`
+//! (feature `storage-redb`) keeps all keys in one `redb` file instead, and
+//! `memory::MemoryStorage` keeps them in memory for unit tests. As with NVS, keys can
+//! be grouped in namespaces that are isolated from each other and erased as a unit.
+//!
+//! `FileStorage` writes are crash-safe: each value is checksummed and replaced through
+//! a synced temporary file, and the previous value is kept to recover from damage.
+//! Any backend can be wrapped in `encrypted::EncryptedStorage` (feature
+//! `storage-encryption`) to keep credentials encrypted at rest.
+
+#[cfg(any(feature = "storage-postcard", feature = "storage-json"))]
+pub mod codec;
+#[cfg(feature = "storage-redb")]
+pub mod database;
+#[cfg(feature = "storage-encryption")]
+pub mod encrypted;
+pub mod memory;
+mod record;
+
+use crate::error::StorageError;
+use embedded_svc::storage::{RawStorage, StorageBase};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+pub use embedded_svc::storage::{Storage, StorageImpl};
+
+/// Maximum key length in bytes, matching NVS.
+pub const MAX_KEY_LEN: usize = 15;
+
+/// Checks that `name` is a valid NVS key.
+pub(crate) fn validate_key(name: &str) -> Result<(), StorageError> {
+    if name.is_empty() || name.len() > MAX_KEY_LEN {
+        return Err(StorageError::InvalidKey(name.to_owned()));
+    }
+    Ok(())
+}
+
+/// Copies `value` into `buf`, failing if it does not fit.
+pub(crate) fn copy_value<'a>(value: &[u8], buf: &'a mut [u8]) -> Result<&'a [u8], StorageError> {
+    let target = buf
+        .get_mut(..value.len())
+        .ok_or(StorageError::BufferTooSmall(value.len()))?;
+    target.copy_from_slice(value);
+    Ok(target)
+}
+
+/// A `RawStorage` keeping one file per key in a directory.
+///
+/// # Example
+///
+/// ```no_run
+/// use embedded_svc::storage::RawStorage;
+/// use native_svc::storage::FileStorage;
+///
+/// let mut storage = FileStorage::open("/var/lib/device/nvs").unwrap();
+/// storage.set_raw("boot_count", &1u32.to_le_bytes()).unwrap();
+///
+/// let mut buf = [0; 4];
+/// let value = storage.get_raw("boot_count", &mut buf).unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct FileStorage {
+    dir: PathBuf,
+}
+
+impl FileStorage {
+    /// Opens the storage in `dir`, creating the directory if needed.
+    pub fn open(dir: impl Into<PathBuf>) -> Result<Self, StorageError> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    /// Returns the directory holding the stored values.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Opens the namespace `namespace`, isolated from the keys of this storage.
+    ///
+    /// Like NVS namespaces, names follow the key rules, so components sharing a
+    /// storage can use the same keys without colliding.
+    pub fn open_namespace(&self, namespace: &str) -> Result<Self, StorageError> {
+        Self::open(self.namespace_dir(namespace)?)
+    }
+
+    /// Removes `namespace` and every value it holds, returning `true` if it existed.
+    pub fn erase_namespace(&self, namespace: &str) -> Result<bool, StorageError> {
+        match fs::remove_dir_all(self.namespace_dir(namespace)?) {
+            Ok(()) => Ok(true),
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(false),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Removes every value of this storage, keeping nested namespaces.
+    pub fn erase_all(&mut self) -> Result<(), StorageError> {
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            if entry.file_type()?.is_file() {
+                fs::remove_file(entry.path())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads the value stored under `name`, recovering the previous one if it is damaged.
+    ///
+    /// A value whose file fails its checksum is replaced by the backup kept from the
+    /// previous write; if both are damaged, `StorageError::Corrupted` is returned.
+    fn read(&self, name: &str) -> Result<Option<Vec<u8>>, StorageError> {
+        let path = self.path(name)?;
+        let current = read_if_exists(&path)?;
+        if let Some(value) = current.as_deref().and_then(record::decode) {
+            return Ok(Some(value.to_vec()));
+        }
+
+        let backup = path.with_extension("bak");
+        match read_if_exists(&backup)? {
+            Some(data) => match record::decode(&data) {
+                Some(value) => {
+                    record::write_atomic(&path, &data, None)?;
+                    Ok(Some(value.to_vec()))
+                }
+                None => Err(StorageError::Corrupted(name.to_owned())),
+            },
+            None if current.is_some() => Err(StorageError::Corrupted(name.to_owned())),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the file holding `name`.
+    fn path(&self, name: &str) -> Result<PathBuf, StorageError> {
+        validate_key(name)?;
+        Ok(self.dir.join(escape(name)))
+    }
+
+    /// Returns the directory holding `namespace`.
+    ///
+    /// The `.ns` suffix cannot appear in an escaped key, so namespaces never clash
+    /// with values.
+    fn namespace_dir(&self, namespace: &str) -> Result<PathBuf, StorageError> {
+        validate_key(namespace)?;
+        Ok(self.dir.join(format!("{}.ns", escape(namespace))))
+    }
+}
+
+/// Escapes characters unsafe in file names as `%XX`.
+fn escape(name: &str) -> String {
+    let mut file = String::with_capacity(name.len());
+    for byte in name.bytes() {
+        match byte {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'_' | b'-' => file.push(byte as char),
+            _ => file.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    file
+}
+
+impl StorageBase for FileStorage {
+    /// The error type returned by this storage.
+    type Error = StorageError;
+
+    /// Returns `true` if a value is stored under `name`.
+    fn contains(&self, name: &str) -> Result<bool, Self::Error> {
+        let path = self.path(name)?;
+        Ok(path.is_file() || path.with_extension("bak").is_file())
+    }
+
+    /// Removes `name`, returning `true` if it existed.
+    fn remove(&mut self, name: &str) -> Result<bool, Self::Error> {
+        let path = self.path(name)?;
+        let removed = remove_if_exists(&path)?;
+        Ok(remove_if_exists(&path.with_extension("bak"))? || removed)
+    }
+}
+
+impl RawStorage for FileStorage {
+    /// Returns the length of the value stored under `name`.
+    fn len(&self, name: &str) -> Result<Option<usize>, Self::Error> {
+        Ok(self.read(name)?.map(|value| value.len()))
+    }
+
+    /// Reads the value stored under `name` into `buf`.
+    ///
+    /// Fails with `StorageError::BufferTooSmall` if `buf` cannot hold the value.
+    fn get_raw<'a>(&self, name: &str, buf: &'a mut [u8]) -> Result<Option<&'a [u8]>, Self::Error> {
+        match self.read(name)? {
+            Some(value) => copy_value(&value, buf).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Stores `buf` under `name`, atomically replacing any previous value.
+    fn set_raw(&mut self, name: &str, buf: &[u8]) -> Result<bool, Self::Error> {
+        let path = self.path(name)?;
+        record::write_atomic(
+            &path,
+            &record::encode(buf),
+            Some(&path.with_extension("bak")),
+        )?;
+        Ok(true)
+    }
+}
+
+/// Reads `path`, returning `None` if it does not exist.
+fn read_if_exists(path: &Path) -> Result<Option<Vec<u8>>, StorageError> {
+    match fs::read(path) {
+        Ok(data) => Ok(Some(data)),
+        Err(error) if error.kind() == ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// Removes `path`, returning `true` if it existed.
+fn remove_if_exists(path: &Path) -> Result<bool, StorageError> {
+    match fs::remove_file(path) {
+        Ok(()) => Ok(true),
+        Err(error) if error.kind() == ErrorKind::NotFound => Ok(false),
+        Err(error) => Err(error.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests storing, reading, and removing a value.
+    #[test]
+    fn test_file_storage_round_trip() {
+        let dir = std::env::temp_dir().join(format!("native-svc-storage-{}", std::process::id()));
+        let mut storage = FileStorage::open(&dir).unwrap();
+
+        storage.set_raw("wifi/ssid", b"home").unwrap();
+        assert!(storage.contains("wifi/ssid").unwrap());
+        assert_eq!(storage.len("wifi/ssid").unwrap(), Some(4));
+
+        let mut buf = [0; 8];
+        assert_eq!(
+            storage.get_raw("wifi/ssid", &mut buf).unwrap(),
+            Some(&b"home"[..])
+        );
+        assert!(matches!(
+            storage.get_raw("wifi/ssid", &mut [0; 2]),
+            Err(StorageError::BufferTooSmall(4))
+        ));
+        assert!(storage.set_raw("a_key_that_is_too_long", b"").is_err());
+
+        assert!(storage.remove("wifi/ssid").unwrap());
+        assert!(!storage.remove("wifi/ssid").unwrap());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    /// Tests that a damaged value is detected and recovered from its backup.
+    #[test]
+    fn test_corruption_recovery() {
+        let dir = std::env::temp_dir().join(format!("native-svc-crc-{}", std::process::id()));
+        let mut storage = FileStorage::open(&dir).unwrap();
+
+        storage.set_raw("ssid", b"home").unwrap();
+        storage.set_raw("ssid", b"office").unwrap();
+        fs::write(dir.join("ssid"), b"NSV1garbage").unwrap();
+
+        let mut buf = [0; 8];
+        assert_eq!(
+            storage.get_raw("ssid", &mut buf).unwrap(),
+            Some(&b"home"[..])
+        );
+        assert_eq!(storage.len("ssid").unwrap(), Some(4));
+
+        fs::write(dir.join("ssid"), b"").unwrap();
+        fs::write(dir.join("ssid.bak"), b"").unwrap();
+        assert!(matches!(
+            storage.get_raw("ssid", &mut buf),
+            Err(StorageError::Corrupted(_))
+        ));
+        assert!(storage.remove("ssid").unwrap());
+        assert!(!dir.join("ssid.bak").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    /// Tests that namespaces isolate keys and can be erased independently.
+    #[test]
+    fn test_namespaces() {
+        let dir = std::env::temp_dir().join(format!("native-svc-ns-{}", std::process::id()));
+        let mut root = FileStorage::open(&dir).unwrap();
+        let mut wifi = root.open_namespace("wifi").unwrap();
+        let mut cloud = root.open_namespace("cloud").unwrap();
+
+        root.set_raw("wifi", b"root").unwrap();
+        wifi.set_raw("token", b"a").unwrap();
+        cloud.set_raw("token", b"b").unwrap();
+
+        let mut buf = [0; 4];
+        assert_eq!(wifi.get_raw("token", &mut buf).unwrap(), Some(&b"a"[..]));
+        assert_eq!(cloud.len("token").unwrap(), Some(1));
+
+        cloud.erase_all().unwrap();
+        assert!(!cloud.contains("token").unwrap());
+        assert!(root.erase_namespace("wifi").unwrap());
+        assert!(
+            !root
+                .open_namespace("wifi")
+                .unwrap()
+                .contains("token")
+                .unwrap()
+        );
+
+        root.erase_all().unwrap();
+        assert!(!root.contains("wifi").unwrap());
+        assert!(dir.join("cloud.ns").is_dir());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,134 @@
+//! Serializers for typed storage.
+//!
+//! `embedded_svc::storage::StorageImpl` layers `Storage::get`/`Storage::set` for any
+//! `Serialize`/`Deserialize` value on top of a `RawStorage`. The `SerDe` implementations
+//! here select the encoding: `PostcardSerDe` for compact binary values (feature
+//! `storage-postcard`) and `JsonSerDe` for human-readable ones (feature `storage-json`).
+
+#[cfg(feature = "storage-json")]
+use std::io::Cursor;
+
+use embedded_svc::storage::SerDe;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+/// Stores values in the compact `postcard` binary format.
+///
+/// # Example
+///
+/// ```no_run
+/// use embedded_svc::storage::{Storage, StorageImpl};
+/// use native_svc::storage::FileStorage;
+/// use native_svc::storage::codec::PostcardSerDe;
+///
+/// let raw = FileStorage::open("/var/lib/device/nvs").unwrap();
+/// let mut storage = StorageImpl::<256, _, _>::new(raw, PostcardSerDe);
+///
+/// storage.set("retries", &3u8).unwrap();
+/// let retries: Option<u8> = storage.get("retries").unwrap();
+/// ```
+#[cfg(feature = "storage-postcard")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PostcardSerDe;
+
+#[cfg(feature = "storage-postcard")]
+impl SerDe for PostcardSerDe {
+    /// The error type returned by `postcard`.
+    type Error = postcard::Error;
+
+    /// Encodes `value` into `slice`, returning the written bytes.
+    fn serialize<'a, T>(&self, slice: &'a mut [u8], value: &T) -> Result<&'a [u8], Self::Error>
+    where
+        T: Serialize,
+    {
+        postcard::to_slice(value, slice).map(|written| &*written)
+    }
+
+    /// Decodes a value from `slice`.
+    fn deserialize<T>(&self, slice: &[u8]) -> Result<T, Self::Error>
+    where
+        T: DeserializeOwned,
+    {
+        postcard::from_bytes(slice)
+    }
+}
+
+/// Stores values as JSON text.
+#[cfg(feature = "storage-json")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonSerDe;
+
+#[cfg(feature = "storage-json")]
+impl SerDe for JsonSerDe {
+    /// The error type returned by `serde_json`.
+    type Error = serde_json::Error;
+
+    /// Encodes `value` into `slice`, returning the written bytes.
+    fn serialize<'a, T>(&self, slice: &'a mut [u8], value: &T) -> Result<&'a [u8], Self::Error>
+    where
+        T: Serialize,
+    {
+        let mut cursor = Cursor::new(slice);
+        serde_json::to_writer(&mut cursor, value)?;
+        let written = cursor.position() as usize;
+        let slice = cursor.into_inner();
+        Ok(&slice[..written])
+    }
+
+    /// Decodes a value from `slice`.
+    fn deserialize<T>(&self, slice: &[u8]) -> Result<T, Self::Error>
+    where
+        T: DeserializeOwned,
+    {
+        serde_json::from_slice(slice)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::FileStorage;
+    use embedded_svc::storage::{Storage, StorageImpl};
+    use serde::Deserialize;
+
+    /// A structured configuration value.
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct WifiConfig {
+        ssid: String,
+        channel: u8,
+    }
+
+    /// Tests storing a structured value through `StorageImpl` with each enabled format.
+    #[test]
+    fn test_typed_round_trip() {
+        let dir = std::env::temp_dir().join(format!("native-svc-codec-{}", std::process::id()));
+        let config = WifiConfig {
+            ssid: "home".to_owned(),
+            channel: 6,
+        };
+
+        #[cfg(feature = "storage-postcard")]
+        {
+            let raw = FileStorage::open(dir.join("postcard")).unwrap();
+            let mut storage = StorageImpl::<64, _, _>::new(raw, PostcardSerDe);
+            storage.set("wifi", &config).unwrap();
+            assert_eq!(
+                storage.get::<WifiConfig>("wifi").unwrap(),
+                Some(config.clone())
+            );
+        }
+
+        #[cfg(feature = "storage-json")]
+        {
+            let raw = FileStorage::open(dir.join("json")).unwrap();
+            let mut storage = StorageImpl::<64, _, _>::new(raw, JsonSerDe);
+            storage.set("wifi", &config).unwrap();
+            assert_eq!(
+                storage.get::<WifiConfig>("wifi").unwrap(),
+                Some(config.clone())
+            );
+        }
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+}
@@ -0,0 +1,225 @@
+//! Transactional storage backed by an embedded `redb` database.
+//!
+//! `DatabaseStorage` keeps every key in a single `redb` file. Each mutation is its own
+//! ACID transaction and survives crashes, and `set_all` writes several keys atomically,
+//! which suits host-side persistence that needs more than one file per key. Namespaces
+//! are stored as separate tables of the same file.
+
+use crate::error::StorageError;
+use crate::storage::{copy_value, validate_key};
+use embedded_svc::storage::{RawStorage, StorageBase};
+use redb::{Database, TableDefinition, TableError};
+use std::path::Path;
+use std::sync::Arc;
+
+/// The table holding the values outside of any namespace.
+const ROOT_TABLE: &str = "values";
+
+/// A table mapping keys to values.
+type Values<'a> = TableDefinition<'a, &'static str, &'static [u8]>;
+
+/// A `RawStorage` keeping values in a `redb` database file.
+///
+/// # Example
+///
+/// ```no_run
+/// use embedded_svc::storage::RawStorage;
+/// use native_svc::storage::database::DatabaseStorage;
+///
+/// let mut storage = DatabaseStorage::open("/var/lib/device/nvs.redb").unwrap();
+/// storage
+///     .set_all(&[("ssid", b"home".as_slice()), ("pass", b"secret".as_slice())])
+///     .unwrap();
+///
+/// let mut buf = [0; 16];
+/// let ssid = storage.get_raw("ssid", &mut buf).unwrap();
+/// ```
+pub struct DatabaseStorage {
+    db: Arc<Database>,
+    table: String,
+}
+
+impl DatabaseStorage {
+    /// Opens the database at `path`, creating it if needed.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, StorageError> {
+        let db = Database::create(path).map_err(redb::Error::from)?;
+        Ok(Self::from_database(db))
+    }
+
+    /// Wraps an already opened database.
+    pub fn from_database(db: Database) -> Self {
+        Self {
+            db: Arc::new(db),
+            table: ROOT_TABLE.to_owned(),
+        }
+    }
+
+    /// Returns the underlying database.
+    pub fn database(&self) -> &Database {
+        &self.db
+    }
+
+    /// Opens the namespace `namespace`, kept in its own table of the same database.
+    pub fn open_namespace(&self, namespace: &str) -> Result<Self, StorageError> {
+        validate_key(namespace)?;
+        Ok(Self {
+            db: self.db.clone(),
+            table: format!("ns:{namespace}"),
+        })
+    }
+
+    /// Removes `namespace` and every value it holds, returning `true` if it existed.
+    pub fn erase_namespace(&self, namespace: &str) -> Result<bool, StorageError> {
+        self.open_namespace(namespace)?.delete_table()
+    }
+
+    /// Removes every value of this storage.
+    pub fn erase_all(&mut self) -> Result<(), StorageError> {
+        self.delete_table().map(|_| ())
+    }
+
+    /// Returns the table holding this storage's values.
+    fn values(&self) -> Values<'_> {
+        TableDefinition::new(&self.table)
+    }
+
+    /// Deletes the table of this storage in one transaction.
+    fn delete_table(&self) -> Result<bool, StorageError> {
+        let txn = self.db.begin_write().map_err(redb::Error::from)?;
+        let deleted = txn.delete_table(self.values()).map_err(redb::Error::from)?;
+        txn.commit().map_err(redb::Error::from)?;
+        Ok(deleted)
+    }
+
+    /// Stores every entry in a single transaction, so either all or none are written.
+    pub fn set_all(&mut self, entries: &[(&str, &[u8])]) -> Result<(), StorageError> {
+        for (name, _) in entries {
+            validate_key(name)?;
+        }
+
+        let txn = self.db.begin_write().map_err(redb::Error::from)?;
+        {
+            let mut table = txn.open_table(self.values()).map_err(redb::Error::from)?;
+            for (name, value) in entries {
+                table.insert(*name, *value).map_err(redb::Error::from)?;
+            }
+        }
+        txn.commit().map_err(redb::Error::from)?;
+        Ok(())
+    }
+
+    /// Runs `read` on the value stored under `name`, if any.
+    fn with_value<T>(
+        &self,
+        name: &str,
+        read: impl FnOnce(&[u8]) -> Result<T, StorageError>,
+    ) -> Result<Option<T>, StorageError> {
+        validate_key(name)?;
+
+        let txn = self.db.begin_read().map_err(redb::Error::from)?;
+        let table = match txn.open_table(self.values()) {
+            Ok(table) => table,
+            Err(TableError::TableDoesNotExist(_)) => return Ok(None),
+            Err(error) => return Err(redb::Error::from(error).into()),
+        };
+        match table.get(name).map_err(redb::Error::from)? {
+            Some(value) => read(value.value()).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+impl StorageBase for DatabaseStorage {
+    /// The error type returned by this storage.
+    type Error = StorageError;
+
+    /// Returns `true` if a value is stored under `name`.
+    fn contains(&self, name: &str) -> Result<bool, Self::Error> {
+        Ok(self.with_value(name, |_| Ok(()))?.is_some())
+    }
+
+    /// Removes `name`, returning `true` if it existed.
+    fn remove(&mut self, name: &str) -> Result<bool, Self::Error> {
+        validate_key(name)?;
+
+        let txn = self.db.begin_write().map_err(redb::Error::from)?;
+        let removed = {
+            let mut table = txn.open_table(self.values()).map_err(redb::Error::from)?;
+            let removed = table.remove(name).map_err(redb::Error::from)?;
+            removed.is_some()
+        };
+        txn.commit().map_err(redb::Error::from)?;
+        Ok(removed)
+    }
+}
+
+impl RawStorage for DatabaseStorage {
+    /// Returns the length of the value stored under `name`.
+    fn len(&self, name: &str) -> Result<Option<usize>, Self::Error> {
+        self.with_value(name, |value| Ok(value.len()))
+    }
+
+    /// Reads the value stored under `name` into `buf`.
+    ///
+    /// Fails with `StorageError::BufferTooSmall` if `buf` cannot hold the value.
+    fn get_raw<'a>(&self, name: &str, buf: &'a mut [u8]) -> Result<Option<&'a [u8]>, Self::Error> {
+        let len = self.with_value(name, |value| {
+            copy_value(value, &mut *buf).map(|value| value.len())
+        })?;
+        Ok(len.map(|len| &buf[..len]))
+    }
+
+    /// Stores `buf` under `name` in its own transaction.
+    fn set_raw(&mut self, name: &str, buf: &[u8]) -> Result<bool, Self::Error> {
+        self.set_all(&[(name, buf)])?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests an atomic batch write, reads, removal, and namespace isolation.
+    #[test]
+    fn test_database_storage_round_trip() {
+        let path = std::env::temp_dir().join(format!("native-svc-redb-{}", std::process::id()));
+        let mut storage = DatabaseStorage::open(&path).unwrap();
+
+        assert!(!storage.contains("ssid").unwrap());
+        storage
+            .set_all(&[("ssid", b"home".as_slice()), ("channel", &[6])])
+            .unwrap();
+        assert_eq!(storage.len("ssid").unwrap(), Some(4));
+
+        let mut buf = [0; 8];
+        assert_eq!(
+            storage.get_raw("channel", &mut buf).unwrap(),
+            Some(&[6][..])
+        );
+        assert!(matches!(
+            storage.get_raw("ssid", &mut [0; 2]),
+            Err(StorageError::BufferTooSmall(4))
+        ));
+        assert!(
+            storage
+                .set_all(&[("pass", b"x".as_slice()), ("a_key_that_is_too_long", b"")])
+                .is_err()
+        );
+        assert!(!storage.contains("pass").unwrap());
+
+        assert!(storage.remove("ssid").unwrap());
+        assert!(!storage.remove("ssid").unwrap());
+        let mut wifi = storage.open_namespace("wifi").unwrap();
+        wifi.set_raw("channel", &[11]).unwrap();
+        assert_eq!(
+            storage.get_raw("channel", &mut buf).unwrap(),
+            Some(&[6][..])
+        );
+        assert!(storage.erase_namespace("wifi").unwrap());
+        assert!(!wifi.contains("channel").unwrap());
+
+        drop((storage, wifi));
+        std::fs::remove_file(path).unwrap();
+    }
+}
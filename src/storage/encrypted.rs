@@ -0,0 +1,251 @@
+//! Encryption at rest for storage values.
+//!
+//! `EncryptedStorage` wraps any storage backend and seals every value with
+//! XChaCha20-Poly1305 under a random nonce. The key name is authenticated along with
+//! the value, so ciphertexts cannot be swapped between keys. The key is supplied by
+//! the application, derived from a secret, or kept in the OS keyring (feature
+//! `storage-keyring`).
+
+use crate::error::StorageError;
+use crate::storage::copy_value;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use embedded_svc::storage::{RawStorage, StorageBase};
+use hkdf::Hkdf;
+use sha2::Sha256;
+use std::fmt;
+
+/// Length of the nonce stored in front of each value.
+const NONCE_LEN: usize = 24;
+
+/// Bytes added to each value by the nonce and authentication tag.
+pub const OVERHEAD: usize = NONCE_LEN + 16;
+
+/// A 256-bit key for `EncryptedStorage`.
+#[derive(Clone, PartialEq, Eq)]
+pub struct EncryptionKey([u8; 32]);
+
+impl EncryptionKey {
+    /// Uses `bytes` as the key.
+    pub fn new(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+
+    /// Generates a random key.
+    pub fn generate() -> Self {
+        let key = XChaCha20Poly1305::generate_key(&mut OsRng);
+        let mut bytes = [0; 32];
+        bytes.copy_from_slice(&key);
+        Self(bytes)
+    }
+
+    /// Derives a key from `secret` and `salt` with HKDF-SHA256.
+    pub fn derive(secret: &[u8], salt: &[u8]) -> Self {
+        let mut bytes = [0; 32];
+        Hkdf::<Sha256>::new(Some(salt), secret)
+            .expand(b"native-svc storage", &mut bytes)
+            .expect("32 bytes is a valid HKDF-SHA256 output length");
+        Self(bytes)
+    }
+
+    /// Derives a key from the secret stored in the OS keyring under `service` and `user`.
+    ///
+    /// A random secret is generated and stored on first use.
+    #[cfg(feature = "storage-keyring")]
+    pub fn from_keyring(service: &str, user: &str) -> Result<Self, StorageError> {
+        let entry = keyring::Entry::new(service, user)?;
+        let secret = match entry.get_secret() {
+            Ok(secret) => secret,
+            Err(keyring::Error::NoEntry) => {
+                let secret = Self::generate().0;
+                entry.set_secret(&secret)?;
+                secret.to_vec()
+            }
+            Err(error) => return Err(error.into()),
+        };
+        Ok(Self::derive(&secret, service.as_bytes()))
+    }
+
+    /// Returns the raw key bytes.
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+impl fmt::Debug for EncryptionKey {
+    /// Formats the key without revealing it.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("EncryptionKey(..)")
+    }
+}
+
+/// A storage encrypting every value of an inner storage.
+///
+/// # Example
+///
+/// ```no_run
+/// use embedded_svc::storage::RawStorage;
+/// use native_svc::storage::FileStorage;
+/// use native_svc::storage::encrypted::{EncryptedStorage, EncryptionKey};
+///
+/// let key = EncryptionKey::derive(b"device secret", b"gateway-01");
+/// let raw = FileStorage::open("/var/lib/device/nvs").unwrap();
+/// let mut storage = EncryptedStorage::new(raw, &key);
+///
+/// storage.set_raw("wifi_pass", b"hunter2").unwrap();
+/// ```
+pub struct EncryptedStorage<S> {
+    inner: S,
+    cipher: XChaCha20Poly1305,
+}
+
+impl<S> EncryptedStorage<S> {
+    /// Encrypts the values of `inner` with `key`.
+    pub fn new(inner: S, key: &EncryptionKey) -> Self {
+        Self {
+            inner,
+            cipher: XChaCha20Poly1305::new(Key::from_slice(&key.0)),
+        }
+    }
+
+    /// Returns the inner storage holding the encrypted values.
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    /// Returns the inner storage, dropping the key.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    /// Seals `value`, prefixing it with a fresh nonce.
+    fn encrypt(&self, name: &str, value: &[u8]) -> Result<Vec<u8>, StorageError> {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let payload = Payload {
+            msg: value,
+            aad: name.as_bytes(),
+        };
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, payload)
+            .map_err(|_| StorageError::Crypto(name.to_owned()))?;
+
+        let mut sealed = Vec::with_capacity(NONCE_LEN + ciphertext.len());
+        sealed.extend_from_slice(&nonce);
+        sealed.extend_from_slice(&ciphertext);
+        Ok(sealed)
+    }
+
+    /// Opens a value sealed by `encrypt`.
+    fn decrypt(&self, name: &str, sealed: &[u8]) -> Result<Vec<u8>, StorageError> {
+        let (nonce, ciphertext) = sealed
+            .split_at_checked(NONCE_LEN)
+            .ok_or_else(|| StorageError::Crypto(name.to_owned()))?;
+        let payload = Payload {
+            msg: ciphertext,
+            aad: name.as_bytes(),
+        };
+        self.cipher
+            .decrypt(XNonce::from_slice(nonce), payload)
+            .map_err(|_| StorageError::Crypto(name.to_owned()))
+    }
+}
+
+impl<S> StorageBase for EncryptedStorage<S>
+where
+    S: StorageBase<Error = StorageError>,
+{
+    /// The error type returned by this storage.
+    type Error = StorageError;
+
+    /// Returns `true` if a value is stored under `name`.
+    fn contains(&self, name: &str) -> Result<bool, Self::Error> {
+        self.inner.contains(name)
+    }
+
+    /// Removes `name`, returning `true` if it existed.
+    fn remove(&mut self, name: &str) -> Result<bool, Self::Error> {
+        self.inner.remove(name)
+    }
+}
+
+impl<S> RawStorage for EncryptedStorage<S>
+where
+    S: RawStorage<Error = StorageError>,
+{
+    /// Returns the plaintext length of the value stored under `name`.
+    fn len(&self, name: &str) -> Result<Option<usize>, Self::Error> {
+        self.inner
+            .len(name)?
+            .map(|len| {
+                len.checked_sub(OVERHEAD)
+                    .ok_or_else(|| StorageError::Crypto(name.to_owned()))
+            })
+            .transpose()
+    }
+
+    /// Decrypts the value stored under `name` into `buf`.
+    ///
+    /// Fails with `StorageError::Crypto` if the value was tampered with or sealed with
+    /// another key.
+    fn get_raw<'a>(&self, name: &str, buf: &'a mut [u8]) -> Result<Option<&'a [u8]>, Self::Error> {
+        let Some(len) = self.inner.len(name)? else {
+            return Ok(None);
+        };
+        let mut sealed = vec![0; len];
+        let Some(sealed) = self.inner.get_raw(name, &mut sealed)? else {
+            return Ok(None);
+        };
+
+        let value = self.decrypt(name, sealed)?;
+        copy_value(&value, buf).map(Some)
+    }
+
+    /// Encrypts `buf` and stores it under `name`.
+    fn set_raw(&mut self, name: &str, buf: &[u8]) -> Result<bool, Self::Error> {
+        let sealed = self.encrypt(name, buf)?;
+        self.inner.set_raw(name, &sealed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::memory::MemoryStorage;
+
+    /// Tests that values are encrypted, authenticated, and bound to their key name.
+    #[test]
+    fn test_encrypted_round_trip() {
+        let key = EncryptionKey::derive(b"secret", b"salt");
+        let mut storage = EncryptedStorage::new(MemoryStorage::new(), &key);
+
+        storage.set_raw("wifi_pass", b"hunter2").unwrap();
+        storage.set_raw("cloud_token", b"abc").unwrap();
+        assert_eq!(storage.len("wifi_pass").unwrap(), Some(7));
+
+        let mut buf = [0; 16];
+        assert_eq!(
+            storage.get_raw("wifi_pass", &mut buf).unwrap(),
+            Some(&b"hunter2"[..])
+        );
+
+        let mut inner = storage.into_inner();
+        let mut sealed = [0; 64];
+        let sealed = inner
+            .get_raw("wifi_pass", &mut sealed)
+            .unwrap()
+            .unwrap()
+            .to_vec();
+        assert!(!sealed.windows(7).any(|window| window == b"hunter2"));
+
+        inner.set_raw("cloud_token", &sealed).unwrap();
+        let storage = EncryptedStorage::new(inner, &key);
+        assert!(matches!(
+            storage.get_raw("cloud_token", &mut buf),
+            Err(StorageError::Crypto(_))
+        ));
+
+        let other = EncryptedStorage::new(storage.into_inner(), &EncryptionKey::generate());
+        assert!(other.get_raw("wifi_pass", &mut buf).is_err());
+    }
+}
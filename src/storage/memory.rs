@@ -0,0 +1,156 @@
+//! In-memory storage for unit tests.
+//!
+//! `MemoryStorage` implements the same traits as `FileStorage` without touching the
+//! filesystem, and can snapshot and restore its contents so tests can rewind
+//! configuration state between steps.
+
+use crate::error::StorageError;
+use crate::storage::{copy_value, validate_key};
+use embedded_svc::storage::{RawStorage, StorageBase};
+use std::collections::BTreeMap;
+
+/// A copy of the contents of a `MemoryStorage`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemorySnapshot {
+    values: BTreeMap<String, Vec<u8>>,
+}
+
+impl MemorySnapshot {
+    /// Returns the value stored under `name` when the snapshot was taken.
+    pub fn get(&self, name: &str) -> Option<&[u8]> {
+        self.values.get(name).map(Vec::as_slice)
+    }
+
+    /// Returns the number of stored keys.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Returns `true` if no key was stored.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+/// A `RawStorage` keeping values in memory.
+///
+/// # Example
+///
+/// ```
+/// use embedded_svc::storage::RawStorage;
+/// use native_svc::storage::memory::MemoryStorage;
+///
+/// let mut storage = MemoryStorage::new();
+/// storage.set_raw("boot_count", &[1]).unwrap();
+///
+/// let snapshot = storage.snapshot();
+/// storage.set_raw("boot_count", &[2]).unwrap();
+/// storage.restore(&snapshot);
+///
+/// let mut buf = [0; 1];
+/// assert_eq!(storage.get_raw("boot_count", &mut buf).unwrap(), Some(&[1][..]));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStorage {
+    values: BTreeMap<String, Vec<u8>>,
+}
+
+impl MemoryStorage {
+    /// Creates an empty storage.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a storage holding the contents of `snapshot`.
+    pub fn from_snapshot(snapshot: MemorySnapshot) -> Self {
+        Self {
+            values: snapshot.values,
+        }
+    }
+
+    /// Captures the current contents.
+    pub fn snapshot(&self) -> MemorySnapshot {
+        MemorySnapshot {
+            values: self.values.clone(),
+        }
+    }
+
+    /// Replaces the current contents with `snapshot`.
+    pub fn restore(&mut self, snapshot: &MemorySnapshot) {
+        self.values.clone_from(&snapshot.values);
+    }
+
+    /// Removes every stored value.
+    pub fn clear(&mut self) {
+        self.values.clear();
+    }
+}
+
+impl StorageBase for MemoryStorage {
+    /// The error type returned by this storage.
+    type Error = StorageError;
+
+    /// Returns `true` if a value is stored under `name`.
+    fn contains(&self, name: &str) -> Result<bool, Self::Error> {
+        validate_key(name)?;
+        Ok(self.values.contains_key(name))
+    }
+
+    /// Removes `name`, returning `true` if it existed.
+    fn remove(&mut self, name: &str) -> Result<bool, Self::Error> {
+        validate_key(name)?;
+        Ok(self.values.remove(name).is_some())
+    }
+}
+
+impl RawStorage for MemoryStorage {
+    /// Returns the length of the value stored under `name`.
+    fn len(&self, name: &str) -> Result<Option<usize>, Self::Error> {
+        validate_key(name)?;
+        Ok(self.values.get(name).map(Vec::len))
+    }
+
+    /// Reads the value stored under `name` into `buf`.
+    ///
+    /// Fails with `StorageError::BufferTooSmall` if `buf` cannot hold the value.
+    fn get_raw<'a>(&self, name: &str, buf: &'a mut [u8]) -> Result<Option<&'a [u8]>, Self::Error> {
+        validate_key(name)?;
+        self.values
+            .get(name)
+            .map(|value| copy_value(value, buf))
+            .transpose()
+    }
+
+    /// Stores `buf` under `name`, replacing any previous value.
+    fn set_raw(&mut self, name: &str, buf: &[u8]) -> Result<bool, Self::Error> {
+        validate_key(name)?;
+        self.values.insert(name.to_owned(), buf.to_vec());
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that restoring a snapshot discards later changes.
+    #[test]
+    fn test_snapshot_restore() {
+        let mut storage = MemoryStorage::new();
+        storage.set_raw("ssid", b"home").unwrap();
+        let snapshot = storage.snapshot();
+
+        storage.set_raw("ssid", b"office").unwrap();
+        storage.set_raw("channel", &[6]).unwrap();
+        assert!(storage.remove("ssid").unwrap());
+
+        storage.restore(&snapshot);
+        assert_eq!(snapshot.get("ssid"), Some(&b"home"[..]));
+        assert_eq!(storage.len("ssid").unwrap(), Some(4));
+        assert!(!storage.contains("channel").unwrap());
+        assert!(matches!(
+            storage.get_raw("ssid", &mut [0; 2]),
+            Err(StorageError::BufferTooSmall(4))
+        ));
+    }
+}
@@ -0,0 +1,123 @@
+//! On-disk record format and crash-safe file replacement.
+//!
+//! Every value written by `FileStorage` is framed as a record: a magic tag, the
+//! CRC-32 of the payload, and the payload itself. Records are written to a temporary
+//! file, synced, and renamed over the previous one, so a power cut leaves either the
+//! old or the new value, and a damaged file is detected instead of read back silently.
+
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Tag identifying a record, including its format version.
+const MAGIC: &[u8; 4] = b"NSV1";
+
+/// Size of the record header.
+const HEADER_LEN: usize = MAGIC.len() + 4;
+
+/// Frames `payload` as a record.
+pub(crate) fn encode(payload: &[u8]) -> Vec<u8> {
+    let mut record = Vec::with_capacity(HEADER_LEN + payload.len());
+    record.extend_from_slice(MAGIC);
+    record.extend_from_slice(&crc32(payload).to_le_bytes());
+    record.extend_from_slice(payload);
+    record
+}
+
+/// Returns the payload of `record`, or `None` if it is truncated or corrupted.
+pub(crate) fn decode(record: &[u8]) -> Option<&[u8]> {
+    let (header, payload) = record.split_at_checked(HEADER_LEN)?;
+    let (magic, checksum) = header.split_at(MAGIC.len());
+    let checksum = u32::from_le_bytes(checksum.try_into().ok()?);
+
+    (magic == MAGIC && crc32(payload) == checksum).then_some(payload)
+}
+
+/// Replaces `path` with `data` through a synced temporary file and a rename.
+///
+/// If `backup` is given, the previous file is moved there first so it can be
+/// recovered if the new one turns out to be damaged. A previous file failing its
+/// checksum is not, so it never replaces the last good backup.
+pub(crate) fn write_atomic(path: &Path, data: &[u8], backup: Option<&Path>) -> io::Result<()> {
+    let temp = path.with_extension("tmp");
+    let mut file = File::create(&temp)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    drop(file);
+
+    if let Some(backup) = backup {
+        match fs::read(path) {
+            Ok(current) if decode(&current).is_some() => fs::rename(path, backup)?,
+            Err(error) if error.kind() != io::ErrorKind::NotFound => return Err(error),
+            _ => {}
+        }
+    }
+    fs::rename(&temp, path)?;
+    sync_dir(path)
+}
+
+/// Flushes the directory entry of `path` so a rename survives a power cut.
+fn sync_dir(path: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    if let Some(dir) = path.parent() {
+        File::open(dir)?.sync_all()?;
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
+
+/// Computes the CRC-32 (IEEE) of `data`.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that damaged or truncated records are rejected.
+    #[test]
+    fn test_record_corruption() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+
+        let mut record = encode(b"home");
+        assert_eq!(decode(&record), Some(&b"home"[..]));
+        assert_eq!(decode(&record[..HEADER_LEN + 2]), None);
+        assert_eq!(decode(&record[..3]), None);
+
+        record[HEADER_LEN] ^= 0x01;
+        assert_eq!(decode(&record), None);
+    }
+
+    /// Tests that only records passing their checksum are rotated to the backup.
+    #[test]
+    fn test_backup_rotation() {
+        let dir = std::env::temp_dir().join(format!("native-svc-record-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("wifi");
+        let backup = dir.join("wifi.bak");
+
+        write_atomic(&path, &encode(b"home"), Some(&backup)).unwrap();
+        write_atomic(&path, &encode(b"office"), Some(&backup)).unwrap();
+        assert_eq!(fs::read(&backup).unwrap(), encode(b"home"));
+
+        fs::write(&path, b"NSV1 damaged").unwrap();
+        write_atomic(&path, &encode(b"lab"), Some(&backup)).unwrap();
+        assert_eq!(fs::read(&backup).unwrap(), encode(b"home"));
+        assert_eq!(fs::read(&path).unwrap(), encode(b"lab"));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,127 @@
+//! Charset-aware decoding of response bodies.
+//!
+//! `HyperHttpConnection::read_text` decodes the rest of the body using the `charset`
+//! parameter of the `Content-Type` header, so pages served by legacy devices in
+//! Latin-1 or UTF-16 do not need every caller to special-case them. A byte order mark
+//! takes precedence over the header, and invalid sequences are replaced with U+FFFD.
+
+use crate::HyperHttpConnection;
+use crate::error::HyperError;
+use hyper::header::CONTENT_TYPE;
+
+/// Character encodings understood by `decode`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Charset {
+    Utf8,
+    Latin1,
+    Utf16Le,
+    Utf16Be,
+}
+
+impl Charset {
+    /// Returns the encoding named `label`.
+    fn from_label(label: &str) -> Option<Self> {
+        match label.to_ascii_lowercase().as_str() {
+            "utf-8" | "utf8" => Some(Self::Utf8),
+            "iso-8859-1" | "iso8859-1" | "latin1" | "l1" | "us-ascii" | "ascii" => {
+                Some(Self::Latin1)
+            }
+            "utf-16" | "utf-16le" => Some(Self::Utf16Le),
+            "utf-16be" => Some(Self::Utf16Be),
+            _ => None,
+        }
+    }
+}
+
+/// Returns the `charset` parameter of a `Content-Type` value, without quotes.
+pub fn charset(content_type: &str) -> Option<&str> {
+    content_type.split(';').skip(1).find_map(|param| {
+        let (name, value) = param.split_once('=')?;
+        name.trim()
+            .eq_ignore_ascii_case("charset")
+            .then(|| value.trim().trim_matches('"'))
+    })
+}
+
+/// Decodes `body` using the charset of `content_type`, UTF-8 if it names none.
+///
+/// Fails with `HyperError::UnsupportedCharset` if the charset is not UTF-8, Latin-1
+/// or UTF-16 and the body has no byte order mark.
+pub fn decode(body: &[u8], content_type: Option<&str>) -> Result<String, HyperError> {
+    let (charset, body) = if let Some(body) = body.strip_prefix(b"\xEF\xBB\xBF") {
+        (Charset::Utf8, body)
+    } else if let Some(body) = body.strip_prefix(b"\xFF\xFE") {
+        (Charset::Utf16Le, body)
+    } else if let Some(body) = body.strip_prefix(b"\xFE\xFF") {
+        (Charset::Utf16Be, body)
+    } else {
+        let charset = match content_type.and_then(charset) {
+            Some(label) => Charset::from_label(label)
+                .ok_or_else(|| HyperError::UnsupportedCharset(label.to_owned()))?,
+            None => Charset::Utf8,
+        };
+        (charset, body)
+    };
+
+    Ok(match charset {
+        Charset::Utf8 => String::from_utf8_lossy(body).into_owned(),
+        Charset::Latin1 => body.iter().map(|&byte| char::from(byte)).collect(),
+        Charset::Utf16Le | Charset::Utf16Be => {
+            let units = body.chunks(2).map(|pair| match (charset, pair) {
+                (Charset::Utf16Le, &[low, high]) => u16::from_le_bytes([low, high]),
+                (_, &[high, low]) => u16::from_be_bytes([high, low]),
+                // A trailing odd byte is invalid.
+                _ => 0xFFFD,
+            });
+            char::decode_utf16(units)
+                .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+                .collect()
+        }
+    })
+}
+
+impl HyperHttpConnection {
+    /// Reads the rest of the response body and decodes it as text.
+    ///
+    /// The encoding is taken from a byte order mark or the `Content-Type` charset,
+    /// defaulting to UTF-8; see `decode`.
+    pub fn read_text(&mut self) -> Result<String, HyperError> {
+        let content_type = self
+            .ensure_response()?
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_owned);
+        let mut body = Vec::new();
+        self.for_each_chunk(|chunk| body.extend_from_slice(&chunk))?;
+        decode(&body, content_type.as_deref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests decoding bodies in each supported charset.
+    #[test]
+    fn test_decode_charsets() {
+        let latin1 = Some("text/html; charset=\"ISO-8859-1\"");
+        assert_eq!(decode(b"caf\xE9", latin1).unwrap(), "café");
+        assert_eq!(decode("café".as_bytes(), None).unwrap(), "café");
+        assert_eq!(
+            decode(b"caf\xE9", Some("text/plain")).unwrap(),
+            "caf\u{FFFD}"
+        );
+
+        let utf16 = b"\xFF\xFEc\x00a\x00f\x00\xE9\x00";
+        assert_eq!(decode(utf16, latin1).unwrap(), "café");
+        let utf16be = Some("text/plain;charset=utf-16be");
+        assert_eq!(decode(b"\x00c\x00\xE9", utf16be).unwrap(), "cé");
+
+        let koi8 = Some("text/plain; charset=koi8-r");
+        assert!(matches!(
+            decode(b"text", koi8),
+            Err(HyperError::UnsupportedCharset(label)) if label == "koi8-r"
+        ));
+    }
+}
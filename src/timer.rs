@@ -0,0 +1,433 @@
+//! Timers implementing `embedded_svc::timer`.
+//!
+//! `NativeTimerService` runs one-shot and periodic timers on a dedicated dispatch
+//! thread, like the ESP-IDF `esp_timer` task: callbacks run one at a time on that
+//! thread, so ESP timer-based code behaves the same when running natively. The
+//! `asynch` module provides the async flavor on top of `tokio::time`.
+//!
+//! Periodic timers follow a `TimerPolicy`. Fixed-rate timers compute every deadline
+//! from the previous deadline on the monotonic clock rather than from when the
+//! callback ran, so they do not drift over hours; the missed-tick policy decides what
+//! happens when callbacks fall behind.
+//!
+//! A panicking callback does not take the dispatch thread down: the panic is caught,
+//! reported by the panic hook, and its timer is disarmed while the others keep running.
+
+pub mod asynch;
+
+use crate::error::TimerError;
+use embedded_svc::timer::{ErrorType, OnceTimer, PeriodicTimer, Timer, TimerService};
+use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Callback invoked when a timer fires.
+type Callback = Box<dyn FnMut() + Send>;
+
+/// How a periodic timer spaces its ticks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Schedule {
+    /// Ticks are a fixed period apart from the first deadline, whatever the callback time.
+    #[default]
+    FixedRate,
+    /// Each tick is a full period after the previous callback returns.
+    FixedDelay,
+}
+
+/// What a fixed-rate timer does with ticks missed because callbacks ran late.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MissedTicks {
+    /// Fires every missed tick back to back until caught up.
+    #[default]
+    Burst,
+    /// Drops missed ticks and resumes at the next tick of the original cadence.
+    Skip,
+    /// Restarts the cadence one period after the late tick.
+    Delay,
+}
+
+/// Scheduling policy of a periodic timer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TimerPolicy {
+    /// How ticks are spaced.
+    pub schedule: Schedule,
+    /// How missed ticks are handled by fixed-rate timers.
+    pub missed_ticks: MissedTicks,
+}
+
+impl TimerPolicy {
+    /// Returns the deadline following `deadline` for a timer with `period`, at `now`.
+    pub fn next_deadline(&self, deadline: Instant, period: Duration, now: Instant) -> Instant {
+        if self.schedule == Schedule::FixedDelay || period.is_zero() {
+            return now + period;
+        }
+
+        let next = deadline + period;
+        if next > now {
+            return next;
+        }
+        match self.missed_ticks {
+            MissedTicks::Burst => next,
+            MissedTicks::Skip => {
+                let missed = (now - deadline).as_nanos() / period.as_nanos();
+                let nanos = period.as_nanos() * (missed + 1);
+                deadline + Duration::from_nanos(nanos.try_into().unwrap_or(u64::MAX))
+            }
+            MissedTicks::Delay => now + period,
+        }
+    }
+}
+
+/// A registered timer.
+struct Entry {
+    /// The callback, taken out while it runs.
+    callback: Option<Callback>,
+    deadline: Option<Instant>,
+    period: Option<Duration>,
+    policy: TimerPolicy,
+    /// Bumped whenever the timer is rescheduled or cancelled.
+    generation: u64,
+}
+
+/// Timers and handle count shared with the dispatch thread.
+#[derive(Default)]
+struct State {
+    entries: HashMap<u64, Entry>,
+    next_id: u64,
+    /// Live services and timers; the dispatch thread exits when it drops to zero.
+    handles: usize,
+}
+
+/// State shared between the service, its timers, and the dispatch thread.
+#[derive(Default)]
+struct Shared {
+    state: Mutex<State>,
+    changed: Condvar,
+}
+
+impl Shared {
+    /// Locks the state, recovering it from poisoning.
+    ///
+    /// Callbacks run without the lock, so they cannot poison it; no update leaves the
+    /// state inconsistent if it panics halfway.
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Updates the state and wakes the dispatch thread.
+    fn update<T>(&self, f: impl FnOnce(&mut State) -> T) -> T {
+        let result = f(&mut self.lock());
+        self.changed.notify_all();
+        result
+    }
+
+    /// Runs due callbacks until every handle is dropped.
+    fn dispatch(&self) {
+        let mut state = self.lock();
+        loop {
+            if state.handles == 0 {
+                return;
+            }
+
+            let next = state
+                .entries
+                .iter()
+                .filter(|(_, entry)| entry.callback.is_some())
+                .filter_map(|(id, entry)| Some((entry.deadline?, *id)))
+                .min();
+            let Some((deadline, id)) = next else {
+                state = self.changed.wait(state).unwrap_or_else(|e| e.into_inner());
+                continue;
+            };
+
+            let now = Instant::now();
+            if deadline > now {
+                state = self
+                    .changed
+                    .wait_timeout(state, deadline - now)
+                    .unwrap_or_else(|e| e.into_inner())
+                    .0;
+                continue;
+            }
+
+            let entry = state.entries.get_mut(&id).expect("timer selected above");
+            let generation = entry.generation;
+            if entry.period.is_none() {
+                entry.deadline = None;
+            }
+            let mut callback = entry.callback.take().expect("timer selected above");
+            drop(state);
+
+            let panicked = panic::catch_unwind(AssertUnwindSafe(&mut callback)).is_err();
+
+            // The next deadline is computed after the callback so fixed-delay timers
+            // and missed ticks account for its run time.
+            state = self.lock();
+            if let Some(entry) = state.entries.get_mut(&id) {
+                entry.callback = Some(callback);
+                if panicked && entry.generation == generation {
+                    entry.deadline = None;
+                } else if entry.generation == generation
+                    && let Some(period) = entry.period
+                {
+                    let next = entry.policy.next_deadline(deadline, period, Instant::now());
+                    entry.deadline = Some(next);
+                }
+            }
+        }
+    }
+}
+
+/// A `TimerService` dispatching callbacks on a dedicated thread.
+///
+/// # Example
+///
+/// ```no_run
+/// use embedded_svc::timer::{PeriodicTimer, TimerService};
+/// use native_svc::timer::NativeTimerService;
+/// use std::time::Duration;
+///
+/// let service = NativeTimerService::new().unwrap();
+/// let mut timer = service.timer(|| println!("tick")).unwrap();
+/// timer.every(Duration::from_secs(1)).unwrap();
+/// ```
+pub struct NativeTimerService {
+    shared: Arc<Shared>,
+}
+
+impl NativeTimerService {
+    /// Starts the dispatch thread.
+    pub fn new() -> Result<Self, TimerError> {
+        let shared = Arc::new(Shared::default());
+        shared.lock().handles = 1;
+
+        let dispatcher = shared.clone();
+        thread::Builder::new()
+            .name("native-svc-timer".to_owned())
+            .spawn(move || dispatcher.dispatch())?;
+
+        Ok(Self { shared })
+    }
+}
+
+impl Clone for NativeTimerService {
+    /// Returns another handle to the same dispatch thread.
+    fn clone(&self) -> Self {
+        self.shared.update(|state| state.handles += 1);
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl Drop for NativeTimerService {
+    /// Stops the dispatch thread once no service or timer is left.
+    fn drop(&mut self) {
+        self.shared.update(|state| state.handles -= 1);
+    }
+}
+
+impl ErrorType for NativeTimerService {
+    type Error = TimerError;
+}
+
+impl TimerService for NativeTimerService {
+    type Timer = NativeTimer;
+
+    /// Creates an unscheduled timer invoking `callback` on the dispatch thread.
+    ///
+    /// If `callback` panics, the timer is disarmed until it is scheduled again.
+    fn timer<F>(&self, callback: F) -> Result<Self::Timer, Self::Error>
+    where
+        F: FnMut() + Send + 'static,
+    {
+        let id = self.shared.update(|state| {
+            let id = state.next_id;
+            state.next_id += 1;
+            state.handles += 1;
+            state.entries.insert(
+                id,
+                Entry {
+                    callback: Some(Box::new(callback)),
+                    deadline: None,
+                    period: None,
+                    policy: TimerPolicy::default(),
+                    generation: 0,
+                },
+            );
+            id
+        });
+
+        Ok(NativeTimer {
+            shared: self.shared.clone(),
+            id,
+        })
+    }
+}
+
+/// A timer created by `NativeTimerService`; dropping it cancels it.
+pub struct NativeTimer {
+    shared: Arc<Shared>,
+    id: u64,
+}
+
+impl NativeTimer {
+    /// Sets the scheduling policy used by `every`.
+    pub fn set_policy(&mut self, policy: TimerPolicy) {
+        let id = self.id;
+        self.shared.update(|state| {
+            if let Some(entry) = state.entries.get_mut(&id) {
+                entry.policy = policy;
+            }
+        });
+    }
+
+    /// Schedules the timer to fire after `duration`, then every `period` if set.
+    fn schedule(&mut self, duration: Duration, period: Option<Duration>) {
+        let id = self.id;
+        self.shared.update(|state| {
+            if let Some(entry) = state.entries.get_mut(&id) {
+                entry.deadline = Some(Instant::now() + duration);
+                entry.period = period;
+                entry.generation += 1;
+            }
+        });
+    }
+}
+
+impl Drop for NativeTimer {
+    /// Removes the timer from the service.
+    fn drop(&mut self) {
+        let id = self.id;
+        self.shared.update(|state| {
+            state.entries.remove(&id);
+            state.handles -= 1;
+        });
+    }
+}
+
+impl ErrorType for NativeTimer {
+    type Error = TimerError;
+}
+
+impl Timer for NativeTimer {
+    /// Returns `true` if the timer will fire.
+    fn is_scheduled(&self) -> Result<bool, Self::Error> {
+        let state = self.shared.lock();
+        Ok(state
+            .entries
+            .get(&self.id)
+            .is_some_and(|entry| entry.deadline.is_some()))
+    }
+
+    /// Cancels the timer, returning `true` if it was scheduled.
+    fn cancel(&mut self) -> Result<bool, Self::Error> {
+        let id = self.id;
+        Ok(self.shared.update(|state| {
+            state.entries.get_mut(&id).is_some_and(|entry| {
+                entry.period = None;
+                entry.generation += 1;
+                entry.deadline.take().is_some()
+            })
+        }))
+    }
+}
+
+impl OnceTimer for NativeTimer {
+    /// Fires the timer once after `duration`.
+    fn after(&mut self, duration: Duration) -> Result<(), Self::Error> {
+        self.schedule(duration, None);
+        Ok(())
+    }
+}
+
+impl PeriodicTimer for NativeTimer {
+    /// Fires the timer every `duration`, following the timer's `TimerPolicy`.
+    fn every(&mut self, duration: Duration) -> Result<(), Self::Error> {
+        self.schedule(duration, Some(duration));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    /// Tests one-shot and periodic timers and cancellation.
+    #[test]
+    fn test_once_and_periodic_timers() {
+        let service = NativeTimerService::new().unwrap();
+        let (tx, rx) = mpsc::channel();
+
+        let once_tx = tx.clone();
+        let mut once = service
+            .timer(move || once_tx.send("once").unwrap())
+            .unwrap();
+        let mut periodic = service.timer(move || tx.send("tick").unwrap()).unwrap();
+
+        once.after(Duration::from_millis(10)).unwrap();
+        periodic.every(Duration::from_millis(5)).unwrap();
+        assert!(once.is_scheduled().unwrap());
+
+        let received: Vec<_> = rx.iter().take(4).collect();
+        assert!(received.contains(&"tick"));
+        assert!(periodic.cancel().unwrap());
+        assert!(!periodic.is_scheduled().unwrap());
+
+        let deadline = Instant::now() + Duration::from_secs(1);
+        while once.is_scheduled().unwrap() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert!(!once.is_scheduled().unwrap());
+        assert!(rx.try_iter().chain(received).any(|event| event == "once"));
+    }
+
+    /// Tests that a panicking callback disarms its timer and leaves the others running.
+    #[test]
+    fn test_panicking_callback() {
+        let service = NativeTimerService::new().unwrap();
+        let (tx, rx) = mpsc::channel();
+
+        let mut panicking = service.timer(|| panic!("callback failed")).unwrap();
+        let mut periodic = service.timer(move || tx.send(()).unwrap()).unwrap();
+        panicking.every(Duration::from_millis(1)).unwrap();
+        periodic.every(Duration::from_millis(5)).unwrap();
+
+        assert_eq!(rx.iter().take(3).count(), 3);
+        assert!(!panicking.is_scheduled().unwrap());
+        assert!(periodic.is_scheduled().unwrap());
+    }
+
+    /// Tests the deadlines chosen by each scheduling policy after a late tick.
+    #[test]
+    fn test_missed_tick_policies() {
+        let start = Instant::now();
+        let period = Duration::from_millis(10);
+        let late = start + Duration::from_millis(35);
+        let policy = |schedule, missed_ticks| TimerPolicy {
+            schedule,
+            missed_ticks,
+        };
+
+        let on_time = TimerPolicy::default().next_deadline(start, period, start);
+        assert_eq!(on_time, start + period);
+
+        let burst = policy(Schedule::FixedRate, MissedTicks::Burst);
+        assert_eq!(burst.next_deadline(start, period, late), start + period);
+        let skip = policy(Schedule::FixedRate, MissedTicks::Skip);
+        assert_eq!(
+            skip.next_deadline(start, period, late),
+            start + Duration::from_millis(40)
+        );
+        let delay = policy(Schedule::FixedRate, MissedTicks::Delay);
+        assert_eq!(delay.next_deadline(start, period, late), late + period);
+        let fixed_delay = policy(Schedule::FixedDelay, MissedTicks::Burst);
+        assert_eq!(
+            fixed_delay.next_deadline(start, period, start),
+            start + period
+        );
+    }
+}
@@ -0,0 +1,149 @@
+//! Asynchronous timers implementing `embedded_svc::timer::asynch`.
+//!
+//! `AsyncTimerService` hands out timers backed by `tokio::time`, so async firmware
+//! tasks awaiting `after` or ticking an `every` clock can be hosted natively. Timers
+//! must be awaited from within a Tokio runtime with the time driver enabled.
+//! Periodic clocks follow the same `TimerPolicy` as the blocking timers.
+
+use crate::error::TimerError;
+use crate::timer::{MissedTicks, Schedule, TimerPolicy};
+use embedded_svc::timer::ErrorType;
+use embedded_svc::timer::asynch::{Clock, OnceTimer, PeriodicTimer, TimerService};
+use std::time::Duration;
+use tokio::time::{Instant, Interval, MissedTickBehavior};
+
+/// A `TimerService` creating timers on the caller's Tokio runtime.
+///
+/// # Example
+///
+/// ```no_run
+/// use embedded_svc::timer::asynch::{Clock, OnceTimer, PeriodicTimer, TimerService};
+/// use native_svc::timer::asynch::AsyncTimerService;
+/// use std::time::Duration;
+///
+/// # async fn run() -> Result<(), native_svc::error::TimerError> {
+/// let mut timer = AsyncTimerService.timer().await?;
+/// timer.after(Duration::from_millis(100)).await?;
+///
+/// let mut clock = timer.every(Duration::from_secs(1))?;
+/// loop {
+///     clock.tick().await;
+/// }
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AsyncTimerService;
+
+impl ErrorType for AsyncTimerService {
+    type Error = TimerError;
+}
+
+impl TimerService for AsyncTimerService {
+    type Timer = AsyncTimer;
+
+    /// Creates a timer.
+    async fn timer(&self) -> Result<Self::Timer, Self::Error> {
+        Ok(AsyncTimer::default())
+    }
+}
+
+/// A timer created by `AsyncTimerService`.
+#[derive(Debug, Default)]
+pub struct AsyncTimer {
+    policy: TimerPolicy,
+}
+
+impl AsyncTimer {
+    /// Sets the scheduling policy of clocks returned by `every`.
+    pub fn set_policy(&mut self, policy: TimerPolicy) {
+        self.policy = policy;
+    }
+}
+
+impl ErrorType for AsyncTimer {
+    type Error = TimerError;
+}
+
+impl OnceTimer for AsyncTimer {
+    /// Completes after `duration`.
+    async fn after(&mut self, duration: Duration) -> Result<(), Self::Error> {
+        tokio::time::sleep(duration).await;
+        Ok(())
+    }
+}
+
+impl PeriodicTimer for AsyncTimer {
+    type Clock<'a>
+        = AsyncClock
+    where
+        Self: 'a;
+
+    /// Returns a clock ticking every `duration`, starting one period from now.
+    fn every(&mut self, duration: Duration) -> Result<Self::Clock<'_>, Self::Error> {
+        let ticks = match self.policy.schedule {
+            Schedule::FixedRate => {
+                let mut interval = tokio::time::interval_at(Instant::now() + duration, duration);
+                interval.set_missed_tick_behavior(match self.policy.missed_ticks {
+                    MissedTicks::Burst => MissedTickBehavior::Burst,
+                    MissedTicks::Skip => MissedTickBehavior::Skip,
+                    MissedTicks::Delay => MissedTickBehavior::Delay,
+                });
+                Ticks::FixedRate(interval)
+            }
+            Schedule::FixedDelay => Ticks::FixedDelay(duration),
+        };
+        Ok(AsyncClock { ticks })
+    }
+}
+
+/// How an `AsyncClock` waits for its next tick.
+#[derive(Debug)]
+enum Ticks {
+    /// Ticks on a drift-free cadence.
+    FixedRate(Interval),
+    /// Ticks one period after each `tick` call.
+    FixedDelay(Duration),
+}
+
+/// A periodic clock returned by `AsyncTimer::every`.
+#[derive(Debug)]
+pub struct AsyncClock {
+    ticks: Ticks,
+}
+
+impl Clock for AsyncClock {
+    /// Completes at the next tick.
+    async fn tick(&mut self) {
+        match &mut self.ticks {
+            Ticks::FixedRate(interval) => {
+                interval.tick().await;
+            }
+            Ticks::FixedDelay(period) => tokio::time::sleep(*period).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::RuntimeConfig;
+
+    /// Tests awaiting a one-shot delay and periodic ticks.
+    #[test]
+    fn test_after_and_every() {
+        let rt = RuntimeConfig::default().build().unwrap();
+
+        rt.block_on(async {
+            let mut timer = AsyncTimerService.timer().await.unwrap();
+            let start = Instant::now();
+            timer.after(Duration::from_millis(10)).await.unwrap();
+            assert!(start.elapsed() >= Duration::from_millis(10));
+
+            let mut clock = timer.every(Duration::from_millis(5)).unwrap();
+            for _ in 0..3 {
+                clock.tick().await;
+            }
+            assert!(start.elapsed() >= Duration::from_millis(25));
+        });
+    }
+}
@@ -0,0 +1,225 @@
+//! Timing of the phases of a request.
+//!
+//! The connector of the client measures how long each new connection spent
+//! resolving the server name, connecting and performing the TLS handshake, and adds
+//! the durations to the extensions of the responses received on it. With
+//! `HyperHttpConnection::with_timing`, the connection also measures the time to the
+//! first byte of each response and to the end of its body, and reports all of them
+//! as `Timings`, like the `--write-out` timings of curl.
+
+use hyper_util::client::legacy::connect::dns::{GaiAddrs, GaiResolver, Name};
+use std::cell::Cell;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tower_service::Service;
+
+/// Durations of the phases of the last request of a `HyperHttpConnection`.
+///
+/// Phases of a connection are `None` when the request was sent on a connection
+/// reused from the pool. With redirects or retries, they are those of the
+/// connection carrying the last response, while `ttfb` and `total` span all the
+/// attempts.
+///
+/// # Example
+///
+/// ```no_run
+/// use embedded_svc::http::Method;
+/// use embedded_svc::http::client::Connection;
+/// use native_svc::HyperHttpConnection;
+///
+/// let mut conn = HyperHttpConnection::new().unwrap().with_timing();
+/// conn.initiate_request(Method::Get, "https://example.com", &[]).unwrap();
+/// conn.initiate_response().unwrap();
+/// let timings = conn.timings().unwrap();
+/// println!("dns {:?}, ttfb {:?}", timings.dns, timings.ttfb);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timings {
+    /// Resolution of the server name; `None` for IP addresses.
+    pub dns: Option<Duration>,
+    /// TCP connection, and the `CONNECT` tunnel of a proxy if any.
+    pub connect: Option<Duration>,
+    /// TLS handshake; `None` for plain HTTP.
+    pub tls: Option<Duration>,
+    /// From sending the request to receiving the head of the response, including
+    /// the phases above.
+    pub ttfb: Duration,
+    /// From sending the request to reading the end of the response body; `None`
+    /// until it is read.
+    pub total: Option<Duration>,
+}
+
+impl Timings {
+    /// Creates the timings of a request sent on a connection opened in `phases`,
+    /// whose response head arrived after `ttfb`.
+    pub(crate) fn new(phases: ConnectPhases, ttfb: Duration) -> Self {
+        Self {
+            dns: phases.dns,
+            connect: phases.connect,
+            tls: phases.tls,
+            ttfb,
+            total: None,
+        }
+    }
+}
+
+/// Durations of the phases opening a connection, in the extensions of its responses.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct ConnectPhases {
+    pub(crate) dns: Option<Duration>,
+    pub(crate) connect: Option<Duration>,
+    pub(crate) tls: Option<Duration>,
+}
+
+/// Phases measured while the connection being opened is polled.
+#[derive(Default)]
+struct Measured {
+    dns: Cell<Option<Duration>>,
+    tls: Cell<Option<Duration>>,
+}
+
+tokio::task_local! {
+    /// Phases of the connection opened by the current connector call.
+    static MEASURED: Measured;
+}
+
+/// Opens a connection with `connecting`, measuring its phases.
+pub(crate) async fn measure<F: Future>(connecting: F) -> (F::Output, ConnectPhases) {
+    let started = Instant::now();
+    MEASURED
+        .scope(Measured::default(), async {
+            let output = connecting.await;
+            let dns = MEASURED.with(|measured| measured.dns.get());
+            let tls = MEASURED.with(|measured| measured.tls.get());
+            let elapsed = started.elapsed();
+            let setup = dns.unwrap_or_default() + tls.unwrap_or_default();
+            let phases = ConnectPhases {
+                dns,
+                connect: Some(elapsed.saturating_sub(setup)),
+                tls,
+            };
+            (output, phases)
+        })
+        .await
+}
+
+/// Records the duration of the TLS handshake of the connection being opened.
+#[cfg_attr(not(feature = "tls"), allow(dead_code))]
+pub(crate) fn record_tls(duration: Duration) {
+    let _ = MEASURED.try_with(|measured| measured.tls.set(Some(duration)));
+}
+
+/// Resolver of the HTTP connector, measuring the resolution of server names.
+#[derive(Clone)]
+pub(crate) struct TimedResolver {
+    inner: GaiResolver,
+}
+
+impl TimedResolver {
+    /// Resolves names with `getaddrinfo`.
+    pub(crate) fn new() -> Self {
+        Self {
+            inner: GaiResolver::new(),
+        }
+    }
+}
+
+impl Service<Name> for TimedResolver {
+    type Response = GaiAddrs;
+    type Error = io::Error;
+    type Future = Pin<Box<dyn Future<Output = io::Result<GaiAddrs>> + Send>>;
+
+    /// Waits until the resolver is ready.
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    /// Resolves `name`, recording how long it took.
+    fn call(&mut self, name: Name) -> Self::Future {
+        let resolving = self.inner.call(name);
+        Box::pin(async move {
+            let started = Instant::now();
+            let addrs = resolving.await;
+            let elapsed = started.elapsed();
+            let _ = MEASURED.try_with(|measured| measured.dns.set(Some(elapsed)));
+            addrs
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::HyperHttpConnection;
+    use embedded_svc::http::Method;
+    use embedded_svc::http::client::Connection;
+    use embedded_svc::io::Read;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// Tests that the phases recorded while connecting are taken out of the connect
+    /// duration.
+    #[test]
+    fn test_measure() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let (output, phases) = rt.block_on(measure(async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            record_tls(Duration::from_millis(10));
+            7
+        }));
+        assert_eq!(output, 7);
+        assert_eq!(phases.dns, None);
+        assert_eq!(phases.tls, Some(Duration::from_millis(10)));
+        assert!(phases.connect.unwrap() >= Duration::from_millis(10));
+
+        // Outside of a connector call, there is nothing to record into.
+        record_tls(Duration::from_millis(10));
+    }
+
+    /// Tests the timings of a request on a new connection and on a reused one.
+    #[test]
+    fn test_timings() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            for _ in 0..2 {
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+                stream
+                    .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok")
+                    .unwrap();
+            }
+        });
+
+        let mut conn = HyperHttpConnection::new().unwrap().with_timing();
+        assert!(conn.timings().is_none());
+        conn.initiate_request(Method::Get, &format!("http://{addr}/"), &[])
+            .unwrap();
+        conn.initiate_response().unwrap();
+        let timings = conn.timings().unwrap();
+        assert_eq!(timings.dns, None);
+        assert_eq!(timings.tls, None);
+        assert!(timings.connect.unwrap() <= timings.ttfb);
+        assert_eq!(timings.total, None);
+        conn.read(&mut [0u8; 8]).unwrap();
+        assert_eq!(conn.read(&mut [0u8; 8]).unwrap(), 0);
+        assert!(conn.timings().unwrap().total.unwrap() >= timings.ttfb);
+
+        conn.initiate_request(Method::Get, &format!("http://{addr}/"), &[])
+            .unwrap();
+        conn.initiate_response().unwrap();
+        assert_eq!(conn.timings().unwrap().connect, None);
+        server.join().unwrap();
+    }
+}
@@ -0,0 +1,311 @@
+//! TLS settings shared by the HTTP and WebSocket transports.
+//!
+//! A single `TlsConfig` describes trusted roots, the client identity, and
+//! verification policy. It is turned into a `native_tls::TlsConnector` by each
+//! transport, so HTTP and WebSocket connections always agree on TLS behavior.
+//!
+//! `TlsOverrides` maps host patterns to their own `TlsConfig`, consulted by the
+//! HTTP client at connect time, so internal hosts can trust a private root or pin
+//! a certificate while every other host keeps the default policy.
+//!
+//! `TlsInfo` describes the TLS session that carried a response, for tooling that
+//! checks connections against a policy.
+
+use native_tls::TlsStream;
+pub use native_tls::{Certificate, Identity, Protocol};
+use std::io;
+
+/// TLS settings applied when establishing secure connections.
+///
+/// # Example
+///
+/// ```no_run
+/// use native_svc::HyperHttpConnection;
+/// use native_svc::tls::TlsConfig;
+///
+/// let pem = std::fs::read("ca.pem").unwrap();
+/// let tls = TlsConfig::new().add_root_certificate_pem(&pem).unwrap();
+/// let conn = HyperHttpConnection::with_tls_config(&tls).unwrap();
+/// ```
+#[derive(Clone, Default)]
+pub struct TlsConfig {
+    root_certificates: Vec<Certificate>,
+    disable_built_in_roots: bool,
+    identity: Option<Identity>,
+    accept_invalid_certs: bool,
+    accept_invalid_hostnames: bool,
+    min_protocol_version: Option<Protocol>,
+    alpn_protocols: Vec<String>,
+    pinned_certificates: Vec<Vec<u8>>,
+}
+
+impl TlsConfig {
+    /// Creates a configuration trusting the system roots with full verification.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a trusted root certificate.
+    pub fn add_root_certificate(mut self, certificate: Certificate) -> Self {
+        self.root_certificates.push(certificate);
+        self
+    }
+
+    /// Adds a trusted root certificate from PEM data.
+    pub fn add_root_certificate_pem(self, pem: &[u8]) -> Result<Self, native_tls::Error> {
+        Ok(self.add_root_certificate(Certificate::from_pem(pem)?))
+    }
+
+    /// Trusts only the explicitly added roots, ignoring the system store.
+    pub fn disable_built_in_roots(mut self, disable: bool) -> Self {
+        self.disable_built_in_roots = disable;
+        self
+    }
+
+    /// Sets the client certificate presented during the handshake.
+    pub fn identity(mut self, identity: Identity) -> Self {
+        self.identity = Some(identity);
+        self
+    }
+
+    /// Sets the client certificate from a PEM certificate chain and PKCS#8 PEM key.
+    pub fn identity_pem(self, certificate: &[u8], key: &[u8]) -> Result<Self, native_tls::Error> {
+        Ok(self.identity(Identity::from_pkcs8(certificate, key)?))
+    }
+
+    /// Disables certificate validation. Only use this against trusted test servers.
+    pub fn danger_accept_invalid_certs(mut self, accept: bool) -> Self {
+        self.accept_invalid_certs = accept;
+        self
+    }
+
+    /// Disables hostname verification. Only use this against trusted test servers.
+    pub fn danger_accept_invalid_hostnames(mut self, accept: bool) -> Self {
+        self.accept_invalid_hostnames = accept;
+        self
+    }
+
+    /// Sets the minimum accepted TLS protocol version.
+    pub fn min_protocol_version(mut self, version: Protocol) -> Self {
+        self.min_protocol_version = Some(version);
+        self
+    }
+
+    /// Sets the protocols offered through ALPN, in order of preference.
+    ///
+    /// The HTTP client only speaks HTTP/1.1, so it leaves `h2` out of the
+    /// protocols it offers. The protocol the server picked is returned by
+    /// `HyperHttpConnection::negotiated_protocol`.
+    pub fn alpn_protocols(mut self, protocols: &[&str]) -> Self {
+        self.alpn_protocols = protocols.iter().map(|&p| p.to_owned()).collect();
+        self
+    }
+
+    /// Returns these settings without `h2` among the ALPN protocols, for the HTTP
+    /// client.
+    pub(crate) fn without_h2(&self) -> Self {
+        let mut config = self.clone();
+        config.alpn_protocols.retain(|protocol| protocol != "h2");
+        config
+    }
+
+    /// Accepts only `certificate` as the server certificate, in addition to the
+    /// usual verification; may be called several times to pin alternatives.
+    ///
+    /// Pins are checked by the HTTP and WebSocket clients after each handshake.
+    pub fn pin_certificate(mut self, certificate: &Certificate) -> Result<Self, native_tls::Error> {
+        self.pinned_certificates.push(certificate.to_der()?);
+        Ok(self)
+    }
+
+    /// Returns the DER encodings of the pinned certificates.
+    pub(crate) fn pinned_certificates(&self) -> &[Vec<u8>] {
+        &self.pinned_certificates
+    }
+
+    /// Builds a `native_tls::TlsConnector` from these settings.
+    pub fn connector(&self) -> Result<native_tls::TlsConnector, native_tls::Error> {
+        let mut builder = native_tls::TlsConnector::builder();
+
+        for certificate in &self.root_certificates {
+            builder.add_root_certificate(certificate.clone());
+        }
+        if let Some(identity) = &self.identity {
+            builder.identity(identity.clone());
+        }
+        if !self.alpn_protocols.is_empty() {
+            let protocols: Vec<&str> = self.alpn_protocols.iter().map(String::as_str).collect();
+            builder.request_alpns(&protocols);
+        }
+
+        builder
+            .disable_built_in_roots(self.disable_built_in_roots)
+            .danger_accept_invalid_certs(self.accept_invalid_certs)
+            .danger_accept_invalid_hostnames(self.accept_invalid_hostnames)
+            .min_protocol_version(self.min_protocol_version)
+            .build()
+    }
+}
+
+/// Returns `true` if `host` matches `pattern`, a host with at most one `*` wildcard.
+pub(crate) fn host_matches(pattern: &str, host: &str) -> bool {
+    let host = host.trim_matches(['[', ']']).to_ascii_lowercase();
+    let pattern = pattern.to_ascii_lowercase();
+    match pattern.split_once('*') {
+        Some((prefix, suffix)) => {
+            host.len() >= prefix.len() + suffix.len()
+                && host.starts_with(prefix)
+                && host.ends_with(suffix)
+        }
+        None => host == pattern,
+    }
+}
+
+/// TLS settings of the hosts that do not follow the default `TlsConfig`.
+///
+/// # Example
+///
+/// ```no_run
+/// use native_svc::HyperHttpConnection;
+/// use native_svc::tls::{Certificate, TlsConfig, TlsOverrides};
+///
+/// let root = std::fs::read("internal-ca.pem").unwrap();
+/// let ota = Certificate::from_pem(&std::fs::read("ota.pem").unwrap()).unwrap();
+/// let overrides = TlsOverrides::new()
+///     .host("*.internal", TlsConfig::new().add_root_certificate_pem(&root).unwrap())
+///     .host("ota.example.com", TlsConfig::new().pin_certificate(&ota).unwrap())
+///     .host("192.168.*", TlsConfig::new().danger_accept_invalid_certs(true));
+/// let conn = HyperHttpConnection::new()
+///     .unwrap()
+///     .with_tls_overrides(&overrides)
+///     .unwrap();
+/// ```
+#[derive(Clone, Default)]
+pub struct TlsOverrides {
+    hosts: Vec<(String, TlsConfig)>,
+}
+
+impl TlsOverrides {
+    /// Creates an empty set of overrides.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Uses `config` for hosts matching `pattern`.
+    ///
+    /// Patterns are host names or IP addresses with at most one `*` wildcard, such
+    /// as `*.internal` or `192.168.*`. The first matching pattern applies.
+    pub fn host(mut self, pattern: &str, config: TlsConfig) -> Self {
+        self.hosts.push((pattern.to_owned(), config));
+        self
+    }
+
+    /// Returns the settings overriding the default for `host`, if any.
+    pub fn get(&self, host: &str) -> Option<&TlsConfig> {
+        self.hosts
+            .iter()
+            .find(|(pattern, _)| host_matches(pattern, host))
+            .map(|(_, config)| config)
+    }
+
+    /// Returns the host patterns and their settings, in order.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&str, &TlsConfig)> {
+        self.hosts
+            .iter()
+            .map(|(pattern, config)| (pattern.as_str(), config))
+    }
+}
+
+/// Details of the TLS session that carried a response.
+///
+/// `native-tls` does not report the negotiated protocol version, the cipher suite,
+/// or the intermediate certificates sent by the server, so only the ALPN protocol
+/// and the server certificate are available.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TlsInfo {
+    alpn_protocol: Option<Vec<u8>>,
+    /// DER encoding of the server certificate.
+    peer_certificate: Option<Vec<u8>>,
+}
+
+impl TlsInfo {
+    /// Reads the details of the session of `stream`, once its handshake completed.
+    pub(crate) fn new<S: io::Read + io::Write>(stream: &TlsStream<S>) -> Self {
+        let certificate = stream.peer_certificate().ok().flatten();
+        Self {
+            alpn_protocol: stream.negotiated_alpn().ok().flatten(),
+            peer_certificate: certificate.and_then(|c| c.to_der().ok()),
+        }
+    }
+
+    /// Returns the protocol negotiated through ALPN, or `None` if the server picked
+    /// none.
+    pub fn alpn_protocol(&self) -> Option<&[u8]> {
+        self.alpn_protocol.as_deref()
+    }
+
+    /// Returns the DER encoding of the certificate presented by the server.
+    pub fn peer_certificate(&self) -> Option<&[u8]> {
+        self.peer_certificate.as_deref()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Self-signed certificate for `localhost` and `127.0.0.1`.
+    pub(crate) const CERTIFICATE: &[u8] = include_bytes!("../testdata/localhost.crt");
+
+    /// PKCS #8 private key of `CERTIFICATE`.
+    const KEY: &[u8] = include_bytes!("../testdata/localhost.key");
+
+    /// Returns a TLS acceptor presenting `CERTIFICATE`.
+    pub(crate) fn acceptor() -> native_tls::TlsAcceptor {
+        let identity = Identity::from_pkcs8(CERTIFICATE, KEY).unwrap();
+        native_tls::TlsAcceptor::new(identity).unwrap()
+    }
+
+    /// Returns client settings trusting `CERTIFICATE`.
+    pub(crate) fn trusting_config() -> TlsConfig {
+        TlsConfig::new()
+            .add_root_certificate_pem(CERTIFICATE)
+            .unwrap()
+    }
+
+    /// Tests matching hosts against override patterns.
+    #[test]
+    fn test_tls_overrides() {
+        let overrides = TlsOverrides::new()
+            .host(
+                "ota.example.com",
+                TlsConfig::new().min_protocol_version(Protocol::Tlsv12),
+            )
+            .host("*.internal", TlsConfig::new().disable_built_in_roots(true))
+            .host(
+                "192.168.*",
+                TlsConfig::new().danger_accept_invalid_certs(true),
+            );
+
+        let ota = overrides.get("OTA.example.com").unwrap();
+        assert!(matches!(ota.min_protocol_version, Some(Protocol::Tlsv12)));
+        assert!(
+            overrides
+                .get("broker.internal")
+                .unwrap()
+                .disable_built_in_roots
+        );
+        assert!(overrides.get("internal").is_none());
+        assert!(overrides.get("192.168.4.1").unwrap().accept_invalid_certs);
+        assert!(overrides.get("10.192.168.1").is_none());
+        assert!(overrides.get("example.com").is_none());
+    }
+
+    /// Tests that `h2` is left out of the ALPN protocols of the HTTP client.
+    #[test]
+    fn test_without_h2() {
+        let config = TlsConfig::new().alpn_protocols(&["h2", "http/1.1", "mqtt"]);
+        assert_eq!(config.without_h2().alpn_protocols, ["http/1.1", "mqtt"]);
+        assert_eq!(config.alpn_protocols, ["h2", "http/1.1", "mqtt"]);
+    }
+}
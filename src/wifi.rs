@@ -0,0 +1,21 @@
+//! Wi-Fi control implementing `embedded_svc::wifi`.
+//!
+//! `mock::MockWifi` simulates a Wi-Fi driver with scripted access points, latencies and
+//! failures, so provisioning and connection state machines written against the ESP
+//! `Wifi` trait can be unit-tested on the host. On Linux,
+//! `network_manager::NetworkManagerWifi` controls the real adapter through
+//! NetworkManager.
+
+pub mod mock;
+#[cfg(target_os = "linux")]
+pub mod network_manager;
+
+use embedded_svc::wifi::{ClientConfiguration, Configuration};
+
+/// Returns the station part of `conf`, if it has one.
+pub(crate) fn client_configuration(conf: &Configuration) -> Option<&ClientConfiguration> {
+    match conf {
+        Configuration::Client(client) | Configuration::Mixed(client, _) => Some(client),
+        _ => None,
+    }
+}
@@ -0,0 +1,348 @@
+//! Scriptable Wi-Fi driver for host tests.
+//!
+//! `MockWifi` behaves like a station driver: it must be started before it can scan or
+//! connect, and connecting succeeds only for a scripted access point whose SSID and
+//! password match the client configuration. Scans and connections can be slowed down
+//! and any operation can be made to fail, to exercise timeout and retry paths.
+
+use super::client_configuration;
+use crate::error::WifiError;
+use embedded_svc::wifi::{AccessPointInfo, AuthMethod, Capability, Configuration, Wifi};
+use enumset::EnumSet;
+use std::thread;
+use std::time::Duration;
+
+/// A `Wifi` operation whose failure can be injected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    /// `set_configuration`.
+    SetConfiguration,
+    /// `start`.
+    Start,
+    /// `stop`.
+    Stop,
+    /// `scan` and `scan_n`.
+    Scan,
+    /// `connect`.
+    Connect,
+    /// `disconnect`.
+    Disconnect,
+}
+
+impl Operation {
+    /// Returns the operation name used in `WifiError::Injected`.
+    fn name(self) -> &'static str {
+        match self {
+            Self::SetConfiguration => "set_configuration",
+            Self::Start => "start",
+            Self::Stop => "stop",
+            Self::Scan => "scan",
+            Self::Connect => "connect",
+            Self::Disconnect => "disconnect",
+        }
+    }
+}
+
+/// A scripted access point.
+#[derive(Debug, Clone)]
+struct Network {
+    info: AccessPointInfo,
+    password: String,
+}
+
+/// A `Wifi` driver simulating scripted access points.
+///
+/// # Example
+///
+/// ```no_run
+/// use embedded_svc::wifi::{AccessPointInfo, ClientConfiguration, Configuration, Wifi};
+/// use native_svc::wifi::mock::{MockWifi, Operation};
+///
+/// let mut wifi = MockWifi::new();
+/// wifi.add_access_point(
+///     AccessPointInfo {
+///         ssid: "home".try_into().unwrap(),
+///         ..Default::default()
+///     },
+///     "secret",
+/// );
+/// wifi.fail_next(Operation::Connect);
+///
+/// wifi.set_configuration(&Configuration::Client(ClientConfiguration {
+///     ssid: "home".try_into().unwrap(),
+///     password: "secret".try_into().unwrap(),
+///     ..Default::default()
+/// }))
+/// .unwrap();
+/// wifi.start().unwrap();
+/// assert!(wifi.connect().is_err());
+/// wifi.connect().unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct MockWifi {
+    capabilities: EnumSet<Capability>,
+    configuration: Configuration,
+    networks: Vec<Network>,
+    scan_latency: Duration,
+    connect_latency: Duration,
+    failures: Vec<Operation>,
+    started: bool,
+    connected: bool,
+}
+
+impl MockWifi {
+    /// Creates a stopped driver with no access points in range.
+    pub fn new() -> Self {
+        Self {
+            capabilities: Capability::Client | Capability::AccessPoint | Capability::Mixed,
+            configuration: Configuration::None,
+            networks: Vec::new(),
+            scan_latency: Duration::ZERO,
+            connect_latency: Duration::ZERO,
+            failures: Vec::new(),
+            started: false,
+            connected: false,
+        }
+    }
+
+    /// Sets the capabilities reported by `get_capabilities`.
+    pub fn set_capabilities(&mut self, capabilities: EnumSet<Capability>) {
+        self.capabilities = capabilities;
+    }
+
+    /// Puts an access point in range, accepting `password` if it is secured.
+    pub fn add_access_point(&mut self, info: AccessPointInfo, password: &str) {
+        self.remove_access_point(&info.ssid);
+        self.networks.push(Network {
+            info,
+            password: password.to_owned(),
+        });
+    }
+
+    /// Takes the access point `ssid` out of range, dropping the connection to it.
+    pub fn remove_access_point(&mut self, ssid: &str) {
+        self.networks.retain(|network| network.info.ssid != ssid);
+        if self.connected
+            && self
+                .connected_ssid()
+                .is_some_and(|connected| connected == ssid)
+        {
+            self.connected = false;
+        }
+    }
+
+    /// Sets how long each scan takes.
+    pub fn set_scan_latency(&mut self, latency: Duration) {
+        self.scan_latency = latency;
+    }
+
+    /// Sets how long each connection attempt takes.
+    pub fn set_connect_latency(&mut self, latency: Duration) {
+        self.connect_latency = latency;
+    }
+
+    /// Makes the next call of `operation` fail with `WifiError::Injected`.
+    ///
+    /// Calling this several times fails that many consecutive calls.
+    pub fn fail_next(&mut self, operation: Operation) {
+        self.failures.push(operation);
+    }
+
+    /// Drops the connection as if the access point went away.
+    pub fn drop_connection(&mut self) {
+        self.connected = false;
+    }
+
+    /// Returns the SSID of the configured access point.
+    fn connected_ssid(&self) -> Option<&str> {
+        client_configuration(&self.configuration).map(|client| client.ssid.as_str())
+    }
+
+    /// Fails if a failure of `operation` was injected.
+    fn check(&mut self, operation: Operation) -> Result<(), WifiError> {
+        match self.failures.iter().position(|failed| *failed == operation) {
+            Some(index) => {
+                self.failures.remove(index);
+                Err(WifiError::Injected(operation.name()))
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Fails if the driver is not started.
+    fn check_started(&self) -> Result<(), WifiError> {
+        if self.started {
+            Ok(())
+        } else {
+            Err(WifiError::NotStarted)
+        }
+    }
+
+    /// Returns the access points in range, strongest first.
+    fn scan_networks(&mut self) -> Result<Vec<AccessPointInfo>, WifiError> {
+        self.check(Operation::Scan)?;
+        self.check_started()?;
+        thread::sleep(self.scan_latency);
+
+        let mut access_points: Vec<_> = self
+            .networks
+            .iter()
+            .map(|network| network.info.clone())
+            .collect();
+        access_points.sort_by_key(|info| std::cmp::Reverse(info.signal_strength));
+        Ok(access_points)
+    }
+}
+
+impl Default for MockWifi {
+    /// Creates a stopped driver with no access points in range.
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Wifi for MockWifi {
+    type Error = WifiError;
+
+    /// Returns the scripted capabilities; all modes by default.
+    fn get_capabilities(&self) -> Result<EnumSet<Capability>, Self::Error> {
+        Ok(self.capabilities)
+    }
+
+    /// Returns the current configuration.
+    fn get_configuration(&self) -> Result<Configuration, Self::Error> {
+        Ok(self.configuration.clone())
+    }
+
+    /// Replaces the configuration, disconnecting from the current access point.
+    fn set_configuration(&mut self, conf: &Configuration) -> Result<(), Self::Error> {
+        self.check(Operation::SetConfiguration)?;
+        self.configuration = conf.clone();
+        self.connected = false;
+        Ok(())
+    }
+
+    /// Starts the driver.
+    fn start(&mut self) -> Result<(), Self::Error> {
+        self.check(Operation::Start)?;
+        self.started = true;
+        Ok(())
+    }
+
+    /// Stops the driver, disconnecting from the current access point.
+    fn stop(&mut self) -> Result<(), Self::Error> {
+        self.check(Operation::Stop)?;
+        self.started = false;
+        self.connected = false;
+        Ok(())
+    }
+
+    /// Connects to the configured access point.
+    fn connect(&mut self) -> Result<(), Self::Error> {
+        self.check(Operation::Connect)?;
+        self.check_started()?;
+        let client =
+            client_configuration(&self.configuration).ok_or(WifiError::InvalidConfiguration)?;
+        thread::sleep(self.connect_latency);
+
+        let network = self
+            .networks
+            .iter()
+            .find(|network| {
+                network.info.ssid == client.ssid
+                    && client.bssid.is_none_or(|bssid| bssid == network.info.bssid)
+            })
+            .ok_or_else(|| WifiError::NotFound(client.ssid.to_string()))?;
+        let open = matches!(network.info.auth_method, None | Some(AuthMethod::None));
+        if !open && network.password != client.password.as_str() {
+            return Err(WifiError::AuthFailed(client.ssid.to_string()));
+        }
+
+        self.connected = true;
+        Ok(())
+    }
+
+    /// Disconnects from the current access point.
+    fn disconnect(&mut self) -> Result<(), Self::Error> {
+        self.check(Operation::Disconnect)?;
+        self.connected = false;
+        Ok(())
+    }
+
+    /// Returns `true` once started.
+    fn is_started(&self) -> Result<bool, Self::Error> {
+        Ok(self.started)
+    }
+
+    /// Returns `true` while connected to an access point.
+    fn is_connected(&self) -> Result<bool, Self::Error> {
+        Ok(self.connected)
+    }
+
+    /// Returns up to `N` access points in range and the total number found.
+    fn scan_n<const N: usize>(
+        &mut self,
+    ) -> Result<(heapless::Vec<AccessPointInfo, N>, usize), Self::Error> {
+        let access_points = self.scan_networks()?;
+        let total = access_points.len();
+        Ok((access_points.into_iter().take(N).collect(), total))
+    }
+
+    /// Returns the access points in range, strongest first.
+    fn scan(&mut self) -> Result<Vec<AccessPointInfo>, Self::Error> {
+        self.scan_networks()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_svc::wifi::ClientConfiguration;
+
+    /// Tests scanning and connecting with wrong credentials and injected failures.
+    #[test]
+    fn test_scripted_connect() {
+        let mut wifi = MockWifi::new();
+        let access_point = |ssid: &str, signal_strength| AccessPointInfo {
+            ssid: ssid.try_into().unwrap(),
+            signal_strength,
+            auth_method: Some(AuthMethod::WPA2Personal),
+            ..Default::default()
+        };
+        wifi.add_access_point(access_point("office", -70), "office-key");
+        wifi.add_access_point(access_point("home", -40), "secret");
+
+        let configure = |wifi: &mut MockWifi, password: &str| {
+            let client = ClientConfiguration {
+                ssid: "home".try_into().unwrap(),
+                password: password.try_into().unwrap(),
+                ..Default::default()
+            };
+            wifi.set_configuration(&Configuration::Client(client))
+                .unwrap();
+        };
+
+        configure(&mut wifi, "secret");
+        assert!(matches!(wifi.connect(), Err(WifiError::NotStarted)));
+        wifi.start().unwrap();
+
+        let (access_points, total) = wifi.scan_n::<1>().unwrap();
+        assert_eq!(total, 2);
+        assert_eq!(access_points[0].ssid, "home");
+
+        configure(&mut wifi, "wrong");
+        assert!(matches!(wifi.connect(), Err(WifiError::AuthFailed(_))));
+        configure(&mut wifi, "secret");
+        wifi.fail_next(Operation::Connect);
+        assert!(matches!(
+            wifi.connect(),
+            Err(WifiError::Injected("connect"))
+        ));
+        wifi.connect().unwrap();
+        assert!(wifi.is_connected().unwrap());
+
+        wifi.remove_access_point("home");
+        assert!(!wifi.is_connected().unwrap());
+        assert!(matches!(wifi.connect(), Err(WifiError::NotFound(_))));
+    }
+}
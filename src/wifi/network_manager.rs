@@ -0,0 +1,379 @@
+//! Wi-Fi control through NetworkManager on Linux.
+//!
+//! `NetworkManagerWifi` drives the host Wi-Fi adapter with `nmcli`, so firmware code
+//! managing Wi-Fi through the `Wifi` trait also runs on Linux gateways and single-board
+//! computers. Starting and stopping toggle the Wi-Fi radio, or only whether NetworkManager
+//! manages the device when one is chosen with `with_interface`. Connecting activates a
+//! NetworkManager connection for the configured access point, creating it if needed; the
+//! password is written to `nmcli` on standard input so it never shows up in the process
+//! list.
+
+use super::client_configuration;
+use crate::error::WifiError;
+use embedded_svc::wifi::{
+    AccessPointInfo, AuthMethod, Capability, ClientConfiguration, Configuration, Wifi,
+};
+use enumset::EnumSet;
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+/// A `Wifi` driver for the host adapter managed by NetworkManager.
+///
+/// # Example
+///
+/// ```no_run
+/// use embedded_svc::wifi::{ClientConfiguration, Configuration, Wifi};
+/// use native_svc::wifi::network_manager::NetworkManagerWifi;
+///
+/// let mut wifi = NetworkManagerWifi::new().with_interface("wlan0");
+/// wifi.set_configuration(&Configuration::Client(ClientConfiguration {
+///     ssid: "home".try_into().unwrap(),
+///     password: "secret".try_into().unwrap(),
+///     ..Default::default()
+/// }))
+/// .unwrap();
+/// wifi.start().unwrap();
+/// wifi.connect().unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct NetworkManagerWifi {
+    interface: Option<String>,
+    configuration: Configuration,
+}
+
+impl NetworkManagerWifi {
+    /// Creates a driver for the first Wi-Fi device known to NetworkManager.
+    pub fn new() -> Self {
+        Self {
+            interface: None,
+            configuration: Configuration::None,
+        }
+    }
+
+    /// Uses the Wi-Fi device `interface`, such as `wlan0`.
+    pub fn with_interface(mut self, interface: &str) -> Self {
+        self.interface = Some(interface.to_owned());
+        self
+    }
+
+    /// Returns the Wi-Fi device name and its NetworkManager state.
+    fn device(&self) -> Result<(String, String), WifiError> {
+        let output = nmcli(&["-t", "-f", "DEVICE,TYPE,STATE", "device"])?;
+        output
+            .lines()
+            .map(split_terse)
+            .find(|fields| {
+                fields.len() == 3
+                    && fields[1] == "wifi"
+                    && self
+                        .interface
+                        .as_ref()
+                        .is_none_or(|name| *name == fields[0])
+            })
+            .map(|fields| (fields[0].clone(), fields[2].clone()))
+            .ok_or(WifiError::NoInterface)
+    }
+
+    /// Returns the `nmcli` arguments turning Wi-Fi on or off.
+    ///
+    /// Without an interface this toggles the radio of every Wi-Fi device; with one, only
+    /// whether NetworkManager manages that device.
+    fn power_args(&self, on: bool) -> Vec<&str> {
+        match &self.interface {
+            Some(interface) => vec![
+                "device",
+                "set",
+                interface,
+                "managed",
+                if on { "yes" } else { "no" },
+            ],
+            None => vec!["radio", "wifi", if on { "on" } else { "off" }],
+        }
+    }
+}
+
+impl Default for NetworkManagerWifi {
+    /// Creates a driver for the first Wi-Fi device known to NetworkManager.
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Wifi for NetworkManagerWifi {
+    type Error = WifiError;
+
+    /// Returns station mode, the only mode supported.
+    fn get_capabilities(&self) -> Result<EnumSet<Capability>, Self::Error> {
+        Ok(Capability::Client.into())
+    }
+
+    /// Returns the configuration set with `set_configuration`.
+    fn get_configuration(&self) -> Result<Configuration, Self::Error> {
+        Ok(self.configuration.clone())
+    }
+
+    /// Sets the access point used by `connect`.
+    fn set_configuration(&mut self, conf: &Configuration) -> Result<(), Self::Error> {
+        client_configuration(conf).ok_or(WifiError::InvalidConfiguration)?;
+        self.configuration = conf.clone();
+        Ok(())
+    }
+
+    /// Turns the Wi-Fi radio on, or hands the chosen interface to NetworkManager.
+    fn start(&mut self) -> Result<(), Self::Error> {
+        nmcli(&self.power_args(true)).map(drop)
+    }
+
+    /// Turns the Wi-Fi radio off, or releases the chosen interface from NetworkManager.
+    ///
+    /// Releasing an interface disconnects it but leaves other Wi-Fi devices untouched.
+    fn stop(&mut self) -> Result<(), Self::Error> {
+        nmcli(&self.power_args(false)).map(drop)
+    }
+
+    /// Connects to the configured access point.
+    fn connect(&mut self) -> Result<(), Self::Error> {
+        let client =
+            client_configuration(&self.configuration).ok_or(WifiError::InvalidConfiguration)?;
+        let (device, _) = self.device()?;
+        let bssid = client.bssid.map(format_bssid);
+        let args = connect_args(client, &device, bssid.as_deref());
+
+        if client.password.is_empty() {
+            nmcli(&args).map(drop)
+        } else {
+            nmcli_with_input(&args, &format!("{}\n", client.password)).map(drop)
+        }
+    }
+
+    /// Disconnects the Wi-Fi device.
+    fn disconnect(&mut self) -> Result<(), Self::Error> {
+        let (device, _) = self.device()?;
+        nmcli(&["device", "disconnect", &device]).map(drop)
+    }
+
+    /// Returns `true` if the Wi-Fi radio is on, or if the chosen interface is managed and
+    /// available.
+    fn is_started(&self) -> Result<bool, Self::Error> {
+        if self.interface.is_some() {
+            let (_, state) = self.device()?;
+            return Ok(state != "unmanaged" && state != "unavailable");
+        }
+        Ok(nmcli(&["radio", "wifi"])?.trim() == "enabled")
+    }
+
+    /// Returns `true` if the Wi-Fi device is connected.
+    fn is_connected(&self) -> Result<bool, Self::Error> {
+        Ok(self.device()?.1 == "connected")
+    }
+
+    /// Returns up to `N` access points in range and the total number found.
+    fn scan_n<const N: usize>(
+        &mut self,
+    ) -> Result<(heapless::Vec<AccessPointInfo, N>, usize), Self::Error> {
+        let access_points = self.scan()?;
+        let total = access_points.len();
+        Ok((access_points.into_iter().take(N).collect(), total))
+    }
+
+    /// Rescans and returns the access points in range, strongest first.
+    fn scan(&mut self) -> Result<Vec<AccessPointInfo>, Self::Error> {
+        let (device, _) = self.device()?;
+        let output = nmcli(&[
+            "-t",
+            "-f",
+            "SSID,BSSID,CHAN,SIGNAL,SECURITY",
+            "device",
+            "wifi",
+            "list",
+            "--rescan",
+            "yes",
+            "ifname",
+            &device,
+        ])?;
+
+        let mut access_points: Vec<_> = output.lines().filter_map(parse_access_point).collect();
+        access_points.sort_by_key(|info| std::cmp::Reverse(info.signal_strength));
+        Ok(access_points)
+    }
+}
+
+/// Returns the `nmcli` arguments connecting `device` to the access point of `client`.
+///
+/// The password is not among them: `--ask` makes `nmcli` prompt for it on standard input.
+fn connect_args<'a>(
+    client: &'a ClientConfiguration,
+    device: &'a str,
+    bssid: Option<&'a str>,
+) -> Vec<&'a str> {
+    let mut args = vec!["device", "wifi", "connect", client.ssid.as_str()];
+    if !client.password.is_empty() {
+        args.insert(0, "--ask");
+    }
+    if let Some(bssid) = bssid {
+        args.extend(["bssid", bssid]);
+    }
+    args.extend(["ifname", device]);
+    args
+}
+
+/// Runs `nmcli` with `args`, returning its standard output.
+fn nmcli(args: &[&str]) -> Result<String, WifiError> {
+    let output = Command::new("nmcli").args(args).output()?;
+    finish(output)
+}
+
+/// Runs `nmcli` with `args`, writing `input` to its standard input.
+fn nmcli_with_input(args: &[&str], input: &str) -> Result<String, WifiError> {
+    let mut child = Command::new("nmcli")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    // nmcli may exit without prompting, for example when the connection already has secrets.
+    match stdin.write_all(input.as_bytes()) {
+        Err(err) if err.kind() != io::ErrorKind::BrokenPipe => return Err(err.into()),
+        _ => drop(stdin),
+    }
+    finish(child.wait_with_output()?)
+}
+
+/// Returns the standard output of a finished `nmcli`, or its error message.
+fn finish(output: std::process::Output) -> Result<String, WifiError> {
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(WifiError::Command(stderr.trim().to_owned()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Splits a line of `nmcli -t` output into fields, unescaping `\:` and `\\`.
+fn split_terse(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => fields.last_mut().unwrap().extend(chars.next()),
+            ':' => fields.push(String::new()),
+            c => fields.last_mut().unwrap().push(c),
+        }
+    }
+    fields
+}
+
+/// Parses a `SSID:BSSID:CHAN:SIGNAL:SECURITY` line of a scan.
+fn parse_access_point(line: &str) -> Option<AccessPointInfo> {
+    let fields = split_terse(line);
+    let [ssid, bssid, channel, signal, security] = fields.as_slice() else {
+        return None;
+    };
+
+    let mut bssid_bytes = [0; 6];
+    let mut octets = bssid.split(':');
+    for byte in &mut bssid_bytes {
+        *byte = u8::from_str_radix(octets.next()?, 16).ok()?;
+    }
+    // NetworkManager reports signal quality in percent; map it back to dBm.
+    let quality: i16 = signal.parse().ok()?;
+
+    Some(AccessPointInfo {
+        ssid: ssid.as_str().try_into().ok()?,
+        bssid: bssid_bytes,
+        channel: channel.parse().ok()?,
+        signal_strength: (quality.clamp(0, 100) / 2 - 100) as i8,
+        auth_method: Some(auth_method(security)),
+        ..Default::default()
+    })
+}
+
+/// Maps an `nmcli` security description to an `AuthMethod`.
+fn auth_method(security: &str) -> AuthMethod {
+    let has = |name| security.split_whitespace().any(|word| word == name);
+    if has("802.1X") {
+        AuthMethod::WPA2Enterprise
+    } else if has("WPA3") && has("WPA2") {
+        AuthMethod::WPA2WPA3Personal
+    } else if has("WPA3") {
+        AuthMethod::WPA3Personal
+    } else if has("WPA2") && has("WPA1") {
+        AuthMethod::WPAWPA2Personal
+    } else if has("WPA2") {
+        AuthMethod::WPA2Personal
+    } else if has("WPA1") {
+        AuthMethod::WPA
+    } else if has("WEP") {
+        AuthMethod::WEP
+    } else {
+        AuthMethod::None
+    }
+}
+
+/// Formats a BSSID as colon-separated hex.
+fn format_bssid(bssid: [u8; 6]) -> String {
+    bssid
+        .iter()
+        .map(|byte| format!("{byte:02X}"))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests parsing escaped `nmcli` scan output.
+    #[test]
+    fn test_parse_access_point() {
+        let line = r"cafe\:guest:AA\:BB\:CC\:00\:11\:22:6:80:WPA1 WPA2";
+        let info = parse_access_point(line).unwrap();
+        assert_eq!(info.ssid, "cafe:guest");
+        assert_eq!(format_bssid(info.bssid), "AA:BB:CC:00:11:22");
+        assert_eq!(info.channel, 6);
+        assert_eq!(info.signal_strength, -60);
+        assert_eq!(info.auth_method, Some(AuthMethod::WPAWPA2Personal));
+
+        let open = parse_access_point(r"lobby:AA\:BB\:CC\:00\:11\:23:11:40:").unwrap();
+        assert_eq!(open.auth_method, Some(AuthMethod::None));
+        assert!(parse_access_point("garbage").is_none());
+    }
+
+    /// Tests that the password is kept out of the `nmcli` arguments.
+    #[test]
+    fn test_connect_args() {
+        let client = ClientConfiguration {
+            ssid: "home".try_into().unwrap(),
+            password: "secret".try_into().unwrap(),
+            ..Default::default()
+        };
+        let args = connect_args(&client, "wlan0", Some("AA:BB:CC:00:11:22"));
+        assert_eq!(args[..2], ["--ask", "device"]);
+        assert!(!args.contains(&"secret"));
+        assert!(args.ends_with(&["ifname", "wlan0"]));
+
+        let open = ClientConfiguration {
+            ssid: "lobby".try_into().unwrap(),
+            ..Default::default()
+        };
+        assert_eq!(
+            connect_args(&open, "wlan0", None),
+            ["device", "wifi", "connect", "lobby", "ifname", "wlan0"]
+        );
+    }
+
+    /// Tests that starting and stopping only touch the chosen interface.
+    #[test]
+    fn test_power_args() {
+        assert_eq!(
+            NetworkManagerWifi::new().power_args(false),
+            ["radio", "wifi", "off"]
+        );
+        assert_eq!(
+            NetworkManagerWifi::new()
+                .with_interface("wlan1")
+                .power_args(true),
+            ["device", "set", "wlan1", "managed", "yes"]
+        );
+    }
+}
@@ -0,0 +1,351 @@
+//! WebSocket client implementation using `tokio-tungstenite` and `tokio` runtime.
+//!
+//! This module provides a `TungsteniteWsConnection` type that implements the `embedded_svc`
+//! WebSocket `Sender` and `Receiver` traits, allowing firmware WebSocket logic to run
+//! on top of the asynchronous `tokio-tungstenite` library.
+
+pub mod asynch;
+#[cfg(feature = "ws-deflate")]
+pub mod deflate;
+pub mod reconnect;
+
+use crate::error::WsError;
+use crate::runtime::RuntimeConfig;
+use crate::tls::TlsConfig;
+use crate::ws::asynch::AsyncTungsteniteWsConnection;
+#[cfg(feature = "ws-deflate")]
+use crate::ws::deflate::{DeflateConfig, DeflateStream};
+use embedded_svc::ws::asynch::{Receiver as _, Sender as _};
+use embedded_svc::ws::{ErrorType, FrameType, Receiver, Sender};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+use tokio::runtime::Runtime;
+use tokio_native_tls::TlsConnector;
+use tokio_tungstenite::WebSocketStream;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::error::{Error, UrlError};
+use tokio_tungstenite::tungstenite::handshake::client::Response;
+use tokio_tungstenite::tungstenite::protocol::WebSocketConfig;
+
+/// Byte stream carrying the frames of a WebSocket connection.
+pub(crate) trait Transport: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Transport for T {}
+
+/// Type alias for the WebSocket stream over plain or TLS TCP.
+pub(crate) type WsStream = WebSocketStream<Box<dyn Transport>>;
+
+/// Size limits and fragmentation of WebSocket messages.
+///
+/// The limits on incoming frames and messages keep a peer from making the client
+/// buffer arbitrarily large messages; a message exceeding them fails the connection
+/// with `WsError::MessageTooLarge`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WsConfig {
+    /// Upper bound for the payload of a single incoming frame; `None` for no limit.
+    pub max_frame_size: Option<usize>,
+    /// Upper bound for an incoming message reassembled from its frames; `None` for
+    /// no limit.
+    pub max_message_size: Option<usize>,
+    /// Payload size of the frames outgoing text and binary messages are split into;
+    /// `None` sends every message as a single frame.
+    pub fragment_size: Option<usize>,
+    /// `permessage-deflate` parameters offered to the server; `None` disables
+    /// compression.
+    #[cfg(feature = "ws-deflate")]
+    pub deflate: Option<DeflateConfig>,
+}
+
+impl Default for WsConfig {
+    fn default() -> Self {
+        Self {
+            max_frame_size: Some(16 << 20),
+            max_message_size: Some(64 << 20),
+            fragment_size: None,
+            #[cfg(feature = "ws-deflate")]
+            deflate: None,
+        }
+    }
+}
+
+impl WsConfig {
+    /// Returns the `tungstenite` settings enforcing the incoming limits.
+    pub(crate) fn websocket_config(&self) -> WebSocketConfig {
+        WebSocketConfig::default()
+            .max_frame_size(self.max_frame_size)
+            .max_message_size(self.max_message_size)
+    }
+}
+
+/// Performs the opening handshake with `uri`, over TLS for `wss://` URIs.
+///
+/// Uses `tls` if set and the default TLS settings otherwise, rejecting servers whose
+/// certificate is not pinned, and offers compression if `config` enables it.
+pub(crate) async fn open(
+    uri: &str,
+    tls: Option<&TlsConfig>,
+    config: &WsConfig,
+) -> Result<(WsStream, Response), WsError> {
+    #[allow(unused_mut)]
+    let mut request = uri.into_client_request()?;
+    #[cfg(feature = "ws-deflate")]
+    if let Some(deflate) = &config.deflate {
+        let offer = deflate.offer().parse().map_err(Error::from)?;
+        request
+            .headers_mut()
+            .insert("Sec-WebSocket-Extensions", offer);
+    }
+
+    let secure = match request.uri().scheme_str() {
+        Some("wss") => true,
+        Some("ws") => false,
+        _ => return Err(Error::Url(UrlError::UnsupportedUrlScheme).into()),
+    };
+    let host = request
+        .uri()
+        .host()
+        .ok_or(Error::Url(UrlError::NoHostName))?
+        .trim_matches(['[', ']'])
+        .to_owned();
+    let port = request
+        .uri()
+        .port_u16()
+        .unwrap_or(if secure { 443 } else { 80 });
+
+    let tcp = TcpStream::connect((host.as_str(), port)).await?;
+    tcp.set_nodelay(true)?;
+    let stream: Box<dyn Transport> = if secure {
+        let default = TlsConfig::default();
+        let tls = tls.unwrap_or(&default);
+        let stream = TlsConnector::from(tls.connector()?)
+            .connect(&host, tcp)
+            .await?;
+        let pins = tls.pinned_certificates();
+        if !pins.is_empty() {
+            let certificate = stream.get_ref().peer_certificate()?;
+            let der = certificate.map(|c| c.to_der()).transpose()?;
+            if !der.is_some_and(|der| pins.contains(&der)) {
+                return Err(WsError::CertificateNotPinned(host));
+            }
+        }
+        Box::new(stream)
+    } else {
+        Box::new(tcp)
+    };
+    #[cfg(feature = "ws-deflate")]
+    let stream: Box<dyn Transport> = match config.deflate {
+        Some(deflate) => Box::new(DeflateStream::new(stream, deflate)),
+        None => stream,
+    };
+
+    Ok(tokio_tungstenite::client_async_with_config(
+        request,
+        stream,
+        Some(config.websocket_config()),
+    )
+    .await?)
+}
+
+/// A WebSocket client connection using `tokio-tungstenite` and a Tokio runtime.
+///
+/// `TungsteniteWsConnection` wraps a blocking API on top of an
+/// `AsyncTungsteniteWsConnection`, implementing the `embedded_svc::ws` `Sender`
+/// and `Receiver` traits.
+///
+/// # Example
+///
+/// ```no_run
+/// use embedded_svc::ws::{FrameType, Receiver, Sender};
+/// use native_svc::ws::TungsteniteWsConnection;
+///
+/// let mut conn = TungsteniteWsConnection::connect("wss://example.com/ws").unwrap();
+/// conn.send(FrameType::Text(false), b"hello").unwrap();
+///
+/// let mut buf = [0u8; 1024];
+/// let (frame_type, len) = conn.recv(&mut buf).unwrap();
+/// ```
+pub struct TungsteniteWsConnection {
+    rt: Runtime,
+    inner: AsyncTungsteniteWsConnection,
+}
+
+impl TungsteniteWsConnection {
+    /// Connects to the WebSocket server at `uri` (`ws://` or `wss://`).
+    ///
+    /// Initializes a Tokio runtime and performs the opening handshake. Returns an
+    /// error if the runtime cannot be created or the handshake fails.
+    pub fn connect(uri: &str) -> Result<Self, WsError> {
+        let rt = RuntimeConfig::default()
+            .build()
+            .map_err(WsError::RuntimeCreation)?;
+        let inner = rt.block_on(AsyncTungsteniteWsConnection::connect(uri))?;
+
+        Ok(Self { rt, inner })
+    }
+
+    /// Connects to `uri` using the given TLS settings for `wss://` URIs.
+    ///
+    /// Accepts the same `TlsConfig` used by `HyperHttpConnection`.
+    pub fn connect_with_tls(uri: &str, tls: &TlsConfig) -> Result<Self, WsError> {
+        let rt = RuntimeConfig::default()
+            .build()
+            .map_err(WsError::RuntimeCreation)?;
+        let inner = rt.block_on(AsyncTungsteniteWsConnection::connect_with_tls(uri, tls))?;
+
+        Ok(Self { rt, inner })
+    }
+
+    /// Connects to `uri` with the given size limits and compression, and TLS settings
+    /// for `wss://` URIs if `tls` is set.
+    pub fn connect_with_config(
+        uri: &str,
+        tls: Option<&TlsConfig>,
+        config: WsConfig,
+    ) -> Result<Self, WsError> {
+        let rt = RuntimeConfig::default()
+            .build()
+            .map_err(WsError::RuntimeCreation)?;
+        let inner = rt.block_on(AsyncTungsteniteWsConnection::connect_with_config(
+            uri, tls, config,
+        ))?;
+
+        Ok(Self { rt, inner })
+    }
+
+    /// Returns `true` while the underlying socket is open.
+    pub fn is_connected(&self) -> bool {
+        self.inner.is_connected()
+    }
+}
+
+impl ErrorType for TungsteniteWsConnection {
+    /// The error type returned by this connection.
+    type Error = WsError;
+}
+
+impl Sender for TungsteniteWsConnection {
+    /// Sends a frame, reassembling fragmented frames into a single message.
+    fn send(&mut self, frame_type: FrameType, frame_data: &[u8]) -> Result<(), Self::Error> {
+        self.rt.block_on(self.inner.send(frame_type, frame_data))
+    }
+}
+
+impl Receiver for TungsteniteWsConnection {
+    /// Receives the next frame into `frame_data_buf`.
+    ///
+    /// Returns `FrameType::SocketClose` once the socket has been closed. If the
+    /// buffer is too small, the frame is kept and `WsError::BufferTooSmall` reports
+    /// the required length so the call can be retried with a larger buffer.
+    fn recv(&mut self, frame_data_buf: &mut [u8]) -> Result<(FrameType, usize), Self::Error> {
+        self.rt.block_on(self.inner.recv(frame_data_buf))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::tls::{self, Certificate};
+    use futures_util::{SinkExt, StreamExt};
+    use std::net::TcpListener;
+
+    /// Spawns a single-connection local echo server and returns its `ws://` URI.
+    pub(crate) fn spawn_echo_server() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        std::thread::spawn(move || {
+            let rt = RuntimeConfig::default().build().unwrap();
+            rt.block_on(async move {
+                listener.set_nonblocking(true).unwrap();
+                let listener = tokio::net::TcpListener::from_std(listener).unwrap();
+                let (stream, _) = listener.accept().await.unwrap();
+                let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+                while let Some(Ok(message)) = ws.next().await {
+                    // Reading on after a close sends the reply and ends the stream.
+                    if !message.is_close() {
+                        ws.send(message).await.unwrap();
+                    }
+                }
+            });
+        });
+
+        format!("ws://{addr}")
+    }
+
+    /// Spawns a local `wss://` echo server presenting the test certificate, and
+    /// returns its URI.
+    fn spawn_tls_echo_server() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let acceptor = tokio_native_tls::TlsAcceptor::from(tls::tests::acceptor());
+
+        std::thread::spawn(move || {
+            let rt = RuntimeConfig::default().build().unwrap();
+            rt.block_on(async move {
+                listener.set_nonblocking(true).unwrap();
+                let listener = tokio::net::TcpListener::from_std(listener).unwrap();
+                loop {
+                    let (stream, _) = listener.accept().await.unwrap();
+                    let acceptor = acceptor.clone();
+                    tokio::spawn(async move {
+                        let stream = acceptor.accept(stream).await?;
+                        let mut ws = tokio_tungstenite::accept_async(stream).await?;
+                        while let Some(message) = ws.next().await {
+                            ws.send(message?).await?;
+                        }
+                        Ok::<_, WsError>(())
+                    });
+                }
+            });
+        });
+
+        format!("wss://localhost:{}", addr.port())
+    }
+
+    /// Tests connecting over TLS with a pinned certificate, and rejecting a server
+    /// whose certificate is not pinned.
+    #[test]
+    fn test_tls_pinning() {
+        let uri = spawn_tls_echo_server();
+        let pinned = Certificate::from_pem(tls::tests::CERTIFICATE).unwrap();
+        let other = Certificate::from_pem(include_bytes!("../testdata/other.crt")).unwrap();
+
+        let config = tls::tests::trusting_config()
+            .pin_certificate(&pinned)
+            .unwrap();
+        let mut conn = TungsteniteWsConnection::connect_with_tls(&uri, &config).unwrap();
+        conn.send(FrameType::Binary(false), &[4, 2]).unwrap();
+        let mut buf = [0u8; 16];
+        let (frame_type, len) = conn.recv(&mut buf).unwrap();
+        assert_eq!(frame_type, FrameType::Binary(false));
+        assert_eq!(&buf[..len], &[4, 2]);
+
+        let config = tls::tests::trusting_config()
+            .pin_certificate(&other)
+            .unwrap();
+        assert!(matches!(
+            TungsteniteWsConnection::connect_with_tls(&uri, &config),
+            Err(WsError::CertificateNotPinned(host)) if host == "localhost"
+        ));
+    }
+
+    /// Tests sending a fragmented text message and receiving it back whole.
+    #[test]
+    fn test_fragmented_echo() {
+        let mut conn = TungsteniteWsConnection::connect(&spawn_echo_server()).unwrap();
+
+        conn.send(FrameType::Text(true), b"hello ").unwrap();
+        conn.send(FrameType::Continue(false), b"world").unwrap();
+
+        let mut small = [0u8; 4];
+        assert!(matches!(
+            conn.recv(&mut small),
+            Err(WsError::BufferTooSmall(11))
+        ));
+
+        let mut buf = [0u8; 64];
+        let (frame_type, len) = conn.recv(&mut buf).unwrap();
+        assert_eq!(frame_type, FrameType::Text(false));
+        assert_eq!(&buf[..len], b"hello world");
+    }
+}
@@ -0,0 +1,308 @@
+//! Asynchronous WebSocket client implementing `embedded_svc::ws::asynch`.
+//!
+//! `AsyncTungsteniteWsConnection` runs on the caller's Tokio runtime, so async
+//! firmware code exchanging WebSocket frames can be hosted without shims. The
+//! blocking `TungsteniteWsConnection` is a thin wrapper around it.
+
+use crate::error::WsError;
+use crate::tls::TlsConfig;
+use crate::ws::{WsConfig, WsStream};
+use embedded_svc::ws::asynch::{Receiver, Sender};
+use embedded_svc::ws::{ErrorType, FrameType};
+use futures_util::{SinkExt, StreamExt};
+use tokio_tungstenite::tungstenite::error::CapacityError;
+use tokio_tungstenite::tungstenite::protocol::frame::Frame;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::{Data, OpCode};
+use tokio_tungstenite::tungstenite::{Bytes, Error, Message};
+
+/// An asynchronous WebSocket client connection using `tokio-tungstenite`.
+///
+/// Must be used from within a Tokio runtime.
+///
+/// # Example
+///
+/// ```no_run
+/// use embedded_svc::ws::FrameType;
+/// use embedded_svc::ws::asynch::{Receiver, Sender};
+/// use native_svc::ws::asynch::AsyncTungsteniteWsConnection;
+///
+/// # async fn run() -> Result<(), native_svc::error::WsError> {
+/// let mut conn = AsyncTungsteniteWsConnection::connect("wss://example.com/ws").await?;
+/// conn.send(FrameType::Text(false), b"hello").await?;
+///
+/// let mut buf = [0u8; 1024];
+/// let (frame_type, len) = conn.recv(&mut buf).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct AsyncTungsteniteWsConnection {
+    stream: Option<WsStream>,
+    pending: Option<Message>,
+    fragment: Option<(bool, Vec<u8>)>,
+    fragment_size: Option<usize>,
+}
+
+impl AsyncTungsteniteWsConnection {
+    /// Connects to the WebSocket server at `uri` (`ws://` or `wss://`).
+    pub async fn connect(uri: &str) -> Result<Self, WsError> {
+        Self::connect_with_config(uri, None, WsConfig::default()).await
+    }
+
+    /// Connects to `uri` using the given TLS settings for `wss://` URIs.
+    ///
+    /// Accepts the same `TlsConfig` used by `HyperHttpConnection`.
+    pub async fn connect_with_tls(uri: &str, tls: &TlsConfig) -> Result<Self, WsError> {
+        Self::connect_with_config(uri, Some(tls), WsConfig::default()).await
+    }
+
+    /// Connects to `uri` with the given size limits and compression, and TLS settings
+    /// for `wss://` URIs if `tls` is set.
+    pub async fn connect_with_config(
+        uri: &str,
+        tls: Option<&TlsConfig>,
+        config: WsConfig,
+    ) -> Result<Self, WsError> {
+        let (stream, _response) = crate::ws::open(uri, tls, &config).await?;
+        let mut conn = Self::from_stream(stream);
+        conn.fragment_size = config.fragment_size;
+        Ok(conn)
+    }
+
+    /// Wraps an already established WebSocket stream.
+    pub(crate) fn from_stream(stream: WsStream) -> Self {
+        Self {
+            stream: Some(stream),
+            pending: None,
+            fragment: None,
+            fragment_size: None,
+        }
+    }
+
+    /// Returns `true` while the underlying socket is open.
+    pub fn is_connected(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    /// Sends a complete message and flushes it to the socket.
+    ///
+    /// Text and binary messages larger than the fragment size are split into frames.
+    async fn send_message(&mut self, message: Message) -> Result<(), WsError> {
+        let stream = self.stream.as_mut().ok_or(WsError::Closed)?;
+        let opcode = match &message {
+            Message::Text(_) => Data::Text,
+            Message::Binary(_) => Data::Binary,
+            _ => return Ok(stream.send(message).await?),
+        };
+        match self.fragment_size {
+            Some(size) if message.len() > size => {
+                for frame in fragments(opcode, Bytes::from(message), size) {
+                    stream.feed(Message::Frame(frame)).await?;
+                }
+                stream.flush().await?;
+            }
+            _ => stream.send(message).await?,
+        }
+        Ok(())
+    }
+
+    /// Waits for the next message, returning `None` once the socket has closed.
+    async fn next_message(&mut self) -> Result<Option<Message>, WsError> {
+        let stream = self.stream.as_mut().ok_or(WsError::Closed)?;
+        match stream.next().await {
+            Some(Ok(message)) => Ok(Some(message)),
+            Some(Err(Error::Capacity(CapacityError::MessageTooLong { size, .. }))) => {
+                self.stream = None;
+                Err(WsError::MessageTooLarge(size))
+            }
+            Some(Err(error)) => {
+                let error = WsError::from(error);
+                if matches!(error, WsError::MessageTooLarge(_)) {
+                    self.stream = None;
+                }
+                Err(error)
+            }
+            None => {
+                self.stream = None;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Builds a complete message from a text or binary payload.
+    fn build_message(text: bool, payload: Vec<u8>) -> Result<Message, WsError> {
+        if text {
+            let text = String::from_utf8(payload).map_err(|_| WsError::InvalidUtf8)?;
+            Ok(Message::text(text))
+        } else {
+            Ok(Message::binary(payload))
+        }
+    }
+}
+
+/// Splits `payload` into frames of at most `size` bytes, the first with `opcode`.
+fn fragments(opcode: Data, payload: Bytes, size: usize) -> Vec<Frame> {
+    let size = size.max(1);
+    let count = payload.len().div_ceil(size);
+    (0..count)
+        .map(|index| {
+            let start = index * size;
+            let chunk = payload.slice(start..(start + size).min(payload.len()));
+            let opcode = if index == 0 { opcode } else { Data::Continue };
+            Frame::message(chunk, OpCode::Data(opcode), index + 1 == count)
+        })
+        .collect()
+}
+
+impl ErrorType for AsyncTungsteniteWsConnection {
+    /// The error type returned by this connection.
+    type Error = WsError;
+}
+
+impl Sender for AsyncTungsteniteWsConnection {
+    /// Sends a frame, reassembling fragmented frames into a single message.
+    async fn send(&mut self, frame_type: FrameType, frame_data: &[u8]) -> Result<(), Self::Error> {
+        match frame_type {
+            FrameType::Text(true) | FrameType::Binary(true) => {
+                let text = matches!(frame_type, FrameType::Text(_));
+                self.fragment = Some((text, frame_data.to_vec()));
+                Ok(())
+            }
+            FrameType::Continue(more) => {
+                let (text, mut payload) = self
+                    .fragment
+                    .take()
+                    .ok_or(WsError::UnexpectedContinuation)?;
+                payload.extend_from_slice(frame_data);
+                if more {
+                    self.fragment = Some((text, payload));
+                    return Ok(());
+                }
+                self.send_message(Self::build_message(text, payload)?).await
+            }
+            FrameType::Text(false) => {
+                self.send_message(Self::build_message(true, frame_data.to_vec())?)
+                    .await
+            }
+            FrameType::Binary(false) => {
+                self.send_message(Message::binary(frame_data.to_vec()))
+                    .await
+            }
+            FrameType::Ping => {
+                self.send_message(Message::Ping(frame_data.to_vec().into()))
+                    .await
+            }
+            FrameType::Pong => {
+                self.send_message(Message::Pong(frame_data.to_vec().into()))
+                    .await
+            }
+            FrameType::Close => self.send_message(Message::Close(None)).await,
+            FrameType::SocketClose => {
+                if let Some(mut stream) = self.stream.take() {
+                    stream.close(None).await?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Receiver for AsyncTungsteniteWsConnection {
+    /// Receives the next frame into `frame_data_buf`.
+    ///
+    /// Returns `FrameType::SocketClose` once the socket has been closed. If the
+    /// buffer is too small, the frame is kept and `WsError::BufferTooSmall` reports
+    /// the required length so the call can be retried with a larger buffer.
+    async fn recv(&mut self, frame_data_buf: &mut [u8]) -> Result<(FrameType, usize), Self::Error> {
+        let message = match self.pending.take() {
+            Some(message) => message,
+            None => match self.next_message().await? {
+                Some(message) => message,
+                None => return Ok((FrameType::SocketClose, 0)),
+            },
+        };
+
+        let (frame_type, payload): (FrameType, &[u8]) = match &message {
+            Message::Text(text) => (FrameType::Text(false), text.as_bytes()),
+            Message::Binary(data) => (FrameType::Binary(false), &data[..]),
+            Message::Ping(data) => (FrameType::Ping, &data[..]),
+            Message::Pong(data) => (FrameType::Pong, &data[..]),
+            Message::Close(_) => (FrameType::Close, &[][..]),
+            Message::Frame(frame) => (FrameType::Continue(false), frame.payload()),
+        };
+
+        let length = payload.len();
+        if length > frame_data_buf.len() {
+            self.pending = Some(message);
+            return Err(WsError::BufferTooSmall(length));
+        }
+        frame_data_buf[..length].copy_from_slice(payload);
+
+        Ok((frame_type, length))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::RuntimeConfig;
+    use crate::ws::tests::spawn_echo_server;
+
+    /// Tests an async binary round trip through the local echo server.
+    #[test]
+    fn test_async_binary_echo() {
+        let uri = spawn_echo_server();
+        let rt = RuntimeConfig::default().build().unwrap();
+
+        rt.block_on(async {
+            let mut conn = AsyncTungsteniteWsConnection::connect(&uri).await.unwrap();
+            conn.send(FrameType::Binary(false), &[1, 2, 3])
+                .await
+                .unwrap();
+
+            let mut buf = [0u8; 16];
+            let (frame_type, len) = conn.recv(&mut buf).await.unwrap();
+            assert_eq!(frame_type, FrameType::Binary(false));
+            assert_eq!(&buf[..len], &[1, 2, 3]);
+        });
+    }
+
+    /// Tests fragmenting large messages and rejecting incoming ones over the limit.
+    #[test]
+    fn test_size_limits() {
+        let uri = spawn_echo_server();
+        let rt = RuntimeConfig::default().build().unwrap();
+
+        rt.block_on(async {
+            let config = WsConfig {
+                max_message_size: Some(64),
+                fragment_size: Some(16),
+                ..WsConfig::default()
+            };
+            let mut conn = AsyncTungsteniteWsConnection::connect_with_config(&uri, None, config)
+                .await
+                .unwrap();
+
+            let frames = fragments(Data::Text, Bytes::from_static(&[b'x'; 40]), 16);
+            let lengths: Vec<_> = frames.iter().map(|f| f.payload().len()).collect();
+            assert_eq!(lengths, [16, 16, 8]);
+            assert!(frames[2].header().is_final && !frames[1].header().is_final);
+
+            conn.send(FrameType::Text(false), &[b'a'; 40])
+                .await
+                .unwrap();
+            let mut buf = [0u8; 128];
+            let (frame_type, len) = conn.recv(&mut buf).await.unwrap();
+            assert_eq!(frame_type, FrameType::Text(false));
+            assert_eq!(&buf[..len], &[b'a'; 40]);
+
+            conn.send(FrameType::Binary(false), &[0; 100])
+                .await
+                .unwrap();
+            assert!(matches!(
+                conn.recv(&mut buf).await,
+                Err(WsError::MessageTooLarge(100))
+            ));
+            assert!(!conn.is_connected());
+        });
+    }
+}
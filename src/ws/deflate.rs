@@ -0,0 +1,794 @@
+//! `permessage-deflate` (RFC 7692) negotiation and payload codec.
+//!
+//! `DeflateConfig` builds the client offer, validates the server's answer, and answers
+//! client offers on the server side. `DeflateCodec` compresses and decompresses
+//! message payloads according to the negotiated parameters.
+//!
+//! `tungstenite` rejects frames with the RSV1 bit set, so client connections configured
+//! with `WsConfig::deflate` run over a `DeflateStream`: it reads the server's answer
+//! from the handshake response, then rewrites frames underneath `tungstenite`,
+//! compressing outgoing data messages and inflating incoming compressed ones.
+
+use crate::error::WsError;
+use flate2::{Compress, Compression, Decompress, FlushCompress, FlushDecompress, Status};
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll, ready};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+/// Extension token used in `Sec-WebSocket-Extensions`.
+pub const EXTENSION_NAME: &str = "permessage-deflate";
+
+/// Trailer removed from compressed payloads and re-appended before inflating.
+const SYNC_FLUSH_TRAILER: [u8; 4] = [0x00, 0x00, 0xff, 0xff];
+
+/// Default upper bound for a single inflated message.
+const DEFAULT_MAX_MESSAGE_SIZE: usize = 16 << 20;
+
+/// Parameters of the `permessage-deflate` extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeflateConfig {
+    /// The client resets its compression context after every message.
+    pub client_no_context_takeover: bool,
+    /// The server resets its compression context after every message.
+    pub server_no_context_takeover: bool,
+    /// Compression level applied to outgoing messages.
+    pub level: u32,
+    /// Upper bound for a single inflated message.
+    pub max_message_size: usize,
+}
+
+impl Default for DeflateConfig {
+    fn default() -> Self {
+        Self {
+            client_no_context_takeover: false,
+            server_no_context_takeover: false,
+            level: Compression::default().level(),
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+        }
+    }
+}
+
+impl DeflateConfig {
+    /// Returns the `Sec-WebSocket-Extensions` value offered by a client.
+    pub fn offer(&self) -> String {
+        let mut offer = String::from(EXTENSION_NAME);
+        if self.client_no_context_takeover {
+            offer.push_str("; client_no_context_takeover");
+        }
+        if self.server_no_context_takeover {
+            offer.push_str("; server_no_context_takeover");
+        }
+        offer
+    }
+
+    /// Parses the server's `Sec-WebSocket-Extensions` response.
+    ///
+    /// Returns `None` if the server declined compression, and an error if it
+    /// answered with parameters that were not offered or are not supported.
+    pub fn negotiate(&self, response: &str) -> Result<Option<DeflateConfig>, WsError> {
+        let Some(params) = Self::find_extension(response) else {
+            return Ok(None);
+        };
+
+        let mut negotiated = *self;
+        for param in params {
+            match param {
+                "client_no_context_takeover" => negotiated.client_no_context_takeover = true,
+                "server_no_context_takeover" => negotiated.server_no_context_takeover = true,
+                "server_max_window_bits=15" | "client_max_window_bits=15" => {}
+                other => return Err(WsError::UnsupportedExtension(other.to_owned())),
+            }
+        }
+        Ok(Some(negotiated))
+    }
+
+    /// Answers a client's `Sec-WebSocket-Extensions` offer on the server side.
+    ///
+    /// Returns the response header value and the agreed parameters, or `None`
+    /// if the client did not offer compression.
+    pub fn accept(&self, offer: &str) -> Option<(String, DeflateConfig)> {
+        let params = Self::find_extension(offer)?;
+
+        let mut agreed = *self;
+        for param in params {
+            match param {
+                "client_no_context_takeover" => agreed.client_no_context_takeover = true,
+                "server_no_context_takeover" => agreed.server_no_context_takeover = true,
+                param if param.starts_with("client_max_window_bits") => {}
+                _ => return None,
+            }
+        }
+
+        let mut response = String::from(EXTENSION_NAME);
+        if agreed.client_no_context_takeover {
+            response.push_str("; client_no_context_takeover");
+        }
+        if agreed.server_no_context_takeover {
+            response.push_str("; server_no_context_takeover");
+        }
+        Some((response, agreed))
+    }
+
+    /// Returns the parameters of the first `permessage-deflate` entry in `header`.
+    fn find_extension(header: &str) -> Option<impl Iterator<Item = &str>> {
+        header.split(',').find_map(|extension| {
+            let mut parts = extension.split(';').map(str::trim);
+            (parts.next() == Some(EXTENSION_NAME)).then_some(parts.filter(|p| !p.is_empty()))
+        })
+    }
+}
+
+/// Which endpoint of the connection a codec belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    /// The endpoint that initiated the handshake.
+    Client,
+    /// The endpoint that accepted the handshake.
+    Server,
+}
+
+/// Compresses outgoing and inflates incoming message payloads.
+pub struct DeflateCodec {
+    compress: Compress,
+    decompress: Decompress,
+    reset_outgoing: bool,
+    reset_incoming: bool,
+    max_message_size: usize,
+}
+
+impl DeflateCodec {
+    /// Creates a codec for `role` using the negotiated `config`.
+    pub fn new(config: &DeflateConfig, role: Role) -> Self {
+        let (reset_outgoing, reset_incoming) = match role {
+            Role::Client => (
+                config.client_no_context_takeover,
+                config.server_no_context_takeover,
+            ),
+            Role::Server => (
+                config.server_no_context_takeover,
+                config.client_no_context_takeover,
+            ),
+        };
+
+        Self {
+            compress: Compress::new(Compression::new(config.level), false),
+            decompress: Decompress::new(false),
+            reset_outgoing,
+            reset_incoming,
+            max_message_size: config.max_message_size,
+        }
+    }
+
+    /// Compresses a complete message payload.
+    pub fn compress(&mut self, payload: &[u8]) -> Result<Vec<u8>, WsError> {
+        let mut output = Vec::with_capacity(payload.len() / 2 + 64);
+        let start = self.compress.total_in();
+
+        loop {
+            let consumed = (self.compress.total_in() - start) as usize;
+            self.compress
+                .compress_vec(&payload[consumed..], &mut output, FlushCompress::Sync)?;
+
+            let done = (self.compress.total_in() - start) as usize == payload.len();
+            if done && output.len() < output.capacity() {
+                break;
+            }
+            output.reserve(output.capacity().max(64));
+        }
+
+        if output.ends_with(&SYNC_FLUSH_TRAILER) {
+            output.truncate(output.len() - SYNC_FLUSH_TRAILER.len());
+        }
+        if self.reset_outgoing {
+            self.compress.reset();
+        }
+        Ok(output)
+    }
+
+    /// Inflates a complete compressed message payload.
+    ///
+    /// Fails with `WsError::MessageTooLarge` if the result exceeds the configured limit.
+    pub fn decompress(&mut self, payload: &[u8]) -> Result<Vec<u8>, WsError> {
+        let mut input = Vec::with_capacity(payload.len() + SYNC_FLUSH_TRAILER.len());
+        input.extend_from_slice(payload);
+        input.extend_from_slice(&SYNC_FLUSH_TRAILER);
+
+        let mut output = Vec::with_capacity(payload.len() * 4 + 64);
+        let start = self.decompress.total_in();
+
+        loop {
+            let consumed = (self.decompress.total_in() - start) as usize;
+            let status = self.decompress.decompress_vec(
+                &input[consumed..],
+                &mut output,
+                FlushDecompress::Sync,
+            )?;
+
+            if output.len() > self.max_message_size {
+                return Err(WsError::MessageTooLarge(output.len()));
+            }
+
+            let done = (self.decompress.total_in() - start) as usize == input.len();
+            if status == Status::StreamEnd || (done && output.len() < output.capacity()) {
+                break;
+            }
+            output.reserve(output.capacity().max(64));
+        }
+
+        if self.reset_incoming {
+            self.decompress.reset(false);
+        }
+        Ok(output)
+    }
+}
+
+/// Rewritten bytes waiting for the connection before writes are held back.
+const MAX_PENDING_WRITE: usize = 64 << 10;
+
+/// Header fields of a WebSocket frame.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct FrameHeader {
+    pub(crate) fin: bool,
+    pub(crate) rsv1: bool,
+    pub(crate) opcode: u8,
+    pub(crate) mask: Option<[u8; 4]>,
+    /// Length of the header itself, including the masking key.
+    pub(crate) header_len: usize,
+    pub(crate) payload_len: usize,
+}
+
+impl FrameHeader {
+    /// Parses the header at the start of `buf`, returning `None` until it is complete.
+    pub(crate) fn parse(buf: &[u8]) -> Option<Self> {
+        let (&first, &second) = (buf.first()?, buf.get(1)?);
+        let (payload_len, mut header_len) = match second & 0x7f {
+            126 => (
+                u16::from_be_bytes(buf.get(2..4)?.try_into().ok()?) as usize,
+                4,
+            ),
+            127 => (
+                u64::from_be_bytes(buf.get(2..10)?.try_into().ok()?) as usize,
+                10,
+            ),
+            len => (len as usize, 2),
+        };
+        let mask = if second & 0x80 != 0 {
+            let key = buf.get(header_len..header_len + 4)?.try_into().ok()?;
+            header_len += 4;
+            Some(key)
+        } else {
+            None
+        };
+
+        Some(Self {
+            fin: first & 0x80 != 0,
+            rsv1: first & 0x40 != 0,
+            opcode: first & 0x0f,
+            mask,
+            header_len,
+            payload_len,
+        })
+    }
+
+    /// Returns `true` for close, ping and pong frames.
+    fn is_control(&self) -> bool {
+        self.opcode & 0x08 != 0
+    }
+}
+
+/// Appends a frame carrying `payload`, masked with `mask` if set.
+pub(crate) fn encode_frame(
+    out: &mut Vec<u8>,
+    fin: bool,
+    rsv1: bool,
+    opcode: u8,
+    payload: &[u8],
+    mask: Option<[u8; 4]>,
+) {
+    out.push(u8::from(fin) << 7 | u8::from(rsv1) << 6 | opcode);
+    let mask_bit = if mask.is_some() { 0x80 } else { 0 };
+    match payload.len() {
+        len if len < 126 => out.push(mask_bit | len as u8),
+        len if len <= usize::from(u16::MAX) => {
+            out.push(mask_bit | 126);
+            out.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            out.push(mask_bit | 127);
+            out.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    match mask {
+        Some(key) => {
+            out.extend_from_slice(&key);
+            let start = out.len();
+            out.extend_from_slice(payload);
+            apply_mask(&mut out[start..], key);
+        }
+        None => out.extend_from_slice(payload),
+    }
+}
+
+/// Masks or unmasks a frame payload in place.
+pub(crate) fn apply_mask(payload: &mut [u8], key: [u8; 4]) {
+    for (index, byte) in payload.iter_mut().enumerate() {
+        *byte ^= key[index % 4];
+    }
+}
+
+/// A compressed message whose fragments are being reassembled.
+struct Fragments {
+    opcode: u8,
+    payload: Vec<u8>,
+    /// Payload size of the first fragment, reused when re-fragmenting.
+    fragment_size: usize,
+    /// Masking keys of the original fragments, reused for the rewritten ones.
+    masks: Vec<Option<[u8; 4]>>,
+}
+
+/// Bytes flowing in one direction of the connection.
+#[derive(Default)]
+struct Direction {
+    /// HTTP head of the handshake, buffered until complete; `None` once passed on.
+    head: Option<Vec<u8>>,
+    /// Frame bytes not yet forming a complete frame.
+    input: Vec<u8>,
+    /// Rewritten bytes, of which the first `consumed` have been passed on.
+    output: Vec<u8>,
+    consumed: usize,
+    message: Option<Fragments>,
+}
+
+impl Direction {
+    /// Creates a direction that starts with an HTTP head.
+    fn new() -> Self {
+        Self {
+            head: Some(Vec::new()),
+            ..Self::default()
+        }
+    }
+
+    /// Moves the HTTP head at the start of `data` to the output, returning the frame
+    /// bytes after it and, once it is complete, the head itself.
+    fn take_head<'a>(&mut self, data: &'a [u8]) -> (&'a [u8], Option<String>) {
+        let Some(head) = self.head.as_mut() else {
+            return (data, None);
+        };
+
+        // The blank line ending the head may be split across reads.
+        let start = head.len().saturating_sub(3);
+        head.extend_from_slice(data);
+        let Some(end) = head[start..]
+            .windows(4)
+            .position(|window| window == b"\r\n\r\n")
+        else {
+            return (&[], None);
+        };
+
+        let end = start + end + 4;
+        let rest = &data[data.len() - (head.len() - end)..];
+        head.truncate(end);
+        let head = self.head.take().unwrap_or_default();
+        self.output.extend_from_slice(&head);
+        (rest, Some(String::from_utf8_lossy(&head).into_owned()))
+    }
+
+    /// Returns the rewritten bytes not yet passed on.
+    fn pending(&self) -> &[u8] {
+        &self.output[self.consumed..]
+    }
+
+    /// Marks `len` rewritten bytes as passed on.
+    fn advance(&mut self, len: usize) {
+        self.consumed += len;
+        if self.consumed == self.output.len() {
+            self.output.clear();
+            self.consumed = 0;
+        }
+    }
+
+    /// Rewrites the complete frames buffered in `input`.
+    ///
+    /// Control frames and uncompressed messages pass through unchanged; compressed
+    /// messages are reassembled and handed to `rewrite` with their opcode, payload,
+    /// fragment size and masking keys.
+    fn rewrite_frames<F>(
+        &mut self,
+        max_message_size: usize,
+        compressed: impl Fn(&FrameHeader) -> bool,
+        mut rewrite: F,
+    ) -> io::Result<()>
+    where
+        F: FnMut(&mut Vec<u8>, Fragments) -> io::Result<()>,
+    {
+        let mut position = 0;
+        while let Some(frame) = FrameHeader::parse(&self.input[position..]) {
+            let buffered = self.message.as_ref().map_or(0, |m| m.payload.len());
+            if frame.payload_len > max_message_size.saturating_sub(buffered) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    WsError::MessageTooLarge(buffered.saturating_add(frame.payload_len)),
+                ));
+            }
+            let end = position + frame.header_len + frame.payload_len;
+            if self.input.len() < end {
+                break;
+            }
+
+            let raw = &self.input[position..end];
+            let continues = frame.opcode == 0 && self.message.is_some();
+            if frame.is_control() || !(continues || compressed(&frame)) {
+                self.output.extend_from_slice(raw);
+            } else {
+                let mut payload = raw[frame.header_len..].to_vec();
+                if let Some(key) = frame.mask {
+                    apply_mask(&mut payload, key);
+                }
+                let message = self.message.get_or_insert_with(|| Fragments {
+                    opcode: frame.opcode,
+                    payload: Vec::new(),
+                    fragment_size: payload.len(),
+                    masks: Vec::new(),
+                });
+                message.payload.extend_from_slice(&payload);
+                message.masks.push(frame.mask);
+                if frame.fin {
+                    let message = self.message.take().expect("message was just inserted");
+                    rewrite(&mut self.output, message)?;
+                }
+            }
+            position = end;
+        }
+
+        self.input.drain(..position);
+        Ok(())
+    }
+}
+
+/// Converts a codec error into an I/O error `tungstenite` passes on.
+fn io_error(error: WsError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+/// Client byte stream applying `permessage-deflate` underneath `tungstenite`.
+///
+/// The handshake passes through unchanged; the offer is part of the request. Once
+/// the response head has been read, the server's answer decides whether frames are
+/// rewritten: outgoing text and binary messages are compressed and sent with RSV1
+/// set, and incoming messages with RSV1 set are inflated and handed on as plain
+/// frames. Control frames are never compressed.
+pub(crate) struct DeflateStream<S> {
+    inner: S,
+    config: DeflateConfig,
+    codec: Option<DeflateCodec>,
+    incoming: Direction,
+    outgoing: Direction,
+}
+
+impl<S> DeflateStream<S> {
+    /// Wraps `inner`, whose handshake request offers `config`.
+    pub(crate) fn new(inner: S, config: DeflateConfig) -> Self {
+        Self {
+            inner,
+            config,
+            codec: None,
+            incoming: Direction::new(),
+            outgoing: Direction::new(),
+        }
+    }
+
+    /// Reads the server's answer from the response head.
+    fn negotiate(&mut self, head: &str) -> io::Result<()> {
+        let answer = head
+            .lines()
+            .skip(1)
+            .filter_map(|line| line.split_once(':'))
+            .filter(|(name, _)| name.trim().eq_ignore_ascii_case("sec-websocket-extensions"))
+            .map(|(_, value)| value.trim())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let negotiated = self.config.negotiate(&answer).map_err(io_error)?;
+        self.codec = negotiated.map(|config| DeflateCodec::new(&config, Role::Client));
+        Ok(())
+    }
+
+    /// Processes bytes read from the server.
+    fn receive(&mut self, data: &[u8]) -> io::Result<()> {
+        let (data, head) = self.incoming.take_head(data);
+        if let Some(head) = head {
+            self.negotiate(&head)?;
+        }
+        if self.incoming.head.is_some() {
+            return Ok(());
+        }
+
+        let Some(codec) = self.codec.as_mut() else {
+            self.incoming.output.extend_from_slice(data);
+            return Ok(());
+        };
+        self.incoming.input.extend_from_slice(data);
+        self.incoming.rewrite_frames(
+            self.config.max_message_size,
+            |frame| frame.rsv1,
+            |output, message| {
+                let payload = codec.decompress(&message.payload).map_err(io_error)?;
+                encode_frame(output, true, false, message.opcode, &payload, None);
+                Ok(())
+            },
+        )
+    }
+
+    /// Processes bytes written by `tungstenite`.
+    fn transmit(&mut self, data: &[u8]) -> io::Result<()> {
+        let (data, _) = self.outgoing.take_head(data);
+        if self.outgoing.head.is_some() {
+            return Ok(());
+        }
+
+        let Some(codec) = self.codec.as_mut() else {
+            self.outgoing.output.extend_from_slice(data);
+            return Ok(());
+        };
+        self.outgoing.input.extend_from_slice(data);
+        self.outgoing.rewrite_frames(
+            usize::MAX,
+            |frame| !frame.rsv1,
+            |output, message| {
+                let payload = codec.compress(&message.payload).map_err(io_error)?;
+                let size = if message.masks.len() > 1 {
+                    message.fragment_size.max(1)
+                } else {
+                    payload.len().max(1)
+                };
+                let count = payload.len().div_ceil(size).max(1);
+                for index in 0..count {
+                    let chunk = payload
+                        .get(index * size..((index + 1) * size).min(payload.len()))
+                        .unwrap_or_default();
+                    let opcode = if index == 0 { message.opcode } else { 0 };
+                    let mask = message.masks[index % message.masks.len()];
+                    encode_frame(output, index + 1 == count, index == 0, opcode, chunk, mask);
+                }
+                Ok(())
+            },
+        )
+    }
+}
+
+impl<S: AsyncWrite + Unpin> DeflateStream<S> {
+    /// Writes rewritten bytes to the connection until none are left.
+    fn poll_send(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while !self.outgoing.pending().is_empty() {
+            let written =
+                ready!(Pin::new(&mut self.inner).poll_write(cx, self.outgoing.pending()))?;
+            if written == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.outgoing.advance(written);
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for DeflateStream<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        loop {
+            let pending = this.incoming.pending();
+            if !pending.is_empty() {
+                let len = pending.len().min(buf.remaining());
+                buf.put_slice(&pending[..len]);
+                this.incoming.advance(len);
+                return Poll::Ready(Ok(()));
+            }
+
+            let mut chunk = [0u8; 8192];
+            let mut read = ReadBuf::new(&mut chunk);
+            ready!(Pin::new(&mut this.inner).poll_read(cx, &mut read))?;
+            if read.filled().is_empty() {
+                return Poll::Ready(Ok(()));
+            }
+            this.receive(read.filled())?;
+        }
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for DeflateStream<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if this.outgoing.pending().len() >= MAX_PENDING_WRITE {
+            ready!(this.poll_send(cx))?;
+        }
+        this.transmit(buf)?;
+        if let Poll::Ready(Err(error)) = this.poll_send(cx) {
+            return Poll::Ready(Err(error));
+        }
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_send(cx))?;
+        Pin::new(&mut this.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_send(cx))?;
+        Pin::new(&mut this.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ws::{TungsteniteWsConnection, WsConfig};
+    use embedded_svc::ws::{FrameType, Receiver, Sender};
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::thread::JoinHandle;
+    use tokio_tungstenite::tungstenite::handshake::derive_accept_key;
+
+    /// Spawns a single-connection server that greets the client and echoes its
+    /// messages, all compressed with `permessage-deflate`.
+    ///
+    /// The handle yields the number of compressed messages received from the client.
+    fn spawn_deflate_echo_server() -> (String, JoinHandle<usize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let handle = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buf = Vec::new();
+            let mut chunk = [0u8; 4096];
+            while !buf.windows(4).any(|window| window == b"\r\n\r\n") {
+                let len = stream.read(&mut chunk).unwrap();
+                buf.extend_from_slice(&chunk[..len]);
+            }
+            let request = String::from_utf8(buf).unwrap();
+            let header = |name: &str| {
+                request
+                    .lines()
+                    .filter_map(|line| line.split_once(':'))
+                    .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                    .map(|(_, value)| value.trim().to_owned())
+                    .unwrap()
+            };
+
+            let offer = header("sec-websocket-extensions");
+            let (answer, config) = DeflateConfig::default().accept(&offer).unwrap();
+            let mut codec = DeflateCodec::new(&config, Role::Server);
+
+            // The greeting follows the response head in the same write.
+            let mut out = format!(
+                "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\
+                 Connection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\
+                 Sec-WebSocket-Extensions: {answer}\r\n\r\n",
+                derive_accept_key(header("sec-websocket-key").as_bytes())
+            )
+            .into_bytes();
+            let greeting = codec.compress(b"welcome").unwrap();
+            encode_frame(&mut out, true, true, 1, &greeting, None);
+            stream.write_all(&out).unwrap();
+
+            let mut input = Vec::new();
+            let mut message: Option<(u8, bool, Vec<u8>)> = None;
+            let mut compressed = 0;
+            loop {
+                let Some(frame) = FrameHeader::parse(&input)
+                    .filter(|frame| input.len() >= frame.header_len + frame.payload_len)
+                else {
+                    let len = stream.read(&mut chunk).unwrap();
+                    assert_ne!(len, 0);
+                    input.extend_from_slice(&chunk[..len]);
+                    continue;
+                };
+
+                let end = frame.header_len + frame.payload_len;
+                let mut payload = input[frame.header_len..end].to_vec();
+                input.drain(..end);
+                apply_mask(&mut payload, frame.mask.unwrap());
+
+                if frame.opcode == 8 {
+                    let mut close = Vec::new();
+                    encode_frame(&mut close, true, false, 8, &[], None);
+                    stream.write_all(&close).unwrap();
+                    return compressed;
+                }
+                let (opcode, rsv1, data) =
+                    message.get_or_insert((frame.opcode, frame.rsv1, Vec::new()));
+                data.extend_from_slice(&payload);
+                if frame.fin {
+                    let (opcode, rsv1, data) = (*opcode, *rsv1, std::mem::take(data));
+                    message = None;
+                    let data = if rsv1 {
+                        compressed += 1;
+                        codec.decompress(&data).unwrap()
+                    } else {
+                        data
+                    };
+                    let mut echo = Vec::new();
+                    encode_frame(
+                        &mut echo,
+                        true,
+                        true,
+                        opcode,
+                        &codec.compress(&data).unwrap(),
+                        None,
+                    );
+                    stream.write_all(&echo).unwrap();
+                }
+            }
+        });
+
+        (format!("ws://{addr}"), handle)
+    }
+
+    /// Tests inflating the "Hello" sample from RFC 7692 section 7.2.3.1.
+    #[test]
+    fn test_rfc_sample() {
+        let mut codec = DeflateCodec::new(&DeflateConfig::default(), Role::Client);
+        let inflated = codec
+            .decompress(&[0xf2, 0x48, 0xcd, 0xc9, 0xc9, 0x07, 0x00])
+            .unwrap();
+        assert_eq!(inflated, b"Hello");
+    }
+
+    /// Tests a compression round trip between a client and a server codec.
+    #[test]
+    fn test_round_trip() {
+        let config = DeflateConfig::default()
+            .negotiate("permessage-deflate; server_no_context_takeover")
+            .unwrap()
+            .unwrap();
+        let mut client = DeflateCodec::new(&config, Role::Client);
+        let mut server = DeflateCodec::new(&config, Role::Server);
+
+        let message = b"telemetry telemetry telemetry telemetry".repeat(8);
+        let compressed = client.compress(&message).unwrap();
+        assert!(compressed.len() < message.len());
+        assert_eq!(server.decompress(&compressed).unwrap(), message);
+    }
+
+    /// Tests exchanging compressed whole and fragmented messages with a
+    /// deflate-enabled echo server.
+    #[test]
+    fn test_deflate_echo() {
+        let (uri, server) = spawn_deflate_echo_server();
+        let config = WsConfig {
+            fragment_size: Some(64),
+            deflate: Some(DeflateConfig::default()),
+            ..WsConfig::default()
+        };
+        let mut conn = TungsteniteWsConnection::connect_with_config(&uri, None, config).unwrap();
+
+        let mut buf = [0u8; 1024];
+        let (frame_type, len) = conn.recv(&mut buf).unwrap();
+        assert_eq!(frame_type, FrameType::Text(false));
+        assert_eq!(&buf[..len], b"welcome");
+
+        let message = b"telemetry ".repeat(50);
+        conn.send(FrameType::Text(false), &message).unwrap();
+        let (frame_type, len) = conn.recv(&mut buf).unwrap();
+        assert_eq!(frame_type, FrameType::Text(false));
+        assert_eq!(&buf[..len], &message[..]);
+
+        conn.send(FrameType::Binary(false), &[7; 10]).unwrap();
+        let (frame_type, len) = conn.recv(&mut buf).unwrap();
+        assert_eq!(frame_type, FrameType::Binary(false));
+        assert_eq!(&buf[..len], &[7; 10]);
+
+        conn.send(FrameType::Close, &[]).unwrap();
+        assert_eq!(conn.recv(&mut buf).unwrap().0, FrameType::Close);
+        assert_eq!(server.join().unwrap(), 2);
+    }
+}
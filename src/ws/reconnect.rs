@@ -0,0 +1,416 @@
+//! Keep-alive and automatic reconnection for the WebSocket client.
+//!
+//! `AsyncReconnectingWsConnection` pings idle connections, drops them when no pong
+//! arrives in time, and reconnects with exponential backoff. After every reconnection
+//! a resubscribe hook supplies the frames needed to restore application state.
+//! Once the application closes the connection, the frames left, such as the peer's
+//! `Close` reply, are still delivered, and no reconnection happens.
+//! `ReconnectingWsConnection` is the blocking counterpart.
+
+use crate::error::WsError;
+use crate::runtime::RuntimeConfig;
+use crate::tls::TlsConfig;
+use crate::ws::WsConfig;
+use crate::ws::asynch::AsyncTungsteniteWsConnection;
+use embedded_svc::ws::asynch::{Receiver as AsyncReceiver, Sender as AsyncSender};
+use embedded_svc::ws::{ErrorType, FrameType, Receiver, Sender};
+use std::time::Duration;
+use tokio::runtime::Runtime;
+use tokio::time::{Instant, sleep, timeout};
+
+/// Frames re-sent after a successful reconnection.
+pub type ResubscribeHook = Box<dyn FnMut() -> Vec<(FrameType, Vec<u8>)> + Send>;
+
+/// Keep-alive and reconnection settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeepAliveConfig {
+    /// Idle time after which a ping is sent; `None` disables pings.
+    pub ping_interval: Option<Duration>,
+    /// Time to wait for a pong before the connection is considered dead.
+    pub pong_timeout: Duration,
+    /// Delay before the first reconnection attempt.
+    pub initial_backoff: Duration,
+    /// Upper bound for the delay between reconnection attempts.
+    pub max_backoff: Duration,
+    /// Maximum consecutive reconnection attempts; `None` retries forever.
+    pub max_retries: Option<u32>,
+}
+
+impl Default for KeepAliveConfig {
+    fn default() -> Self {
+        Self {
+            ping_interval: Some(Duration::from_secs(30)),
+            pong_timeout: Duration::from_secs(10),
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            max_retries: None,
+        }
+    }
+}
+
+/// An asynchronous WebSocket connection that keeps itself alive and reconnects.
+pub struct AsyncReconnectingWsConnection {
+    uri: String,
+    /// TLS settings for `wss://` URIs, reused on every reconnection.
+    tls: Option<TlsConfig>,
+    /// Size limits, fragmentation and compression, reused on every reconnection.
+    ws_config: WsConfig,
+    config: KeepAliveConfig,
+    conn: Option<AsyncTungsteniteWsConnection>,
+    resubscribe: Option<ResubscribeHook>,
+    last_activity: Instant,
+    ping_sent: Option<Instant>,
+    closed: bool,
+}
+
+impl AsyncReconnectingWsConnection {
+    /// Connects to `uri` with the given keep-alive settings.
+    pub async fn connect(uri: &str, config: KeepAliveConfig) -> Result<Self, WsError> {
+        Self::connect_with_config(uri, None, WsConfig::default(), config).await
+    }
+
+    /// Connects to `uri` with the given keep-alive settings, size limits, compression,
+    /// and TLS settings for `wss://` URIs if `tls` is set, all reused on every
+    /// reconnection.
+    ///
+    /// Accepts the same `TlsConfig` used by `HyperHttpConnection`.
+    pub async fn connect_with_config(
+        uri: &str,
+        tls: Option<&TlsConfig>,
+        ws_config: WsConfig,
+        config: KeepAliveConfig,
+    ) -> Result<Self, WsError> {
+        let conn = AsyncTungsteniteWsConnection::connect_with_config(uri, tls, ws_config).await?;
+
+        Ok(Self {
+            uri: uri.to_owned(),
+            tls: tls.cloned(),
+            ws_config,
+            config,
+            conn: Some(conn),
+            resubscribe: None,
+            last_activity: Instant::now(),
+            ping_sent: None,
+            closed: false,
+        })
+    }
+
+    /// Sets the hook supplying frames to send after each reconnection.
+    pub fn set_resubscribe<F>(&mut self, hook: F)
+    where
+        F: FnMut() -> Vec<(FrameType, Vec<u8>)> + Send + 'static,
+    {
+        self.resubscribe = Some(Box::new(hook));
+    }
+
+    /// Returns `true` while a live connection is held.
+    pub fn is_connected(&self) -> bool {
+        self.conn.as_ref().is_some_and(|conn| conn.is_connected())
+    }
+
+    /// Returns the live connection, reconnecting with backoff if needed.
+    ///
+    /// Once the application closed the connection, returns it without reconnecting.
+    async fn ensure_connected(&mut self) -> Result<&mut AsyncTungsteniteWsConnection, WsError> {
+        if self.closed {
+            return self.conn.as_mut().ok_or(WsError::Closed);
+        }
+
+        if !self.is_connected() {
+            self.conn = None;
+            let mut conn = self.reconnect().await?;
+            if let Some(hook) = self.resubscribe.as_mut() {
+                for (frame_type, payload) in hook() {
+                    conn.send(frame_type, &payload).await?;
+                }
+            }
+            self.conn = Some(conn);
+            self.last_activity = Instant::now();
+            self.ping_sent = None;
+        }
+
+        self.conn.as_mut().ok_or(WsError::Closed)
+    }
+
+    /// Attempts to reconnect, doubling the delay after every failure.
+    async fn reconnect(&self) -> Result<AsyncTungsteniteWsConnection, WsError> {
+        let mut backoff = self.config.initial_backoff;
+        let mut attempt = 0;
+
+        loop {
+            sleep(backoff).await;
+            attempt += 1;
+
+            let connecting = AsyncTungsteniteWsConnection::connect_with_config(
+                &self.uri,
+                self.tls.as_ref(),
+                self.ws_config,
+            );
+            match connecting.await {
+                Ok(conn) => return Ok(conn),
+                Err(error) if self.config.max_retries.is_some_and(|max| attempt >= max) => {
+                    return Err(error);
+                }
+                Err(_) => backoff = (backoff * 2).min(self.config.max_backoff),
+            }
+        }
+    }
+
+    /// Returns the instant at which the keep-alive logic must next act, if any.
+    fn next_deadline(&self) -> Option<Instant> {
+        match self.ping_sent {
+            Some(sent) => Some(sent + self.config.pong_timeout),
+            None => self
+                .config
+                .ping_interval
+                .map(|interval| self.last_activity + interval),
+        }
+    }
+
+    /// Handles an expired deadline by pinging, or dropping a connection whose pong is overdue.
+    async fn on_deadline(&mut self) -> Result<(), WsError> {
+        if self.ping_sent.is_some() {
+            self.conn = None;
+            return Ok(());
+        }
+
+        let conn = self.conn.as_mut().ok_or(WsError::Closed)?;
+        if conn.send(FrameType::Ping, &[]).await.is_err() {
+            self.conn = None;
+        } else {
+            self.ping_sent = Some(Instant::now());
+        }
+        Ok(())
+    }
+
+    /// Receives the frames left after the application closed the connection, until
+    /// the socket closes.
+    async fn drain(&mut self, frame_data_buf: &mut [u8]) -> Result<(FrameType, usize), WsError> {
+        let Some(conn) = self.conn.as_mut() else {
+            return Ok((FrameType::SocketClose, 0));
+        };
+        match conn.recv(frame_data_buf).await {
+            Err(WsError::BufferTooSmall(length)) => Err(WsError::BufferTooSmall(length)),
+            Ok((FrameType::SocketClose, _)) | Err(_) => {
+                self.conn = None;
+                Ok((FrameType::SocketClose, 0))
+            }
+            Ok(frame) => Ok(frame),
+        }
+    }
+}
+
+impl ErrorType for AsyncReconnectingWsConnection {
+    /// The error type returned by this connection.
+    type Error = WsError;
+}
+
+impl AsyncSender for AsyncReconnectingWsConnection {
+    /// Sends a frame, reconnecting first if the connection was lost.
+    ///
+    /// Sending `Close` or `SocketClose` disables further reconnection.
+    async fn send(&mut self, frame_type: FrameType, frame_data: &[u8]) -> Result<(), Self::Error> {
+        let conn = self.ensure_connected().await?;
+        let result = conn.send(frame_type, frame_data).await;
+
+        if frame_type == FrameType::SocketClose {
+            self.closed = true;
+            self.conn = None;
+        } else if frame_type == FrameType::Close {
+            self.closed = true;
+        } else if result.is_err() {
+            self.conn = None;
+        }
+        result
+    }
+}
+
+impl AsyncReceiver for AsyncReconnectingWsConnection {
+    /// Receives the next frame, transparently pinging and reconnecting.
+    ///
+    /// Returns `FrameType::SocketClose` only after the application closed the
+    /// connection, once the frames left, such as the peer's `Close` reply, are read.
+    async fn recv(&mut self, frame_data_buf: &mut [u8]) -> Result<(FrameType, usize), Self::Error> {
+        loop {
+            if self.closed {
+                return self.drain(frame_data_buf).await;
+            }
+
+            let deadline = self.next_deadline();
+            let conn = self.ensure_connected().await?;
+            let result = match deadline {
+                Some(deadline) => {
+                    let remaining = deadline.saturating_duration_since(Instant::now());
+                    timeout(remaining, conn.recv(frame_data_buf)).await.ok()
+                }
+                None => Some(conn.recv(frame_data_buf).await),
+            };
+
+            match result {
+                None => self.on_deadline().await?,
+                Some(Err(WsError::BufferTooSmall(length))) => {
+                    return Err(WsError::BufferTooSmall(length));
+                }
+                Some(Ok((FrameType::SocketClose, _))) | Some(Err(_)) => {
+                    self.conn = None;
+                }
+                Some(Ok(frame)) => {
+                    self.last_activity = Instant::now();
+                    self.ping_sent = None;
+                    return Ok(frame);
+                }
+            }
+        }
+    }
+}
+
+/// A blocking WebSocket connection that keeps itself alive and reconnects.
+pub struct ReconnectingWsConnection {
+    rt: Runtime,
+    inner: AsyncReconnectingWsConnection,
+}
+
+impl ReconnectingWsConnection {
+    /// Connects to `uri` with the given keep-alive settings.
+    pub fn connect(uri: &str, config: KeepAliveConfig) -> Result<Self, WsError> {
+        Self::connect_with_config(uri, None, WsConfig::default(), config)
+    }
+
+    /// Connects to `uri` with the given keep-alive settings, size limits, compression,
+    /// and TLS settings for `wss://` URIs if `tls` is set, all reused on every
+    /// reconnection.
+    pub fn connect_with_config(
+        uri: &str,
+        tls: Option<&TlsConfig>,
+        ws_config: WsConfig,
+        config: KeepAliveConfig,
+    ) -> Result<Self, WsError> {
+        let rt = RuntimeConfig::default()
+            .build()
+            .map_err(WsError::RuntimeCreation)?;
+        let inner = rt.block_on(AsyncReconnectingWsConnection::connect_with_config(
+            uri, tls, ws_config, config,
+        ))?;
+
+        Ok(Self { rt, inner })
+    }
+
+    /// Sets the hook supplying frames to send after each reconnection.
+    pub fn set_resubscribe<F>(&mut self, hook: F)
+    where
+        F: FnMut() -> Vec<(FrameType, Vec<u8>)> + Send + 'static,
+    {
+        self.inner.set_resubscribe(hook);
+    }
+
+    /// Returns `true` while a live connection is held.
+    pub fn is_connected(&self) -> bool {
+        self.inner.is_connected()
+    }
+}
+
+impl ErrorType for ReconnectingWsConnection {
+    /// The error type returned by this connection.
+    type Error = WsError;
+}
+
+impl Sender for ReconnectingWsConnection {
+    /// Sends a frame, reconnecting first if the connection was lost.
+    fn send(&mut self, frame_type: FrameType, frame_data: &[u8]) -> Result<(), Self::Error> {
+        self.rt.block_on(self.inner.send(frame_type, frame_data))
+    }
+}
+
+impl Receiver for ReconnectingWsConnection {
+    /// Receives the next frame, transparently pinging and reconnecting.
+    fn recv(&mut self, frame_data_buf: &mut [u8]) -> Result<(FrameType, usize), Self::Error> {
+        self.rt.block_on(self.inner.recv(frame_data_buf))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ws::tests::spawn_echo_server;
+    use futures_util::{SinkExt, StreamExt};
+    use std::net::TcpListener;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Spawns a server leaving its first connection silent, so pings go unanswered,
+    /// and echoing on the next ones. Returns its `ws://` URI and the number of
+    /// connections accepted.
+    fn spawn_silent_then_echo_server() -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let accepted = Arc::new(AtomicUsize::new(0));
+        let counter = accepted.clone();
+
+        std::thread::spawn(move || {
+            let rt = RuntimeConfig::default().build().unwrap();
+            rt.block_on(async move {
+                listener.set_nonblocking(true).unwrap();
+                let listener = tokio::net::TcpListener::from_std(listener).unwrap();
+                loop {
+                    let (stream, _) = listener.accept().await.unwrap();
+                    let silent = counter.fetch_add(1, Ordering::SeqCst) == 0;
+                    tokio::spawn(async move {
+                        let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+                        if silent {
+                            sleep(Duration::from_secs(10)).await;
+                            return;
+                        }
+                        while let Some(Ok(message)) = ws.next().await {
+                            if !message.is_close() {
+                                ws.send(message).await.unwrap();
+                            }
+                        }
+                    });
+                }
+            });
+        });
+
+        (format!("ws://{addr}"), accepted)
+    }
+
+    /// Tests that a connection whose pong is overdue is replaced, and that the
+    /// resubscribe hook runs on the new connection.
+    #[test]
+    fn test_pong_timeout_reconnects_and_resubscribes() {
+        let (uri, accepted) = spawn_silent_then_echo_server();
+        let config = KeepAliveConfig {
+            ping_interval: Some(Duration::from_millis(50)),
+            pong_timeout: Duration::from_millis(100),
+            initial_backoff: Duration::from_millis(10),
+            ..KeepAliveConfig::default()
+        };
+        let mut conn = ReconnectingWsConnection::connect(&uri, config).unwrap();
+        conn.set_resubscribe(|| vec![(FrameType::Text(false), b"subscribe".to_vec())]);
+
+        let mut buf = [0u8; 32];
+        let (frame_type, len) = conn.recv(&mut buf).unwrap();
+        assert_eq!(frame_type, FrameType::Text(false));
+        assert_eq!(&buf[..len], b"subscribe");
+        assert_eq!(accepted.load(Ordering::SeqCst), 2);
+        assert!(conn.is_connected());
+    }
+
+    /// Tests that the peer's reply to a local `Close` is delivered before
+    /// `SocketClose`, without reconnecting.
+    #[test]
+    fn test_close_drains_connection() {
+        let mut conn =
+            ReconnectingWsConnection::connect(&spawn_echo_server(), KeepAliveConfig::default())
+                .unwrap();
+
+        conn.send(FrameType::Close, &[]).unwrap();
+        let mut buf = [0u8; 32];
+        assert_eq!(conn.recv(&mut buf).unwrap().0, FrameType::Close);
+        assert_eq!(conn.recv(&mut buf).unwrap().0, FrameType::SocketClose);
+        assert_eq!(conn.recv(&mut buf).unwrap().0, FrameType::SocketClose);
+        assert!(matches!(
+            conn.send(FrameType::Text(false), b"late"),
+            Err(WsError::Closed)
+        ));
+    }
+}